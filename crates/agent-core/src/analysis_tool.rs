@@ -0,0 +1,135 @@
+//! The analysis engine as a [`Tool`].
+//!
+//! Lets clients of the tool framework, such as MCP editors, ask for file
+//! summaries, diagnostics, symbols, metrics, dead code and references.
+//! Paths are relative to the working directory, like the filesystem tool's.
+
+use analysis::AnalysisEngine;
+use common::{async_trait, Error, Result};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tools::{Parameter, ParameterType, ReturnType, Tool};
+
+const OPERATIONS: &[&str] = &["summary", "diagnostics", "symbols", "metrics", "dead_code", "references"];
+
+/// Read-only access to an [`AnalysisEngine`]
+pub struct AnalysisTool {
+    engine: Arc<AnalysisEngine>,
+}
+
+impl AnalysisTool {
+    pub fn new(engine: Arc<AnalysisEngine>) -> Self {
+        Self { engine }
+    }
+}
+
+#[async_trait]
+impl Tool for AnalysisTool {
+    fn name(&self) -> &str {
+        "analysis"
+    }
+
+    fn description(&self) -> &str {
+        "Analyze source files: summary, diagnostics, symbols, metrics, dead code and symbol references"
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "operation".to_string(),
+                description: "Operation to perform".to_string(),
+                required: true,
+                parameter_type: ParameterType::Enum(OPERATIONS.iter().map(|op| op.to_string()).collect()),
+                default: None,
+            },
+            Parameter {
+                name: "path".to_string(),
+                description: "File to analyze (all operations except references)".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: None,
+            },
+            Parameter {
+                name: "symbol".to_string(),
+                description: "Symbol name (references only)".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: None,
+            },
+        ]
+    }
+
+    fn returns(&self) -> ReturnType {
+        ReturnType {
+            description: "Analysis results".to_string(),
+            return_type: ParameterType::Object(Default::default()),
+        }
+    }
+
+    async fn execute(&self, args: &Value) -> Result<Value> {
+        let operation = args["operation"].as_str().unwrap_or_default();
+        if operation == "references" {
+            let symbol = args["symbol"].as_str().unwrap_or_default();
+            return Ok(serde_json::to_value(self.engine.find_symbol_references(symbol).await?)?);
+        }
+
+        let path = PathBuf::from(args["path"].as_str().unwrap_or_default());
+        let result = match operation {
+            "summary" => serde_json::to_value(self.engine.get_summary(&path).await?)?,
+            "diagnostics" => serde_json::to_value(self.engine.get_diagnostics(&path).await?)?,
+            "symbols" => serde_json::to_value(self.engine.document_symbols(&path).await?)?,
+            "metrics" => serde_json::to_value(self.engine.calculate_metrics(&path).await?)?,
+            "dead_code" => serde_json::to_value(self.engine.find_dead_code(&path).await?)?,
+            _ => return Err(Error::Validation(format!("Unknown operation: {}", operation))),
+        };
+        Ok(result)
+    }
+
+    fn validate(&self, args: &Value) -> Result<()> {
+        let operation = args
+            .get("operation")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| Error::Validation("Missing operation parameter".to_string()))?;
+        if !OPERATIONS.contains(&operation) {
+            return Err(Error::Validation(format!("Unknown operation: {}", operation)));
+        }
+        let needed = if operation == "references" { "symbol" } else { "path" };
+        if args.get(needed).and_then(|v| v.as_str()).is_none() {
+            return Err(Error::Validation(format!("Missing {} parameter", needed)));
+        }
+        Ok(())
+    }
+
+    fn is_safe(&self, args: &Value) -> bool {
+        // Keep reads inside the working directory
+        args.get("path")
+            .and_then(|v| v.as_str())
+            .map(|path| {
+                Path::new(path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            })
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_analysis_tool_checks_arguments() {
+        let tool = AnalysisTool::new(Arc::new(AnalysisEngine::new()));
+
+        assert!(tool.validate(&json!({"operation": "summary", "path": "src/lib.rs"})).is_ok());
+        assert!(tool.validate(&json!({"operation": "summary"})).is_err());
+        assert!(tool.validate(&json!({"operation": "references", "symbol": "main"})).is_ok());
+        assert!(tool.validate(&json!({"operation": "rewrite", "path": "a.rs"})).is_err());
+
+        assert!(tool.is_safe(&json!({"operation": "summary", "path": "src/lib.rs"})));
+        assert!(!tool.is_safe(&json!({"operation": "summary", "path": "/etc/hosts"})));
+        assert!(!tool.is_safe(&json!({"operation": "summary", "path": "src/../../secret.rs"})));
+    }
+}
@@ -0,0 +1,585 @@
+//! Offline benchmark harness.
+//!
+//! A suite is a directory of fixtures, each a small repository, a task and
+//! a script that checks the result:
+//!
+//! ```text
+//! <suite>/<fixture>/task.md     task given to the agent
+//! <suite>/<fixture>/verify.sh   exits 0 when the task is solved; run in the repository
+//! <suite>/<fixture>/repo/       starting state of the repository
+//! ```
+//!
+//! Every fixture runs once per model, in a scratch copy of its repository
+//! and a separate agent process. Model exchanges can be recorded and later
+//! replayed so a run is repeatable offline. Runs are scored by the
+//! verification script and optionally an LLM judge, and summarised per
+//! model with pass rate, cost and latency.
+
+use crate::budget::ResourceUsage;
+use crate::evaluation::{EvaluationEngine, Persona};
+use crate::events::{Event, EventKind};
+use common::chrono::{DateTime, Utc};
+use common::{Error, Result, TaskId};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Longest verification output kept in a report
+const MAX_VERIFY_OUTPUT: usize = 2000;
+
+/// One benchmark task
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub name: String,
+    pub root: PathBuf,
+    pub task: String,
+}
+
+impl Fixture {
+    pub fn repo(&self) -> PathBuf {
+        self.root.join("repo")
+    }
+
+    pub fn verify_script(&self) -> PathBuf {
+        self.root.join("verify.sh")
+    }
+}
+
+/// Load the fixtures of a suite, sorted by name
+///
+/// Subdirectories without a `task.md` are ignored; a fixture missing its
+/// repository or verification script is an error.
+pub fn load_suite(dir: &Path) -> Result<Vec<Fixture>> {
+    let mut fixtures = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let root = entry?.path();
+        let task_file = root.join("task.md");
+        if !root.is_dir() || !task_file.is_file() {
+            continue;
+        }
+        let name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let fixture = Fixture {
+            task: std::fs::read_to_string(&task_file)?.trim().to_string(),
+            root: std::fs::canonicalize(&root)?,
+            name,
+        };
+        if !fixture.repo().is_dir() || !fixture.verify_script().is_file() {
+            return Err(Error::Validation(format!(
+                "Fixture {} needs a repo/ directory and a verify.sh script",
+                fixture.name
+            )));
+        }
+        fixtures.push(fixture);
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(fixtures)
+}
+
+/// Where model answers come from during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GatewayMode {
+    /// Call the configured provider
+    Live,
+    /// Call the provider and record every exchange
+    Record,
+    /// Answer from earlier recordings without calling the provider
+    Replay,
+}
+
+/// Outcome of one fixture with one model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunResult {
+    pub fixture: String,
+    /// The model the run was requested with; runs are grouped by it
+    pub model: String,
+    /// The model the agent reported streaming from, which may differ from
+    /// the requested one when the provider resolves an alias
+    #[serde(default)]
+    pub observed_model: Option<String>,
+    /// The verification script passed
+    pub passed: bool,
+    /// The agent reported the task as successful
+    pub task_success: bool,
+    /// Judge score as a percentage of the rubric's maximum
+    pub judge_score: Option<f32>,
+    pub tokens: u32,
+    pub cost_usd: f64,
+    /// Wall-clock time of the agent process
+    pub latency_ms: u64,
+    pub error: Option<String>,
+    /// Tail of the verification script's output
+    pub verify_output: String,
+}
+
+/// Results of one model across the suite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelSummary {
+    pub model: String,
+    pub runs: usize,
+    pub passed: usize,
+    pub pass_rate: f32,
+    pub mean_judge_score: Option<f32>,
+    pub total_tokens: u64,
+    pub total_cost_usd: f64,
+    pub mean_latency_ms: u64,
+}
+
+/// A benchmark run, comparable across prompt and model changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub suite: String,
+    pub mode: GatewayMode,
+    pub started_at: DateTime<Utc>,
+    pub models: Vec<ModelSummary>,
+    pub runs: Vec<RunResult>,
+}
+
+impl BenchReport {
+    /// Summarise runs per model, in the order models were first run
+    pub fn new(suite: impl Into<String>, mode: GatewayMode, started_at: DateTime<Utc>, runs: Vec<RunResult>) -> Self {
+        let mut names: Vec<&str> = Vec::new();
+        for run in &runs {
+            if !names.contains(&run.model.as_str()) {
+                names.push(&run.model);
+            }
+        }
+
+        let models = names
+            .iter()
+            .map(|model| {
+                let runs: Vec<&RunResult> = runs.iter().filter(|r| r.model == *model).collect();
+                let passed = runs.iter().filter(|r| r.passed).count();
+                let judged: Vec<f32> = runs.iter().filter_map(|r| r.judge_score).collect();
+                ModelSummary {
+                    model: model.to_string(),
+                    runs: runs.len(),
+                    passed,
+                    pass_rate: passed as f32 / runs.len() as f32,
+                    mean_judge_score: (!judged.is_empty())
+                        .then(|| judged.iter().sum::<f32>() / judged.len() as f32),
+                    total_tokens: runs.iter().map(|r| r.tokens as u64).sum(),
+                    total_cost_usd: runs.iter().map(|r| r.cost_usd).sum(),
+                    mean_latency_ms: runs.iter().map(|r| r.latency_ms).sum::<u64>() / runs.len() as u64,
+                }
+            })
+            .collect();
+
+        Self {
+            suite: suite.into(),
+            mode,
+            started_at,
+            models,
+            runs,
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Benchmark: {}\n\n", self.suite);
+        out.push_str(&format!(
+            "Started {} ({:?} gateway)\n\n",
+            self.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.mode
+        ));
+
+        out.push_str("| Model | Pass rate | Judge | Tokens | Cost | Mean latency |\n");
+        out.push_str("|---|---|---|---|---|---|\n");
+        for m in &self.models {
+            out.push_str(&format!(
+                "| {} | {:.0}% ({}/{}) | {} | {} | ${:.4} | {:.1}s |\n",
+                m.model,
+                m.pass_rate * 100.0,
+                m.passed,
+                m.runs,
+                m.mean_judge_score.map(|s| format!("{:.0}%", s)).unwrap_or_else(|| "-".to_string()),
+                m.total_tokens,
+                m.total_cost_usd,
+                m.mean_latency_ms as f64 / 1000.0
+            ));
+        }
+
+        out.push_str("\n## Runs\n\n| Fixture | Model | Result | Judge | Tokens | Cost | Latency |\n");
+        out.push_str("|---|---|---|---|---|---|---|\n");
+        for r in &self.runs {
+            let result = match (&r.error, r.passed) {
+                (Some(error), _) => format!("error: {}", error.replace('|', "\\|")),
+                (None, true) => "pass".to_string(),
+                (None, false) => "fail".to_string(),
+            };
+            let model = match &r.observed_model {
+                Some(observed) if *observed != r.model => format!("{} ({})", r.model, observed),
+                _ => r.model.clone(),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | ${:.4} | {:.1}s |\n",
+                r.fixture,
+                model,
+                result,
+                r.judge_score.map(|s| format!("{:.0}%", s)).unwrap_or_else(|| "-".to_string()),
+                r.tokens,
+                r.cost_usd,
+                r.latency_ms as f64 / 1000.0
+            ));
+        }
+        out
+    }
+}
+
+/// Runs fixtures against agent processes
+pub struct BenchRunner {
+    program: PathBuf,
+    args: Vec<String>,
+    mode: GatewayMode,
+    recordings: Option<PathBuf>,
+    timeout: Duration,
+    judge: Option<Arc<crate::intelligence::IntelligenceEngine>>,
+}
+
+impl BenchRunner {
+    /// Run fixtures with the agent binary at `program`
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            mode: GatewayMode::Live,
+            recordings: None,
+            timeout: Duration::from_secs(600),
+            judge: None,
+        }
+    }
+
+    /// Arguments passed to every agent process before the task, such as `--config`
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Record to or replay from `dir`, one file per model and fixture
+    pub fn with_recordings(mut self, mode: GatewayMode, dir: impl Into<PathBuf>) -> Self {
+        self.mode = mode;
+        self.recordings = Some(dir.into());
+        self
+    }
+
+    /// Time allowed for the agent and for the verification script
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Score each task's output with an LLM judge
+    pub fn with_judge(mut self, judge: Arc<crate::intelligence::IntelligenceEngine>) -> Self {
+        self.judge = Some(judge);
+        self
+    }
+
+    /// Run every fixture with every model
+    pub async fn run(&self, suite: &Path, models: &[String]) -> Result<BenchReport> {
+        let fixtures = load_suite(suite)?;
+        if fixtures.is_empty() {
+            return Err(Error::NotFound(format!("No fixtures in {}", suite.display())));
+        }
+
+        let started_at = Utc::now();
+        let mut runs = Vec::with_capacity(fixtures.len() * models.len());
+        for model in models {
+            for fixture in &fixtures {
+                info!("Benchmarking {} with {}", fixture.name, model);
+                runs.push(self.run_fixture(fixture, model).await);
+            }
+        }
+        Ok(BenchReport::new(suite.display().to_string(), self.mode, started_at, runs))
+    }
+
+    /// Recording file for one model and fixture
+    fn recording(&self, model: &str, fixture: &Fixture) -> Option<PathBuf> {
+        let model: String = model
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        self.recordings
+            .as_ref()
+            .map(|dir| dir.join(model).join(format!("{}.jsonl", fixture.name)))
+    }
+
+    /// Run one fixture in a scratch copy of its repository
+    pub async fn run_fixture(&self, fixture: &Fixture, model: &str) -> RunResult {
+        let mut result = RunResult {
+            fixture: fixture.name.clone(),
+            model: model.to_string(),
+            observed_model: None,
+            passed: false,
+            task_success: false,
+            judge_score: None,
+            tokens: 0,
+            cost_usd: 0.0,
+            latency_ms: 0,
+            error: None,
+            verify_output: String::new(),
+        };
+
+        let scratch = std::env::temp_dir().join(format!("coding-agent-bench-{}", common::uuid::Uuid::new_v4()));
+        if let Err(e) = self.run_in(&scratch, fixture, &mut result).await {
+            result.error = Some(e.to_string());
+        }
+        if let Err(e) = std::fs::remove_dir_all(&scratch) {
+            warn!("Could not remove {}: {}", scratch.display(), e);
+        }
+        result
+    }
+
+    async fn run_in(&self, scratch: &Path, fixture: &Fixture, result: &mut RunResult) -> Result<()> {
+        let repo = scratch.join("repo");
+        let events_path = scratch.join("events.jsonl");
+        copy_dir(&fixture.repo(), &repo)?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .args(["--events", "jsonl", "--events-out"])
+            .arg(&events_path)
+            .arg(&fixture.task)
+            .current_dir(&repo)
+            .env("CODING_AGENT_LLM_MODEL", &result.model)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        match (self.mode, self.recording(&result.model, fixture)) {
+            (GatewayMode::Record, Some(recording)) => {
+                // A fresh recording, not appended to an old one
+                let _ = std::fs::remove_file(&recording);
+                command.env("CODING_AGENT_LLM_RECORD", recording);
+            }
+            (GatewayMode::Replay, Some(recording)) => {
+                if !recording.is_file() {
+                    return Err(Error::NotFound(format!("No recording at {}", recording.display())));
+                }
+                command.env("CODING_AGENT_LLM_REPLAY", recording);
+            }
+            _ => {}
+        }
+
+        let started = Instant::now();
+        let status = tokio::time::timeout(self.timeout, command.status())
+            .await
+            .map_err(|_| Error::Timeout(format!("Agent did not finish {} in time", fixture.name)))??;
+        result.latency_ms = started.elapsed().as_millis() as u64;
+        if !status.success() {
+            warn!("Agent exited with {} on {}", status, fixture.name);
+        }
+
+        let outcome = read_outcome(&events_path)?;
+        result.task_success = outcome.success;
+        result.tokens = outcome.usage.tokens;
+        result.cost_usd = outcome.usage.cost_usd;
+        result.observed_model = outcome.model;
+        if let Some(error) = outcome.error {
+            result.error = Some(error);
+        }
+
+        let (passed, output) = self.verify(fixture, &repo).await?;
+        result.passed = passed;
+        result.verify_output = output;
+
+        if let (Some(judge), false) = (&self.judge, outcome.output.is_empty()) {
+            match judge_output(judge, &fixture.task, &outcome.output).await {
+                Ok(score) => result.judge_score = Some(score),
+                Err(e) => warn!("Judge failed on {}: {}", fixture.name, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the fixture's verification script in the worked-on repository
+    async fn verify(&self, fixture: &Fixture, repo: &Path) -> Result<(bool, String)> {
+        let output = tokio::time::timeout(
+            self.timeout,
+            Command::new("sh")
+                .arg(fixture.verify_script())
+                .current_dir(repo)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| Error::Timeout(format!("Verification of {} timed out", fixture.name)))??;
+
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let start = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .find(|i| text.len() - i <= MAX_VERIFY_OUTPUT)
+            .unwrap_or(text.len());
+        Ok((output.status.success(), text[start..].to_string()))
+    }
+}
+
+/// What the agent reported about the fixture's task
+#[derive(Debug, Default)]
+struct Outcome {
+    success: bool,
+    output: String,
+    usage: ResourceUsage,
+    model: Option<String>,
+    error: Option<String>,
+}
+
+/// Read the root task's result from an agent's event stream
+fn read_outcome(path: &Path) -> Result<Outcome> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::Execution(format!("Agent wrote no events: {}", e)))?;
+
+    let mut root: Option<TaskId> = None;
+    let mut outcome = Outcome::default();
+    let mut finished = false;
+    for event in content.lines().filter_map(|line| serde_json::from_str::<Event>(line).ok()) {
+        match event.kind {
+            EventKind::TaskSubmitted { task_id, parent_id: None, .. } if root.is_none() => {
+                root = Some(task_id);
+            }
+            EventKind::TokensStreamed { model, .. } if outcome.model.is_none() => {
+                outcome.model = Some(model);
+            }
+            EventKind::TaskFinished {
+                task_id,
+                success,
+                output,
+                usage,
+                error,
+                ..
+            } if Some(task_id) == root => {
+                finished = true;
+                outcome.success = success;
+                outcome.output = output;
+                outcome.usage = usage;
+                outcome.error = error;
+            }
+            _ => {}
+        }
+    }
+
+    if !finished {
+        return Err(Error::Execution("Agent stopped before the task finished".to_string()));
+    }
+    Ok(outcome)
+}
+
+/// Score a task's output with a reviewer judge, as a percentage
+async fn judge_output(
+    judge: &crate::intelligence::IntelligenceEngine,
+    description: &str,
+    output: &str,
+) -> Result<f32> {
+    let mut task = crate::Task::new(description);
+    task.intent = crate::intelligence::intent::IntentParser::new().parse(description).await?;
+    let report = EvaluationEngine::new()
+        .evaluate(judge, &task, output, Persona::Reviewer)
+        .await?;
+    Ok(if report.max_possible_score > 0.0 {
+        report.weighted_score / report.max_possible_score * 100.0
+    } else {
+        0.0
+    })
+}
+
+/// Copy a directory tree
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for the agent: solves the task and reports it as events
+    const FAKE_AGENT: &str = r#"
+out=""
+while [ $# -gt 0 ]; do
+  case "$1" in --events-out) out="$2"; shift 2 ;; *) shift ;; esac
+done
+echo solved > answer.txt
+id=11111111-2222-3333-4444-555555555555
+{
+  printf '{"timestamp":"2024-01-01T00:00:00Z","type":"task_submitted","task_id":"%s","description":"t","parent_id":null}\n' "$id"
+  printf '{"timestamp":"2024-01-01T00:00:00Z","type":"tokens_streamed","task_id":"%s","model":"provider/%s","content":"","tokens":40}\n' "$id" "$CODING_AGENT_LLM_MODEL"
+  printf '{"timestamp":"2024-01-01T00:00:01Z","type":"task_finished","task_id":"%s","success":true,"output":"done","usage":{"tokens":40,"llm_calls":1,"tool_calls":1,"cost_usd":0.002,"elapsed_ms":5},"exhausted":null,"error":null}\n' "$id"
+} > "$out"
+"#;
+
+    fn write_fixture(suite: &Path, name: &str, expected: &str) {
+        let root = suite.join(name);
+        std::fs::create_dir_all(root.join("repo/src")).unwrap();
+        std::fs::write(root.join("repo/src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("task.md"), format!("Solve {}\n", name)).unwrap();
+        std::fs::write(
+            root.join("verify.sh"),
+            format!("test \"$(cat answer.txt)\" = {}", expected),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bench_scores_fixtures_per_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let suite = dir.path().join("suite");
+        write_fixture(&suite, "easy", "solved");
+        write_fixture(&suite, "hard", "perfect");
+        std::fs::create_dir_all(suite.join("notes")).unwrap();
+        let agent = dir.path().join("agent.sh");
+        std::fs::write(&agent, FAKE_AGENT).unwrap();
+
+        let runner = BenchRunner::new("sh").with_args(vec![agent.display().to_string()]);
+        let models = vec!["model-a".to_string(), "model-b".to_string()];
+        let report = runner.run(&suite, &models).await.unwrap();
+
+        assert_eq!(report.runs.len(), 4);
+        assert!(report.runs.iter().all(|r| r.error.is_none() && r.task_success));
+        assert_eq!(report.runs[0].fixture, "easy");
+        assert_eq!(report.runs[0].model, "model-a");
+        assert_eq!(report.runs[0].observed_model.as_deref(), Some("provider/model-a"));
+        assert!(report.runs[0].passed);
+        assert!(!report.runs[1].passed);
+
+        assert_eq!(report.models.len(), 2);
+        let summary = &report.models[1];
+        assert_eq!(summary.model, "model-b");
+        assert_eq!(summary.pass_rate, 0.5);
+        assert_eq!(summary.total_tokens, 80);
+        assert!((summary.total_cost_usd - 0.004).abs() < 1e-9);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("| model-a | 50% (1/2) |"));
+        assert!(markdown.contains("| easy | model-a (provider/model-a) | pass |"));
+        let json: BenchReport = serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+        assert_eq!(json.runs.len(), 4);
+
+        // Replaying needs an earlier recording
+        let replay = BenchRunner::new("sh")
+            .with_args(vec![agent.display().to_string()])
+            .with_recordings(GatewayMode::Replay, dir.path().join("recordings"));
+        let run = replay.run_fixture(&load_suite(&suite).unwrap()[0], "model-a").await;
+        assert!(run.error.unwrap().contains("No recording"));
+    }
+}
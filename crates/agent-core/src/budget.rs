@@ -0,0 +1,233 @@
+//! Per-task resource budgets.
+//!
+//! A budget caps the tokens, LLM calls, tool calls, wall-clock time and
+//! estimated cost a task may use. The orchestrator charges each task's
+//! tracker as the pipeline runs and, once any limit is reached, winds the
+//! task down with a partial result instead of starting more work.
+
+use agent_config::{AgentConfig, TaskBudget};
+use intelligence::{IntentCategory, ResourceLimits};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// What a task has consumed so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub tokens: u32,
+    pub llm_calls: u32,
+    pub tool_calls: u32,
+    /// Estimated spend in USD
+    pub cost_usd: f64,
+    pub elapsed_ms: u64,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tokens, {} LLM call(s), {} tool call(s), ${:.4}, {:.1}s",
+            self.tokens,
+            self.llm_calls,
+            self.tool_calls,
+            self.cost_usd,
+            self.elapsed_ms as f64 / 1000.0
+        )
+    }
+}
+
+/// Limits for a task
+///
+/// A budget declared on the task wins; otherwise the configured budget for
+/// its intent category applies, falling back to the configured default.
+pub fn limits_for(
+    task: &super::Task,
+    category: IntentCategory,
+    config: Option<&AgentConfig>,
+) -> ResourceLimits {
+    if let Some(limits) = &task.budget {
+        return limits.clone();
+    }
+    config
+        .map(|c| to_limits(c.agent.budget.for_intent(&category.to_string())))
+        .unwrap_or_default()
+}
+
+fn to_limits(budget: &TaskBudget) -> ResourceLimits {
+    ResourceLimits {
+        max_tokens: budget.max_tokens,
+        timeout_seconds: budget.max_seconds,
+        max_llm_calls: budget.max_llm_calls,
+        max_tool_calls: budget.max_tool_calls,
+        max_cost_usd: budget.max_cost_usd,
+    }
+}
+
+/// USD price per 1K tokens by model
+///
+/// Gateways report a single token count, so routed models are priced at
+/// the mean of their input and output rates.
+#[derive(Debug, Clone, Default)]
+pub struct Pricing {
+    per_model: HashMap<String, f64>,
+    fallback: f64,
+}
+
+impl Pricing {
+    pub fn from_config(config: &AgentConfig) -> Self {
+        let per_model = config
+            .llm
+            .routing
+            .providers
+            .iter()
+            .map(|route| {
+                (
+                    route.model.clone(),
+                    (route.cost_per_1k_input + route.cost_per_1k_output) / 2.0,
+                )
+            })
+            .collect();
+        Self {
+            per_model,
+            fallback: config.agent.budget.cost_per_1k_tokens,
+        }
+    }
+
+    /// Estimated cost of a generation
+    pub fn cost(&self, model: &str, tokens: u32) -> f64 {
+        let rate = self.per_model.get(model).copied().unwrap_or(self.fallback);
+        rate * tokens as f64 / 1000.0
+    }
+}
+
+/// Live usage of one task against its limits
+pub struct BudgetTracker {
+    started: Instant,
+    pricing: Pricing,
+    limits: parking_lot::Mutex<ResourceLimits>,
+    usage: parking_lot::Mutex<ResourceUsage>,
+}
+
+impl BudgetTracker {
+    pub fn new(limits: ResourceLimits, pricing: Pricing) -> Self {
+        Self {
+            started: Instant::now(),
+            pricing,
+            limits: parking_lot::Mutex::new(limits),
+            usage: parking_lot::Mutex::new(ResourceUsage::default()),
+        }
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits.lock().clone()
+    }
+
+    /// Replace the limits, e.g. once the task's intent is known
+    pub fn set_limits(&self, limits: ResourceLimits) {
+        *self.limits.lock() = limits;
+    }
+
+    /// Record a model call, returning what it cost
+    pub fn charge_llm(&self, model: &str, tokens: u32) -> f64 {
+        let cost = self.pricing.cost(model, tokens);
+        let mut usage = self.usage.lock();
+        usage.llm_calls += 1;
+        usage.tokens += tokens;
+        usage.cost_usd += cost;
+        cost
+    }
+
+    /// Record a tool call
+    pub fn charge_tool(&self) {
+        self.usage.lock().tool_calls += 1;
+    }
+
+    pub fn usage(&self) -> ResourceUsage {
+        let mut usage = self.usage.lock().clone();
+        usage.elapsed_ms = self.started.elapsed().as_millis() as u64;
+        usage
+    }
+
+    /// Wall-clock time left, if the task has a time limit
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let timeout = self.limits.lock().timeout_seconds;
+        (timeout > 0).then(|| Duration::from_secs(timeout).saturating_sub(self.started.elapsed()))
+    }
+
+    /// The first limit that has been reached, if any
+    pub fn exhausted(&self) -> Option<String> {
+        let limits = self.limits();
+        let usage = self.usage();
+        if limits.timeout_seconds > 0 && usage.elapsed_ms >= limits.timeout_seconds * 1000 {
+            Some(format!("time budget of {}s used up", limits.timeout_seconds))
+        } else if limits.max_tokens > 0 && usage.tokens >= limits.max_tokens {
+            Some(format!("token budget of {} used up", limits.max_tokens))
+        } else if limits.max_llm_calls > 0 && usage.llm_calls >= limits.max_llm_calls {
+            Some(format!("LLM call budget of {} used up", limits.max_llm_calls))
+        } else if limits.max_tool_calls > 0 && usage.tool_calls >= limits.max_tool_calls {
+            Some(format!("tool call budget of {} used up", limits.max_tool_calls))
+        } else if limits.max_cost_usd > 0.0 && usage.cost_usd >= limits.max_cost_usd {
+            Some(format!("cost budget of ${:.2} used up", limits.max_cost_usd))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_resolution() {
+        let mut config = AgentConfig::default();
+        config.agent.budget.default.max_tokens = 10_000;
+        config.agent.budget.per_intent.insert(
+            "testing".to_string(),
+            TaskBudget {
+                max_tool_calls: 5,
+                ..Default::default()
+            },
+        );
+
+        let task = super::super::Task::new("write tests");
+        assert_eq!(limits_for(&task, IntentCategory::Testing, Some(&config)).max_tool_calls, 5);
+        assert_eq!(limits_for(&task, IntentCategory::Analysis, Some(&config)).max_tokens, 10_000);
+        assert_eq!(limits_for(&task, IntentCategory::Analysis, None), ResourceLimits::default());
+
+        let declared = ResourceLimits {
+            max_llm_calls: 2,
+            ..Default::default()
+        };
+        let task = task.with_budget(declared.clone());
+        assert_eq!(limits_for(&task, IntentCategory::Testing, Some(&config)), declared);
+    }
+
+    #[test]
+    fn test_tracker_reports_first_exhausted_limit() {
+        let mut config = AgentConfig::default();
+        config.agent.budget.cost_per_1k_tokens = 0.5;
+        let tracker = BudgetTracker::new(
+            ResourceLimits {
+                max_tool_calls: 2,
+                max_cost_usd: 1.0,
+                ..Default::default()
+            },
+            Pricing::from_config(&config),
+        );
+        assert!(tracker.exhausted().is_none());
+        assert!(tracker.remaining_time().is_none());
+
+        tracker.charge_tool();
+        tracker.charge_llm("unlisted-model", 1_000);
+        assert!(tracker.exhausted().is_none());
+        tracker.charge_llm("unlisted-model", 1_000);
+        assert_eq!(tracker.usage().cost_usd, 1.0);
+        assert!(tracker.exhausted().unwrap().contains("cost"));
+
+        tracker.charge_tool();
+        assert!(tracker.exhausted().unwrap().contains("tool call"));
+        assert_eq!(tracker.usage().llm_calls, 2);
+    }
+}
@@ -0,0 +1,531 @@
+//! Code-level self-improvement.
+//!
+//! A `Code` strategy is turned into a patch written by the model. The patch
+//! is committed in a scratch git worktree, built from a snapshot of that
+//! commit with the `SelfCompiler` and tested in the worktree; only if the
+//! build and the full test suite pass is it merged into the tree. If a
+//! benchmark suite is configured, the new binary is then benchmarked against
+//! the running one and the merge is reverted when the pass rate drops.
+
+use crate::bench::{BenchReport, BenchRunner};
+use crate::compile_fix::FileFix;
+use crate::improvement::ImprovementStrategy;
+use crate::otel;
+use crate::self_compile::SelfCompiler;
+use crate::worktree::{self, TaskWorktree, WorktreeManager};
+use crate::{Change, ChangeType};
+use agent_config::{AgentConfig, GitToolConfig, SelfCompileConfig};
+use common::{Error, Result};
+use intelligence::IntelligenceEngine;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn, Instrument};
+
+/// Most source text shown to the model when proposing a patch
+const MAX_CONTEXT_CHARS: usize = 60_000;
+
+/// A model-written change to the agent's source
+#[derive(Debug, Clone)]
+pub struct CodePatch {
+    pub summary: String,
+    /// Complete new contents, with paths relative to the project root
+    pub files: Vec<FileFix>,
+    /// Unified diff against the tree the patch was proposed for
+    pub diff: String,
+}
+
+impl CodePatch {
+    /// Build a patch, diffing each file against its current contents in `root`
+    pub fn new(summary: impl Into<String>, files: Vec<FileFix>, root: &Path) -> Result<Self> {
+        let mut diff = String::new();
+        for file in &files {
+            check_path(&file.path)?;
+            let old = std::fs::read_to_string(root.join(&file.path)).ok();
+            diff.push_str(&tools::diff::file_diff(&file.path, old.as_deref(), Some(&file.content)));
+        }
+        Ok(Self {
+            summary: summary.into(),
+            files,
+            diff,
+        })
+    }
+}
+
+/// Proposes, gates and promotes code changes to the agent itself
+pub struct CodeImprover {
+    settings: SelfCompileConfig,
+    git: GitToolConfig,
+    model: String,
+    intelligence: Arc<IntelligenceEngine>,
+    tools: Arc<tools::ToolFramework>,
+    project_root: PathBuf,
+    baseline: PathBuf,
+    bench_args: Vec<String>,
+}
+
+impl CodeImprover {
+    /// Improve the project at `project_root`, which must be a git repository
+    pub fn new(
+        config: &AgentConfig,
+        intelligence: Arc<IntelligenceEngine>,
+        tools: Arc<tools::ToolFramework>,
+        project_root: impl Into<PathBuf>,
+    ) -> Self {
+        let project_root = project_root.into();
+        Self {
+            settings: config.self_compile.clone(),
+            git: config.tools.git.clone(),
+            model: config.llm.model.clone(),
+            intelligence,
+            tools,
+            baseline: std::env::current_exe().unwrap_or_else(|_| project_root.join("coding-agent")),
+            project_root,
+            bench_args: Vec::new(),
+        }
+    }
+
+    /// Binary the benchmark compares candidates against; defaults to the running one
+    pub fn with_baseline(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.baseline = binary.into();
+        self
+    }
+
+    /// Arguments passed to both binaries when benchmarking, such as `--config`
+    pub fn with_bench_args(mut self, args: Vec<String>) -> Self {
+        self.bench_args = args;
+        self
+    }
+
+    /// Ask the model for a patch implementing `strategy`
+    pub async fn propose(&self, strategy: &ImprovementStrategy) -> Result<CodePatch> {
+        let files = self.component_sources(&strategy.target_component);
+        let prompt = build_prompt(strategy, &files);
+        let span = otel::chat_span(None);
+        let result = self
+            .intelligence
+            .generate(&intelligence::Context::default(), &prompt)
+            .instrument(span.clone())
+            .await;
+        otel::record_generation(&span, &result);
+        let result = result?;
+        let (summary, files) = parse_patch(&result.content)?;
+        CodePatch::new(summary, files, &self.project_root)
+    }
+
+    /// Gate `patch` on a clean build and test run, then promote it
+    ///
+    /// The returned change holds the promoted commit so it can be reverted.
+    pub async fn apply(&self, strategy: &ImprovementStrategy, patch: &CodePatch) -> Result<Change> {
+        if patch.files.is_empty() {
+            return Err(Error::Validation("Patch changes no files".to_string()));
+        }
+
+        let manager = WorktreeManager::new(self.git.clone());
+        let task = crate::Task::new(format!("self-improvement: {}", strategy.name));
+        let worktree = manager
+            .create(&self.tools, &task, Some(&self.project_root))
+            .await?
+            .ok_or_else(|| {
+                Error::Config(format!("{} is not a git repository", self.project_root.display()))
+            })?;
+
+        let candidate = match self.build_candidate(&worktree, &task, patch).await {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                warn!("Rejected code change '{}': {}", patch.summary, e);
+                if let Err(e) = manager.discard(&self.tools, &worktree).await {
+                    warn!("Could not remove worktree {}: {}", worktree.root.display(), e);
+                }
+                return Err(e);
+            }
+        };
+
+        manager.integrate(&self.tools, &worktree, &patch.summary).await?;
+        let commit = self.head().await?;
+        info!("Promoted code change '{}' as {}", patch.summary, commit);
+
+        if let Some(suite) = &self.settings.benchmark_suite {
+            if let Err(e) = self.benchmark(suite, &candidate).await {
+                warn!("Reverting {}: {}", commit, e);
+                self.revert_commit(&commit).await?;
+                return Err(e);
+            }
+        }
+
+        Ok(Change {
+            id: task.id.0.to_string(),
+            file_path: PathBuf::from(&patch.files[0].path),
+            change_type: ChangeType::Code,
+            description: patch.summary.clone(),
+            timestamp: common::chrono::Utc::now(),
+            rollback_data: Some(serde_json::json!({
+                "commit": commit,
+                "diff": patch.diff,
+                "binary": candidate,
+            })),
+        })
+    }
+
+    /// Undo a promoted change with a revert commit
+    pub async fn revert(&self, change: &Change) -> Result<()> {
+        let commit = change
+            .rollback_data
+            .as_ref()
+            .and_then(|data| data.get("commit"))
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| Error::NotFound(format!("No commit recorded for change {}", change.id)))?;
+        self.revert_commit(commit).await
+    }
+
+    /// Commit the patch in the worktree, build the commit and run the tests
+    async fn build_candidate(
+        &self,
+        worktree: &TaskWorktree,
+        task: &crate::Task,
+        patch: &CodePatch,
+    ) -> Result<PathBuf> {
+        // Written through the tool framework so approval and dry runs apply
+        let root = relative_to_cwd(&worktree.root)?;
+        for file in &patch.files {
+            let result = self
+                .tools
+                .execute(
+                    "filesystem",
+                    serde_json::json!({
+                        "operation": "write",
+                        "path": root.join(&file.path).to_string_lossy(),
+                        "content": file.content,
+                    }),
+                )
+                .await?;
+            if !result.success {
+                return Err(Error::ExternalService(format!("Could not write {}: {}", file.path, result.data)));
+            }
+        }
+
+        let message = format!("{} {}", self.git.commit_prefix, patch.summary);
+        worktree::git(
+            &self.tools,
+            serde_json::json!({
+                "operation": "commit",
+                "path": worktree.root.to_string_lossy(),
+                "message": message.trim(),
+            }),
+        )
+        .await?;
+
+        let binary = SelfCompiler::new(self.settings.clone(), &self.project_root)
+            .build_rev(&worktree.branch)
+            .await?;
+        SelfCompiler::new(self.settings.clone(), &worktree.root).run_tests().await?;
+
+        // Old builds are pruned, so the candidate is kept for the ledger
+        let candidates = self.project_root.join(".agent/candidates");
+        tokio::fs::create_dir_all(&candidates).await?;
+        let kept = candidates.join(format!("coding-agent-{}", task.id.0.simple()));
+        tokio::fs::copy(&binary, &kept).await?;
+        Ok(kept)
+    }
+
+    /// Compare the candidate binary's benchmark pass rate with the baseline's
+    async fn benchmark(&self, suite: &Path, candidate: &Path) -> Result<()> {
+        let models = vec![self.model.clone()];
+        let runner = |binary: &Path| BenchRunner::new(binary).with_args(self.bench_args.clone());
+        let before = pass_rate(&runner(&self.baseline).run(suite, &models).await?);
+        let after = pass_rate(&runner(candidate).run(suite, &models).await?);
+        info!("Benchmark pass rate: {:.0}% before, {:.0}% after", before * 100.0, after * 100.0);
+
+        if regressed(before, after, self.settings.max_benchmark_regression) {
+            return Err(Error::Validation(format!(
+                "Benchmark pass rate fell from {:.0}% to {:.0}%",
+                before * 100.0,
+                after * 100.0
+            )));
+        }
+        Ok(())
+    }
+
+    async fn head(&self) -> Result<String> {
+        let log = worktree::git(
+            &self.tools,
+            serde_json::json!({"operation": "log", "path": self.root_arg()}),
+        )
+        .await?;
+        log["commits"][0]["hash"]
+            .as_str()
+            .filter(|hash| !hash.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| Error::NotFound("No commit after promotion".to_string()))
+    }
+
+    async fn revert_commit(&self, commit: &str) -> Result<()> {
+        worktree::git(
+            &self.tools,
+            serde_json::json!({"operation": "revert", "path": self.root_arg(), "commit": commit}),
+        )
+        .await?;
+        info!("Reverted {}", commit);
+        Ok(())
+    }
+
+    fn root_arg(&self) -> String {
+        self.project_root.to_string_lossy().into_owned()
+    }
+
+    /// Rust sources of a component, relative to the project root
+    fn component_sources(&self, component: &str) -> Vec<(String, String)> {
+        let dir = self.project_root.join("crates").join(component).join("src");
+        let mut paths = Vec::new();
+        if dir.is_dir() {
+            collect_sources(&dir, &mut paths);
+        } else {
+            paths.extend(
+                named_sources(&self.project_root, component)
+                    .into_iter()
+                    .filter(|p| p.is_file()),
+            );
+        }
+        paths.sort();
+
+        let mut files = Vec::new();
+        let mut budget = MAX_CONTEXT_CHARS;
+        for path in paths {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            if content.len() > budget {
+                continue;
+            }
+            budget -= content.len();
+            let relative = path.strip_prefix(&self.project_root).unwrap_or(&path);
+            files.push((relative.to_string_lossy().into_owned(), content));
+        }
+        files
+    }
+}
+
+/// `path` relative to the working directory, as the filesystem tool expects
+fn relative_to_cwd(path: &Path) -> Result<PathBuf> {
+    if path.is_relative() {
+        return Ok(path.to_path_buf());
+    }
+    let cwd = std::env::current_dir()?;
+    path.strip_prefix(&cwd).map(Path::to_path_buf).map_err(|_| {
+        Error::Config(format!("{} is outside the working directory", path.display()))
+    })
+}
+
+/// Source files named after a component, such as `orchestrator.rs`
+fn named_sources(root: &Path, component: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    collect_sources(&root.join("crates"), &mut paths);
+    collect_sources(&root.join("src"), &mut paths);
+    let name = format!("{}.rs", component);
+    paths.retain(|p| p.file_name().is_some_and(|f| f.to_string_lossy() == name));
+    paths
+}
+
+fn collect_sources(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|n| n != "target") {
+                collect_sources(&path, paths);
+            }
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            paths.push(path);
+        }
+    }
+}
+
+/// Build the prompt asking the model for a patch
+pub fn build_prompt(strategy: &ImprovementStrategy, files: &[(String, String)]) -> String {
+    let files: Vec<String> = files
+        .iter()
+        .map(|(path, content)| format!("--- {} ---\n{}", path, content))
+        .collect();
+
+    format!(
+        "You are improving your own source code.\n\
+        Goal: {}\n{}\n\n\
+        Steps:\n- {}\n\n\
+        Current source of the {} component:\n{}\n\n\
+        Make a small, self-contained change that keeps the build and every test passing. \
+        Respond with a JSON object containing:\n\
+        - summary: one line describing the change, used as the commit message\n\
+        - files: array of objects with path (relative to the repository root) and the complete new content",
+        strategy.name,
+        strategy.description,
+        strategy.implementation.steps.join("\n- "),
+        strategy.target_component,
+        files.join("\n\n"),
+    )
+}
+
+/// Parse the model's patch into a summary and rewritten files
+pub fn parse_patch(content: &str) -> Result<(String, Vec<FileFix>)> {
+    #[derive(Deserialize)]
+    struct Patch {
+        #[serde(default)]
+        summary: String,
+        files: Vec<FileFix>,
+    }
+
+    let start = content
+        .find('{')
+        .ok_or_else(|| Error::Validation("No JSON object in model response".to_string()))?;
+    let end = content
+        .rfind('}')
+        .filter(|end| *end > start)
+        .ok_or_else(|| Error::Validation("Unterminated JSON object in model response".to_string()))?;
+
+    let patch: Patch = serde_json::from_str(&content[start..=end])?;
+    for file in &patch.files {
+        check_path(&file.path)?;
+    }
+    let summary = match patch.summary.trim() {
+        "" => "Self-improvement".to_string(),
+        summary => summary.to_string(),
+    };
+    Ok((summary, patch.files))
+}
+
+/// Patches may only touch tracked project files
+fn check_path(path: &str) -> Result<()> {
+    let path = Path::new(path);
+    let inside = !path.as_os_str().is_empty()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+        && !path.starts_with(".git")
+        && !path.starts_with(".agent");
+    if inside {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "Patch path must be inside the project: {}",
+            path.display()
+        )))
+    }
+}
+
+fn pass_rate(report: &BenchReport) -> f64 {
+    report.models.first().map_or(0.0, |m| m.pass_rate as f64)
+}
+
+/// The candidate lost more pass rate than `tolerance` allows
+pub fn regressed(baseline: f64, candidate: f64, tolerance: f64) -> bool {
+    candidate + tolerance < baseline - f64::EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::improvement::{ImplementationPlan, Impact, RiskLevel};
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn lib(answer: &str) -> FileFix {
+        FileFix {
+            path: "src/lib.rs".to_string(),
+            content: format!(
+                "pub fn answer() -> u32 {{\n    {}\n}}\n\n#[test]\nfn answer_is_42() {{\n    assert_eq!(answer(), 42);\n}}\n",
+                answer
+            ),
+        }
+    }
+
+    #[test]
+    fn test_parse_patch_rejects_paths_outside_project() {
+        let (summary, files) =
+            parse_patch("{\"summary\": \"tidy\", \"files\": [{\"path\": \"src/a.rs\", \"content\": \"\"}]}")
+                .unwrap();
+        assert_eq!(summary, "tidy");
+        assert_eq!(files[0].path, "src/a.rs");
+
+        for path in ["../a.rs", "/etc/passwd", ".git/config", "./src/a.rs", ""] {
+            let response = format!("{{\"files\": [{{\"path\": \"{}\", \"content\": \"\"}}]}}", path);
+            assert!(parse_patch(&response).is_err(), "{}", path);
+        }
+
+        assert!(regressed(0.8, 0.6, 0.1));
+        assert!(!regressed(0.8, 0.7, 0.1));
+        assert!(!regressed(0.8, 0.9, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_code_change_is_gated_promoted_and_reverted() {
+        // The filesystem tool only writes below the working directory
+        let dir = tempfile::tempdir_in(std::env::current_dir().unwrap()).unwrap();
+        let repo = dir.path();
+        run(repo, &["init", "-q", "-b", "main"]);
+        run(repo, &["config", "user.email", "agent@example.com"]);
+        run(repo, &["config", "user.name", "agent"]);
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::write(
+            repo.join("Cargo.toml"),
+            "[package]\nname = \"coding-agent\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join(".gitignore"), "target/\n.agent/\n").unwrap();
+        std::fs::write(repo.join("src/main.rs"), "fn main() {\n    println!(\"0.1.0\");\n}\n").unwrap();
+        std::fs::write(repo.join("src/lib.rs"), &lib("40 + 2").content).unwrap();
+        run(repo, &["add", "-A"]);
+        run(repo, &["commit", "-q", "-m", "init"]);
+
+        let mut config = AgentConfig::default();
+        config.self_compile.build_profile = "dev".to_string();
+        config.self_compile.compile_timeout_seconds = 300;
+        config.self_compile.test_command = vec!["cargo".to_string(), "test".to_string(), "-q".to_string()];
+        config.self_compile.verify_before_restart = true;
+        let improver = CodeImprover::new(
+            &config,
+            Arc::new(IntelligenceEngine::new(Box::new(intelligence::gateway::MockGateway::new()))),
+            Arc::new(tools::ToolFramework::new()),
+            repo,
+        );
+        let strategy = ImprovementStrategy {
+            id: "s1".to_string(),
+            name: "Simplify answer".to_string(),
+            description: "Return the constant directly".to_string(),
+            target_component: "lib".to_string(),
+            change_type: ChangeType::Code,
+            expected_impact: Impact::Low,
+            risk_level: RiskLevel::High,
+            implementation: ImplementationPlan {
+                steps: vec![],
+                rollback_steps: vec![],
+            },
+        };
+        let original = std::fs::read_to_string(repo.join("src/lib.rs")).unwrap();
+
+        // A change that breaks the tests never reaches the tree
+        let failing = CodePatch::new("Break answer", vec![lib("41")], repo).unwrap();
+        assert!(failing.diff.contains("+    41"));
+        assert!(improver.apply(&strategy, &failing).await.is_err());
+        assert_eq!(std::fs::read_to_string(repo.join("src/lib.rs")).unwrap(), original);
+        assert!(run(repo, &["log", "--oneline"]).lines().count() == 1);
+
+        // A passing change is merged, and can be reverted
+        let passing = CodePatch::new("Return 42 directly", vec![lib("42")], repo).unwrap();
+        let change = improver.apply(&strategy, &passing).await.unwrap();
+        assert_eq!(change.change_type, ChangeType::Code);
+        assert!(std::fs::read_to_string(repo.join("src/lib.rs")).unwrap().contains("    42\n"));
+        let binary = change.rollback_data.as_ref().unwrap()["binary"].as_str().unwrap().to_string();
+        assert!(Path::new(&binary).exists());
+
+        improver.revert(&change).await.unwrap();
+        assert_eq!(std::fs::read_to_string(repo.join("src/lib.rs")).unwrap(), original);
+        assert!(run(repo, &["status", "--porcelain"]).is_empty());
+    }
+}
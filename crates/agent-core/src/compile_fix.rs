@@ -0,0 +1,181 @@
+//! Compile-and-fix loop support.
+//!
+//! After the agent edits a watched source file, the configured checker runs
+//! in the task's workspace. New errors are shown to the model, which
+//! rewrites the affected files, for up to a configured number of
+//! iterations. Errors already present before the task's first edit do not
+//! count against it.
+
+use agent_config::{CheckOutputFormat, CheckToolConfig};
+use analysis::checker::{format_diagnostic, CheckReport, Checker, OutputFormat};
+use analysis::Diagnostic;
+use common::{Error, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Runs the configured checker after edits
+pub struct CompileFixer {
+    checker: Checker,
+    extensions: Vec<String>,
+    max_iterations: u32,
+}
+
+impl CompileFixer {
+    /// Build from configuration; `None` if checking is disabled
+    pub fn from_config(config: &CheckToolConfig) -> Option<Self> {
+        let (program, args) = config.command.split_first()?;
+        if !config.enabled {
+            return None;
+        }
+        let format = match config.format {
+            CheckOutputFormat::CargoJson => OutputFormat::CargoJson,
+            CheckOutputFormat::Lines => OutputFormat::Lines,
+        };
+        Some(Self {
+            checker: Checker::new(program.clone(), args.to_vec(), format)
+                .with_timeout(config.timeout_seconds),
+            extensions: config.extensions.clone(),
+            max_iterations: config.max_fix_iterations,
+        })
+    }
+
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+    }
+
+    /// Whether a tool call edits a watched file
+    pub fn watches(&self, tool: Option<&str>, parameters: &serde_json::Value) -> bool {
+        if tool != Some("filesystem") {
+            return false;
+        }
+        let operation = parameters.get("operation").and_then(|v| v.as_str());
+        if !matches!(operation, Some("write") | Some("delete")) {
+            return false;
+        }
+        parameters
+            .get("path")
+            .and_then(|v| v.as_str())
+            .and_then(|path| Path::new(path).extension())
+            .is_some_and(|ext| self.extensions.iter().any(|e| ext == e.as_str()))
+    }
+
+    /// Run the checker in `root`
+    pub async fn check(&self, root: &Path) -> Result<CheckReport> {
+        self.checker.run(root).await
+    }
+}
+
+/// Check state of one task
+#[derive(Debug, Default)]
+pub struct CompileState {
+    /// Errors present before the task's first edit
+    pub baseline: Vec<Diagnostic>,
+    /// New errors left after the last check
+    pub outstanding: Vec<Diagnostic>,
+    /// The checker could not run here; edits are not checked
+    pub unavailable: bool,
+}
+
+/// Result of checking one edit
+#[derive(Debug, Default)]
+pub struct CheckOutcome {
+    /// No new errors remain
+    pub passed: bool,
+    /// Log line describing the outcome
+    pub message: String,
+    /// Tokens spent on fix requests
+    pub tokens_used: u32,
+    pub model_calls: u32,
+}
+
+/// A file rewritten by the model
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileFix {
+    pub path: String,
+    pub content: String,
+}
+
+/// Build the prompt asking the model to fix new errors
+pub fn build_prompt(goal: &str, errors: &[Diagnostic], files: &[(String, String)]) -> String {
+    let errors: Vec<String> = errors.iter().map(format_diagnostic).collect();
+    let files: Vec<String> = files
+        .iter()
+        .map(|(path, content)| format!("--- {} ---\n{}", path, content))
+        .collect();
+
+    format!(
+        "Your last edit for this task does not compile.\n\
+        Task: {}\n\n\
+        New errors:\n{}\n\n\
+        Current contents of the affected files:\n{}\n\n\
+        Fix the errors without changing unrelated code. Respond with a JSON object containing:\n\
+        - files: array of objects with path (as given above) and the complete corrected content",
+        goal,
+        errors.join("\n"),
+        files.join("\n\n"),
+    )
+}
+
+/// Parse the model's rewritten files
+pub fn parse_fixes(content: &str) -> Result<Vec<FileFix>> {
+    #[derive(Deserialize)]
+    struct Fixes {
+        files: Vec<FileFix>,
+    }
+
+    let start = content
+        .find('{')
+        .ok_or_else(|| Error::Validation("No JSON object in model response".to_string()))?;
+    let end = content
+        .rfind('}')
+        .filter(|end| *end > start)
+        .ok_or_else(|| Error::Validation("Unterminated JSON object in model response".to_string()))?;
+
+    let fixes: Fixes = serde_json::from_str(&content[start..=end])?;
+    Ok(fixes.files)
+}
+
+/// Files with errors, each listed once
+pub fn affected_files(errors: &[Diagnostic]) -> Vec<String> {
+    let mut files: Vec<String> = errors
+        .iter()
+        .map(|d| d.location.path.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watches_edits_to_configured_extensions() {
+        let fixer = CompileFixer::from_config(&CheckToolConfig::default()).unwrap();
+        let write = |path: &str| serde_json::json!({"operation": "write", "path": path, "content": ""});
+
+        assert!(fixer.watches(Some("filesystem"), &write("src/lib.rs")));
+        assert!(!fixer.watches(Some("filesystem"), &write("README.md")));
+        assert!(!fixer.watches(
+            Some("filesystem"),
+            &serde_json::json!({"operation": "read", "path": "src/lib.rs"})
+        ));
+        assert!(!fixer.watches(Some("git"), &write("src/lib.rs")));
+
+        let disabled = CheckToolConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(CompileFixer::from_config(&disabled).is_none());
+    }
+
+    #[test]
+    fn test_parse_fixes() {
+        let response = "Here is the fix:\n{\"files\": [{\"path\": \"src/lib.rs\", \"content\": \"fn main() {}\\n\"}]}";
+        let fixes = parse_fixes(response).unwrap();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].path, "src/lib.rs");
+        assert!(parse_fixes("no json here").is_err());
+    }
+}
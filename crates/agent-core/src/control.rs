@@ -0,0 +1,754 @@
+//! Local control API for a running agent.
+//!
+//! A daemon serves JSON-RPC 2.0 over a Unix socket or a loopback TCP port,
+//! one request and one response per line. Every request carries the token
+//! from the configured token file, which is rewritten with a fresh token
+//! each time the server starts. `coding-agent client` is a thin wrapper
+//! around [`ControlClient`].
+//!
+//! Methods: `submit`, `status`, `task`, `sessions`, `cancel`, `approvals`,
+//! `decide`, `metrics` and `swap`.
+//!
+//! `swap` probes a new binary and, if it is healthy, hands the listener and
+//! token over to it, so clients keep talking to the same address.
+
+use crate::budget::ResourceUsage;
+use crate::events::{Event, EventKind};
+use crate::orchestrator::PipelineStage;
+use crate::self_compile::{ControlHandoff, ProbeReport};
+use crate::{Agent, Task, TaskContext, TaskPriority};
+use agent_config::ControlConfig;
+use common::chrono::{DateTime, Utc};
+use common::{Error, Result, TaskId};
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tools::approval::{ApprovalDecision, ApprovalGate};
+use tools::jsonrpc::{Request, Response, RpcError, INVALID_PARAMS, METHOD_NOT_FOUND, NOT_FOUND, PARSE_ERROR, UNAUTHORIZED};
+use tracing::{debug, info, warn};
+
+/// Longest request line accepted
+const MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+
+/// Connections idle for longer than this are closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Sessions kept; the oldest finished ones are forgotten first
+const MAX_SESSIONS: usize = 1000;
+
+/// Where a session is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// A task submitted while the server was running, and what became of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub task_id: TaskId,
+    pub description: String,
+    pub parent_id: Option<TaskId>,
+    pub status: SessionStatus,
+    /// Last pipeline stage reached
+    pub stage: Option<PipelineStage>,
+    pub submitted_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub output: Option<String>,
+    pub usage: Option<ResourceUsage>,
+    pub error: Option<String>,
+}
+
+/// Sessions built up from the agent's event stream
+#[derive(Default)]
+pub struct Sessions {
+    inner: parking_lot::Mutex<HashMap<TaskId, Session>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the sessions from an event
+    pub fn record(&self, event: &Event) {
+        let mut sessions = self.inner.lock();
+        match &event.kind {
+            EventKind::TaskSubmitted {
+                task_id,
+                description,
+                parent_id,
+            } => {
+                if !sessions.contains_key(task_id) && sessions.len() >= MAX_SESSIONS {
+                    evict_oldest(&mut sessions);
+                }
+                sessions.entry(*task_id).or_insert_with(|| Session {
+                    task_id: *task_id,
+                    description: description.clone(),
+                    parent_id: *parent_id,
+                    status: SessionStatus::Queued,
+                    stage: None,
+                    submitted_at: event.timestamp,
+                    finished_at: None,
+                    output: None,
+                    usage: None,
+                    error: None,
+                });
+            }
+            EventKind::StageChanged { task_id, stage } => {
+                if let Some(session) = sessions.get_mut(task_id) {
+                    if session.status == SessionStatus::Queued {
+                        session.status = SessionStatus::Running;
+                    }
+                    session.stage = Some(*stage);
+                }
+            }
+            EventKind::TaskFinished {
+                task_id,
+                success,
+                output,
+                usage,
+                exhausted,
+                error,
+            } => {
+                if let Some(session) = sessions.get_mut(task_id) {
+                    // A cancelled task stays cancelled however it ends
+                    if session.status != SessionStatus::Cancelled {
+                        session.status = if *success {
+                            SessionStatus::Succeeded
+                        } else {
+                            SessionStatus::Failed
+                        };
+                    }
+                    session.finished_at = Some(event.timestamp);
+                    session.output = Some(output.clone());
+                    session.usage = Some(usage.clone());
+                    session.error = error.clone().or_else(|| exhausted.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get(&self, task_id: &TaskId) -> Option<Session> {
+        self.inner.lock().get(task_id).cloned()
+    }
+
+    /// All sessions, oldest first
+    pub fn list(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self.inner.lock().values().cloned().collect();
+        sessions.sort_by_key(|s| s.submitted_at);
+        sessions
+    }
+
+    pub fn mark_cancelled(&self, task_ids: &[TaskId]) {
+        let mut sessions = self.inner.lock();
+        for task_id in task_ids {
+            if let Some(session) = sessions.get_mut(task_id) {
+                if matches!(session.status, SessionStatus::Queued | SessionStatus::Running) {
+                    session.status = SessionStatus::Cancelled;
+                }
+            }
+        }
+    }
+}
+
+/// Forget the oldest finished session, or the oldest one if none has finished
+fn evict_oldest(sessions: &mut HashMap<TaskId, Session>) {
+    let oldest = sessions
+        .values()
+        .min_by_key(|s| (s.finished_at.is_none(), s.submitted_at))
+        .map(|s| s.task_id);
+    if let Some(task_id) = oldest {
+        sessions.remove(&task_id);
+    }
+}
+
+/// A probed binary to hand off to once the `swap` reply is sent
+struct PendingSwap {
+    binary: PathBuf,
+    probe: ProbeReport,
+}
+
+/// A connected control stream
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+enum Listener {
+    Tcp(TcpListener),
+    /// The socket file is removed when the listener is dropped
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+            Listener::Unix(listener, _) => listener.as_raw_fd(),
+        }
+    }
+
+    async fn accept(&self) -> std::io::Result<Box<dyn Connection>> {
+        Ok(match self {
+            Listener::Tcp(listener) => Box::new(listener.accept().await?.0),
+            Listener::Unix(listener, _) => Box::new(listener.accept().await?.0),
+        })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Serves the control API for one agent
+pub struct ControlServer {
+    listener: Listener,
+    token: String,
+    sessions: Sessions,
+}
+
+impl ControlServer {
+    /// Listen on the configured address and write a fresh token
+    ///
+    /// Clients can connect as soon as this returns; requests are answered
+    /// once [`ControlServer::serve`] runs.
+    pub async fn bind(config: &ControlConfig) -> Result<Self> {
+        let listener = match config.tcp_addr() {
+            Some(addr) if !addr.ip().is_loopback() => {
+                return Err(Error::Config(format!(
+                    "Control API must listen on a loopback address, got {}",
+                    addr
+                )));
+            }
+            Some(addr) => Listener::Tcp(TcpListener::bind(addr).await?),
+            None => {
+                let path = PathBuf::from(&config.listen);
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                // A socket left behind by a crashed daemon blocks the bind,
+                // but one that still answers belongs to a live daemon
+                if path.exists() {
+                    if UnixStream::connect(&path).await.is_ok() {
+                        return Err(Error::Config(format!(
+                            "Control API already running on {}",
+                            config.listen
+                        )));
+                    }
+                    tokio::fs::remove_file(&path).await?;
+                }
+                let listener = UnixListener::bind(&path)?;
+                restrict_to_owner(&path).await?;
+                Listener::Unix(listener, path)
+            }
+        };
+
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        write_token(&config.token_file, &token).await?;
+        info!("Control API listening on {}", config.listen);
+
+        Ok(Self {
+            listener,
+            token,
+            sessions: Sessions::new(),
+        })
+    }
+
+    /// Serve on the listener and token handed over by a replaced daemon
+    pub fn inherit(config: &ControlConfig, handoff: ControlHandoff) -> Result<Self> {
+        set_close_on_exec(handoff.fd, true)?;
+        // SAFETY: the replaced process left this descriptor open for us and
+        // nothing else in this process owns it
+        let listener = match config.tcp_addr() {
+            Some(_) => {
+                let listener = unsafe { std::net::TcpListener::from_raw_fd(handoff.fd) };
+                listener.set_nonblocking(true)?;
+                Listener::Tcp(TcpListener::from_std(listener)?)
+            }
+            None => {
+                let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(handoff.fd) };
+                listener.set_nonblocking(true)?;
+                Listener::Unix(UnixListener::from_std(listener)?, PathBuf::from(&config.listen))
+            }
+        };
+        info!("Control API taken over on {}", config.listen);
+
+        Ok(Self {
+            listener,
+            token: handoff.token,
+            sessions: Sessions::new(),
+        })
+    }
+
+    /// Keep the listener open across exec for the process replacing this one
+    pub fn hand_off(&self) -> Result<ControlHandoff> {
+        let fd = self.listener.as_raw_fd();
+        set_close_on_exec(fd, false)?;
+        Ok(ControlHandoff {
+            fd,
+            token: self.token.clone(),
+        })
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    /// Answer requests until the agent's event stream closes
+    pub async fn serve(&self, agent: &Agent, approvals: &ApprovalGate) -> Result<()> {
+        let mut events = agent.subscribe();
+        let mut connections = FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => self.sessions.record(&event),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Control API missed {} event(s); session status may be stale", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                accepted = self.listener.accept() => match accepted {
+                    Ok(connection) => connections.push(self.serve_connection(connection, agent, approvals)),
+                    Err(e) => warn!("Control API connection failed: {}", e),
+                },
+                Some(()) = connections.next(), if !connections.is_empty() => {}
+            }
+        }
+    }
+
+    async fn serve_connection(&self, connection: Box<dyn Connection>, agent: &Agent, approvals: &ApprovalGate) {
+        let (reader, mut writer) = tokio::io::split(connection);
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut line = String::new();
+            let mut limited = (&mut reader).take(MAX_REQUEST_BYTES);
+            match tokio::time::timeout(IDLE_TIMEOUT, limited.read_line(&mut line)).await {
+                Ok(Ok(0)) | Err(_) => return,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    debug!("Control API read failed: {}", e);
+                    return;
+                }
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (response, swap) = self.handle_line(&line, agent, approvals).await;
+            let Ok(mut reply) = serde_json::to_vec(&response) else {
+                return;
+            };
+            reply.push(b'\n');
+            if writer.write_all(&reply).await.is_err() || writer.flush().await.is_err() {
+                return;
+            }
+
+            if let Some(PendingSwap { binary, probe }) = swap {
+                self.swap(&binary, probe, agent).await;
+            }
+        }
+    }
+
+    /// Hand the queue and listener to a probed binary; returns only on failure
+    async fn swap(&self, binary: &Path, probe: ProbeReport, agent: &Agent) {
+        let control = match self.hand_off() {
+            Ok(control) => control,
+            Err(e) => {
+                warn!("Cannot hand off the control listener: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = agent.hand_off(binary, probe, Some(control)).await {
+            warn!("Swap to {:?} failed, keeping this process: {}", binary, e);
+        }
+        // The listener must not leak into anything spawned later
+        if let Err(e) = set_close_on_exec(self.listener.as_raw_fd(), true) {
+            warn!("Cannot restore close-on-exec on the control listener: {}", e);
+        }
+    }
+
+    /// Answer one request, along with the swap it asks for, if any
+    async fn handle_line(
+        &self,
+        line: &str,
+        agent: &Agent,
+        approvals: &ApprovalGate,
+    ) -> (Response, Option<PendingSwap>) {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return (Response::new(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))), None),
+        };
+        let id = request.id.unwrap_or_default();
+        let token = request.token.as_deref().unwrap_or_default();
+        if !tokens_match(token.as_bytes(), self.token.as_bytes()) {
+            return (Response::new(id, Err(RpcError::new(UNAUTHORIZED, "Invalid control token"))), None);
+        }
+        debug!("Control API request: {}", request.method);
+        match self.dispatch(&request.method, request.params, agent, approvals).await {
+            Ok((result, swap)) => (Response::new(id, Ok(result)), swap),
+            Err(error) => (Response::new(id, Err(error)), None),
+        }
+    }
+
+    async fn dispatch(
+        &self,
+        method: &str,
+        params: Value,
+        agent: &Agent,
+        approvals: &ApprovalGate,
+    ) -> std::result::Result<(Value, Option<PendingSwap>), RpcError> {
+        if method == "swap" {
+            #[derive(Deserialize)]
+            struct Params {
+                binary: PathBuf,
+            }
+            let Params { binary } = parse_params(params)?;
+            let probe = agent.probe_binary(&binary).await?;
+            let result = serde_json::to_value(&probe).map_err(Error::from)?;
+            return Ok((result, Some(PendingSwap { binary, probe })));
+        }
+
+        let result = match method {
+            "submit" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    description: String,
+                    #[serde(default)]
+                    priority: Option<TaskPriority>,
+                    #[serde(default)]
+                    workspace: Option<PathBuf>,
+                }
+                let params: Params = parse_params(params)?;
+                let mut task = Task::new(params.description);
+                if let Some(priority) = params.priority {
+                    task = task.with_priority(priority);
+                }
+                if let Some(workspace) = params.workspace {
+                    task = task.with_context(TaskContext {
+                        workspace_path: Some(workspace),
+                        ..Default::default()
+                    });
+                }
+                let submitted = Event {
+                    timestamp: Utc::now(),
+                    kind: EventKind::TaskSubmitted {
+                        task_id: task.id,
+                        description: task.description.clone(),
+                        parent_id: None,
+                    },
+                };
+                let task_id = agent.submit_task(task).await?;
+                // Known before the broadcast reaches the serve loop
+                self.sessions.record(&submitted);
+                Ok(json!({ "task_id": task_id }))
+            }
+            "status" => {
+                let state = agent.current_state().await;
+                let running = match &state {
+                    crate::state::AgentState::Running(task) => Some(task.id),
+                    _ => None,
+                };
+                Ok(json!({
+                    "state": state.to_string(),
+                    "running": running,
+                    "queued": agent.queue_len().await,
+                    "sessions": self.sessions.list().len(),
+                    "pending_approvals": approvals.pending().len(),
+                }))
+            }
+            "task" => {
+                let task_id = task_id_param(params)?;
+                let session = self
+                    .sessions
+                    .get(&task_id)
+                    .ok_or_else(|| Error::NotFound(format!("No session for task {}", task_id)))?;
+                Ok(serde_json::to_value(session).map_err(Error::from)?)
+            }
+            "sessions" => Ok(serde_json::to_value(self.sessions.list()).map_err(Error::from)?),
+            "cancel" => {
+                let task_id = task_id_param(params)?;
+                let cancelled = agent.cancel_task(task_id).await;
+                self.sessions.mark_cancelled(&cancelled);
+                Ok(json!({ "cancelled": cancelled }))
+            }
+            "approvals" => Ok(serde_json::to_value(approvals.pending()).map_err(Error::from)?),
+            "decide" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    /// Request id or a unique prefix of one
+                    id: String,
+                    #[serde(flatten)]
+                    decision: ApprovalDecision,
+                    #[serde(default)]
+                    reviewer: Option<String>,
+                }
+                let params: Params = parse_params(params)?;
+                let pending = approvals.pending();
+                let matches: Vec<&str> = pending
+                    .iter()
+                    .map(|r| r.id.as_str())
+                    .filter(|id| id.starts_with(params.id.as_str()))
+                    .collect();
+                let id = match matches.as_slice() {
+                    [id] => id.to_string(),
+                    [] => {
+                        return Err(RpcError::new(
+                            NOT_FOUND,
+                            format!("No pending approval request matches '{}'", params.id),
+                        ))
+                    }
+                    _ => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            format!("'{}' matches several pending requests", params.id),
+                        ))
+                    }
+                };
+                let reviewer = params.reviewer.unwrap_or_else(|| "control".to_string());
+                approvals.decide(&id, params.decision, &reviewer)?;
+                Ok(json!({ "id": id }))
+            }
+            "metrics" => Ok(serde_json::to_value(agent.get_metrics().await).map_err(Error::from)?),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        };
+        result.map(|result| (result, None))
+    }
+}
+
+/// Compare tokens without leaking how much of a guess was right
+fn tokens_match(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn task_id_param(params: Value) -> std::result::Result<TaskId, RpcError> {
+    #[derive(Deserialize)]
+    struct Params {
+        task_id: TaskId,
+    }
+    parse_params::<Params>(params).map(|p| p.task_id)
+}
+
+/// Write the token so only the current user can read it
+async fn write_token(path: &Path, token: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // Recreate the file so an older, looser mode is not kept
+    let _ = tokio::fs::remove_file(path).await;
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .await?;
+    file.write_all(token.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+fn set_close_on_exec(fd: RawFd, close: bool) -> Result<()> {
+    // SAFETY: fcntl only reads and sets the descriptor flags of an fd we own
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let flags = if close {
+        flags | libc::FD_CLOEXEC
+    } else {
+        flags & !libc::FD_CLOEXEC
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+async fn restrict_to_owner(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    Ok(())
+}
+
+/// Talks to a running daemon's control API
+pub struct ControlClient {
+    listen: String,
+    token: String,
+}
+
+impl ControlClient {
+    /// Read the daemon's token; fails if no daemon has written one
+    pub async fn new(config: &ControlConfig) -> Result<Self> {
+        let token = tokio::fs::read_to_string(&config.token_file).await.map_err(|e| {
+            Error::NotFound(format!(
+                "Cannot read control token from {} (is the daemon running with control enabled?): {}",
+                config.token_file.display(),
+                e
+            ))
+        })?;
+        Ok(Self {
+            listen: config.listen.clone(),
+            token: token.trim().to_string(),
+        })
+    }
+
+    /// Call a method and return its result
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let request = Request::new(Some(json!(1)), method, params).with_token(&self.token);
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+
+        let connection: Box<dyn Connection> = match self.listen.parse::<std::net::SocketAddr>() {
+            Ok(addr) => Box::new(TcpStream::connect(addr).await?),
+            Err(_) => Box::new(UnixStream::connect(&self.listen).await?),
+        };
+        let (reader, mut writer) = tokio::io::split(connection);
+        writer.write_all(&line).await?;
+        writer.flush().await?;
+
+        let mut reply = String::new();
+        BufReader::new(reader).read_line(&mut reply).await?;
+        if reply.is_empty() {
+            return Err(Error::ExternalService("Control API closed the connection".to_string()));
+        }
+        let response: Response = serde_json::from_str(&reply)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(error.into()),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind) -> Event {
+        Event {
+            timestamp: Utc::now(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_sessions_follow_events() {
+        let sessions = Sessions::new();
+        let (done, dropped) = (TaskId::new(), TaskId::new());
+        for task_id in [done, dropped] {
+            sessions.record(&event(EventKind::TaskSubmitted {
+                task_id,
+                description: "fix the parser".to_string(),
+                parent_id: None,
+            }));
+        }
+        sessions.record(&event(EventKind::StageChanged {
+            task_id: done,
+            stage: PipelineStage::Execution,
+        }));
+        assert_eq!(sessions.get(&done).unwrap().status, SessionStatus::Running);
+
+        sessions.record(&event(EventKind::TaskFinished {
+            task_id: done,
+            success: true,
+            output: "parser fixed".to_string(),
+            usage: ResourceUsage::default(),
+            exhausted: None,
+            error: None,
+        }));
+        let session = sessions.get(&done).unwrap();
+        assert_eq!(session.status, SessionStatus::Succeeded);
+        assert_eq!(session.output.as_deref(), Some("parser fixed"));
+
+        sessions.mark_cancelled(&[done, dropped]);
+        assert_eq!(sessions.get(&done).unwrap().status, SessionStatus::Succeeded);
+        assert_eq!(sessions.get(&dropped).unwrap().status, SessionStatus::Cancelled);
+        assert_eq!(sessions.list().len(), 2);
+
+        // Past the limit the oldest finished session goes first
+        for _ in 0..MAX_SESSIONS - 1 {
+            sessions.record(&event(EventKind::TaskSubmitted {
+                task_id: TaskId::new(),
+                description: "later".to_string(),
+                parent_id: None,
+            }));
+        }
+        assert_eq!(sessions.list().len(), MAX_SESSIONS);
+        assert!(sessions.get(&done).is_none());
+        assert!(sessions.get(&dropped).is_some());
+
+        assert!(tokens_match(b"secret", b"secret"));
+        assert!(!tokens_match(b"secreT", b"secret"));
+        assert!(!tokens_match(b"secret!", b"secret"));
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ControlConfig {
+            enabled: true,
+            listen: dir.path().join("control.sock").to_string_lossy().into_owned(),
+            token_file: dir.path().join("control.token"),
+        };
+        let agent = Agent::new(agent_config::AgentConfig::default());
+        let approvals = ApprovalGate::new(Default::default());
+        // A socket left by a dead daemon is replaced; a live one is not
+        drop(std::os::unix::net::UnixListener::bind(&config.listen).unwrap());
+        let server = ControlServer::bind(&config).await.unwrap();
+        let second = ControlServer::bind(&config).await;
+        assert!(matches!(second, Err(Error::Config(message)) if message.contains("already running")));
+
+        let calls = async {
+            let client = ControlClient::new(&config).await.unwrap();
+            let submitted = client
+                .call("submit", json!({"description": "Add a README section"}))
+                .await
+                .unwrap();
+            let task_id = submitted["task_id"].clone();
+
+            let session = client.call("task", json!({ "task_id": task_id })).await.unwrap();
+            assert_eq!(session["status"], "queued");
+            let status = client.call("status", Value::Null).await.unwrap();
+            assert_eq!(status["queued"], 1);
+
+            let cancelled = client.call("cancel", json!({ "task_id": task_id })).await.unwrap();
+            assert_eq!(cancelled["cancelled"], json!([task_id]));
+            let sessions = client.call("sessions", Value::Null).await.unwrap();
+            assert_eq!(sessions[0]["status"], "cancelled");
+
+            let unknown = client.call("reboot", Value::Null).await;
+            assert!(matches!(unknown, Err(Error::Validation(_))));
+
+            let intruder = ControlClient {
+                listen: config.listen.clone(),
+                token: "guess".to_string(),
+            };
+            let denied = intruder.call("metrics", Value::Null).await;
+            assert!(matches!(denied, Err(Error::PermissionDenied(_))));
+        };
+
+        tokio::select! {
+            result = server.serve(&agent, &approvals) => panic!("server stopped: {:?}", result),
+            _ = calls => {}
+        }
+    }
+}
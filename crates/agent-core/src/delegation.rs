@@ -0,0 +1,414 @@
+//! Sub-agent delegation.
+//!
+//! A parent task can hand a sub-task to a child orchestrator that sees a
+//! narrower tool set and path scope, runs under its own token and time
+//! budget, and may use a different model. Children report structured
+//! results back, and cancelling a parent cancels all of its descendants.
+
+use common::{Error, Result, TaskId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+
+/// What a sub-agent may touch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolScope {
+    /// Allowed tool names; `None` allows every tool
+    pub tools: Option<HashSet<String>>,
+    /// Allowed path prefixes for every path argument of a call; empty allows any path
+    pub paths: Vec<PathBuf>,
+}
+
+/// Argument names whose values are paths, alone or in a list
+pub(crate) const PATH_ARGUMENTS: [&str; 9] = ["path", "paths", "file", "files", "dir", "directory", "cwd", "source", "destination"];
+
+/// Whether a tool accepts any path argument
+pub fn takes_path(tool: &dyn tools::Tool) -> bool {
+    tool.parameters()
+        .iter()
+        .any(|parameter| PATH_ARGUMENTS.contains(&parameter.name.as_str()))
+}
+
+impl ToolScope {
+    /// Whether a tool is in the allowed set
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        self.tools.as_ref().map_or(true, |tools| tools.contains(tool))
+    }
+
+    /// Check a tool call against the scope
+    ///
+    /// A path-scoped call to a tool that `takes_path` must name its paths;
+    /// left out, they would default to the whole working directory.
+    pub fn check(&self, tool: &str, parameters: &serde_json::Value, takes_path: bool) -> Result<()> {
+        if !self.allows_tool(tool) {
+            return Err(Error::PermissionDenied(format!(
+                "Tool '{}' is outside this sub-agent's scope",
+                tool
+            )));
+        }
+        if self.paths.is_empty() {
+            return Ok(());
+        }
+        let mut paths = Vec::new();
+        path_arguments(parameters, &mut paths);
+        if paths.is_empty() && takes_path {
+            return Err(Error::PermissionDenied(format!(
+                "Tool '{}' needs an explicit path inside this sub-agent's scope",
+                tool
+            )));
+        }
+        match paths.into_iter().find(|path| !self.allows_path(Path::new(path))) {
+            Some(path) => Err(Error::PermissionDenied(format!(
+                "Path '{}' is outside this sub-agent's scope",
+                path
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn allows_path(&self, path: &Path) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        if path.is_absolute() || path.components().any(|c| matches!(c, Component::ParentDir)) {
+            return false;
+        }
+        let path = normalize(path);
+        self.paths.iter().any(|prefix| path.starts_with(normalize(prefix)))
+    }
+
+    /// Restrict a requested scope to what this (parent) scope allows
+    ///
+    /// Fails if the request names only paths outside this scope.
+    pub fn narrow(&self, requested: &ToolScope) -> Result<ToolScope> {
+        let tools = match (&self.tools, &requested.tools) {
+            (Some(parent), Some(child)) => Some(parent.intersection(child).cloned().collect()),
+            (Some(parent), None) => Some(parent.clone()),
+            (None, child) => child.clone(),
+        };
+
+        let paths = if requested.paths.is_empty() {
+            self.paths.clone()
+        } else {
+            let inside: Vec<PathBuf> = requested
+                .paths
+                .iter()
+                .filter(|p| self.allows_path(p))
+                .cloned()
+                .collect();
+            if inside.is_empty() {
+                return Err(Error::PermissionDenied(
+                    "Sub-agent path scope lies outside its parent's".to_string(),
+                ));
+            }
+            inside
+        };
+
+        Ok(ToolScope { tools, paths })
+    }
+}
+
+/// How a sub-task is delegated
+#[derive(Debug, Clone, Default)]
+pub struct DelegationSpec {
+    pub scope: ToolScope,
+    /// Token budget for the child's execution loop
+    pub max_tokens: Option<u32>,
+    /// Wall-clock budget for the whole sub-task
+    pub max_duration: Option<Duration>,
+    /// Model to use instead of the parent's
+    pub model: Option<String>,
+}
+
+impl DelegationSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow these tools
+    pub fn with_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scope.tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only allow paths under these prefixes
+    pub fn with_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.scope.paths = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the token budget
+    pub fn with_token_budget(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the time budget
+    pub fn with_time_budget(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Use a different model for the child
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+/// How a sub-agent finished
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SubAgentStatus {
+    Completed,
+    Failed { error: String },
+    /// The time budget ran out
+    TimedOut,
+    /// The sub-agent or one of its ancestors was cancelled
+    Cancelled,
+}
+
+/// Structured result a sub-agent returns to its parent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubAgentResult {
+    pub task_id: TaskId,
+    pub parent_id: TaskId,
+    pub status: SubAgentStatus,
+    /// The child's task result, when it ran to completion
+    pub result: Option<super::TaskResult>,
+    pub duration_ms: u64,
+}
+
+impl SubAgentResult {
+    /// Whether the sub-task completed and its result validated
+    pub fn succeeded(&self) -> bool {
+        self.status == SubAgentStatus::Completed && self.result.as_ref().is_some_and(|r| r.success)
+    }
+}
+
+/// A running sub-agent
+pub struct SubAgentHandle {
+    pub task_id: TaskId,
+    pub parent_id: TaskId,
+    pub(crate) result: oneshot::Receiver<SubAgentResult>,
+}
+
+impl SubAgentHandle {
+    /// Wait for the sub-agent's result
+    pub async fn join(self) -> SubAgentResult {
+        let (task_id, parent_id) = (self.task_id, self.parent_id);
+        // A dropped sender means the child was aborted
+        self.result.await.unwrap_or(SubAgentResult {
+            task_id,
+            parent_id,
+            status: SubAgentStatus::Cancelled,
+            result: None,
+            duration_ms: 0,
+        })
+    }
+}
+
+/// Live parent/child links between delegated tasks
+#[derive(Default)]
+pub struct Delegations {
+    inner: parking_lot::Mutex<DelegationState>,
+}
+
+#[derive(Default)]
+struct DelegationState {
+    children: HashMap<TaskId, Vec<TaskId>>,
+    running: HashMap<TaskId, AbortHandle>,
+    scopes: HashMap<TaskId, ToolScope>,
+}
+
+impl Delegations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Effective scope of a delegated task, if it is one
+    ///
+    /// Scopes outlive the task so later children of it are still narrowed.
+    pub fn scope_of(&self, task_id: &TaskId) -> Option<ToolScope> {
+        self.inner.lock().scopes.get(task_id).cloned()
+    }
+
+    /// Delegated children of a task that are still running
+    pub fn running_children(&self, parent_id: &TaskId) -> Vec<TaskId> {
+        let state = self.inner.lock();
+        state
+            .children
+            .get(parent_id)
+            .map(|children| {
+                children
+                    .iter()
+                    .filter(|c| state.running.contains_key(c))
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every delegated task that is still running
+    pub fn running(&self) -> Vec<TaskId> {
+        self.inner.lock().running.keys().copied().collect()
+    }
+
+    /// Start a child and record it under its parent
+    ///
+    /// The registry stays locked while the child is spawned, so a child that
+    /// finishes immediately cannot race its own registration.
+    pub(crate) fn spawn<F>(&self, parent_id: TaskId, child_id: TaskId, scope: ToolScope, future: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut state = self.inner.lock();
+        let handle = tokio::spawn(future);
+        state.children.entry(parent_id).or_default().push(child_id);
+        state.running.insert(child_id, handle.abort_handle());
+        state.scopes.insert(child_id, scope);
+    }
+
+    /// Forget a child that has finished
+    pub(crate) fn finish(&self, parent_id: &TaskId, child_id: &TaskId) {
+        let mut state = self.inner.lock();
+        state.running.remove(child_id);
+        if let Some(children) = state.children.get_mut(parent_id) {
+            children.retain(|c| c != child_id);
+            if children.is_empty() {
+                state.children.remove(parent_id);
+            }
+        }
+    }
+
+    /// Abort every running descendant of a task
+    ///
+    /// Returns the IDs of the cancelled sub-agents.
+    pub fn cancel(&self, task_id: &TaskId) -> Vec<TaskId> {
+        let mut state = self.inner.lock();
+        let mut cancelled = Vec::new();
+        let mut stack = state.children.remove(task_id).unwrap_or_default();
+        while let Some(child) = stack.pop() {
+            if let Some(handle) = state.running.remove(&child) {
+                handle.abort();
+                cancelled.push(child);
+            }
+            stack.extend(state.children.remove(&child).unwrap_or_default());
+        }
+        cancelled
+    }
+}
+
+/// Collect the values of path arguments, including in nested objects
+fn path_arguments<'a>(value: &'a serde_json::Value, paths: &mut Vec<&'a str>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                if PATH_ARGUMENTS.contains(&key.as_str()) {
+                    match value {
+                        serde_json::Value::String(path) => paths.push(path),
+                        serde_json::Value::Array(items) => paths.extend(items.iter().filter_map(|i| i.as_str())),
+                        _ => {}
+                    }
+                }
+                path_arguments(value, paths);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                path_arguments(item, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_checks_and_narrowing() {
+        let parent = DelegationSpec::new()
+            .with_tools(["filesystem", "search"])
+            .with_paths(["crates/tools"])
+            .scope;
+
+        assert!(parent
+            .check("filesystem", &serde_json::json!({"path": "./crates/tools/src/lib.rs"}), true)
+            .is_ok());
+        assert!(parent.check("git", &serde_json::json!({"path": "crates/tools"}), true).is_err());
+        assert!(parent
+            .check("filesystem", &serde_json::json!({"path": "crates/tools/../config/src/lib.rs"}), true)
+            .is_err());
+        assert!(parent.check("filesystem", &serde_json::json!({"path": "src/main.rs"}), true).is_err());
+
+        // Every path argument is checked, not only `path`
+        let git = DelegationSpec::new().with_tools(["git"]).with_paths(["crates/tools"]).scope;
+        let files = |files: serde_json::Value| serde_json::json!({"operation": "add", "path": "crates/tools", "files": files});
+        assert!(git.check("git", &files(serde_json::json!(["crates/tools/src/lib.rs"])), true).is_ok());
+        assert!(git.check("git", &files(serde_json::json!(["crates/tools/src/lib.rs", "src/main.rs"])), true).is_err());
+        assert!(git
+            .check("git", &serde_json::json!({"operation": "mv", "source": "crates/tools/a.rs", "destination": "../a.rs"}), true)
+            .is_err());
+
+        // Tools that take paths must name one rather than default to the whole tree
+        let status = serde_json::json!({"operation": "status"});
+        assert!(git.check("git", &status, true).is_err());
+        assert!(git.check("git", &serde_json::json!({"operation": "status", "path": "crates/tools"}), true).is_ok());
+        let http = DelegationSpec::new().with_paths(["crates/tools"]).scope;
+        assert!(http.check("http", &serde_json::json!({"method": "GET", "url": "http://x"}), false).is_ok());
+        assert!(ToolScope::default().check("git", &status, true).is_ok());
+        assert!(takes_path(&tools::git::GitTool));
+
+        let child = parent
+            .narrow(&DelegationSpec::new().with_tools(["filesystem", "git"]).with_paths(["crates/tools/src"]).scope)
+            .unwrap();
+        assert_eq!(child.tools, Some(HashSet::from(["filesystem".to_string()])));
+        assert_eq!(child.paths, vec![PathBuf::from("crates/tools/src")]);
+
+        assert!(parent.narrow(&DelegationSpec::new().with_paths(["src"]).scope).is_err());
+        assert_eq!(parent.narrow(&ToolScope::default()).unwrap(), parent);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_cascades_to_descendants() {
+        let delegations = Delegations::new();
+        let (parent, child, grandchild) = (TaskId::new(), TaskId::new(), TaskId::new());
+
+        let mut handles = Vec::new();
+        for (owner, id) in [(parent, child), (child, grandchild)] {
+            let (tx, rx) = oneshot::channel::<SubAgentResult>();
+            delegations.spawn(owner, id, ToolScope::default(), async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                drop(tx);
+            });
+            handles.push(SubAgentHandle { task_id: id, parent_id: owner, result: rx });
+        }
+        assert_eq!(delegations.running_children(&parent), vec![child]);
+
+        let cancelled = delegations.cancel(&parent);
+        assert_eq!(cancelled.len(), 2);
+        for handle in handles {
+            assert_eq!(handle.join().await.status, SubAgentStatus::Cancelled);
+        }
+        assert!(delegations.running_children(&child).is_empty());
+    }
+}
@@ -5,7 +5,9 @@
 
 use serde::{Deserialize, Serialize};
 use common::{Result, Error};
+use tracing::{warn, Instrument};
 use crate::intelligence::IntentCategory;
+use crate::otel;
 
 /// A distinct role the agent assumes during evaluation
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -20,6 +22,18 @@ pub enum Persona {
     ProductOwner,
 }
 
+impl Persona {
+    /// What a judge in this role pays attention to
+    pub fn judge_instructions(&self) -> &'static str {
+        match self {
+            Persona::Worker => "You produced this work yourself. Assess it honestly against each criterion; do not defend shortcuts or gaps.",
+            Persona::Architect => "Focus on design: structure, module boundaries, fit with the existing architecture and long-term maintainability.",
+            Persona::Reviewer => "Focus on correctness: bugs, unhandled edge cases, security problems and code quality. Be skeptical and cite specifics.",
+            Persona::ProductOwner => "Focus on the user: does the output solve the request as asked, and is it usable and clearly communicated?",
+        }
+    }
+}
+
 impl std::fmt::Display for Persona {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -115,6 +129,12 @@ pub struct EvaluationReport {
     pub weighted_score: f32,
     pub max_possible_score: f32,
     pub timestamp: common::chrono::DateTime<common::chrono::Utc>,
+    /// Model that judged
+    #[serde(default)]
+    pub model: String,
+    /// Tokens the judge's reply used
+    #[serde(default)]
+    pub tokens_used: u32,
 }
 
 impl EvaluationReport {
@@ -147,15 +167,24 @@ impl EvaluationReport {
 pub struct EvaluationEngine {
     // In a real system, this would connect to a DB
     history: std::sync::Arc<tokio::sync::RwLock<Vec<EvaluationReport>>>,
+    /// Score standard deviation above which judges are said to disagree
+    disagreement_threshold: f32,
 }
 
 impl EvaluationEngine {
     pub fn new() -> Self {
         Self {
             history: std::sync::Arc::new(tokio::sync::RwLock::new(Vec::new())),
+            disagreement_threshold: DEFAULT_DISAGREEMENT_THRESHOLD,
         }
     }
 
+    /// Set the score spread at which consensus reports are flagged
+    pub fn with_disagreement_threshold(mut self, threshold: f32) -> Self {
+        self.disagreement_threshold = threshold;
+        self
+    }
+
     /// Perform a cross-evaluation using the LLM
     pub async fn evaluate(
         &self,
@@ -165,69 +194,26 @@ impl EvaluationEngine {
         evaluator_persona: Persona,
     ) -> Result<EvaluationReport> {
         let rubric = Rubric::for_intent(&task.intent.category);
-        
-        let mut criteria_strings = Vec::new();
-        for m in &rubric.criteria {
-            criteria_strings.push(format!("- {}: {}", m.name, m.description));
-        }
-        let criteria_text = criteria_strings.join("\n");
-
-        let prompt = format!(
-            r###"You are acting as a {}. 
-Your goal is to evaluate the following work output based on strict criteria.
-
-Task Description: "{}"
-
-Output to Evaluate:
-```
-{}
-```
-
-Rubric Criteria:
-{}
-
-Provide your evaluation in JSON format matching this structure:
-[
-  {{ "name": "MetricName", "score": <1-10 integer>, "reasoning": "..." }}, ...
-]
-Only return the JSON array."###,
-            evaluator_persona,
-            task.description,
-            common::utils::truncate(output, 2000), 
-            criteria_text
-        );
+        let prompt = build_judge_prompt(&evaluator_persona, &task.description, output, &rubric);
 
         // Call LLM
         let context = crate::intelligence::Context::default();
-        let response = intelligence.generate(&context, &prompt).await?;
-        
-        // Parse JSON response
-        let clean_json = response.content
-            .trim()
-            .trim_start_matches("```json")
-            .trim_start_matches("```")
-            .trim_end_matches("```");
-            
-        let scored_metrics: Vec<MetricRaw> = serde_json::from_str(clean_json)
-            .map_err(|e| Error::Serialization(e))?;
-
-        // Merge scores back into rubric
-        let mut final_metrics = rubric.criteria;
-        for metric in &mut final_metrics {
-            if let Some(scored) = scored_metrics.iter().find(|m| m.name == metric.name) {
-                metric.score = scored.score;
-                metric.reasoning = scored.reasoning.clone();
-            }
-        }
+        let span = otel::chat_span(None);
+        let response = intelligence.generate(&context, &prompt).instrument(span.clone()).await;
+        otel::record_generation(&span, &response);
+        let response = response?;
+        let metrics = parse_judgement(&response.content, &rubric)?;
 
         let mut report = EvaluationReport {
             task_id: task.id.to_string(),
             evaluator: evaluator_persona,
             evaluatee: Persona::Worker,
-            metrics: final_metrics,
+            metrics,
             weighted_score: 0.0,
             max_possible_score: 0.0,
             timestamp: common::chrono::Utc::now(),
+            model: response.model,
+            tokens_used: response.tokens_used,
         };
         
         report.calculate_score();
@@ -237,6 +223,40 @@ Only return the JSON array."###,
         
         Ok(report)
     }
+
+    /// Have several judges evaluate the same output and combine their scores
+    ///
+    /// Judges whose replies fail or cannot be parsed are left out; it is an
+    /// error if none remain.
+    pub async fn evaluate_with_judges(
+        &self,
+        intelligence: &crate::intelligence::IntelligenceEngine,
+        task: &crate::Task,
+        output: &str,
+        judges: &[Persona],
+    ) -> Result<Consensus> {
+        let mut reports = Vec::with_capacity(judges.len());
+        for judge in judges {
+            match self.evaluate(intelligence, task, output, judge.clone()).await {
+                Ok(report) => reports.push(report),
+                Err(e) => warn!("{} judge failed for task {}: {}", judge, task.id, e),
+            }
+        }
+        if reports.is_empty() {
+            return Err(Error::Execution(format!("No judge produced an evaluation for task {}", task.id)));
+        }
+
+        let consensus = Consensus::from_reports(task.id.to_string(), reports, self.disagreement_threshold);
+        if consensus.flagged {
+            warn!(
+                "Judges disagree on task {} (spread {:.1} on {})",
+                task.id,
+                consensus.disagreement,
+                consensus.disputed().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+            );
+        }
+        Ok(consensus)
+    }
     
     pub async fn get_dashboard(&self) -> String {
         let history = self.history.read().await;
@@ -263,9 +283,269 @@ Only return the JSON array."###,
     }
 }
 
+/// Build the prompt asking a judge to score `output` against `rubric`
+pub fn build_judge_prompt(judge: &Persona, task_description: &str, output: &str, rubric: &Rubric) -> String {
+    let criteria_text = rubric
+        .criteria
+        .iter()
+        .map(|m| format!("- {}: {}", m.name, m.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r###"You are acting as a {}. {}
+Your goal is to evaluate the following work output based on strict criteria.
+
+Task Description: "{}"
+
+Output to Evaluate:
+```
+{}
+```
+
+Rubric Criteria:
+{}
+
+Score every criterion above exactly once, using its exact name, with an integer
+from 1 (unacceptable) to 10 (excellent) and a short justification:
+[
+  {{ "name": "CriterionName", "score": <1-10 integer>, "reasoning": "..." }}, ...
+]
+Only return the JSON array."###,
+        judge,
+        judge.judge_instructions(),
+        task_description,
+        common::utils::truncate(output, 2000),
+        criteria_text
+    )
+}
+
 #[derive(Deserialize)]
 struct MetricRaw {
     name: String,
-    score: u8,
+    score: serde_json::Value,
     reasoning: String,
-}
\ No newline at end of file
+}
+
+/// Parse a judge's reply into the rubric's metrics
+///
+/// The reply must contain a JSON array scoring every criterion exactly once
+/// with an integer from 1 to 10 and non-empty reasoning; anything else is a
+/// validation error rather than a silently missing score.
+pub fn parse_judgement(content: &str, rubric: &Rubric) -> Result<Vec<Metric>> {
+    let start = content
+        .find('[')
+        .ok_or_else(|| Error::Validation("No JSON array in judge reply".to_string()))?;
+    let end = content
+        .rfind(']')
+        .filter(|end| *end > start)
+        .ok_or_else(|| Error::Validation("Unterminated JSON array in judge reply".to_string()))?;
+    let scored: Vec<MetricRaw> = serde_json::from_str(&content[start..=end])?;
+
+    let mut metrics = rubric.criteria.clone();
+    let mut seen = vec![false; metrics.len()];
+    for raw in scored {
+        let index = metrics
+            .iter()
+            .position(|m| m.name.eq_ignore_ascii_case(raw.name.trim()))
+            .ok_or_else(|| Error::Validation(format!("Judge scored unknown criterion '{}'", raw.name)))?;
+        if std::mem::replace(&mut seen[index], true) {
+            return Err(Error::Validation(format!("Judge scored '{}' more than once", raw.name)));
+        }
+
+        let score = raw
+            .score
+            .as_u64()
+            .filter(|s| (1..=10).contains(s))
+            .ok_or_else(|| Error::Validation(format!(
+                "Score for '{}' must be an integer from 1 to 10, got {}",
+                raw.name, raw.score
+            )))?;
+        if raw.reasoning.trim().is_empty() {
+            return Err(Error::Validation(format!("No reasoning given for '{}'", raw.name)));
+        }
+        metrics[index].score = score as u8;
+        metrics[index].reasoning = raw.reasoning.trim().to_string();
+    }
+
+    if let Some(missing) = metrics.iter().zip(&seen).find(|(_, seen)| !**seen) {
+        return Err(Error::Validation(format!("Judge did not score '{}'", missing.0.name)));
+    }
+    Ok(metrics)
+}
+
+/// Score standard deviation, in points, above which judges disagree widely
+pub const DEFAULT_DISAGREEMENT_THRESHOLD: f32 = 2.0;
+
+/// How the judges scored one criterion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CriterionConsensus {
+    pub name: String,
+    pub weight: f32,
+    pub scores: Vec<(Persona, u8)>,
+    pub mean: f32,
+    /// Population standard deviation of the scores
+    pub std_dev: f32,
+    pub disputed: bool,
+}
+
+/// Combined verdict of several judges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Consensus {
+    pub task_id: String,
+    pub reports: Vec<EvaluationReport>,
+    pub criteria: Vec<CriterionConsensus>,
+    /// Weighted mean score over all criteria
+    pub weighted_score: f32,
+    pub max_possible_score: f32,
+    /// Largest per-criterion standard deviation
+    pub disagreement: f32,
+    /// Judges disagree widely on at least one criterion
+    pub flagged: bool,
+}
+
+impl Consensus {
+    /// Combine judges' reports; criteria are matched by name
+    pub fn from_reports(task_id: String, reports: Vec<EvaluationReport>, threshold: f32) -> Self {
+        let names: Vec<(String, f32)> = reports
+            .first()
+            .map(|r| r.metrics.iter().map(|m| (m.name.clone(), m.weight)).collect())
+            .unwrap_or_default();
+
+        let criteria: Vec<CriterionConsensus> = names
+            .into_iter()
+            .map(|(name, weight)| {
+                let scores: Vec<(Persona, u8)> = reports
+                    .iter()
+                    .filter_map(|r| {
+                        r.metrics
+                            .iter()
+                            .find(|m| m.name == name)
+                            .map(|m| (r.evaluator.clone(), m.score))
+                    })
+                    .collect();
+                let n = scores.len().max(1) as f32;
+                let mean = scores.iter().map(|(_, s)| *s as f32).sum::<f32>() / n;
+                let variance = scores.iter().map(|(_, s)| (*s as f32 - mean).powi(2)).sum::<f32>() / n;
+                let std_dev = variance.sqrt();
+                CriterionConsensus {
+                    name,
+                    weight,
+                    scores,
+                    mean,
+                    std_dev,
+                    disputed: std_dev > threshold,
+                }
+            })
+            .collect();
+
+        let weighted_score = criteria.iter().map(|c| c.mean * c.weight).sum();
+        let max_possible_score = criteria.iter().map(|c| 10.0 * c.weight).sum();
+        let disagreement = criteria.iter().map(|c| c.std_dev).fold(0.0, f32::max);
+        let flagged = criteria.iter().any(|c| c.disputed);
+
+        Self {
+            task_id,
+            reports,
+            criteria,
+            weighted_score,
+            max_possible_score,
+            disagreement,
+            flagged,
+        }
+    }
+
+    /// Criteria the judges disagree widely on
+    pub fn disputed(&self) -> impl Iterator<Item = &CriterionConsensus> {
+        self.criteria.iter().filter(|c| c.disputed)
+    }
+
+    pub fn summary(&self) -> String {
+        let percentage = if self.max_possible_score > 0.0 {
+            (self.weighted_score / self.max_possible_score) * 100.0
+        } else {
+            0.0
+        };
+        format!(
+            "Consensus of {} judge(s): {:.1}% (disagreement {:.1}{})",
+            self.reports.len(),
+            percentage,
+            self.disagreement,
+            if self.flagged { ", flagged" } else { "" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(evaluator: Persona, scores: &[u8]) -> EvaluationReport {
+        let mut metrics = Rubric::for_intent(&IntentCategory::CodeGeneration).criteria;
+        for (metric, score) in metrics.iter_mut().zip(scores) {
+            metric.score = *score;
+        }
+        let mut report = EvaluationReport {
+            task_id: "t".to_string(),
+            evaluator,
+            evaluatee: Persona::Worker,
+            metrics,
+            weighted_score: 0.0,
+            max_possible_score: 0.0,
+            timestamp: common::chrono::Utc::now(),
+            model: String::new(),
+            tokens_used: 0,
+        };
+        report.calculate_score();
+        report
+    }
+
+    #[test]
+    fn test_parse_judgement() {
+        let rubric = Rubric::for_intent(&IntentCategory::Documentation);
+        let reply = r#"Here you go:
+```json
+[{"name": "relevance", "score": 8, "reasoning": "Covers the request"},
+ {"name": "Clarity", "score": 6, "reasoning": "Dense in places"}]
+```"#;
+        let metrics = parse_judgement(reply, &rubric).unwrap();
+        assert_eq!(metrics[0].name, "Relevance");
+        assert_eq!(metrics[0].score, 8);
+        assert_eq!(metrics[1].reasoning, "Dense in places");
+
+        let reject = |reply: &str| parse_judgement(reply, &rubric).is_err();
+        assert!(reject("Looks good to me, 9/10"));
+        assert!(reject(r#"[{"name": "Relevance", "score": 8, "reasoning": "ok"}]"#));
+        assert!(reject(r#"[{"name": "Relevance", "score": 11, "reasoning": "ok"}, {"name": "Clarity", "score": 6, "reasoning": "ok"}]"#));
+        assert!(reject(r#"[{"name": "Relevance", "score": 7.5, "reasoning": "ok"}, {"name": "Clarity", "score": 6, "reasoning": "ok"}]"#));
+        assert!(reject(r#"[{"name": "Relevance", "score": 8, "reasoning": " "}, {"name": "Clarity", "score": 6, "reasoning": "ok"}]"#));
+        assert!(reject(r#"[{"name": "Relevance", "score": 8, "reasoning": "ok"}, {"name": "Clarity", "score": 6, "reasoning": "ok"}, {"name": "Speed", "score": 6, "reasoning": "ok"}]"#));
+    }
+
+    #[test]
+    fn test_consensus_flags_wide_disagreement() {
+        let agreeing = Consensus::from_reports(
+            "t".to_string(),
+            vec![report(Persona::Reviewer, &[8, 7, 9, 6]), report(Persona::Architect, &[7, 7, 8, 7])],
+            DEFAULT_DISAGREEMENT_THRESHOLD,
+        );
+        assert!(!agreeing.flagged);
+        assert_eq!(agreeing.criteria[0].mean, 7.5);
+        assert_eq!(agreeing.criteria[0].std_dev, 0.5);
+
+        let split = Consensus::from_reports(
+            "t".to_string(),
+            vec![
+                report(Persona::Reviewer, &[2, 7, 3, 6]),
+                report(Persona::Architect, &[9, 7, 8, 7]),
+                report(Persona::ProductOwner, &[9, 8, 7, 6]),
+            ],
+            DEFAULT_DISAGREEMENT_THRESHOLD,
+        );
+        assert!(split.flagged);
+        let disputed: Vec<&str> = split.disputed().map(|c| c.name.as_str()).collect();
+        assert_eq!(disputed, ["Accuracy", "Safety"]);
+        assert!(split.disagreement > 3.0);
+        assert!(split.summary().contains("flagged"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Structured event stream for external consumers.
+//!
+//! The agent broadcasts what it is doing as typed [`Event`]s: submitted
+//! tasks, pipeline stage changes, tool calls, model output, approval
+//! requests and finished tasks. Editors and dashboards subscribe with
+//! `Agent::subscribe` or follow the JSON lines written by
+//! `coding-agent --events jsonl`.
+
+use crate::budget::ResourceUsage;
+use crate::orchestrator::PipelineStage;
+use common::chrono::{DateTime, Utc};
+use common::{Result, TaskId};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+use tools::approval::{ApprovalGate, ApprovalRequest};
+use tracing::warn;
+
+/// Events a subscriber may fall behind by before it starts missing some
+const EVENT_BUFFER: usize = 1024;
+
+/// Longest string parameter a tool call event carries, in characters
+const MAX_PARAMETER_CHARS: usize = 200;
+
+/// Parameter names whose values never leave the agent
+const SECRET_PARAMETERS: [&str; 6] = ["token", "password", "secret", "api_key", "authorization", "credential"];
+
+/// Something the agent did, with when it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Event payloads, tagged by `type` when serialized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    TaskSubmitted {
+        task_id: TaskId,
+        description: String,
+        parent_id: Option<TaskId>,
+    },
+    StageChanged {
+        task_id: TaskId,
+        stage: PipelineStage,
+    },
+    /// A tool call finished, or was refused before running
+    ToolCalled {
+        task_id: TaskId,
+        tool: String,
+        /// The call's parameters, with secrets redacted and long values
+        /// such as file contents truncated
+        parameters: serde_json::Value,
+        success: bool,
+        duration_ms: u64,
+    },
+    /// Model output for a task; gateways without streaming send one chunk per call
+    TokensStreamed {
+        task_id: TaskId,
+        model: String,
+        content: String,
+        tokens: u32,
+    },
+    /// A tool call is held until a reviewer decides it
+    ApprovalRequested { request: ApprovalRequest },
+    TaskFinished {
+        task_id: TaskId,
+        success: bool,
+        /// The task's result text; empty if it errored
+        output: String,
+        usage: ResourceUsage,
+        /// Budget limit the task stopped at, if any
+        exhausted: Option<String>,
+        error: Option<String>,
+    },
+}
+
+/// Broadcasts events to every subscriber
+///
+/// Clones share the same channel. Emitting never blocks; a subscriber that
+/// falls too far behind skips the oldest events.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    /// Receive events emitted from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        // Nobody listening is the common case
+        let _ = self.sender.send(Event {
+            timestamp: Utc::now(),
+            kind,
+        });
+    }
+
+    /// Emit the gate's approval requests until the returned task is aborted
+    pub fn forward_approvals(&self, gate: &ApprovalGate) -> tokio::task::JoinHandle<()> {
+        let mut requests = gate.observe();
+        let bus = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match requests.recv().await {
+                    Ok(request) => bus.emit(EventKind::ApprovalRequested { request }),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tool call parameters as they may appear in an event
+pub fn redact_parameters(value: &serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let name = key.to_lowercase();
+                    let value = if SECRET_PARAMETERS.iter().any(|secret| name.contains(secret)) {
+                        Value::String("[redacted]".to_string())
+                    } else {
+                        redact_parameters(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_parameters).collect()),
+        Value::String(text) if text.chars().count() > MAX_PARAMETER_CHARS => {
+            let kept: String = text.chars().take(MAX_PARAMETER_CHARS).collect();
+            Value::String(format!("{}... ({} chars)", kept, text.chars().count()))
+        }
+        other => other.clone(),
+    }
+}
+
+/// Write events as JSON lines until every sender is dropped
+///
+/// Each line is flushed as soon as it is written so readers can follow the
+/// stream live.
+pub async fn write_jsonl<W>(mut events: broadcast::Receiver<Event>, mut out: W) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let mut line = serde_json::to_vec(&event)?;
+                line.push(b'\n');
+                out.write_all(&line).await?;
+                out.flush().await?;
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Event stream fell behind and skipped {} event(s)", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_jsonl() {
+        let bus = EventBus::new();
+        let events = bus.subscribe();
+        let task_id = TaskId::new();
+
+        bus.emit(EventKind::StageChanged {
+            task_id,
+            stage: PipelineStage::Planning,
+        });
+        bus.emit(EventKind::TaskFinished {
+            task_id,
+            success: true,
+            output: "done".to_string(),
+            usage: ResourceUsage::default(),
+            exhausted: None,
+            error: None,
+        });
+        drop(bus);
+
+        let mut out = Vec::new();
+        write_jsonl(events, &mut out).await.unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["type"], "stage_changed");
+        assert_eq!(first["stage"], "planning");
+        assert!(first["timestamp"].is_string());
+
+        let last: Event = serde_json::from_str(lines[1]).unwrap();
+        assert!(matches!(last.kind, EventKind::TaskFinished { success: true, .. }));
+    }
+
+    #[test]
+    fn test_redact_parameters() {
+        let content = "é".repeat(MAX_PARAMETER_CHARS + 10);
+        let parameters = serde_json::json!({
+            "operation": "write",
+            "path": "src/lib.rs",
+            "content": content,
+            "headers": [{"Authorization": "Bearer abc"}],
+            "api_key": "sk-123",
+        });
+
+        let redacted = redact_parameters(&parameters);
+        assert_eq!(redacted["operation"], "write");
+        assert_eq!(redacted["path"], "src/lib.rs");
+        assert_eq!(redacted["api_key"], "[redacted]");
+        assert_eq!(redacted["headers"][0]["Authorization"], "[redacted]");
+        let content = redacted["content"].as_str().unwrap();
+        assert!(content.ends_with(&format!("... ({} chars)", MAX_PARAMETER_CHARS + 10)));
+        assert!(content.chars().count() < MAX_PARAMETER_CHARS + 20);
+    }
+}
@@ -4,7 +4,7 @@
 //! performance tracking, bottleneck identification, strategy generation,
 //! and safe self-modification with rollback support.
 
-use common::{async_trait, Error, Module, Result};
+use common::{async_trait, Error, Module, Result, TaskId};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
@@ -12,6 +12,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+use crate::code_improvement::CodeImprover;
+use crate::stats;
 use crate::{AgentMetrics, Change, ChangeType};
 
 /// Self-improvement engine
@@ -22,21 +24,41 @@ pub struct ImprovementEngine {
     rollback_store: Arc<RwLock<RollbackStore>>,
     config: ImprovementConfig,
     ab_test_framework: AbTestFramework,
+    code_improver: Option<Arc<CodeImprover>>,
 }
 
 impl ImprovementEngine {
     /// Create a new improvement engine
     pub fn new() -> Self {
+        let config = ImprovementConfig::default();
         Self {
             metrics_history: Arc::new(RwLock::new(VecDeque::new())),
             strategies: Arc::new(RwLock::new(Vec::new())),
             applied_changes: Arc::new(RwLock::new(Vec::new())),
             rollback_store: Arc::new(RwLock::new(RollbackStore::new())),
-            config: ImprovementConfig::default(),
-            ab_test_framework: AbTestFramework::new(),
+            ab_test_framework: AbTestFramework::new().with_design(config.experiment.clone()),
+            config,
+            code_improver: None,
         }
     }
 
+    /// Keep the ledger of applied changes in `dir` instead of `.agent/improvements`
+    pub fn with_storage(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.rollback_store = Arc::new(RwLock::new(RollbackStore::with_path(dir)));
+        self
+    }
+
+    /// Allow `Code` strategies, applied through `improver`
+    pub fn with_code_improver(mut self, improver: Arc<CodeImprover>) -> Self {
+        self.set_code_improver(improver);
+        self
+    }
+
+    /// Allow `Code` strategies on an engine that is already in use
+    pub fn set_code_improver(&mut self, improver: Arc<CodeImprover>) {
+        self.code_improver = Some(improver);
+    }
+
     /// Record current performance metrics
     pub async fn record_metrics(&mut self, metrics: AgentMetrics) -> Result<()> {
         let snapshot = PerformanceSnapshot {
@@ -168,6 +190,26 @@ impl ImprovementEngine {
         for bottleneck in &analysis.bottlenecks {
             match bottleneck.category {
                 BottleneckCategory::Reliability => {
+                    if self.code_improver.is_some() {
+                        strategies.push(ImprovementStrategy {
+                            id: uuid::Uuid::new_v4().to_string(),
+                            name: "Harden Failure Paths".to_string(),
+                            description: "Change the ReAct loop so failing steps are retried or reported clearly"
+                                .to_string(),
+                            target_component: "react".to_string(),
+                            change_type: ChangeType::Code,
+                            expected_impact: Impact::High,
+                            risk_level: RiskLevel::High,
+                            implementation: ImplementationPlan {
+                                steps: vec![
+                                    "Find error paths that give up without recovering".to_string(),
+                                    "Add retries or clearer errors there".to_string(),
+                                    "Keep every existing test passing".to_string(),
+                                ],
+                                rollback_steps: vec!["Revert the promoted commit".to_string()],
+                            },
+                        });
+                    }
                     strategies.push(ImprovementStrategy {
                         id: uuid::Uuid::new_v4().to_string(),
                         name: "Improve Error Handling".to_string(),
@@ -273,13 +315,14 @@ impl ImprovementEngine {
         info!("Applying improvement strategy: {}", strategy.name);
 
         // Store rollback data before making changes
-        let rollback_data = self.prepare_rollback(&strategy.target_component).await?;
+        let rollback_data = self.prepare_rollback(strategy.change_type).await?;
 
         // Apply the change
         let change = match strategy.change_type {
             ChangeType::Prompt => self.apply_prompt_improvement(strategy).await?,
             ChangeType::Configuration => self.apply_config_improvement(strategy).await?,
             ChangeType::Strategy => self.apply_strategy_improvement(strategy).await?,
+            ChangeType::Code => self.apply_code_improvement(strategy).await?,
             _ => {
                 return Err(Error::Internal(format!(
                     "Unsupported change type: {:?}",
@@ -288,14 +331,19 @@ impl ImprovementEngine {
             }
         };
 
-        // Store applied change with rollback data
+        // Record the applied change with its rollback data in the ledger
         let applied_change = AppliedChange {
             change: change.clone(),
             strategy_id: strategy.id.clone(),
+            strategy: Some(strategy.clone()),
             rollback_data,
             applied_at: common::chrono::Utc::now(),
+            status: ChangeStatus::UnderTest,
+            experiment: None,
+            decided_at: None,
         };
 
+        self.rollback_store.read().await.save(&applied_change).await?;
         self.applied_changes.write().await.push(applied_change);
 
         // Register with A/B test framework
@@ -307,40 +355,129 @@ impl ImprovementEngine {
         Ok(change)
     }
 
+    /// Put a task in an arm of a running experiment, if there is one
+    pub async fn assign_experiment(&self, task_id: &TaskId) -> Option<Assignment> {
+        self.ab_test_framework.assign(task_id).await
+    }
+
+    /// Prompt and model settings a task runs with
+    ///
+    /// Tasks get the files as they are now, with every applied change; a
+    /// task in the control arm of an experiment gets the changed file as it
+    /// was before the change instead.
+    pub async fn treatment(&self, assignment: Option<&Assignment>) -> Result<Treatment> {
+        let mut treatment = Treatment {
+            prompt: read_if_exists(target_path(ChangeType::Prompt)).await?,
+            config: parse_settings(read_if_exists(target_path(ChangeType::Configuration)).await?)?,
+        };
+        let Some(assignment) = assignment else {
+            return Ok(treatment);
+        };
+        let Some(applied) = self
+            .applied_changes
+            .read()
+            .await
+            .iter()
+            .find(|a| a.change.id == assignment.change_id)
+            .cloned()
+        else {
+            return Ok(treatment);
+        };
+
+        let contents = match assignment.arm {
+            Arm::Control => applied
+                .rollback_data
+                .get("previous")
+                .and_then(|p| p.as_str())
+                .map(str::to_string),
+            Arm::Variant => read_if_exists(Some(applied.change.file_path.clone())).await?,
+        };
+        match applied.change.change_type {
+            ChangeType::Prompt => treatment.prompt = contents,
+            ChangeType::Configuration => treatment.config = parse_settings(contents)?,
+            _ => {}
+        }
+        Ok(treatment)
+    }
+
+    /// Record how a task went for the experiment it was assigned to
+    pub async fn record_outcome(&self, task_id: &TaskId, success: bool, latency_ms: u64) {
+        self.ab_test_framework.record(task_id, success, latency_ms).await;
+    }
+
+    /// Record an evaluation score, as a fraction of the maximum, for a task
+    pub async fn record_score(&self, task_id: &TaskId, score: f64) {
+        self.ab_test_framework.record_score(task_id, score).await;
+    }
+
+    /// Applied changes whose experiment has not been decided
+    pub async fn changes_under_test(&self) -> Vec<Change> {
+        let running = self.ab_test_framework.running().await;
+        self.applied_changes
+            .read()
+            .await
+            .iter()
+            .filter(|a| running.contains(&a.change.id))
+            .map(|a| a.change.clone())
+            .collect()
+    }
+
     /// Validate improvements through A/B testing
+    ///
+    /// Changes are only sorted into successful or rolled back once their
+    /// experiment has stopped; until then they are reported as pending.
+    /// A change that reached the sample limit without a significant
+    /// difference is rolled back, since it did not show it helps.
     pub async fn validate_improvements(&self, changes: &[Change]) -> Result<ValidationResult> {
         info!("Validating {} improvements", changes.len());
 
         let mut successful = Vec::new();
         let mut rollbacks = Vec::new();
+        let mut pending = Vec::new();
         let mut total_duration = 0;
 
         for change in changes {
-            let test_result = self
-                .ab_test_framework
-                .evaluate_variant(&change.id, self.metrics_history.clone())
-                .await?;
-            
+            let test_result = self.ab_test_framework.evaluate_variant(&change.id).await?;
             total_duration += test_result.duration_ms;
+            let record = ExperimentRecord::from(&test_result);
+            self.update_entry(&change.id, |entry| entry.experiment = Some(record))
+                .await?;
 
-            if test_result.is_better {
-                info!(
-                    "Improvement {} validated successfully: {:.1}% improvement",
-                    change.id, test_result.improvement_percentage * 100.0
-                );
-                successful.push(change.clone());
-            } else {
-                warn!(
-                    "Improvement {} degraded performance: {:.1}% worse",
-                    change.id, test_result.improvement_percentage * 100.0
-                );
-                rollbacks.push(change.clone());
+            match test_result.verdict {
+                Verdict::Pending => {
+                    debug!(
+                        "Improvement {} still under test ({} control, {} variant tasks)",
+                        change.id, test_result.samples.0, test_result.samples.1
+                    );
+                    pending.push(change.clone());
+                    continue;
+                }
+                Verdict::Better => {
+                    info!(
+                        "Improvement {} validated successfully: {:.1}% improvement",
+                        change.id, test_result.improvement_percentage * 100.0
+                    );
+                    successful.push(change.clone());
+                }
+                Verdict::Worse => {
+                    warn!(
+                        "Improvement {} degraded performance: {:.1}% change in success rate",
+                        change.id, test_result.improvement_percentage * 100.0
+                    );
+                    rollbacks.push(change.clone());
+                }
+                Verdict::NoDifference => {
+                    warn!("Improvement {} made no significant difference", change.id);
+                    rollbacks.push(change.clone());
+                }
             }
+            self.ab_test_framework.finish(&change.id).await;
         }
 
         Ok(ValidationResult {
             successful,
             rollbacks,
+            pending,
             test_duration_ms: total_duration,
         })
     }
@@ -349,18 +486,32 @@ impl ImprovementEngine {
     pub async fn rollback(&self, change: &Change) -> Result<()> {
         warn!("Rolling back change: {}", change.description);
 
-        let applied_changes = self.applied_changes.read().await;
-        if let Some(applied) = applied_changes.iter().find(|a| a.change.id == change.id) {
+        let applied = self
+            .applied_changes
+            .read()
+            .await
+            .iter()
+            .find(|a| a.change.id == change.id)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("Change not found for rollback: {}", change.id)))?;
+        if applied.status == ChangeStatus::RolledBack {
+            return Err(Error::Validation(format!("Change {} is already rolled back", change.id)));
+        }
+
+        if applied.change.change_type == ChangeType::Code {
+            self.code_improver()?.revert(&applied.change).await?;
+        } else {
             // Execute rollback using stored data
             self.execute_rollback(&applied.rollback_data).await?;
-            info!("Successfully rolled back change: {}", change.id);
-        } else {
-            return Err(Error::NotFound(format!(
-                "Change not found for rollback: {}",
-                change.id
-            )));
         }
 
+        self.ab_test_framework.finish(&change.id).await;
+        self.update_entry(&change.id, |entry| {
+            entry.status = ChangeStatus::RolledBack;
+            entry.decided_at = Some(common::chrono::Utc::now());
+        })
+        .await?;
+        info!("Successfully rolled back change: {}", change.id);
         Ok(())
     }
 
@@ -368,9 +519,12 @@ impl ImprovementEngine {
     pub async fn persist_improvements(&self, changes: &[Change]) -> Result<()> {
         info!("Persisting {} successful improvements", changes.len());
 
-        let persistence = self.rollback_store.read().await;
         for change in changes {
-            persistence.persist_change(change).await?;
+            self.update_entry(&change.id, |entry| {
+                entry.status = ChangeStatus::Persisted;
+                entry.decided_at = Some(common::chrono::Utc::now());
+            })
+            .await?;
         }
 
         Ok(())
@@ -381,6 +535,17 @@ impl ImprovementEngine {
         self.applied_changes.read().await.clone()
     }
 
+    /// Change a ledger entry in memory and on disk
+    async fn update_entry(&self, change_id: &str, update: impl FnOnce(&mut AppliedChange)) -> Result<()> {
+        let mut applied_changes = self.applied_changes.write().await;
+        let entry = applied_changes
+            .iter_mut()
+            .find(|a| a.change.id == change_id)
+            .ok_or_else(|| Error::NotFound(format!("No applied change {}", change_id)))?;
+        update(entry);
+        self.rollback_store.read().await.save(entry).await
+    }
+
     /// Get current performance metrics summary
     pub async fn get_metrics_summary(&self) -> Option<PerformanceMetrics> {
         let history = self.metrics_history.read().await;
@@ -430,22 +595,57 @@ impl ImprovementEngine {
         recommendations
     }
 
-    async fn prepare_rollback(&self, _component: &str) -> Result<serde_json::Value> {
-        // Capture current state for rollback
+    async fn prepare_rollback(&self, change_type: ChangeType) -> Result<serde_json::Value> {
+        // Capture the file the change will overwrite, if any
+        let path = target_path(change_type);
+        let previous = match &path {
+            Some(path) => match tokio::fs::read_to_string(path).await {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            },
+            None => None,
+        };
         Ok(serde_json::json!({
             "timestamp": common::chrono::Utc::now().to_rfc3339(),
             "version": env!("CARGO_PKG_VERSION"),
+            "path": path,
+            "previous": previous,
         }))
     }
 
-    async fn execute_rollback(&self, _rollback_data: &serde_json::Value) -> Result<()> {
-        // Execute rollback based on stored data
-        info!("Executing rollback");
+    async fn execute_rollback(&self, rollback_data: &serde_json::Value) -> Result<()> {
+        let Some(path) = rollback_data.get("path").and_then(|p| p.as_str()) else {
+            info!("Nothing to restore for this change");
+            return Ok(());
+        };
+
+        // Put back the previous contents, or remove a file the change created
+        match rollback_data.get("previous").and_then(|p| p.as_str()) {
+            Some(previous) => tokio::fs::write(path, previous).await?,
+            None => match tokio::fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        info!("Restored {}", path);
         Ok(())
     }
 
+    fn code_improver(&self) -> Result<&CodeImprover> {
+        self.code_improver
+            .as_deref()
+            .ok_or_else(|| Error::Config("Code improvements need self-compilation enabled".to_string()))
+    }
+
+    async fn apply_code_improvement(&self, strategy: &ImprovementStrategy) -> Result<Change> {
+        let improver = self.code_improver()?;
+        let patch = improver.propose(strategy).await?;
+        improver.apply(strategy, &patch).await
+    }
+
     async fn apply_prompt_improvement(&self, strategy: &ImprovementStrategy) -> Result<Change> {
-        let path = PathBuf::from("prompts/default.txt");
+        let path = target_path(ChangeType::Prompt).unwrap_or_default();
         
         // Ensure directory exists
         if let Some(parent) = path.parent() {
@@ -469,7 +669,7 @@ impl ImprovementEngine {
     }
 
     async fn apply_config_improvement(&self, strategy: &ImprovementStrategy) -> Result<Change> {
-        let path = PathBuf::from("config/agent.toml");
+        let path = target_path(ChangeType::Configuration).unwrap_or_default();
 
         // Ensure directory exists
         if let Some(parent) = path.parent() {
@@ -493,7 +693,7 @@ impl ImprovementEngine {
     }
 
     async fn apply_strategy_improvement(&self, strategy: &ImprovementStrategy) -> Result<Change> {
-        let path = PathBuf::from("src/strategy.rs");
+        let path = target_path(ChangeType::Strategy).unwrap_or_default();
 
         // Ensure directory exists
         if let Some(parent) = path.parent() {
@@ -525,7 +725,20 @@ impl Module for ImprovementEngine {
 
     async fn initialize(&mut self) -> Result<()> {
         info!("Initializing improvement engine");
-        // Load any persisted improvements
+
+        // Pick up the ledger; undecided changes go back under test
+        let entries = self.rollback_store.read().await.list().await?;
+        for entry in &entries {
+            if entry.status == ChangeStatus::UnderTest {
+                self.ab_test_framework
+                    .register_variant(&entry.change.id, &entry.strategy_id)
+                    .await;
+            }
+        }
+        if !entries.is_empty() {
+            info!("Loaded {} applied changes from the ledger", entries.len());
+        }
+        *self.applied_changes.write().await = entries;
         Ok(())
     }
 
@@ -664,13 +877,56 @@ pub struct ImplementationPlan {
     pub rollback_steps: Vec<String>,
 }
 
-/// Applied change record
+/// Applied change record, as kept in the ledger
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppliedChange {
     pub change: crate::Change,
     pub strategy_id: String,
+    /// Strategy the change implements
+    #[serde(default)]
+    pub strategy: Option<ImprovementStrategy>,
     pub rollback_data: serde_json::Value,
     pub applied_at: common::chrono::DateTime<common::chrono::Utc>,
+    #[serde(default)]
+    pub status: ChangeStatus,
+    /// Latest A/B experiment result
+    #[serde(default)]
+    pub experiment: Option<ExperimentRecord>,
+    /// When the change was persisted or rolled back
+    #[serde(default)]
+    pub decided_at: Option<common::chrono::DateTime<common::chrono::Utc>>,
+}
+
+/// Where an applied change stands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeStatus {
+    #[default]
+    UnderTest,
+    Persisted,
+    RolledBack,
+}
+
+/// A/B experiment result as recorded in the ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentRecord {
+    pub verdict: Verdict,
+    pub control_samples: usize,
+    pub variant_samples: usize,
+    pub comparisons: Vec<MetricComparison>,
+    pub evaluated_at: common::chrono::DateTime<common::chrono::Utc>,
+}
+
+impl From<&TestResult> for ExperimentRecord {
+    fn from(result: &TestResult) -> Self {
+        Self {
+            verdict: result.verdict,
+            control_samples: result.samples.0,
+            variant_samples: result.samples.1,
+            comparisons: result.comparisons.clone(),
+            evaluated_at: common::chrono::Utc::now(),
+        }
+    }
 }
 
 /// Validation result
@@ -678,6 +934,9 @@ pub struct AppliedChange {
 pub struct ValidationResult {
     pub successful: Vec<crate::Change>,
     pub rollbacks: Vec<crate::Change>,
+    /// Changes whose experiment needs more tasks
+    #[serde(default)]
+    pub pending: Vec<crate::Change>,
     pub test_duration_ms: u64,
 }
 
@@ -698,6 +957,7 @@ pub struct ImprovementConfig {
     pub max_acceptable_latency_ms: u64,
     pub max_strategies_per_cycle: usize,
     pub ab_test_duration_seconds: u64,
+    pub experiment: ExperimentDesign,
 }
 
 impl Default for ImprovementConfig {
@@ -708,40 +968,77 @@ impl Default for ImprovementConfig {
             max_acceptable_latency_ms: 5000,
             max_strategies_per_cycle: 3,
             ab_test_duration_seconds: 300,
+            experiment: ExperimentDesign::default(),
         }
     }
 }
 
-/// Rollback store for persisting changes
+/// Ledger of applied changes, one JSON file per change
 pub struct RollbackStore {
     storage_path: PathBuf,
 }
 
 impl RollbackStore {
     pub fn new() -> Self {
+        Self::with_path(".agent/improvements")
+    }
+
+    pub fn with_path(dir: impl Into<PathBuf>) -> Self {
         Self {
-            storage_path: PathBuf::from(".agent/improvements"),
+            storage_path: dir.into(),
         }
     }
 
-    pub async fn persist_change(&self, change: &crate::Change) -> Result<()> {
-        let path = self.storage_path.join(format!("{}.json", change.id));
-        
-        // Ensure directory exists
-        if let Some(parent) = path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
-        
-        let json = serde_json::to_string_pretty(change)?;
-        tokio::fs::write(path, json).await?;
+    /// Write an entry, replacing the file in one step
+    pub async fn save(&self, applied: &AppliedChange) -> Result<()> {
+        tokio::fs::create_dir_all(&self.storage_path).await?;
+        let path = self.storage_path.join(format!("{}.json", applied.change.id));
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(applied)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
         Ok(())
     }
 
-    pub async fn load_change(&self, change_id: &str) -> Result<crate::Change> {
+    pub async fn load(&self, change_id: &str) -> Result<AppliedChange> {
         let path = self.storage_path.join(format!("{}.json", change_id));
-        let json = tokio::fs::read_to_string(path).await?;
-        let change = serde_json::from_str(&json)?;
-        Ok(change)
+        let json = match tokio::fs::read_to_string(path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::NotFound(format!("No applied change {}", change_id)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub async fn load_change(&self, change_id: &str) -> Result<crate::Change> {
+        Ok(self.load(change_id).await?.change)
+    }
+
+    /// Every entry, oldest first; unreadable files are skipped
+    pub async fn list(&self) -> Result<Vec<AppliedChange>> {
+        let mut entries = Vec::new();
+        let mut dir = match tokio::fs::read_dir(&self.storage_path).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            if path.extension() != Some(std::ffi::OsStr::new("json")) {
+                continue;
+            }
+            let parsed = tokio::fs::read_to_string(&path)
+                .await
+                .map_err(Error::from)
+                .and_then(|json| serde_json::from_str::<AppliedChange>(&json).map_err(Error::from));
+            match parsed {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Skipping ledger entry {}: {}", path.display(), e),
+            }
+        }
+        entries.sort_by_key(|e| e.applied_at);
+        Ok(entries)
     }
 }
 
@@ -751,18 +1048,130 @@ impl Default for RollbackStore {
     }
 }
 
+/// Metrics compared between arms, and whether higher is better
+const METRICS: [(&str, bool); 3] = [("success_rate", true), ("latency_ms", false), ("score", true)];
+
+/// Which side of an experiment a task ran on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arm {
+    /// Without the change
+    Control,
+    /// With the change
+    Variant,
+}
+
+/// A task's place in an experiment, stored in its context as `experiment`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub change_id: String,
+    pub arm: Arm,
+}
+
+/// What a task runs with, stored in its context as `treatment`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Treatment {
+    /// Instructions put ahead of every model prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Model settings from the `[llm]` table of the configuration file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<serde_json::Value>,
+}
+
+/// Outcome of one task in an experiment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Observation {
+    pub success: bool,
+    pub latency_ms: u64,
+    /// Evaluation score as a fraction of the maximum, if the task was evaluated
+    pub score: Option<f64>,
+}
+
+/// Sample sizes and significance of A/B experiments
+///
+/// Results are looked at each time both arms gain `min_samples_per_arm`
+/// tasks, up to `max_samples_per_arm`. The significance level is split
+/// evenly over every metric and planned look (Bonferroni), so stopping
+/// at the first significant look keeps the overall false-positive rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentDesign {
+    pub min_samples_per_arm: usize,
+    pub max_samples_per_arm: usize,
+    pub significance_level: f64,
+}
+
+impl ExperimentDesign {
+    pub fn planned_looks(&self) -> usize {
+        (self.max_samples_per_arm / self.min_samples_per_arm.max(1)).max(1)
+    }
+
+    /// p-value a single comparison must beat
+    pub fn threshold(&self) -> f64 {
+        self.significance_level / (METRICS.len() * self.planned_looks()) as f64
+    }
+}
+
+impl Default for ExperimentDesign {
+    fn default() -> Self {
+        Self {
+            min_samples_per_arm: 20,
+            max_samples_per_arm: 200,
+            significance_level: 0.05,
+        }
+    }
+}
+
+/// State of an experiment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// Not enough evidence yet; keep collecting
+    Pending,
+    /// The variant is significantly better and nothing is significantly worse
+    Better,
+    /// Some metric is significantly worse with the variant
+    Worse,
+    /// The sample limit was reached without a significant difference
+    NoDifference,
+}
+
+/// One metric compared between arms
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricComparison {
+    pub metric: String,
+    pub control_mean: f64,
+    pub variant_mean: f64,
+    pub p_value: f64,
+    pub significant: bool,
+    /// The variant moved the metric in the better direction
+    pub improved: bool,
+}
+
+/// Each task's assignment, and where its outcome is stored in its arm
+type Assignments = HashMap<TaskId, (Assignment, Option<usize>)>;
+
 /// A/B testing framework
 pub struct AbTestFramework {
     variants: Arc<RwLock<HashMap<String, TestVariant>>>,
+    assignments: Arc<RwLock<Assignments>>,
+    design: ExperimentDesign,
 }
 
 impl AbTestFramework {
     pub fn new() -> Self {
         Self {
             variants: Arc::new(RwLock::new(HashMap::new())),
+            assignments: Arc::new(RwLock::new(HashMap::new())),
+            design: ExperimentDesign::default(),
         }
     }
 
+    pub fn with_design(mut self, design: ExperimentDesign) -> Self {
+        self.design = design;
+        self
+    }
+
     pub async fn register_variant(&self, change_id: &str, strategy_id: &str) {
         let mut variants = self.variants.write().await;
         variants.insert(
@@ -771,37 +1180,140 @@ impl AbTestFramework {
                 change_id: change_id.to_string(),
                 strategy_id: strategy_id.to_string(),
                 start_time: common::chrono::Utc::now(),
-                metrics_before: None,
-                metrics_after: None,
+                control: Vec::new(),
+                variant: Vec::new(),
+                looks: 0,
+                concluded: None,
             },
         );
     }
 
-    pub async fn evaluate_variant(
-        &self,
-        change_id: &str,
-        metrics_history: Arc<RwLock<VecDeque<PerformanceSnapshot>>>,
-    ) -> Result<TestResult> {
-        let variants = self.variants.read().await;
-        let variant = variants.get(change_id).ok_or_else(|| {
+    /// Changes whose experiment has not been finished
+    pub async fn running(&self) -> Vec<String> {
+        self.variants.read().await.keys().cloned().collect()
+    }
+
+    /// Put a task in a random arm of a random undecided experiment
+    pub async fn assign(&self, task_id: &TaskId) -> Option<Assignment> {
+        let mut open: Vec<(common::chrono::DateTime<common::chrono::Utc>, String)> = self
+            .variants
+            .read()
+            .await
+            .values()
+            .filter(|v| v.concluded.is_none())
+            .map(|v| (v.start_time, v.change_id.clone()))
+            .collect();
+        if open.is_empty() {
+            return None;
+        }
+        open.sort();
+
+        let bits = uuid::Uuid::new_v4().as_u128();
+        let assignment = Assignment {
+            change_id: open.swap_remove((bits >> 1) as usize % open.len()).1,
+            arm: if bits & 1 == 0 { Arm::Control } else { Arm::Variant },
+        };
+        self.assignments
+            .write()
+            .await
+            .insert(*task_id, (assignment.clone(), None));
+        Some(assignment)
+    }
+
+    /// Record how an assigned task went; unassigned tasks are ignored
+    pub async fn record(&self, task_id: &TaskId, success: bool, latency_ms: u64) {
+        let mut assignments = self.assignments.write().await;
+        let Some((assignment, index)) = assignments.get_mut(task_id) else {
+            return;
+        };
+        let mut variants = self.variants.write().await;
+        let Some(variant) = variants.get_mut(&assignment.change_id) else {
+            return;
+        };
+        let arm = variant.arm_mut(assignment.arm);
+        arm.push(Observation {
+            success,
+            latency_ms,
+            score: None,
+        });
+        *index = Some(arm.len() - 1);
+    }
+
+    /// Attach an evaluation score to an assigned task's outcome
+    pub async fn record_score(&self, task_id: &TaskId, score: f64) {
+        let assignments = self.assignments.read().await;
+        let Some((assignment, Some(index))) = assignments.get(task_id) else {
+            return;
+        };
+        let mut variants = self.variants.write().await;
+        if let Some(observation) = variants
+            .get_mut(&assignment.change_id)
+            .and_then(|v| v.arm_mut(assignment.arm).get_mut(*index))
+        {
+            observation.score = Some(score);
+        }
+    }
+
+    /// Compare the arms if a look is due, and stop the experiment once decided
+    pub async fn evaluate_variant(&self, change_id: &str) -> Result<TestResult> {
+        let mut variants = self.variants.write().await;
+        let variant = variants.get_mut(change_id).ok_or_else(|| {
             Error::NotFound(format!("Variant not found: {}", change_id))
         })?;
 
-        let history = metrics_history.read().await;
-        
-        // Simple evaluation: compare metrics before and after
-        let is_better = true; // Placeholder
-        let improvement_percentage = 0.05; // Placeholder
-
         let duration_ms = common::chrono::Utc::now()
             .signed_duration_since(variant.start_time)
             .num_milliseconds() as u64;
-
-        Ok(TestResult {
-            is_better,
-            improvement_percentage,
+        let samples = (variant.control.len(), variant.variant.len());
+        let result = |verdict: Verdict, comparisons: Vec<MetricComparison>| TestResult {
+            is_better: verdict == Verdict::Better,
+            improvement_percentage: relative_change(&comparisons),
             duration_ms,
-        })
+            verdict,
+            samples,
+            comparisons,
+        };
+
+        if let Some((verdict, comparisons)) = &variant.concluded {
+            return Ok(result(*verdict, comparisons.clone()));
+        }
+
+        let n = samples.0.min(samples.1);
+        let finished = n >= self.design.max_samples_per_arm;
+        let look = n / self.design.min_samples_per_arm.max(1);
+        if look == 0 || (look <= variant.looks && !finished) {
+            return Ok(result(Verdict::Pending, Vec::new()));
+        }
+        variant.looks = look;
+
+        let comparisons = compare_arms(&variant.control, &variant.variant, self.design.threshold());
+        let verdict = if comparisons.iter().any(|c| c.significant && !c.improved) {
+            Verdict::Worse
+        } else if comparisons.iter().any(|c| c.significant) {
+            Verdict::Better
+        } else if finished {
+            Verdict::NoDifference
+        } else {
+            Verdict::Pending
+        };
+        debug!(
+            "Experiment {} look {}: {:?} with {} control and {} variant tasks",
+            change_id, look, verdict, samples.0, samples.1
+        );
+
+        if verdict != Verdict::Pending {
+            variant.concluded = Some((verdict, comparisons.clone()));
+        }
+        Ok(result(verdict, comparisons))
+    }
+
+    /// Forget a decided experiment and its task assignments
+    pub async fn finish(&self, change_id: &str) {
+        self.variants.write().await.remove(change_id);
+        self.assignments
+            .write()
+            .await
+            .retain(|_, (assignment, _)| assignment.change_id != change_id);
     }
 }
 
@@ -811,14 +1323,65 @@ impl Default for AbTestFramework {
     }
 }
 
+/// Welch's t-test on each metric, variant against control
+fn compare_arms(control: &[Observation], variant: &[Observation], threshold: f64) -> Vec<MetricComparison> {
+    let values = |observations: &[Observation], metric: &str| -> Vec<f64> {
+        observations
+            .iter()
+            .filter_map(|o| match metric {
+                "success_rate" => Some(if o.success { 1.0 } else { 0.0 }),
+                "latency_ms" => Some(o.latency_ms as f64),
+                _ => o.score,
+            })
+            .collect()
+    };
+
+    METRICS
+        .iter()
+        .filter_map(|(metric, higher_is_better)| {
+            let (a, b) = (values(control, metric), values(variant, metric));
+            let test = stats::welch_t_test(&a, &b)?;
+            Some(MetricComparison {
+                metric: metric.to_string(),
+                control_mean: stats::Summary::of(&a).mean,
+                variant_mean: stats::Summary::of(&b).mean,
+                p_value: test.p_value,
+                significant: test.p_value < threshold,
+                improved: (test.t > 0.0) == *higher_is_better,
+            })
+        })
+        .collect()
+}
+
+/// Relative change in success rate, the headline number of an experiment
+fn relative_change(comparisons: &[MetricComparison]) -> f64 {
+    comparisons
+        .iter()
+        .find(|c| c.metric == "success_rate" && c.control_mean > 0.0)
+        .map_or(0.0, |c| (c.variant_mean - c.control_mean) / c.control_mean)
+}
+
 /// Test variant
 #[derive(Debug, Clone)]
 pub struct TestVariant {
     pub change_id: String,
     pub strategy_id: String,
     pub start_time: common::chrono::DateTime<common::chrono::Utc>,
-    pub metrics_before: Option<PerformanceMetrics>,
-    pub metrics_after: Option<PerformanceMetrics>,
+    pub control: Vec<Observation>,
+    pub variant: Vec<Observation>,
+    /// Interim looks taken so far
+    pub looks: usize,
+    /// Final verdict, once the experiment has stopped
+    pub concluded: Option<(Verdict, Vec<MetricComparison>)>,
+}
+
+impl TestVariant {
+    fn arm_mut(&mut self, arm: Arm) -> &mut Vec<Observation> {
+        match arm {
+            Arm::Control => &mut self.control,
+            Arm::Variant => &mut self.variant,
+        }
+    }
 }
 
 /// Test result
@@ -827,10 +1390,44 @@ pub struct TestResult {
     pub is_better: bool,
     pub improvement_percentage: f64,
     pub duration_ms: u64,
+    pub verdict: Verdict,
+    /// Tasks observed in the control and variant arms
+    pub samples: (usize, usize),
+    pub comparisons: Vec<MetricComparison>,
 }
 
 // Helper functions
 
+/// File a simulated prompt, configuration or strategy change rewrites
+fn target_path(change_type: ChangeType) -> Option<PathBuf> {
+    match change_type {
+        ChangeType::Prompt => Some(PathBuf::from("prompts/default.txt")),
+        ChangeType::Configuration => Some(PathBuf::from("config/agent.toml")),
+        ChangeType::Strategy => Some(PathBuf::from("src/strategy.rs")),
+        _ => None,
+    }
+}
+
+async fn read_if_exists(path: Option<PathBuf>) -> Result<Option<String>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The `[llm]` table of a configuration file, if it has one
+fn parse_settings(content: Option<String>) -> Result<Option<serde_json::Value>> {
+    let Some(content) = content else {
+        return Ok(None);
+    };
+    let config: toml::Value = toml::from_str(&content).map_err(|e| Error::Config(e.to_string()))?;
+    Ok(config.get("llm").map(serde_json::to_value).transpose()?)
+}
+
 fn calculate_average_success_rate(snapshots: &[&PerformanceSnapshot]) -> Option<f64> {
     if snapshots.is_empty() {
         return None;
@@ -881,4 +1478,179 @@ mod tests {
         assert!(Severity::High > Severity::Medium);
         assert!(Severity::Medium > Severity::Low);
     }
+
+    #[tokio::test]
+    async fn test_ledger_survives_restart_and_reverts() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = dir.path().join("ledger");
+        let target = dir.path().join("prompt.txt");
+        std::fs::write(&target, "improved\n").unwrap();
+
+        let entry = AppliedChange {
+            change: Change {
+                id: "c1".to_string(),
+                file_path: target.clone(),
+                change_type: ChangeType::Prompt,
+                description: "Shorter prompt".to_string(),
+                timestamp: common::chrono::Utc::now(),
+                rollback_data: None,
+            },
+            strategy_id: "s1".to_string(),
+            strategy: None,
+            rollback_data: serde_json::json!({"path": target, "previous": "original\n"}),
+            applied_at: common::chrono::Utc::now(),
+            status: ChangeStatus::UnderTest,
+            experiment: None,
+            decided_at: None,
+        };
+        RollbackStore::with_path(&ledger).save(&entry).await.unwrap();
+        std::fs::write(ledger.join("stray.json"), "not a ledger entry").unwrap();
+
+        // A restarted engine resumes testing the change and records results
+        let mut engine = ImprovementEngine::new().with_storage(&ledger);
+        engine.initialize().await.unwrap();
+        let under_test = engine.changes_under_test().await;
+        assert_eq!(under_test.len(), 1);
+        let validation = engine.validate_improvements(&under_test).await.unwrap();
+        assert_eq!(validation.pending.len(), 1);
+        let stored = RollbackStore::with_path(&ledger).load("c1").await.unwrap();
+        assert_eq!(stored.experiment.unwrap().verdict, Verdict::Pending);
+
+        // Reverting restores the file and is recorded for the next start
+        engine.rollback(&entry.change).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "original\n");
+        assert!(engine.rollback(&entry.change).await.is_err());
+
+        let mut restarted = ImprovementEngine::new().with_storage(&ledger);
+        restarted.initialize().await.unwrap();
+        let history = restarted.get_improvement_history().await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].status, ChangeStatus::RolledBack);
+        assert!(history[0].decided_at.is_some());
+        assert!(restarted.changes_under_test().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_arms_get_their_own_prompt_and_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = dir.path().join("ledger");
+        let prompt = dir.path().join("prompt.txt");
+        let config = dir.path().join("agent.toml");
+        std::fs::write(&prompt, "Think step by step.\n").unwrap();
+        std::fs::write(&config, "[llm]\ntemperature = 0.2\n").unwrap();
+
+        let entry = |id: &str, path: &std::path::Path, change_type, previous: &str| AppliedChange {
+            change: Change {
+                id: id.to_string(),
+                file_path: path.to_path_buf(),
+                change_type,
+                description: String::new(),
+                timestamp: common::chrono::Utc::now(),
+                rollback_data: None,
+            },
+            strategy_id: "s1".to_string(),
+            strategy: None,
+            rollback_data: serde_json::json!({"path": path, "previous": previous}),
+            applied_at: common::chrono::Utc::now(),
+            status: ChangeStatus::UnderTest,
+            experiment: None,
+            decided_at: None,
+        };
+        let store = RollbackStore::with_path(&ledger);
+        store.save(&entry("p1", &prompt, ChangeType::Prompt, "Be brief.\n")).await.unwrap();
+        store
+            .save(&entry("c1", &config, ChangeType::Configuration, "[llm]\ntemperature = 0.7\n"))
+            .await
+            .unwrap();
+        let mut engine = ImprovementEngine::new().with_storage(&ledger);
+        engine.initialize().await.unwrap();
+
+        let arm = |change_id: &str, arm| Assignment {
+            change_id: change_id.to_string(),
+            arm,
+        };
+        let control = engine.treatment(Some(&arm("p1", Arm::Control))).await.unwrap();
+        let variant = engine.treatment(Some(&arm("p1", Arm::Variant))).await.unwrap();
+        assert_eq!(control.prompt.as_deref(), Some("Be brief.\n"));
+        assert_eq!(variant.prompt.as_deref(), Some("Think step by step.\n"));
+
+        let control = engine.treatment(Some(&arm("c1", Arm::Control))).await.unwrap();
+        let variant = engine.treatment(Some(&arm("c1", Arm::Variant))).await.unwrap();
+        assert_eq!(control.config, Some(serde_json::json!({"temperature": 0.7})));
+        assert_eq!(variant.config, Some(serde_json::json!({"temperature": 0.2})));
+    }
+
+    /// Run tasks until the experiment is decided; `succeeds(arm, i)` scripts outcomes
+    async fn run_experiment(
+        framework: &AbTestFramework,
+        change_id: &str,
+        succeeds: impl Fn(Arm, usize) -> bool,
+    ) -> TestResult {
+        for i in 0.. {
+            let task_id = TaskId::new();
+            let assignment = framework.assign(&task_id).await.unwrap();
+            assert_eq!(assignment.change_id, change_id);
+            framework.record(&task_id, succeeds(assignment.arm, i), 100).await;
+            let result = framework.evaluate_variant(change_id).await.unwrap();
+            if result.verdict != Verdict::Pending {
+                return result;
+            }
+            assert!(i < 1000, "experiment never stopped");
+        }
+        unreachable!()
+    }
+
+    #[tokio::test]
+    async fn test_experiment_stops_only_when_significant() {
+        let design = ExperimentDesign {
+            min_samples_per_arm: 10,
+            max_samples_per_arm: 40,
+            significance_level: 0.05,
+        };
+        assert_eq!(design.planned_looks(), 4);
+
+        // Too few tasks: nothing is decided, whatever the outcomes look like
+        let framework = AbTestFramework::new().with_design(design.clone());
+        framework.register_variant("c1", "s1").await;
+        for _ in 0..5 {
+            let task_id = TaskId::new();
+            let assignment = framework.assign(&task_id).await.unwrap();
+            framework.record(&task_id, assignment.arm == Arm::Variant, 100).await;
+        }
+        let early = framework.evaluate_variant("c1").await.unwrap();
+        assert_eq!(early.verdict, Verdict::Pending);
+        assert!(!early.is_better);
+
+        // A clearly better variant stops early
+        let result = run_experiment(&framework, "c1", |arm, i| arm == Arm::Variant || i % 3 == 0).await;
+        assert_eq!(result.verdict, Verdict::Better);
+        assert!(result.samples.0.min(result.samples.1) < 40);
+        let success = result.comparisons.iter().find(|c| c.metric == "success_rate").unwrap();
+        assert!(success.significant && success.improved);
+        assert!(result.improvement_percentage > 0.0);
+
+        // The decision sticks, and finished experiments take no more tasks
+        assert_eq!(framework.evaluate_variant("c1").await.unwrap().verdict, Verdict::Better);
+        assert!(framework.assign(&TaskId::new()).await.is_none());
+        framework.finish("c1").await;
+        assert!(framework.running().await.is_empty());
+
+        // A worse variant is caught, a neutral one runs to the limit
+        framework.register_variant("c2", "s2").await;
+        let result = run_experiment(&framework, "c2", |arm, i| arm == Arm::Control || i % 3 == 0).await;
+        assert_eq!(result.verdict, Verdict::Worse);
+        framework.finish("c2").await;
+
+        framework.register_variant("c3", "s3").await;
+        let seen = std::cell::Cell::new([0usize; 2]);
+        let alternate = |arm: Arm, _| {
+            let mut counts = seen.get();
+            counts[arm as usize] += 1;
+            seen.set(counts);
+            counts[arm as usize] % 2 == 0
+        };
+        let result = run_experiment(&framework, "c3", alternate).await;
+        assert_eq!(result.verdict, Verdict::NoDifference);
+        assert!(result.samples.0.min(result.samples.1) >= 40);
+    }
 }
@@ -6,25 +6,42 @@
 use common::{async_trait, Error, Module, Result, TaskId};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info, warn};
 
+pub mod analysis_tool;
+pub mod bench;
+pub mod budget;
+pub mod code_improvement;
+pub mod compile_fix;
+pub mod control;
+pub mod delegation;
 pub mod improvement;
+pub mod method_library;
 pub mod orchestrator;
+pub mod otel;
 pub mod planning;
+pub mod react;
 pub mod self_compile;
 pub mod state;
+pub mod stats;
 pub mod evaluation;
+pub mod events;
 pub mod telemetry;
 pub mod reporting;
+pub mod routing;
 pub mod model_assignment;
+pub mod world_probe;
+pub mod worktree;
 
+use delegation::{DelegationSpec, Delegations, SubAgentHandle, SubAgentResult, SubAgentStatus};
 use improvement::{ImprovementEngine, PerformanceMetrics, calculate_success_rate, calculate_throughput};
 use orchestrator::{Orchestrator, TaskExecutionPipeline};
-use self_compile::SelfCompiler;
+use self_compile::{Handoff, SelfCompiler, SwapOutcome, SwapRecord};
 use evaluation::EvaluationEngine;
+use events::{Event, EventBus, EventKind};
 use telemetry::TelemetryManager;
 use state::{AgentState, Checkpoint, StateManager};
 
@@ -38,18 +55,24 @@ pub struct Agent {
     self_compiler: Option<Arc<RwLock<SelfCompiler>>>,
     task_queue: TaskQueue,
     task_relationships: TaskRelationshipTracker,
+    delegations: Arc<Delegations>,
     metrics: Arc<RwLock<AgentMetrics>>,
     modules: Vec<Box<dyn Module>>,
     config: agent_config::AgentConfig,
-    shutdown_tx: Option<mpsc::Sender<()>>,
+    shutdown_tx: parking_lot::Mutex<Option<mpsc::Sender<()>>>,
     event_tx: mpsc::Sender<AgentEvent>,
     event_rx: Arc<RwLock<mpsc::Receiver<AgentEvent>>>,
+    events: EventBus,
+    span_exporter: Option<Arc<otel::SpanExporter>>,
+    /// Held while a swap is under way; the run loop takes no task meanwhile
+    swap_lock: tokio::sync::Mutex<()>,
 }
 
 impl Agent {
     /// Create a new agent with the given configuration
     pub fn new(config: agent_config::AgentConfig) -> Self {
         let (event_tx, event_rx) = mpsc::channel(1000);
+        let events = EventBus::new();
         
         // Initialize self-compiler if enabled
         let self_compiler = if config.self_compile.enabled {
@@ -63,7 +86,7 @@ impl Agent {
         };
         
         Self {
-            orchestrator: Arc::new(RwLock::new(Orchestrator::new())),
+            orchestrator: Arc::new(RwLock::new(Orchestrator::new().with_events(events.clone()))),
             state_manager: Arc::new(RwLock::new(StateManager::new())),
             improvement_engine: Arc::new(RwLock::new(ImprovementEngine::new())),
             evaluation_engine: Arc::new(RwLock::new(EvaluationEngine::new())),
@@ -71,12 +94,16 @@ impl Agent {
             self_compiler,
             task_queue: TaskQueue::new(),
             task_relationships: TaskRelationshipTracker::new(),
+            delegations: Arc::new(Delegations::new()),
             metrics: Arc::new(RwLock::new(AgentMetrics::default())),
             modules: Vec::new(),
             config,
-            shutdown_tx: None,
+            shutdown_tx: parking_lot::Mutex::new(None),
             event_tx,
             event_rx: Arc::new(RwLock::new(event_rx)),
+            events,
+            span_exporter: None,
+            swap_lock: tokio::sync::Mutex::new(()),
         }
     }
 
@@ -87,11 +114,48 @@ impl Agent {
 
     /// Set the orchestrator
     pub fn with_orchestrator(mut self, mut orchestrator: Orchestrator) -> Self {
-        orchestrator = orchestrator.with_evaluation(self.evaluation_engine.clone());
+        orchestrator = orchestrator
+            .with_evaluation(self.evaluation_engine.clone())
+            .with_events(self.events.clone());
         self.orchestrator = Arc::new(RwLock::new(orchestrator));
         self
     }
 
+    /// Exporter flushed before the process is replaced by a new binary
+    pub fn with_span_exporter(mut self, exporter: Arc<otel::SpanExporter>) -> Self {
+        self.span_exporter = Some(exporter);
+        self
+    }
+
+    /// Let self-improvement change the agent's own code
+    pub fn with_code_improver(self, improver: Arc<code_improvement::CodeImprover>) -> Self {
+        match self.improvement_engine.try_write() {
+            Ok(mut engine) => engine.set_code_improver(improver),
+            Err(_) => warn!("Improvement engine busy; code changes stay disabled"),
+        }
+        self
+    }
+
+    /// Arguments a new binary's health probe is started with, e.g. the config file
+    pub fn with_probe_args(self, args: Vec<String>) -> Self {
+        if let Some(compiler) = &self.self_compiler {
+            match compiler.try_write() {
+                Ok(mut compiler) => compiler.set_probe_args(args),
+                Err(_) => warn!("Self-compiler busy; probes run without arguments"),
+            }
+        }
+        self
+    }
+
+    pub fn config(&self) -> &agent_config::AgentConfig {
+        &self.config
+    }
+
+    /// Receive structured events about what the agent is doing
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
     /// Initialize the agent and all modules
     pub async fn initialize(&mut self) -> Result<()> {
         info!("Initializing agent");
@@ -117,6 +181,20 @@ impl Agent {
         // Initialize orchestrator
         self.orchestrator.write().await.initialize().await?;
 
+        // Requeue tasks interrupted by a previous crash so they resume from
+        // their checkpoints; a dry run leaves them for the next real run
+        let orchestrator = self.orchestrator.read().await;
+        let interrupted = if orchestrator.is_dry_run() {
+            Vec::new()
+        } else {
+            orchestrator.interrupted_tasks().await
+        };
+        drop(orchestrator);
+        for task in interrupted {
+            info!("Requeueing interrupted task: {:?}", task.id);
+            self.task_queue.push(task).await;
+        }
+
         // Load any persisted state
         self.load_state().await?;
 
@@ -125,11 +203,14 @@ impl Agent {
     }
 
     /// Run the agent main loop
-    pub async fn run(&mut self) -> Result<()> {
+    ///
+    /// The loop only needs a shared borrow, so tasks can be submitted and
+    /// inspected through the same agent while it runs.
+    pub async fn run(&self) -> Result<()> {
         info!("Starting agent main loop");
 
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
-        self.shutdown_tx = Some(shutdown_tx);
+        *self.shutdown_tx.lock() = Some(shutdown_tx);
 
         // Start the event processor
         let event_rx = Arc::clone(&self.event_rx);
@@ -141,6 +222,9 @@ impl Agent {
             }
         });
 
+        // Held tool calls are reported to event subscribers
+        let approval_forwarder = self.orchestrator.read().await.forward_approvals();
+
         // Start self-improvement timer if enabled
         let improvement_handle = if self.config.agent.self_improvement.enabled {
             let interval = self.config.agent.improvement_interval;
@@ -190,6 +274,9 @@ impl Agent {
             handle.abort();
         }
         event_processor.abort();
+        if let Some(handle) = approval_forwarder {
+            handle.abort();
+        }
 
         info!("Agent main loop ended");
         Ok(())
@@ -201,7 +288,8 @@ impl Agent {
 
         match state {
             AgentState::Idle => {
-                // Check for pending tasks
+                // Check for pending tasks, unless a swap is taking the queue
+                let swap = self.swap_lock.lock().await;
                 if let Some(task) = self.task_queue.pop_highest_priority().await {
                     info!("Popped task from queue: {:?}", task.id);
                     self.state_manager
@@ -209,6 +297,7 @@ impl Agent {
                         .await
                         .transition_to(AgentState::Running(task));
                 } else {
+                    drop(swap);
                     // No tasks, sleep briefly
                     tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
                 }
@@ -280,10 +369,30 @@ impl Agent {
     }
 
     /// Process a single task through the full pipeline
-    async fn process_task(&self, task: Task) -> Result<TaskResult> {
+    async fn process_task(&self, mut task: Task) -> Result<TaskResult> {
         info!("Processing task: {:?}", task.id);
         let start_time = common::chrono::Utc::now();
 
+        // Tasks take part in running improvement experiments at random, and
+        // run with the prompt and settings of their arm
+        let experiment = {
+            let engine = self.improvement_engine.read().await;
+            let experiment = engine.assign_experiment(&task.id).await;
+            let treatment = engine.treatment(experiment.as_ref()).await?;
+            if treatment != Default::default() {
+                task.context
+                    .metadata
+                    .insert("treatment".to_string(), serde_json::to_value(&treatment)?);
+            }
+            experiment
+        };
+        if let Some(assignment) = &experiment {
+            debug!("Task {:?} runs in the {:?} arm of {}", task.id, assignment.arm, assignment.change_id);
+            task.context
+                .metadata
+                .insert("experiment".to_string(), serde_json::to_value(assignment)?);
+        }
+
         // Create checkpoint before task execution
         let checkpoint_id = self
             .state_manager
@@ -303,10 +412,24 @@ impl Agent {
             .signed_duration_since(start_time)
             .num_milliseconds() as u64;
 
+        if experiment.is_some() {
+            let success = result.as_ref().is_ok_and(|r| r.success);
+            self.improvement_engine.read().await.record_outcome(&task.id, success, duration).await;
+        }
+
         match &result {
             Ok(task_result) => {
-                info!("Task {:?} completed successfully in {}ms", task.id, duration);
-                self.metrics.write().await.record_success(&task.id, duration, task.parent_id.is_some());
+                // A task wound down by its budget did not finish its work
+                match &task_result.exhausted {
+                    Some(reason) => {
+                        warn!("Task {:?} stopped early after {}ms: {}", task.id, duration, reason);
+                        self.metrics.write().await.record_failure(&task.id, task.parent_id.is_some());
+                    }
+                    None => {
+                        info!("Task {:?} completed successfully in {}ms", task.id, duration);
+                        self.metrics.write().await.record_success(&task.id, duration, task.parent_id.is_some());
+                    }
+                }
                 
                 // If this is a subtask, mark it as completed in the task relationships tracker
                 if let Some(parent_id) = task.parent_id {
@@ -360,8 +483,9 @@ impl Agent {
             }
         }
 
-        // Step 5: Validate - A/B test and measure impact
-        let validation = improvement_engine.validate_improvements(&applied_improvements).await?;
+        // Step 5: Validate - A/B test every change still under test
+        let under_test = improvement_engine.changes_under_test().await;
+        let validation = improvement_engine.validate_improvements(&under_test).await?;
         
         // Rollback any changes that degraded performance
         for rollback in &validation.rollbacks {
@@ -374,10 +498,11 @@ impl Agent {
 
         let improvement_summary = Improvement {
             description: format!(
-                "Self-improvement cycle completed. Applied: {}, Successful: {}, Rolled back: {}",
+                "Self-improvement cycle completed. Applied: {}, Successful: {}, Rolled back: {}, Under test: {}",
                 applied_improvements.len(),
                 validation.successful.len(),
-                validation.rollbacks.len()
+                validation.rollbacks.len(),
+                validation.pending.len()
             ),
             changes: validation.successful,
             metrics_before: analysis.metrics_before.map(|m| PerformanceMetrics {
@@ -433,14 +558,20 @@ impl Agent {
         Ok(())
     }
 
+    /// Ask a running main loop to return
+    pub async fn stop(&self) {
+        let shutdown_tx = self.shutdown_tx.lock().clone();
+        if let Some(tx) = shutdown_tx {
+            let _ = tx.send(()).await;
+        }
+    }
+
     /// Shutdown the agent gracefully
     pub async fn shutdown(&mut self) -> Result<()> {
         info!("Shutting down agent");
 
         // Signal shutdown
-        if let Some(tx) = &self.shutdown_tx {
-            let _ = tx.send(()).await;
-        }
+        self.stop().await;
 
         // Transition to shutting down state
         self.state_manager
@@ -499,8 +630,12 @@ impl Agent {
             self.task_relationships.add_subtask(task.id, subtask_id).await;
         }
         
+        // A task queued during a swap would be left behind by the handoff
+        let swap = self.swap_lock.lock().await;
         self.task_queue.push(task.clone()).await;
+        drop(swap);
         let task_id = task.id;
+        self.emit_submitted(&task);
         self.event_tx.send(AgentEvent::TaskSubmitted(task)).await
             .map_err(|_| Error::Internal("Failed to send task event".to_string()))?;
         Ok(task_id)
@@ -516,13 +651,120 @@ impl Agent {
         // Add to task relationships tracker
         self.task_relationships.add_subtask(parent_id, task.id).await;
         
+        // A task queued during a swap would be left behind by the handoff
+        let swap = self.swap_lock.lock().await;
         self.task_queue.push(task.clone()).await;
+        drop(swap);
         let task_id = task.id;
+        self.emit_submitted(&task);
         self.event_tx.send(AgentEvent::TaskSubmitted(task)).await
             .map_err(|_| Error::Internal("Failed to send task event".to_string()))?;
         Ok(task_id)
     }
 
+    /// Run a subtask in a child orchestrator with its own scope and budget
+    ///
+    /// The child runs concurrently with the agent loop; join the returned
+    /// handle for its structured result. Scopes only ever narrow: a child of
+    /// a delegated task gets at most its parent's tools and paths.
+    pub async fn delegate(&self, parent_id: TaskId, mut task: Task, spec: DelegationSpec) -> Result<SubAgentHandle> {
+        task.parent_id = Some(parent_id);
+        let scope = match self.delegations.scope_of(&parent_id) {
+            Some(parent_scope) => parent_scope.narrow(&spec.scope)?,
+            None => spec.scope.clone(),
+        };
+        let child = self.orchestrator.read().await.child(&spec, scope.clone())?;
+        info!("Delegating subtask {:?} for parent {:?}", task.id, parent_id);
+
+        self.task_relationships.add_subtask(parent_id, task.id).await;
+        self.metrics.write().await.record_task_start(&task.id, true);
+        self.emit_submitted(&task);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let task_id = task.id;
+        let delegations = self.delegations.clone();
+        let relationships = self.task_relationships.clone();
+        let metrics = self.metrics.clone();
+        let max_duration = spec.max_duration;
+
+        // A swap checks for running children while holding this lock
+        let _swap = self.swap_lock.lock().await;
+        self.delegations.spawn(parent_id, task_id, scope, async move {
+            let start = std::time::Instant::now();
+            let run = child.process_task(task);
+            let outcome = match max_duration {
+                Some(limit) => tokio::time::timeout(limit, run).await.ok(),
+                None => Some(run.await),
+            };
+            let duration_ms = start.elapsed().as_millis() as u64;
+
+            let (status, result) = match outcome {
+                Some(Ok(result)) => (SubAgentStatus::Completed, Some(result)),
+                Some(Err(Error::Cancelled)) => (SubAgentStatus::Cancelled, None),
+                Some(Err(e)) => (SubAgentStatus::Failed { error: e.to_string() }, None),
+                None => (SubAgentStatus::TimedOut, None),
+            };
+            if result.as_ref().is_some_and(|r| r.success) {
+                metrics.write().await.record_success(&task_id, duration_ms, true);
+                relationships.mark_subtask_completed(parent_id, task_id).await;
+            } else {
+                metrics.write().await.record_failure(&task_id, true);
+            }
+
+            delegations.finish(&parent_id, &task_id);
+            let _ = tx.send(SubAgentResult {
+                task_id,
+                parent_id,
+                status,
+                result,
+                duration_ms,
+            });
+        });
+
+        Ok(SubAgentHandle {
+            task_id,
+            parent_id,
+            result: rx,
+        })
+    }
+
+    /// Cancel a task and, transitively, all of its subtasks
+    ///
+    /// Queued tasks are dropped, delegated children are aborted, and a task
+    /// that is already running stops before its next step. Returns the IDs
+    /// of every task that was cancelled.
+    pub async fn cancel_task(&self, task_id: TaskId) -> Vec<TaskId> {
+        let mut cancelled = vec![task_id];
+        let mut stack = vec![task_id];
+        while let Some(id) = stack.pop() {
+            let subtasks = self.task_relationships.get_subtasks(id).await;
+            cancelled.extend(subtasks.iter().copied());
+            stack.extend(subtasks);
+        }
+        cancelled.extend(self.delegations.cancel(&task_id));
+        cancelled.sort_by_key(|id| id.0);
+        cancelled.dedup();
+
+        for id in &cancelled {
+            self.task_queue.remove(id).await;
+        }
+        if let AgentState::Running(running) = self.current_state().await {
+            if cancelled.contains(&running.id) {
+                self.orchestrator.read().await.cancel(running.id);
+            }
+        }
+        info!("Cancelled task {:?} and {} subtask(s)", task_id, cancelled.len() - 1);
+        cancelled
+    }
+
+    fn emit_submitted(&self, task: &Task) {
+        self.events.emit(EventKind::TaskSubmitted {
+            task_id: task.id,
+            description: task.description.clone(),
+            parent_id: task.parent_id,
+        });
+    }
+
     /// Get all subtasks for a parent task
     pub async fn get_subtasks(&self, parent_id: TaskId) -> Vec<TaskId> {
         self.task_relationships.get_subtasks(parent_id).await
@@ -543,6 +785,11 @@ impl Agent {
         self.task_relationships.get_subtask_completion_status(parent_id).await
     }
 
+    /// Number of tasks waiting to run
+    pub async fn queue_len(&self) -> usize {
+        self.task_queue.len().await
+    }
+
     /// Get current agent metrics
     pub async fn get_metrics(&self) -> AgentMetrics {
         self.metrics.read().await.clone()
@@ -583,7 +830,26 @@ impl Agent {
         // Actually, Orchestrator has `intelligence`. We can add a method there or expose it.
         // For this step, I will add a method to `Orchestrator` to run evaluation.
         
-        self.orchestrator.read().await.run_evaluation(task, output).await
+        let report = self.orchestrator.read().await.run_evaluation(task, output).await?;
+        if report.max_possible_score > 0.0 {
+            let score = (report.weighted_score / report.max_possible_score) as f64;
+            self.improvement_engine.read().await.record_score(&task.id, score).await;
+        }
+        Ok(report)
+    }
+
+    /// Evaluate a completed task with several judges and combine their scores
+    pub async fn evaluate_task_with_judges(
+        &self,
+        task: &Task,
+        output: &str,
+        judges: &[evaluation::Persona],
+    ) -> Result<evaluation::Consensus> {
+        self.orchestrator
+            .read()
+            .await
+            .run_consensus_evaluation(task, output, judges)
+            .await
     }
 
     /// Get evaluation dashboard
@@ -614,12 +880,106 @@ impl Agent {
     }
 
     /// Restart the agent with a new binary
+    ///
+    /// The binary must pass a health probe first; otherwise this process
+    /// keeps running. Does not return if the swap succeeds.
     pub async fn restart_with_new_binary(&self, binary_path: PathBuf) -> Result<()> {
+        let probe = self.probe_binary(&binary_path).await?;
+        self.hand_off(&binary_path, probe, None).await
+    }
+
+    /// Run a new binary's health probe, recording a failure in the swap log
+    pub async fn probe_binary(&self, binary_path: &Path) -> Result<self_compile::ProbeReport> {
+        let Some(compiler) = &self.self_compiler else {
+            return Err(Error::Config("Self-compilation is not enabled".to_string()));
+        };
+        let compiler = compiler.read().await;
+        match compiler.probe(binary_path).await {
+            Ok(report) => Ok(report),
+            Err(e) => {
+                warn!("Keeping the running binary: {}", e);
+                let record = SwapRecord::new(binary_path, SwapOutcome::ProbeFailed, None).with_error(&e);
+                compiler.record_swap(&record).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Replace this process with a probed binary, passing it the queued tasks
+    ///
+    /// `control` is the daemon's listener, kept open for the new process.
+    /// A running task or sub-agent blocks the swap. Only returns if the swap failed, in
+    /// which case the tasks are back in this process's queue.
+    pub async fn hand_off(
+        &self,
+        binary_path: &Path,
+        probe: self_compile::ProbeReport,
+        control: Option<self_compile::ControlHandoff>,
+    ) -> Result<()> {
+        let Some(compiler) = &self.self_compiler else {
+            return Err(Error::Config("Self-compilation is not enabled".to_string()));
+        };
+        let compiler = compiler.read().await;
+        let record = SwapRecord::new(binary_path, SwapOutcome::Failed, Some(probe));
+
+        // No task starts between this check and the exec
+        let _paused = self.swap_lock.lock().await;
+        if let AgentState::Running(task) = self.current_state().await {
+            let e = Error::Validation(format!("Task {:?} is still running", task.id));
+            compiler.record_swap(&record.with_error(&e)).await;
+            return Err(e);
+        }
+        // The exec would kill sub-agents and orphan their worktrees
+        let children = self.delegations.running();
+        if !children.is_empty() {
+            let e = Error::Validation(format!("{} sub-agent(s) still running: {:?}", children.len(), children));
+            compiler.record_swap(&record.with_error(&e)).await;
+            return Err(e);
+        }
+        let backup = match compiler.install(binary_path).await {
+            Ok(backup) => backup,
+            Err(e) => {
+                compiler.record_swap(&record.with_error(&e)).await;
+                return Err(e);
+            }
+        };
+
+        let tasks = self.task_queue.drain().await;
+        // Exec replaces the process, so buffered spans go out first
+        if let Some(exporter) = &self.span_exporter {
+            if let Err(e) = exporter.flush().await {
+                warn!("{}", e);
+            }
+        }
+        let e = compiler.hand_off(&Handoff::new(tasks.clone(), control, record.clone()));
+
+        // Still here, so the new process never started
+        error!("Swap to {:?} failed: {}", binary_path, e);
+        for task in tasks {
+            self.task_queue.push(task).await;
+        }
+        if let Some(backup) = backup {
+            if let Err(e) = compiler.restore(&backup).await {
+                error!("Could not restore {:?}: {}", backup, e);
+            }
+        }
+        compiler.record_swap(&record.with_error(&e)).await;
+        Err(e)
+    }
+
+    /// Queue the tasks handed off by the process this one replaced
+    pub async fn adopt(&self, handoff: Handoff) {
+        info!(
+            "Adopting {} task(s) from v{}",
+            handoff.tasks.len(),
+            handoff.swap.from_version
+        );
+        for task in handoff.tasks {
+            self.emit_submitted(&task);
+            self.task_queue.push(task).await;
+        }
         if let Some(compiler) = &self.self_compiler {
-            let compiler = compiler.read().await;
-            compiler.restart_with_new_binary(&binary_path).await
-        } else {
-            Err(Error::Config("Self-compilation is not enabled".to_string()))
+            compiler.read().await.record_swap(&handoff.swap).await;
         }
     }
 
@@ -634,7 +994,7 @@ impl Agent {
     }
 
     /// List available backups
-    pub async fn list_backups(&self) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
+    pub async fn list_backups(&self) -> Result<Vec<self_compile::Backup>> {
         if let Some(compiler) = &self.self_compiler {
             let compiler = compiler.read().await;
             compiler.list_backups().await
@@ -677,6 +1037,9 @@ pub struct Task {
     pub dependencies: Vec<TaskId>,
     pub parent_id: Option<TaskId>,
     pub subtasks: Vec<TaskId>,
+    /// Resource budget for this task, overriding the configured one
+    #[serde(default)]
+    pub budget: Option<intelligence::ResourceLimits>,
 }
 
 impl Task {
@@ -698,6 +1061,7 @@ impl Task {
             dependencies: Vec::new(),
             parent_id: None,
             subtasks: Vec::new(),
+            budget: None,
         }
     }
 
@@ -736,6 +1100,12 @@ impl Task {
         self.dependencies.push(task_id);
         self
     }
+
+    /// Set the resource budget
+    pub fn with_budget(mut self, budget: intelligence::ResourceLimits) -> Self {
+        self.budget = Some(budget);
+        self
+    }
 }
 
 /// Task context
@@ -772,6 +1142,16 @@ pub struct TaskResult {
     pub completed_at: common::chrono::DateTime<common::chrono::Utc>,
     pub execution_time_ms: u64,
     pub metrics: TaskExecutionMetrics,
+    /// Resources consumed against the task's budget
+    #[serde(default)]
+    pub usage: budget::ResourceUsage,
+    /// Why the task stopped early; the output then summarizes partial progress
+    #[serde(default)]
+    pub exhausted: Option<String>,
+    /// Why a successful task's worktree could not be integrated; its work
+    /// is kept in the worktree
+    #[serde(default)]
+    pub integration_error: Option<String>,
 }
 
 /// Task execution metrics
@@ -834,10 +1214,12 @@ pub enum ChangeType {
     Configuration,
     Prompt,
     Strategy,
+    /// Source change to the agent itself, applied as a git commit
+    Code,
 }
 
 /// Task relationship tracker to manage parent-child relationships
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct TaskRelationshipTracker {
     /// Map of task IDs to their subtask IDs
     task_subtasks: Arc<RwLock<HashMap<TaskId, Vec<TaskId>>>>,
@@ -969,6 +1351,11 @@ impl TaskQueue {
         self.inner.read().await.iter().cloned().collect()
     }
 
+    /// Remove and return every pending task in priority order
+    pub async fn drain(&self) -> Vec<Task> {
+        self.inner.write().await.drain(..).collect()
+    }
+
     /// Remove a task by ID
     pub async fn remove(&self, task_id: &TaskId) -> Option<Task> {
         let mut queue = self.inner.write().await;
@@ -1114,4 +1501,34 @@ mod tests {
         metrics.record_failure(&TaskId::new(), false);
         assert_eq!(metrics.success_rate, 0.5);
     }
+
+    #[tokio::test]
+    async fn test_running_sub_agents_block_the_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = agent_config::AgentConfig::default();
+        config.self_compile.enabled = true;
+        let mut agent = Agent::new(config.clone());
+        agent.self_compiler = Some(Arc::new(RwLock::new(SelfCompiler::new(config.self_compile, dir.path()))));
+
+        let (parent, child) = (TaskId::new(), TaskId::new());
+        agent.delegations.spawn(parent, child, delegation::ToolScope::default(), async {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        });
+
+        let probe = self_compile::ProbeReport {
+            healthy: true,
+            version: "9.9.9".to_string(),
+            elapsed_ms: 5,
+            error: None,
+        };
+        let err = agent.hand_off(&dir.path().join("new-binary"), probe, None).await.unwrap_err();
+        assert!(err.to_string().contains("sub-agent"), "{}", err);
+        assert_eq!(agent.delegations.running(), vec![child]);
+
+        let compiler = agent.self_compiler.as_ref().unwrap().read().await;
+        let history = compiler.swap_history().await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].error.as_deref().unwrap().contains("sub-agent"));
+        agent.delegations.cancel(&parent);
+    }
 }
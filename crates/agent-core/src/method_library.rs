@@ -0,0 +1,480 @@
+//! Declarative HTN method libraries.
+//!
+//! Teams describe their own workflows as TOML or YAML files under
+//! `.agent/methods/`. Each file defines compound tasks whose methods are
+//! validated and registered with the [`HTNPlanner`] at startup.
+
+use common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::planning::{
+    CompoundTask, Condition, Cost, Effect, HTNPlanner, Method, OrderingConstraint, PrimitiveTask,
+    Task,
+};
+
+/// Default directory for method library files
+pub const DEFAULT_METHODS_DIR: &str = ".agent/methods";
+
+/// Contents of one method library file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MethodLibrary {
+    #[serde(default)]
+    pub tasks: Vec<CompoundTaskSpec>,
+}
+
+/// Declarative compound task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompoundTaskSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Task descriptions containing any of these select this task
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub methods: Vec<MethodSpec>,
+}
+
+/// Declarative decomposition method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodSpec {
+    pub name: String,
+    #[serde(default)]
+    pub preconditions: Vec<Condition>,
+    pub subtasks: Vec<SubtaskSpec>,
+    #[serde(default = "default_ordering")]
+    pub ordering: OrderingConstraint,
+    #[serde(default)]
+    pub cost: Cost,
+}
+
+fn default_ordering() -> OrderingConstraint {
+    OrderingConstraint::Sequential
+}
+
+/// Subtask of a method: either a reference to another compound task or a primitive step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SubtaskSpec {
+    Reference { task: String },
+    Primitive(PrimitiveTaskSpec),
+}
+
+/// Declarative primitive task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrimitiveTaskSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub args: serde_json::Value,
+    #[serde(default)]
+    pub preconditions: Vec<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub cost: Cost,
+}
+
+impl SubtaskSpec {
+    fn name(&self) -> &str {
+        match self {
+            SubtaskSpec::Reference { task } => task,
+            SubtaskSpec::Primitive(spec) => &spec.name,
+        }
+    }
+}
+
+impl MethodLibrary {
+    /// Parse a library from TOML or YAML based on the file extension
+    pub fn parse(path: &Path, content: &str) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(content)
+                .map_err(|e| Error::Config(format!("{}: {}", path.display(), e))),
+            Some("yaml") | Some("yml") => serde_yaml::from_str(content)
+                .map_err(|e| Error::Config(format!("{}: {}", path.display(), e))),
+            _ => Err(Error::Config(format!(
+                "{}: method libraries must be .toml, .yaml or .yml",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Read every method library file in a directory
+///
+/// A missing directory is not an error; it simply yields no libraries.
+pub async fn load_dir(dir: &Path) -> Result<Vec<(PathBuf, MethodLibrary)>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("toml") | Some("yaml") | Some("yml")
+        ) {
+            paths.push(path);
+        }
+    }
+    // Deterministic order so duplicate reports are stable
+    paths.sort();
+
+    let mut libraries = Vec::new();
+    for path in paths {
+        let content = tokio::fs::read_to_string(&path).await?;
+        let library = MethodLibrary::parse(&path, &content)?;
+        debug!("Read {} task(s) from {}", library.tasks.len(), path.display());
+        libraries.push((path, library));
+    }
+
+    Ok(libraries)
+}
+
+/// Validate libraries and resolve them into compound tasks
+///
+/// `builtin` holds tasks already known to the planner, which subtasks may
+/// reference. When `available_tools` is non-empty every primitive tool must
+/// be one of them. All problems are reported together.
+pub fn resolve(
+    libraries: &[(PathBuf, MethodLibrary)],
+    builtin: &HashMap<String, CompoundTask>,
+    available_tools: &[String],
+) -> Result<Vec<(CompoundTaskSpec, CompoundTask)>> {
+    let mut errors = Vec::new();
+    let mut specs: HashMap<String, (&Path, &CompoundTaskSpec)> = HashMap::new();
+
+    for (path, library) in libraries {
+        for spec in &library.tasks {
+            if let Some((other, _)) = specs.get(&spec.name) {
+                errors.push(format!(
+                    "{}: task '{}' is already defined in {}",
+                    path.display(),
+                    spec.name,
+                    other.display()
+                ));
+                continue;
+            }
+            validate_task(path, spec, available_tools, &mut errors);
+            specs.insert(spec.name.clone(), (path.as_path(), spec));
+        }
+    }
+
+    // Resolve references; a reference may point at another library task or a built-in
+    let mut resolved: HashMap<String, CompoundTask> = HashMap::new();
+    let mut names: Vec<&String> = specs.keys().collect();
+    names.sort();
+    for name in names {
+        let mut stack = Vec::new();
+        if let Err(e) = resolve_task(name, &specs, builtin, &mut resolved, &mut stack) {
+            errors.push(e);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(Error::Validation(format!(
+            "Invalid method libraries:\n  - {}",
+            errors.join("\n  - ")
+        )));
+    }
+
+    let mut tasks: Vec<(CompoundTaskSpec, CompoundTask)> = specs
+        .values()
+        .map(|(_, spec)| ((*spec).clone(), resolved[&spec.name].clone()))
+        .collect();
+    tasks.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+    Ok(tasks)
+}
+
+fn validate_task(path: &Path, spec: &CompoundTaskSpec, tools: &[String], errors: &mut Vec<String>) {
+    let at = |detail: String| format!("{}: task '{}': {}", path.display(), spec.name, detail);
+
+    if spec.name.trim().is_empty() {
+        errors.push(format!("{}: task name cannot be empty", path.display()));
+    }
+    if spec.methods.is_empty() {
+        errors.push(at("must define at least one method".to_string()));
+    }
+
+    let mut method_names = HashSet::new();
+    for method in &spec.methods {
+        if !method_names.insert(method.name.as_str()) {
+            errors.push(at(format!("duplicate method '{}'", method.name)));
+        }
+        if method.subtasks.is_empty() {
+            errors.push(at(format!("method '{}' has no subtasks", method.name)));
+        }
+
+        for condition in &method.preconditions {
+            if condition.expression.trim().is_empty() && condition.condition_type.requires_expression() {
+                errors.push(at(format!("method '{}' has an empty precondition", method.name)));
+            }
+        }
+
+        let subtask_names: HashSet<&str> = method.subtasks.iter().map(|s| s.name()).collect();
+        if let OrderingConstraint::Partial(pairs) = &method.ordering {
+            for (before, after) in pairs {
+                for name in [before, after] {
+                    if !subtask_names.contains(name.as_str()) {
+                        errors.push(at(format!(
+                            "method '{}' orders unknown subtask '{}'",
+                            method.name, name
+                        )));
+                    }
+                }
+            }
+        }
+
+        for subtask in &method.subtasks {
+            if let SubtaskSpec::Primitive(primitive) = subtask {
+                if let Some(tool) = &primitive.tool {
+                    if !tools.is_empty() && !tools.contains(tool) {
+                        errors.push(at(format!(
+                            "subtask '{}' uses unknown tool '{}'",
+                            primitive.name, tool
+                        )));
+                    }
+                }
+                for effect in &primitive.effects {
+                    if effect.target.trim().is_empty() {
+                        errors.push(at(format!(
+                            "subtask '{}' has an effect without a target",
+                            primitive.name
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn resolve_task(
+    name: &str,
+    specs: &HashMap<String, (&Path, &CompoundTaskSpec)>,
+    builtin: &HashMap<String, CompoundTask>,
+    resolved: &mut HashMap<String, CompoundTask>,
+    stack: &mut Vec<String>,
+) -> std::result::Result<CompoundTask, String> {
+    if let Some(task) = resolved.get(name) {
+        return Ok(task.clone());
+    }
+    if stack.iter().any(|n| n == name) {
+        stack.push(name.to_string());
+        return Err(format!("cyclic task references: {}", stack.join(" -> ")));
+    }
+
+    let Some((_, spec)) = specs.get(name) else {
+        return builtin
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("task '{}' references unknown task '{}'", stack.last().map(String::as_str).unwrap_or("?"), name));
+    };
+
+    stack.push(name.to_string());
+    let mut methods = Vec::new();
+    for method in &spec.methods {
+        let mut subtasks = Vec::new();
+        for (i, subtask) in method.subtasks.iter().enumerate() {
+            let task = match subtask {
+                SubtaskSpec::Reference { task } => {
+                    Task::Compound(resolve_task(task, specs, builtin, resolved, stack)?)
+                }
+                SubtaskSpec::Primitive(primitive) => Task::Primitive(PrimitiveTask {
+                    id: format!("{}.{}.{}", spec.name, method.name, i),
+                    name: primitive.name.clone(),
+                    description: if primitive.description.is_empty() {
+                        primitive.name.clone()
+                    } else {
+                        primitive.description.clone()
+                    },
+                    tool: primitive.tool.clone(),
+                    tool_args: if primitive.args.is_null() {
+                        serde_json::json!({})
+                    } else {
+                        primitive.args.clone()
+                    },
+                    preconditions: primitive.preconditions.clone(),
+                    effects: primitive.effects.clone(),
+                    estimated_cost: primitive.cost.clone(),
+                }),
+            };
+            subtasks.push(task);
+        }
+
+        methods.push(Method {
+            name: method.name.clone(),
+            preconditions: method.preconditions.clone(),
+            subtasks,
+            ordering: method.ordering.clone(),
+            cost: method.cost.clone(),
+        });
+    }
+    stack.pop();
+
+    let task = CompoundTask {
+        id: spec.name.clone(),
+        name: spec.name.clone(),
+        description: spec.description.clone(),
+        methods,
+    };
+    resolved.insert(name.to_string(), task.clone());
+    Ok(task)
+}
+
+impl HTNPlanner {
+    /// Load, validate and register all method libraries in a directory
+    ///
+    /// Returns the number of compound tasks registered. Library tasks
+    /// replace built-in tasks of the same name.
+    pub async fn load_method_libraries(
+        &mut self,
+        dir: impl AsRef<Path>,
+        available_tools: &[String],
+    ) -> Result<usize> {
+        let dir = dir.as_ref();
+        let libraries = load_dir(dir).await?;
+        if libraries.is_empty() {
+            return Ok(0);
+        }
+
+        let builtin: HashMap<String, CompoundTask> = self
+            .task_names()
+            .into_iter()
+            .filter_map(|name| self.task(&name).cloned().map(|t| (name, t)))
+            .collect();
+
+        let tasks = resolve(&libraries, &builtin, available_tools)?;
+        let count = tasks.len();
+        for (spec, task) in tasks {
+            if builtin.contains_key(&task.name) {
+                info!("Method library overrides built-in task: {}", task.name);
+            }
+            self.register_keywords(&task.name, &spec.keywords);
+            self.register_task(task);
+        }
+
+        info!("Loaded {} task(s) from method libraries in {}", count, dir.display());
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::{GoalType, WorldState};
+
+    const ENDPOINT_TOML: &str = r#"
+[[tasks]]
+name = "add_endpoint"
+description = "Add an HTTP endpoint"
+keywords = ["endpoint"]
+
+[[tasks.methods]]
+name = "axum"
+cost = { time_ms = 60000, tokens = 4000 }
+
+[[tasks.methods.subtasks]]
+name = "edit_router"
+description = "Register the route"
+tool = "filesystem"
+args = { operation = "read", path = "src/router.rs" }
+effects = [{ effect_type = "modify_file", target = "src/router.rs" }]
+
+[[tasks.methods.subtasks]]
+task = "run_tests"
+"#;
+
+    const TESTS_YAML: &str = r#"
+tasks:
+  - name: run_tests
+    methods:
+      - name: cargo
+        subtasks:
+          - name: cargo_test
+            tool: test
+"#;
+
+    fn library(name: &str, content: &str) -> (PathBuf, MethodLibrary) {
+        let path = PathBuf::from(name);
+        let library = MethodLibrary::parse(&path, content).unwrap();
+        (path, library)
+    }
+
+    #[tokio::test]
+    async fn test_load_toml_and_yaml_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("endpoint.toml"), ENDPOINT_TOML).unwrap();
+        std::fs::write(dir.path().join("tests.yaml"), TESTS_YAML).unwrap();
+
+        let mut planner = HTNPlanner::new();
+        let count = planner.load_method_libraries(dir.path(), &[]).await.unwrap();
+        assert_eq!(count, 2);
+
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::CodeGeneration,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: String::new(),
+        };
+        let goal = planner.goal_for(&intent, "Add an endpoint for users");
+        assert!(matches!(goal.goal_type, GoalType::Custom(ref name) if name == "add_endpoint"));
+
+        let plan = planner.plan(&goal, &WorldState::default(), &[]).await.unwrap();
+        let names: Vec<&str> = plan.tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["edit_router", "cargo_test"]);
+    }
+
+    #[test]
+    fn test_validation_reports_all_problems() {
+        let broken = r#"
+[[tasks]]
+name = "broken"
+
+[[tasks.methods]]
+name = "m"
+ordering = { partial = [["a", "missing"]] }
+
+[[tasks.methods.subtasks]]
+name = "a"
+tool = "teleport"
+
+[[tasks.methods.subtasks]]
+task = "nowhere"
+"#;
+        let libraries = vec![library("broken.toml", broken)];
+        let tools = vec!["filesystem".to_string()];
+        let err = resolve(&libraries, &HashMap::new(), &tools).unwrap_err().to_string();
+
+        assert!(err.contains("unknown subtask 'missing'"));
+        assert!(err.contains("unknown tool 'teleport'"));
+        assert!(err.contains("unknown task 'nowhere'"));
+    }
+
+    #[test]
+    fn test_cyclic_references_are_rejected() {
+        let cyclic = r#"
+tasks:
+  - name: a
+    methods:
+      - name: m
+        subtasks:
+          - task: b
+  - name: b
+    methods:
+      - name: m
+        subtasks:
+          - task: a
+"#;
+        let libraries = vec![library("cyclic.yml", cyclic)];
+        let err = resolve(&libraries, &HashMap::new(), &[]).unwrap_err().to_string();
+        assert!(err.contains("cyclic task references"));
+    }
+}
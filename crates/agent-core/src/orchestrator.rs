@@ -5,12 +5,24 @@
 
 use common::{async_trait, Error, Module, Result, TaskId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
-
-use crate::evaluation::{EvaluationEngine, EvaluationReport, Persona};
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::budget::{self, BudgetTracker, Pricing};
+use crate::compile_fix::{self, CheckOutcome, CompileFixer, CompileState};
+use crate::delegation::{self, DelegationSpec, ToolScope};
+use crate::evaluation::{Consensus, EvaluationEngine, EvaluationReport, Persona};
+use crate::events::{self, EventBus, EventKind};
+use crate::improvement::Treatment;
+use crate::otel;
+use crate::planning::{self, Goal, HTNPlanner, PlanExecutor, WorldState};
+use crate::react::{self, ReactDecision};
+use crate::routing::{ModelRoute, ModelRouter, RouteStage};
+use crate::world_probe::{ProbeSession, WorldProbe};
+use crate::worktree::{TaskWorktree, WorktreeGuard, WorktreeManager};
 
 /// Orchestrator for task coordination
 pub struct Orchestrator {
@@ -23,6 +35,31 @@ pub struct Orchestrator {
     pipeline: TaskExecutionPipeline,
     retry_policy: RetryPolicy,
     checkpoint_store: Arc<RwLock<CheckpointStore>>,
+    plan_executor: PlanExecutor,
+    world_probe: WorldProbe,
+    probe_sessions: parking_lot::Mutex<HashMap<TaskId, Arc<ProbeSession>>>,
+    worktrees: parking_lot::Mutex<HashMap<TaskId, Arc<TaskWorktree>>>,
+    budgets: parking_lot::Mutex<HashMap<TaskId, Arc<BudgetTracker>>>,
+    compile_states: parking_lot::Mutex<HashMap<TaskId, CompileState>>,
+    /// Tools and paths a delegated child may use
+    scope: Option<ToolScope>,
+    /// Token budget of a delegated child, on top of the configured limits
+    token_budget: Option<u32>,
+    cancelled: parking_lot::Mutex<HashSet<TaskId>>,
+    events: EventBus,
+    /// Picks a model per intent category and stage when adaptive routing is on
+    router: Option<Arc<ModelRouter>>,
+    /// Engines for routed models other than the configured one
+    engines: parking_lot::Mutex<HashMap<String, Arc<intelligence::IntelligenceEngine>>>,
+    categories: parking_lot::Mutex<HashMap<TaskId, intelligence::IntentCategory>>,
+}
+
+/// An engine chosen for one model call
+struct Routed {
+    engine: Arc<intelligence::IntelligenceEngine>,
+    /// Where to record the outcome; `None` when the call was not routed
+    route: Option<(intelligence::IntentCategory, RouteStage, ModelRoute)>,
+    started: std::time::Instant,
 }
 
 impl Orchestrator {
@@ -37,6 +74,19 @@ impl Orchestrator {
             pipeline: TaskExecutionPipeline::new(),
             retry_policy: RetryPolicy::default(),
             checkpoint_store: Arc::new(RwLock::new(CheckpointStore::new())),
+            plan_executor: PlanExecutor::new(),
+            world_probe: WorldProbe::new(),
+            probe_sessions: parking_lot::Mutex::new(HashMap::new()),
+            worktrees: parking_lot::Mutex::new(HashMap::new()),
+            budgets: parking_lot::Mutex::new(HashMap::new()),
+            compile_states: parking_lot::Mutex::new(HashMap::new()),
+            scope: None,
+            token_budget: None,
+            cancelled: parking_lot::Mutex::new(HashSet::new()),
+            events: EventBus::new(),
+            router: None,
+            engines: parking_lot::Mutex::new(HashMap::new()),
+            categories: parking_lot::Mutex::new(HashMap::new()),
         }
     }
 
@@ -46,12 +96,24 @@ impl Orchestrator {
         self
     }
 
+    /// Report progress on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
     /// Set the intelligence engine
     pub fn with_intelligence(mut self, intelligence: Arc<intelligence::IntelligenceEngine>) -> Self {
         self.intelligence = Some(intelligence);
         self
     }
 
+    /// Choose models per intent category and stage from measured outcomes
+    pub fn with_router(mut self, router: Arc<ModelRouter>) -> Self {
+        self.router = Some(router);
+        self
+    }
+
     /// Set the analysis engine
     pub fn with_analysis(mut self, analysis: Arc<analysis::AnalysisEngine>) -> Self {
         self.analysis = Some(analysis);
@@ -82,70 +144,373 @@ impl Orchestrator {
         self
     }
 
+    /// Set the HTN planner used before falling back to LLM planning
+    pub fn with_planner(mut self, planner: HTNPlanner) -> Self {
+        self.plan_executor = PlanExecutor::new().with_planner(planner);
+        self
+    }
+
+    /// Set the evaluators used to ground planner preconditions in the workspace
+    pub fn with_world_probe(mut self, probe: WorldProbe) -> Self {
+        self.world_probe = probe;
+        self
+    }
+
+    /// Persist checkpoints under the given directory so tasks survive restarts
+    pub fn with_checkpoint_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint_store = Arc::new(RwLock::new(CheckpointStore::with_storage(path.into())));
+        self
+    }
+
+    /// Build a child orchestrator for a delegated sub-task
+    ///
+    /// The child shares this orchestrator's engines and planner but only
+    /// sees the tools and paths in `scope`, stops at the spec's token
+    /// budget, and uses the spec's model if one is given. Its checkpoints
+    /// are kept in memory: an interrupted child is re-delegated by its
+    /// parent rather than resumed.
+    pub fn child(&self, spec: &DelegationSpec, scope: ToolScope) -> Result<Orchestrator> {
+        let mut config = self.config.clone();
+        let intelligence = match &spec.model {
+            // A replay is one ordered stream, shared with the parent
+            Some(_) if self.replaying() => self.intelligence.clone(),
+            Some(model) => {
+                let config = config.as_mut().ok_or_else(|| {
+                    Error::Config("A sub-agent model override needs the agent configuration".to_string())
+                })?;
+                config.llm.model = model.clone();
+                Some(self.build_engine(&config.llm.provider, model)?)
+            }
+            None => self.intelligence.clone(),
+        };
+
+        Ok(Self {
+            intelligence,
+            analysis: self.analysis.clone(),
+            knowledge: self.knowledge.clone(),
+            tools: self.tools.clone(),
+            evaluation: self.evaluation.clone(),
+            config,
+            pipeline: TaskExecutionPipeline::new(),
+            retry_policy: self.retry_policy.clone(),
+            checkpoint_store: Arc::new(RwLock::new(CheckpointStore::new())),
+            plan_executor: self.plan_executor.clone(),
+            world_probe: self.world_probe.clone(),
+            probe_sessions: parking_lot::Mutex::new(HashMap::new()),
+            worktrees: parking_lot::Mutex::new(HashMap::new()),
+            budgets: parking_lot::Mutex::new(HashMap::new()),
+            compile_states: parking_lot::Mutex::new(HashMap::new()),
+            scope: Some(scope),
+            token_budget: match (self.token_budget, spec.max_tokens) {
+                (Some(parent), Some(child)) => Some(parent.min(child)),
+                (parent, child) => child.or(parent),
+            },
+            cancelled: parking_lot::Mutex::new(HashSet::new()),
+            events: self.events.clone(),
+            // The spec's model overrides routing
+            router: spec.model.is_none().then(|| self.router.clone()).flatten(),
+            engines: parking_lot::Mutex::new(HashMap::new()),
+            categories: parking_lot::Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether model calls are answered from a recording
+    fn replaying(&self) -> bool {
+        self.config.as_ref().is_some_and(|c| c.llm.replay_from.is_some())
+    }
+
+    /// Create an engine for another model, recording it like the main one
+    fn build_engine(&self, provider: &str, model: &str) -> Result<Arc<intelligence::IntelligenceEngine>> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| Error::Config("Another model needs the agent configuration".to_string()))?;
+        let gateway = intelligence::gateway::GatewayFactory::new().create(
+            provider,
+            Some(config.api_key_for(provider).to_string()),
+            model.to_string(),
+        )?;
+        let gateway = intelligence::gateway::with_recording(gateway, config.llm.record_to.as_deref(), None)?;
+        Ok(Arc::new(intelligence::IntelligenceEngine::new(gateway)))
+    }
+
+    /// Engine for a model call, chosen by the router if there is one
+    fn routed_engine(&self, category: intelligence::IntentCategory, stage: RouteStage) -> Option<Routed> {
+        let engine = self.intelligence.clone()?;
+        let started = std::time::Instant::now();
+        let Some(router) = self.router.as_ref().filter(|_| !self.replaying()) else {
+            return Some(Routed { engine, route: None, started });
+        };
+
+        let route = router.select(category, stage);
+        let configured = self
+            .config
+            .as_ref()
+            .is_some_and(|c| c.llm.provider == route.provider && c.llm.model == route.model);
+        let engine = if configured {
+            engine
+        } else {
+            let cached = self.engines.lock().get(&route.to_string()).cloned();
+            match cached.map(Ok).unwrap_or_else(|| self.build_engine(&route.provider, &route.model)) {
+                Ok(routed) => {
+                    self.engines.lock().insert(route.to_string(), routed.clone());
+                    routed
+                }
+                Err(e) => {
+                    warn!("Cannot use {} for {:?}: {}", route, stage, e);
+                    router.record(category, stage, &route, false, std::time::Duration::ZERO);
+                    return Some(Routed { engine, route: None, started });
+                }
+            }
+        };
+        debug!("Routing {:?} {:?} call to {}", category, stage, route);
+        Some(Routed {
+            engine,
+            route: Some((category, stage, route)),
+            started,
+        })
+    }
+
+    /// Record whether a routed call gave a usable answer
+    fn record_route(&self, routed: &Routed, success: bool) {
+        if let (Some(router), Some((category, stage, route))) = (&self.router, &routed.route) {
+            router.record(*category, *stage, route, success, routed.started.elapsed());
+        }
+    }
+
+    /// Intent category of a running task
+    fn category(&self, task_id: &TaskId) -> intelligence::IntentCategory {
+        self.categories
+            .lock()
+            .get(task_id)
+            .copied()
+            .unwrap_or(intelligence::IntentCategory::Unknown)
+    }
+
+    /// Emit the tool framework's approval requests as events
+    pub fn forward_approvals(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let tools = self.tools.as_ref()?;
+        Some(self.events.forward_approvals(tools.approval_gate()))
+    }
+
+    /// Stop a running task before its next step
+    pub fn cancel(&self, task_id: TaskId) {
+        self.cancelled.lock().insert(task_id);
+    }
+
     /// Process a task through the full pipeline
+    ///
+    /// If an unfinished checkpoint exists for the task, stages whose output
+    /// was already recorded are skipped and execution resumes from there.
     pub async fn process_task(&self, task: super::Task) -> Result<super::TaskResult> {
+        let span = otel::task_span(&task);
+        self.run_task(task).instrument(span).await
+    }
+
+    async fn run_task(&self, task: super::Task) -> Result<super::TaskResult> {
         // Guardrail: Ensure task description is not empty
         debug_assert!(!task.description.is_empty(), "Task description cannot be empty");
         
         info!("Orchestrator processing task: {:?}", task.id);
-        let start_time = common::chrono::Utc::now();
 
-        // Create initial checkpoint
-        let checkpoint = self.create_checkpoint(&task, PipelineStage::IntentParsing).await?;
+        let checkpoint = match self.find_resumable_checkpoint(&task.id).await {
+            Some(checkpoint) => {
+                info!(
+                    "Resuming task {:?} from checkpoint {} at stage {:?}",
+                    task.id, checkpoint.id, checkpoint.stage
+                );
+                checkpoint
+            }
+            None => self.create_checkpoint(&task, PipelineStage::IntentParsing).await?,
+        };
 
-        // Step 1: Parse intent (if not already done)
-        let intent = if task.intent.category == intelligence::IntentCategory::Unknown {
-            match self.parse_intent_with_retry(&task.description).await {
-                Ok(intent) => {
-                    debug!("Parsed intent: {:?} (confidence: {:.2})", intent.category, intent.confidence);
-                    intent
-                }
-                Err(e) => {
-                    error!("Failed to parse intent after retries: {}", e);
-                    return Err(e);
-                }
+        let worktree = self.open_worktree(&task).await?;
+        // Removes the worktree if this future is dropped before closing it
+        let guard = worktree.clone().zip(self.worktree_manager()).zip(self.tools.clone()).map(
+            |((worktree, manager), tools)| WorktreeGuard::new(manager, tools, worktree),
+        );
+        let budget = self.open_budget(&task);
+        let outcome = self.run_pipeline(&task, &checkpoint, &budget).await;
+        let usage = budget.usage();
+        // Probe results only hold for this task's planning session
+        self.probe_sessions.lock().remove(&task.id);
+        self.budgets.lock().remove(&task.id);
+        self.compile_states.lock().remove(&task.id);
+        self.categories.lock().remove(&task.id);
+        if let Some(router) = &self.router {
+            if let Err(e) = router.save() {
+                warn!("Failed to save routing table: {}", e);
             }
-        } else {
-            task.intent.clone()
+        }
+        let outcome = match worktree {
+            Some(worktree) => self.close_worktree(&task, &worktree, outcome).await,
+            None => outcome,
         };
+        if let Some(guard) = guard {
+            guard.disarm();
+        }
+        self.cancelled.lock().remove(&task.id);
 
-        self.update_checkpoint(&checkpoint, PipelineStage::ContextGathering).await?;
+        // Waiters may stop polling once the task is reported finished
+        if let Err(e) = &outcome {
+            otel::record_error(&tracing::Span::current(), e);
+        }
+        self.events.emit(match &outcome {
+            Ok(result) => EventKind::TaskFinished {
+                task_id: task.id,
+                success: result.success,
+                output: result.output.clone(),
+                usage: result.usage.clone(),
+                exhausted: result.exhausted.clone(),
+                error: None,
+            },
+            Err(e) => EventKind::TaskFinished {
+                task_id: task.id,
+                success: false,
+                output: String::new(),
+                usage,
+                exhausted: None,
+                error: Some(e.to_string()),
+            },
+        });
 
-        // Step 2: Gather context
-        let context = match self.gather_context(&task, &intent).await {
-            Ok(ctx) => ctx,
+        match outcome {
+            Ok(result) => Ok(result),
             Err(e) => {
-                warn!("Context gathering failed: {}, proceeding with minimal context", e);
-                intelligence::Context::default()
+                if let Err(persist_err) = self.fail_checkpoint(&checkpoint).await {
+                    warn!("Failed to record checkpoint failure: {}", persist_err);
+                }
+                Err(e)
             }
+        }
+    }
+
+    /// Run the pipeline stages, skipping any already recorded in the checkpoint
+    async fn run_pipeline(
+        &self,
+        task: &super::Task,
+        checkpoint: &TaskCheckpoint,
+        budget: &BudgetTracker,
+    ) -> Result<super::TaskResult> {
+        let start_time = common::chrono::Utc::now();
+
+        // Step 1: Parse intent (if not already done)
+        let intent = if let Some(intent) = &checkpoint.intent {
+            debug!("Restored intent from checkpoint: {:?}", intent.category);
+            intent.clone()
+        } else {
+            let intent = if task.intent.category == intelligence::IntentCategory::Unknown {
+                match otel::in_stage(PipelineStage::IntentParsing, self.parse_intent_with_retry(&task.id, &task.description)).await {
+                    Ok(intent) => {
+                        debug!("Parsed intent: {:?} (confidence: {:.2})", intent.category, intent.confidence);
+                        intent
+                    }
+                    Err(e) => {
+                        error!("Failed to parse intent after retries: {}", e);
+                        return Err(e);
+                    }
+                }
+            } else {
+                task.intent.clone()
+            };
+            self.record_checkpoint(checkpoint, |cp| cp.intent = Some(intent.clone())).await?;
+            intent
         };
+        budget.set_limits(self.budget_limits(task, intent.category));
+        self.categories.lock().insert(task.id, intent.category);
 
-        self.update_checkpoint(&checkpoint, PipelineStage::Planning).await?;
+        // Step 2: Gather context
+        let mut context = if let Some(context) = &checkpoint.context {
+            debug!("Restored context from checkpoint");
+            context.clone()
+        } else {
+            self.update_checkpoint(checkpoint, PipelineStage::ContextGathering).await?;
+            let context = match otel::in_stage(PipelineStage::ContextGathering, self.gather_context(task, &intent)).await {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    warn!("Context gathering failed: {}, proceeding with minimal context", e);
+                    intelligence::Context::default()
+                }
+            };
+            self.record_checkpoint(checkpoint, |cp| cp.context = Some(context.clone())).await?;
+            context
+        };
+        context.system_context.resource_limits = budget.limits();
 
-        // Step 3: Generate plan
-        let plan = match self.generate_plan_with_retry(&intent, &context).await {
-            Ok(plan) => plan,
-            Err(e) => {
-                error!("Failed to generate plan: {}", e);
-                return Err(e);
+        // Step 3: Generate plan, preferring HTN decomposition
+        let (plan, htn) = if let Some(plan) = &checkpoint.plan {
+            debug!("Restored plan with {} steps from checkpoint", plan.steps.len());
+            let htn = checkpoint.goal.clone().zip(checkpoint.htn_plan.clone());
+            (plan.clone(), htn)
+        } else {
+            if let Some(reason) = budget.exhausted() {
+                return self.wind_down(task, checkpoint, start_time, reason, None, budget).await;
             }
+            self.update_checkpoint(checkpoint, PipelineStage::Planning).await?;
+            let (plan, htn) = match otel::in_stage(PipelineStage::Planning, self.plan_task(task, &intent, &context)).await {
+                Ok(planned) => planned,
+                Err(e) => {
+                    error!("Failed to generate plan: {}", e);
+                    return Err(e);
+                }
+            };
+            let recorded = (plan.clone(), htn.clone());
+            self.record_checkpoint(checkpoint, move |cp| {
+                cp.plan = Some(recorded.0);
+                if let Some((goal, htn_plan)) = recorded.1 {
+                    cp.goal = Some(goal);
+                    cp.htn_plan = Some(htn_plan);
+                }
+            })
+            .await?;
+            (plan, htn)
         };
 
-        self.update_checkpoint(&checkpoint, PipelineStage::Execution).await?;
-
         // Step 4: Execute plan
-        let execution_result = match self.execute_plan_with_checkpoint(&plan, &checkpoint).await {
-            Ok(result) => result,
-            Err(e) => {
-                error!("Plan execution failed: {}", e);
-                return Err(e);
+        let execution_result = if let Some(result) = &checkpoint.execution {
+            debug!("Restored execution result from checkpoint");
+            result.clone()
+        } else {
+            if let Some(reason) = budget.exhausted() {
+                return self.wind_down(task, checkpoint, start_time, reason, None, budget).await;
             }
+            self.update_checkpoint(checkpoint, PipelineStage::Execution).await?;
+            let outcome = otel::in_stage(PipelineStage::Execution, async {
+                match &htn {
+                    Some((goal, htn_plan)) => {
+                        self.execute_htn_plan(task, goal, htn_plan, checkpoint, &mut context).await
+                    }
+                    None => self.execute_plan_with_checkpoint(&plan, checkpoint, &mut context).await,
+                }
+            })
+            .await;
+            let result = match outcome {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Plan execution failed: {}", e);
+                    return Err(e);
+                }
+            };
+            // Keep the trajectory alongside the result
+            let trajectory_context = context.clone();
+            self.record_checkpoint(checkpoint, |cp| {
+                cp.context = Some(trajectory_context);
+                cp.execution = Some(result.clone());
+            })
+            .await?;
+            result
         };
 
-        self.update_checkpoint(&checkpoint, PipelineStage::Validation).await?;
+        if let Some(reason) = execution_result.exhausted.clone() {
+            return self
+                .wind_down(task, checkpoint, start_time, reason, Some(execution_result), budget)
+                .await;
+        }
+
+        self.update_checkpoint(checkpoint, PipelineStage::Validation).await?;
 
         // Step 5: Validate results
-        let validation = match self.validate_results_with_retry(&execution_result).await {
+        let validation = match otel::in_stage(PipelineStage::Validation, self.validate_results_with_retry(&execution_result)).await {
             Ok(val) => val,
             Err(e) => {
                 warn!("Validation failed: {}, using basic validation", e);
@@ -156,16 +521,16 @@ impl Orchestrator {
             }
         };
 
-        self.update_checkpoint(&checkpoint, PipelineStage::KnowledgeUpdate).await?;
+        self.update_checkpoint(checkpoint, PipelineStage::KnowledgeUpdate).await?;
 
         // Step 6: Update knowledge
-        if let Err(e) = self.update_knowledge(&task, &execution_result).await {
+        if let Err(e) = otel::in_stage(PipelineStage::KnowledgeUpdate, self.update_knowledge(task, &execution_result)).await {
             warn!("Failed to update knowledge: {}", e);
             // Non-fatal, continue
         }
 
         // Mark checkpoint as complete
-        self.complete_checkpoint(&checkpoint).await?;
+        self.complete_checkpoint(checkpoint).await?;
 
         let execution_time_ms = common::chrono::Utc::now()
             .signed_duration_since(start_time)
@@ -185,12 +550,88 @@ impl Orchestrator {
                 tools_used: execution_result.tools_used,
                 retries: execution_result.retries,
             },
+            usage: budget.usage(),
+            exhausted: None,
+            integration_error: None,
+        })
+    }
+
+    /// Finish a task whose budget ran out with what it achieved so far
+    ///
+    /// No further model or tool calls are made: the output lists the steps
+    /// that ran and the resources used.
+    async fn wind_down(
+        &self,
+        task: &super::Task,
+        checkpoint: &TaskCheckpoint,
+        start_time: common::chrono::DateTime<common::chrono::Utc>,
+        reason: String,
+        execution: Option<ExecutionResult>,
+        budget: &BudgetTracker,
+    ) -> Result<super::TaskResult> {
+        warn!("Task {:?} winding down: {}", task.id, reason);
+        self.complete_checkpoint(checkpoint).await?;
+
+        let usage = budget.usage();
+        let mut output = format!("Stopped early: {}.\n", reason);
+        match &execution {
+            Some(execution) if !execution.summary.is_empty() => {
+                output.push_str("Progress so far:\n");
+                output.push_str(&execution.summary);
+                output.push('\n');
+            }
+            _ => output.push_str("No plan steps were executed.\n"),
+        }
+        output.push_str(&format!("Used {}", usage));
+
+        let execution_time_ms = common::chrono::Utc::now()
+            .signed_duration_since(start_time)
+            .num_milliseconds() as u64;
+        let (artifacts, metrics) = match execution {
+            Some(execution) => (
+                execution.artifacts,
+                super::TaskExecutionMetrics {
+                    tokens_used: execution.tokens_used,
+                    api_calls: execution.api_calls,
+                    tools_used: execution.tools_used,
+                    retries: execution.retries,
+                },
+            ),
+            None => (Vec::new(), super::TaskExecutionMetrics::default()),
+        };
+
+        Ok(super::TaskResult {
+            task_id: task.id,
+            success: false,
+            output,
+            artifacts,
+            completed_at: common::chrono::Utc::now(),
+            execution_time_ms,
+            metrics,
+            usage,
+            exhausted: Some(reason),
+            integration_error: None,
         })
     }
 
+    /// Tasks whose checkpoints were left unfinished, e.g. by a crash
+    pub async fn interrupted_tasks(&self) -> Vec<super::Task> {
+        self.checkpoint_store
+            .read()
+            .await
+            .interrupted()
+            .into_iter()
+            .map(|cp| cp.task.clone())
+            .collect()
+    }
+
     /// Run a cross-evaluation for a task
     pub async fn run_evaluation(&self, task: &super::Task, output: &str) -> Result<EvaluationReport> {
-        if let (Some(intelligence), Some(evaluation)) = (&self.intelligence, &self.evaluation) {
+        let routed = self
+            .evaluation
+            .as_ref()
+            .and_then(|evaluation| Some((evaluation, self.routed_engine(task.intent.category, RouteStage::Evaluation)?)));
+        if let Some((evaluation, routed)) = routed {
             info!("Running cross-evaluation for task: {}", task.id);
             
             // Determine persona based on task intent
@@ -201,18 +642,57 @@ impl Orchestrator {
             };
             
             let engine = evaluation.read().await;
-            engine.evaluate(intelligence, task, output, persona).await
+            let opened = self.open_evaluation_budget(task);
+            let report = self
+                .within_budget(&task.id, engine.evaluate(&routed.engine, task, output, persona))
+                .await;
+            if let (Some(budget), Ok(report)) = (self.budget(&task.id), &report) {
+                budget.charge_llm(&report.model, report.tokens_used);
+            }
+            if opened {
+                self.budgets.lock().remove(&task.id);
+            }
+            self.record_route(&routed, report.is_ok());
+            report
+        } else {
+            Err(Error::Internal("Intelligence or Evaluation engine not available".to_string()))
+        }
+    }
+
+    /// Have several judges evaluate a task and combine their scores
+    pub async fn run_consensus_evaluation(
+        &self,
+        task: &super::Task,
+        output: &str,
+        judges: &[Persona],
+    ) -> Result<Consensus> {
+        if let (Some(intelligence), Some(evaluation)) = (&self.intelligence, &self.evaluation) {
+            info!("Running {}-judge evaluation for task: {}", judges.len(), task.id);
+            let engine = evaluation.read().await;
+            let opened = self.open_evaluation_budget(task);
+            let consensus = self
+                .within_budget(&task.id, engine.evaluate_with_judges(intelligence, task, output, judges))
+                .await;
+            if let (Some(budget), Ok(consensus)) = (self.budget(&task.id), &consensus) {
+                for report in &consensus.reports {
+                    budget.charge_llm(&report.model, report.tokens_used);
+                }
+            }
+            if opened {
+                self.budgets.lock().remove(&task.id);
+            }
+            consensus
         } else {
             Err(Error::Internal("Intelligence or Evaluation engine not available".to_string()))
         }
     }
 
     /// Parse intent from task description with retry logic
-    async fn parse_intent_with_retry(&self, description: &str) -> Result<intelligence::Intent> {
+    async fn parse_intent_with_retry(&self, task_id: &TaskId, description: &str) -> Result<intelligence::Intent> {
         let mut last_error = None;
 
         for attempt in 0..self.retry_policy.max_retries {
-            match self.parse_intent(description).await {
+            match self.within_budget(task_id, self.parse_intent(description)).await {
                 Ok(intent) => return Ok(intent),
                 Err(e) => {
                     warn!("Intent parsing attempt {} failed: {}", attempt + 1, e);
@@ -231,8 +711,10 @@ impl Orchestrator {
 
     /// Parse intent from task description
     async fn parse_intent(&self, description: &str) -> Result<intelligence::Intent> {
-        if let Some(intelligence) = &self.intelligence {
-            intelligence.parse_intent(description).await
+        if let Some(routed) = self.routed_engine(intelligence::IntentCategory::Unknown, RouteStage::Intent) {
+            let intent = routed.engine.parse_intent(description).await;
+            self.record_route(&routed, intent.is_ok());
+            intent
         } else {
             // Fallback: simple keyword-based intent parsing
             let category = self.classify_intent_simple(description);
@@ -345,27 +827,366 @@ impl Orchestrator {
             });
         }
 
+        // Tasks in an experiment run with their arm's prompt and settings
+        if let Some(treatment) = task.context.metadata.get("treatment") {
+            let treatment: Treatment = serde_json::from_value(treatment.clone())?;
+            if !context.system_context.config.is_object() {
+                context.system_context.config = serde_json::json!({});
+            }
+            let config = context.system_context.config.as_object_mut().unwrap();
+            if let Some(serde_json::Value::Object(settings)) = treatment.config {
+                config.extend(settings);
+            }
+            if let Some(prompt) = treatment.prompt {
+                config.insert("instructions".to_string(), serde_json::Value::String(prompt));
+            }
+        }
+
         if let Some(tools) = &self.tools {
             context.system_context.available_tools = tools
                 .list_tools()
                 .iter()
                 .map(|t| t.name().to_string())
+                .filter(|name| self.scope.as_ref().map_or(true, |scope| scope.allows_tool(name)))
                 .collect();
         }
 
         Ok(context)
     }
 
+    /// Plan a task, trying HTN decomposition before asking the model
+    ///
+    /// Returns the executable plan and, when HTN planning succeeded, the goal
+    /// and HTN plan needed for replanning on failure.
+    async fn plan_task(
+        &self,
+        task: &super::Task,
+        intent: &intelligence::Intent,
+        context: &intelligence::Context,
+    ) -> Result<(ActionPlan, Option<(Goal, planning::Plan)>)> {
+        let goal = self.plan_executor.planner().goal_for(intent, &task.description);
+
+        if self.plan_executor.planner().covers(&goal) {
+            let state = self.initial_world_state(task, &goal, context).await;
+            match self
+                .plan_executor
+                .planner()
+                .plan(&goal, &state, &context.system_context.available_tools)
+                .await
+            {
+                Ok(htn_plan) => {
+                    info!("Planned {:?} with HTN decomposition ({} tasks)", goal.goal_type, htn_plan.tasks.len());
+                    let plan = htn_plan.to_action_plan(intent.category);
+                    return Ok((plan, Some((goal, htn_plan))));
+                }
+                Err(e) => {
+                    warn!("HTN planning failed: {}. Falling back to LLM planning.", e);
+                }
+            }
+        } else {
+            debug!("No HTN method covers {:?}, using LLM planning", goal.goal_type);
+        }
+
+        let plan = self.generate_plan_with_retry(&task.id, intent, context).await?;
+        Ok((plan, None))
+    }
+
+    /// World state the planner starts from
+    async fn initial_world_state(
+        &self,
+        task: &super::Task,
+        goal: &Goal,
+        context: &intelligence::Context,
+    ) -> WorldState {
+        let mut state = WorldState {
+            available_tools: context.system_context.available_tools.iter().cloned().collect(),
+            ..Default::default()
+        };
+        for file in &task.context.files {
+            if file.is_file() {
+                state.files.insert(file.to_string_lossy().to_string());
+            } else if file.is_dir() {
+                state.directories.insert(file.to_string_lossy().to_string());
+            }
+        }
+        self.ground_world_state(task, goal, &mut state).await;
+        state
+    }
+
+    /// Probe the workspace for the preconditions the goal depends on
+    ///
+    /// Results are cached for the task's planning session, so only
+    /// conditions invalidated since the last call are probed again.
+    pub(crate) async fn ground_world_state(&self, task: &super::Task, goal: &Goal, state: &mut WorldState) {
+        let conditions = self.plan_executor.planner().conditions_for(goal);
+        if conditions.is_empty() {
+            return;
+        }
+        let session = self.probe_session(task);
+        let grounded = session.ground(state, &conditions).await;
+        debug!("Grounded {} of {} precondition(s) in {:?}", grounded, conditions.len(), session.root());
+    }
+
+    /// Drop probe results that the effects of an executed task may have changed
+    pub(crate) fn observe_effects(&self, task_id: &TaskId, effects: &[planning::Effect]) {
+        let Some(session) = self.probe_sessions.lock().get(task_id).cloned() else {
+            return;
+        };
+        for effect in effects {
+            let dropped = session.invalidate(effect);
+            if dropped > 0 {
+                debug!("{:?} on {} invalidated {} probe(s)", effect.effect_type, effect.target, dropped);
+            }
+        }
+    }
+
+    /// Probe session for a task, started on first use
+    fn probe_session(&self, task: &super::Task) -> Arc<ProbeSession> {
+        let worktree = self.worktrees.lock().get(&task.id).map(|w| w.root.clone());
+        self.probe_sessions
+            .lock()
+            .entry(task.id)
+            .or_insert_with(|| {
+                let root = worktree
+                    .or_else(|| task.context.workspace_path.clone())
+                    .unwrap_or_else(|| PathBuf::from("."));
+                Arc::new(self.world_probe.session(root))
+            })
+            .clone()
+    }
+
+    /// Move a task into its own git worktree if isolation is enabled
+    async fn open_worktree(&self, task: &super::Task) -> Result<Option<Arc<TaskWorktree>>> {
+        let (Some(manager), Some(tools)) = (self.worktree_manager(), &self.tools) else {
+            return Ok(None);
+        };
+        let Some(worktree) = manager
+            .create(tools, task, task.context.workspace_path.as_deref())
+            .await?
+        else {
+            return Ok(None);
+        };
+        let worktree = Arc::new(worktree);
+        self.worktrees.lock().insert(task.id, worktree.clone());
+        Ok(Some(worktree))
+    }
+
+    /// Integrate a successful task's worktree, or discard a failed one
+    ///
+    /// A successful task that cannot be integrated stays successful; the
+    /// failure is reported in its result and the worktree is kept.
+    async fn close_worktree(
+        &self,
+        task: &super::Task,
+        worktree: &TaskWorktree,
+        outcome: Result<super::TaskResult>,
+    ) -> Result<super::TaskResult> {
+        self.worktrees.lock().remove(&task.id);
+        let (Some(manager), Some(tools)) = (self.worktree_manager(), &self.tools) else {
+            return outcome;
+        };
+
+        match outcome {
+            // The task's work stands even when it cannot be brought back
+            Ok(mut result) if result.success => {
+                if let Err(e) = manager.integrate(tools, worktree, &task.description).await {
+                    warn!("Task {:?} succeeded but was not integrated: {}", task.id, e);
+                    result.output.push_str(&format!(
+                        "\nNot integrated: {}\nWork kept in {} on {}",
+                        e,
+                        worktree.root.display(),
+                        worktree.branch
+                    ));
+                    result.integration_error = Some(e.to_string());
+                }
+                Ok(result)
+            }
+            // Partial work from an exhausted budget is kept for review
+            Ok(mut result) if result.exhausted.is_some() => {
+                info!("Keeping partial work of task {:?} in {}", task.id, worktree.root.display());
+                result.output.push_str(&format!(
+                    "\nPartial work kept in {} on {}",
+                    worktree.root.display(),
+                    worktree.branch
+                ));
+                Ok(result)
+            }
+            outcome => {
+                if let Err(e) = manager.discard(tools, worktree).await {
+                    warn!("Failed to remove worktree {}: {}", worktree.root.display(), e);
+                }
+                outcome
+            }
+        }
+    }
+
+    /// Run a model call inside the task's budget
+    ///
+    /// The call is refused once any limit is used up and cut off when the
+    /// task's time runs out.
+    async fn within_budget<T>(
+        &self,
+        task_id: &TaskId,
+        call: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Some(budget) = self.budget(task_id) else {
+            return call.await;
+        };
+        if let Some(reason) = budget.exhausted() {
+            return Err(Error::Execution(format!("No model calls left: {}", reason)));
+        }
+        match budget.remaining_time() {
+            Some(remaining) => tokio::time::timeout(remaining, call)
+                .await
+                .map_err(|_| Error::Timeout("Model call ran past the task's time budget".to_string()))?,
+            None => call.await,
+        }
+    }
+
+    /// Give a finished task's evaluation a budget of its own
+    ///
+    /// Evaluating a task that is still running charges the task's budget.
+    /// Returns whether a budget was opened, to be removed afterwards.
+    fn open_evaluation_budget(&self, task: &super::Task) -> bool {
+        if self.budget(&task.id).is_some() {
+            return false;
+        }
+        self.open_budget(task);
+        true
+    }
+
+    /// Start tracking a task's resource usage
+    fn open_budget(&self, task: &super::Task) -> Arc<BudgetTracker> {
+        let pricing = self.config.as_ref().map(Pricing::from_config).unwrap_or_default();
+        let tracker = Arc::new(BudgetTracker::new(
+            self.budget_limits(task, task.intent.category),
+            pricing,
+        ));
+        self.budgets.lock().insert(task.id, tracker.clone());
+        tracker
+    }
+
+    /// Budget for a task, capped by a delegated child's token budget
+    fn budget_limits(
+        &self,
+        task: &super::Task,
+        category: intelligence::IntentCategory,
+    ) -> intelligence::ResourceLimits {
+        let mut limits = budget::limits_for(task, category, self.config.as_ref());
+        if let Some(cap) = self.token_budget {
+            limits.max_tokens = match limits.max_tokens {
+                0 => cap,
+                max => max.min(cap),
+            };
+        }
+        limits
+    }
+
+    fn budget(&self, task_id: &TaskId) -> Option<Arc<BudgetTracker>> {
+        self.budgets.lock().get(task_id).cloned()
+    }
+
+    /// Call the routed model in a `chat` span and charge the result to the task
+    async fn generate(
+        &self,
+        task_id: &TaskId,
+        routed: &Routed,
+        context: &intelligence::Context,
+        prompt: &str,
+    ) -> Result<intelligence::GenerationResult> {
+        // Unrouted calls go to the configured model
+        let configured = self.config.as_ref().map(|c| ModelRoute {
+            provider: c.llm.provider.clone(),
+            model: c.llm.model.clone(),
+        });
+        let span = otel::chat_span(routed.route.as_ref().map(|(_, _, route)| route).or(configured.as_ref()));
+        let prompt = match context.system_context.config.get("instructions").and_then(|i| i.as_str()) {
+            Some(instructions) => format!("{}\n\n{}", instructions.trim_end(), prompt),
+            None => prompt.to_string(),
+        };
+        let result = self
+            .within_budget(task_id, routed.engine.generate(context, &prompt).instrument(span.clone()))
+            .await;
+        otel::record_generation(&span, &result);
+        if let Ok(result) = &result {
+            if let Some(cost) = self.charge_llm(task_id, result) {
+                span.record(otel::COST_ATTRIBUTE, cost);
+            }
+        }
+        result
+    }
+
+    /// Charge a generation to the task's budget and report its output
+    ///
+    /// Returns the call's cost when the task has a budget.
+    fn charge_llm(&self, task_id: &TaskId, result: &intelligence::GenerationResult) -> Option<f64> {
+        let cost = self
+            .budget(task_id)
+            .map(|budget| budget.charge_llm(&result.model, result.tokens_used));
+        self.events.emit(EventKind::TokensStreamed {
+            task_id: *task_id,
+            model: result.model.clone(),
+            content: result.content.clone(),
+            tokens: result.tokens_used,
+        });
+        cost
+    }
+
+    /// Tool calls only change an in-memory overlay
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.tools.as_ref().is_some_and(|tools| tools.overlay().is_some())
+    }
+
+    /// Worktree manager when per-task isolation is configured
+    ///
+    /// Dry runs keep git untouched, so they are never isolated.
+    fn worktree_manager(&self) -> Option<WorktreeManager> {
+        let git = &self.config.as_ref()?.tools.git;
+        let tools = self.tools.as_ref()?;
+        (git.enabled && git.worktree_isolation && tools.overlay().is_none())
+            .then(|| WorktreeManager::new(git.clone()))
+    }
+
+    /// Execute an HTN plan, replanning through the plan executor on failure
+    async fn execute_htn_plan(
+        &self,
+        task: &super::Task,
+        goal: &Goal,
+        htn_plan: &planning::Plan,
+        checkpoint: &TaskCheckpoint,
+        context: &mut intelligence::Context,
+    ) -> Result<ExecutionResult> {
+        let state = self.initial_world_state(task, goal, context).await;
+        let tools = context.system_context.available_tools.clone();
+        let outcome = self
+            .plan_executor
+            .execute_with_replanning(goal, htn_plan, self, checkpoint, context, &state, &tools)
+            .await?;
+
+        match outcome.output {
+            Some(mut result) => {
+                result.retries += outcome.retries;
+                Ok(result)
+            }
+            None => Err(Error::Execution(format!(
+                "HTN plan failed after {} replanning attempt(s): {}",
+                outcome.retries,
+                outcome.error.unwrap_or_else(|| "unknown error".to_string())
+            ))),
+        }
+    }
+
     /// Generate a plan with retry logic
     async fn generate_plan_with_retry(
         &self,
+        task_id: &TaskId,
         intent: &intelligence::Intent,
         context: &intelligence::Context,
     ) -> Result<ActionPlan> {
         let mut last_error = None;
 
         for attempt in 0..self.retry_policy.max_retries {
-            match self.generate_plan(intent, context).await {
+            match self.generate_plan(task_id, intent, context).await {
                 Ok(plan) => return Ok(plan),
                 Err(e) => {
                     warn!("Plan generation attempt {} failed: {}", attempt + 1, e);
@@ -385,10 +1206,11 @@ impl Orchestrator {
     /// Generate an action plan
     async fn generate_plan(
         &self,
+        task_id: &TaskId,
         intent: &intelligence::Intent,
         context: &intelligence::Context,
     ) -> Result<ActionPlan> {
-        if let Some(intelligence) = &self.intelligence {
+        if let Some(routed) = self.routed_engine(intent.category, RouteStage::Planning) {
             // Use specialized prompt template based on intent category
             let template_name = match intent.category {
                 intelligence::IntentCategory::CodeGeneration => "plan_code_generation",
@@ -414,10 +1236,18 @@ impl Orchestrator {
                 context.code_context.related_files
             );
             
-            let result = intelligence.generate(context, &prompt).await?;
+            let result = match self.generate(task_id, &routed, context, &prompt).await {
+                Ok(result) => result,
+                Err(e) => {
+                    self.record_route(&routed, false);
+                    return Err(e);
+                }
+            };
 
             // Try to parse structured JSON output
-            let steps = match self.parse_structured_plan(&result.content) {
+            let parsed = self.parse_structured_plan(&result.content);
+            self.record_route(&routed, parsed.is_ok());
+            let steps = match parsed {
                 Ok(s) => s,
                 Err(e) => {
                     warn!("Failed to parse structured plan: {}. Falling back to text parsing.", e);
@@ -481,114 +1311,590 @@ impl Orchestrator {
     }
 
     /// Execute plan with checkpointing
-    async fn execute_plan_with_checkpoint(
+    ///
+    /// Runs an observe-think-act loop: each step's outcome is fed back to the
+    /// model, which decides whether to continue, retry, revise the remaining
+    /// plan, finish early or abort. The trajectory is recorded in `context`.
+    pub(crate) async fn execute_plan_with_checkpoint(
         &self,
         plan: &ActionPlan,
         checkpoint: &TaskCheckpoint,
+        context: &mut intelligence::Context,
     ) -> Result<ExecutionResult> {
+        let mut limits = self
+            .config
+            .as_ref()
+            .map(|c| c.agent.react.clone())
+            .unwrap_or_default();
+        if let Some(budget) = self.token_budget {
+            limits.max_tokens = limits.max_tokens.min(budget);
+        }
+
+        let budget = self.budget(&checkpoint.task.id);
+        let fixer = self.compile_fixer();
+        let goal = checkpoint.task.description.clone();
+        let mut steps = plan.steps.clone();
         let mut artifacts = Vec::new();
         let mut logs = Vec::new();
         let mut tokens_used = 0u32;
         let mut api_calls = 0u32;
         let mut tools_used = Vec::new();
         let mut retries = 0u32;
+        let mut step_retries = 0u32;
+        let mut iteration = 0u32;
+        let mut i = 0usize;
+
+        while i < steps.len() {
+            // Skip steps already completed before an interruption
+            if let Some(done) = checkpoint.completed_steps.iter().find(|s| s.index == i) {
+                debug!("Step {} restored from checkpoint", i + 1);
+                logs.push(format!("↺ Step {} restored from checkpoint", i + 1));
+                if let Some(tool_name) = &done.tool {
+                    tools_used.push(tool_name.clone());
+                }
+                if let Some(artifact) = self.data_to_artifact(&done.output).await {
+                    artifacts.push(artifact);
+                }
+                i += 1;
+                continue;
+            }
+
+            if self.cancelled.lock().contains(&checkpoint.task.id) {
+                info!("Task {:?} cancelled before step {}", checkpoint.task.id, i + 1);
+                return Err(Error::Cancelled);
+            }
 
-        for (i, step) in plan.steps.iter().enumerate() {
+            // Enforce loop limits, then the task's budget
+            let budget_exhausted = budget.as_ref().and_then(|b| b.exhausted());
+            let exhausted = if iteration >= limits.max_steps {
+                Some(format!("step limit of {} reached", limits.max_steps))
+            } else if tokens_used >= limits.max_tokens {
+                Some(format!("token limit of {} reached", limits.max_tokens))
+            } else {
+                budget_exhausted.clone()
+            };
+            if let Some(reason) = exhausted {
+                warn!("Stopping execution at step {}: {}", i + 1, reason);
+                logs.push(format!("■ Stopped before step {}: {}", i + 1, reason));
+                return Ok(ExecutionResult {
+                    success: false,
+                    summary: logs.join("\n"),
+                    artifacts,
+                    tokens_used,
+                    api_calls,
+                    tools_used,
+                    retries,
+                    exhausted: budget_exhausted,
+                });
+            }
+            iteration += 1;
+
+            let step = steps[i].clone();
             info!("Executing plan step {}: {}", i + 1, step.description);
 
             // Update checkpoint with current step
             self.update_checkpoint_step(checkpoint, i).await?;
 
+            // Act
             let step_start = common::chrono::Utc::now();
-            
-            let step_result = if let Some(tool_name) = &step.tool {
-                if let Some(tools) = &self.tools {
-                    tools_used.push(tool_name.clone());
-                    
-                    match tokio::time::timeout(
-                        tokio::time::Duration::from_secs(step.timeout_seconds),
-                        tools.execute(tool_name, serde_json::json!(step.parameters))
-                    ).await {
-                        Ok(Ok(result)) => {
-                            api_calls += 1;
-                            if result.success {
-                                logs.push(format!("✓ Step {} succeeded", i + 1));
-                                StepResult::Success(result.data)
-                            } else {
-                                logs.push(format!("✗ Step {} failed: {:?}", i + 1, result.data));
-                                StepResult::Failure(result.data.to_string())
-                            }
-                        }
-                        Ok(Err(e)) => {
-                            retries += 1;
-                            StepResult::Failure(e.to_string())
-                        }
-                        Err(_) => {
-                            retries += 1;
-                            StepResult::Timeout
-                        }
-                    }
+            let action = intelligence::Action {
+                id: uuid::Uuid::new_v4().to_string(),
+                tool: step.tool.clone().unwrap_or_default(),
+                parameters: step.parameters.clone(),
+                timestamp: step_start,
+            };
+            if let Some(tool_name) = &step.tool {
+                tools_used.push(tool_name.clone());
+                api_calls += 1;
+            }
+            // Edits to source files must leave the build no worse than it was
+            let checked = fixer
+                .as_ref()
+                .filter(|f| f.watches(step.tool.as_deref(), &step.parameters));
+            if let Some(fixer) = checked {
+                self.record_check_baseline(&checkpoint.task, fixer).await;
+            }
+            let mut step_result = self.run_step(&checkpoint.task.id, &step).await;
+            if let (Some(fixer), StepResult::Success(_)) = (checked, &step_result) {
+                let outcome = self.verify_edit(&checkpoint.task, &goal, fixer, context).await;
+                tokens_used += outcome.tokens_used;
+                api_calls += outcome.model_calls;
+                if outcome.passed {
+                    logs.push(outcome.message);
                 } else {
-                    StepResult::Failure("Tools not available".to_string())
+                    step_result = StepResult::Failure(outcome.message);
                 }
-            } else {
-                // No tool specified, treat as informational step
-                logs.push(format!("→ Step {}: {}", i + 1, step.description));
-                StepResult::Success(serde_json::json!({"info": step.description}))
-            };
+            }
 
             let step_duration = common::chrono::Utc::now()
                 .signed_duration_since(step_start)
                 .num_milliseconds();
-            
             debug!("Step {} completed in {}ms", i + 1, step_duration);
 
-            // Handle step failure with retry
-            match step_result {
+            // Observe
+            let (success, output, error) = match &step_result {
                 StepResult::Success(data) => {
-                    if let Some(artifact) = self.data_to_artifact(&data).await {
-                        artifacts.push(artifact);
+                    if step.tool.is_some() {
+                        logs.push(format!("✓ Step {} succeeded", i + 1));
+                    } else {
+                        logs.push(format!("→ Step {}: {}", i + 1, step.description));
                     }
+                    (true, data.clone(), String::new())
                 }
                 StepResult::Failure(msg) => {
                     warn!("Step {} failed: {}", i + 1, msg);
-                    
-                    // Try recovery if configured
-                    if self.retry_policy.retry_on_failure && retries < self.retry_policy.max_retries {
-                        warn!("Attempting recovery for step {} (retry {}/{})", i + 1, retries, self.retry_policy.max_retries);
+                    logs.push(format!("✗ Step {} failed: {}", i + 1, msg));
+                    (false, serde_json::json!({ "error": msg }), msg.clone())
+                }
+                StepResult::Timeout => {
+                    error!("Step {} timed out", i + 1);
+                    logs.push(format!("✗ Step {} timed out", i + 1));
+                    let msg = format!("timed out after {}s", step.timeout_seconds);
+                    (false, serde_json::json!({ "error": msg }), msg)
+                }
+            };
+            let observation = intelligence::ToolOutput {
+                tool: action.tool.clone(),
+                output,
+                success,
+            };
 
-                        // Simple retry logic: just retry the step once more after a delay
-                        // In a real implementation, this would involve analyzing the error and potentially modifying the plan
-                        let delay = self.retry_policy.calculate_delay(retries);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+            // Think
+            let can_retry = self.retry_policy.retry_on_failure && step_retries < self.retry_policy.max_retries;
+            let (reasoning, mut decision, think_tokens) = self
+                .think(&checkpoint.task.id, &goal, &steps, i, &observation, can_retry, &error, context)
+                .await;
+            if think_tokens > 0 {
+                api_calls += 1;
+            }
+            tokens_used += think_tokens;
 
-                        // Re-execute step (recursive call or loop would be better, but for now we just fail after one retry in this block structure)
-                        // Note: ideally we would loop here, but refactoring the loop structure is risky.
-                        // Instead, we'll mark it as failed but non-fatal if possible, or propagate error.
-                        // For this implementation, we propagate the error to stop execution on persistent failure.
-                        return Err(Error::Execution(format!("Step {} failed after retry: {}", i + 1, msg)));
-                    } else {
-                        return Err(Error::Execution(format!("Step {} failed: {}", i + 1, msg)));
+            // Guardrail: never retry past the retry budget
+            if matches!(decision, ReactDecision::Retry) && !can_retry {
+                decision = ReactDecision::Abort {
+                    reason: format!("retry limit reached: {}", error),
+                };
+            }
+
+            debug!("Step {} decision: {}", i + 1, decision.label());
+            let exec_ctx = &mut context.execution_context;
+            exec_ctx.previous_actions.push(action.clone());
+            exec_ctx.tool_outputs.push(observation.clone());
+            if !success {
+                exec_ctx.error_messages.push(format!("Step {}: {}", i + 1, error));
+            }
+            exec_ctx.trajectory.push(intelligence::TrajectoryStep {
+                iteration,
+                thought: reasoning,
+                action: Some(action),
+                observation: Some(observation.clone()),
+                decision: decision.label().to_string(),
+                tokens_used: think_tokens,
+            });
+
+            if success {
+                if let Some(artifact) = self.data_to_artifact(&observation.output).await {
+                    artifacts.push(artifact);
+                }
+                if !matches!(decision, ReactDecision::Retry) {
+                    let completed = CompletedStep {
+                        index: i,
+                        tool: step.tool.clone(),
+                        output: observation.output.clone(),
+                        completed_at: common::chrono::Utc::now(),
+                    };
+                    self.record_checkpoint(checkpoint, move |cp| cp.completed_steps.push(completed))
+                        .await?;
+                }
+            }
+
+            // Apply the decision
+            match decision {
+                ReactDecision::Continue => {
+                    if !success {
+                        warn!("Skipping failed step {} as instructed", i + 1);
                     }
+                    step_retries = 0;
+                    i += 1;
                 }
-                StepResult::Timeout => {
-                    error!("Step {} timed out", i + 1);
-                    return Err(Error::Timeout(format!("Step {} timed out", i + 1)));
+                ReactDecision::Retry => {
+                    retries += 1;
+                    step_retries += 1;
+                    warn!(
+                        "Retrying step {} (retry {}/{})",
+                        i + 1,
+                        step_retries,
+                        self.retry_policy.max_retries
+                    );
+                    let delay = self.retry_policy.calculate_delay(step_retries - 1);
+                    tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+                }
+                ReactDecision::Revise { steps: revised } => {
+                    let keep = if success { i + 1 } else { i };
+                    info!(
+                        "Revising plan after step {}: {} step(s) replace {}",
+                        i + 1,
+                        revised.len(),
+                        steps.len() - keep
+                    );
+                    steps.truncate(keep);
+                    steps.extend(revised);
+                    let revised_plan = ActionPlan {
+                        steps: steps.clone(),
+                        intent_category: plan.intent_category,
+                        estimated_tokens: plan.estimated_tokens,
+                    };
+                    self.record_checkpoint(checkpoint, move |cp| cp.plan = Some(revised_plan))
+                        .await?;
+                    step_retries = 0;
+                    i = keep;
+                }
+                ReactDecision::Finish { summary } => {
+                    info!("Execution finished early after step {}", i + 1);
+                    logs.push(format!("✔ {}", summary));
+                    break;
+                }
+                ReactDecision::Abort { reason } => {
+                    return Err(match step_result {
+                        StepResult::Timeout => Error::Timeout(format!("Step {} timed out", i + 1)),
+                        _ => Error::Execution(format!("Step {} failed: {}", i + 1, reason)),
+                    });
                 }
             }
         }
 
+        // A task that broke the build only succeeds once it is fixed
+        let outstanding = self
+            .compile_states
+            .lock()
+            .get(&checkpoint.task.id)
+            .map_or(0, |state| state.outstanding.len());
+        if outstanding > 0 {
+            logs.push(format!("✗ {} new build error(s) remain", outstanding));
+        }
+
         Ok(ExecutionResult {
-            success: true,
+            success: outstanding == 0,
             summary: logs.join("\n"),
             artifacts,
             tokens_used,
             api_calls,
             tools_used,
             retries,
+            exhausted: None,
         })
     }
 
+    /// Build checker for edits, unless disabled or in a dry run
+    fn compile_fixer(&self) -> Option<CompileFixer> {
+        // A dry run's edits only exist in memory, so there is nothing to build
+        if self.tools.as_ref()?.overlay().is_some() {
+            return None;
+        }
+        CompileFixer::from_config(&self.config.as_ref()?.tools.check)
+    }
+
+    /// Directory the checker runs in: the task's worktree, else its workspace
+    fn check_root(&self, task: &super::Task) -> PathBuf {
+        match self.worktrees.lock().get(&task.id) {
+            Some(worktree) => worktree.root.clone(),
+            None => workspace_of(task),
+        }
+    }
+
+    /// Record the errors present before a task's first checked edit
+    async fn record_check_baseline(&self, task: &super::Task, fixer: &CompileFixer) {
+        if self.compile_states.lock().contains_key(&task.id) {
+            return;
+        }
+        let state = match fixer.check(&self.check_root(task)).await {
+            Ok(report) => CompileState {
+                baseline: report.errors().cloned().collect(),
+                ..Default::default()
+            },
+            Err(e) => {
+                warn!("Build checker unavailable, edits will not be checked: {}", e);
+                CompileState {
+                    unavailable: true,
+                    ..Default::default()
+                }
+            }
+        };
+        debug!("Build baseline for {:?}: {} error(s)", task.id, state.baseline.len());
+        self.compile_states.lock().insert(task.id, state);
+    }
+
+    /// Check the build after an edit, asking the model to fix new errors
+    async fn verify_edit(
+        &self,
+        task: &super::Task,
+        goal: &str,
+        fixer: &CompileFixer,
+        context: &intelligence::Context,
+    ) -> CheckOutcome {
+        let mut outcome = CheckOutcome::default();
+        let baseline = match self.compile_states.lock().get(&task.id) {
+            Some(state) if !state.unavailable => state.baseline.clone(),
+            _ => {
+                outcome.passed = true;
+                outcome.message = "⚠ Build check skipped: checker unavailable".to_string();
+                return outcome;
+            }
+        };
+        let root = self.check_root(task);
+
+        let mut attempt = 0;
+        let errors = loop {
+            let report = match fixer.check(&root).await {
+                Ok(report) => report,
+                Err(e) => {
+                    warn!("Build check failed to run: {}", e);
+                    outcome.passed = true;
+                    outcome.message = format!("⚠ Build check skipped: {}", e);
+                    return outcome;
+                }
+            };
+            let errors = report.new_errors(&baseline);
+            if errors.is_empty() {
+                if let Some(state) = self.compile_states.lock().get_mut(&task.id) {
+                    state.outstanding.clear();
+                }
+                let existing = report.errors().count();
+                outcome.passed = true;
+                outcome.message = match existing {
+                    0 => "✓ Build check passed".to_string(),
+                    n => format!("✓ Build check: {} error(s) predate this task, none new", n),
+                };
+                return outcome;
+            }
+
+            let exhausted = self.budget(&task.id).is_some_and(|b| b.exhausted().is_some());
+            if attempt >= fixer.max_iterations() || exhausted {
+                break errors;
+            }
+            attempt += 1;
+            info!(
+                "Edit introduced {} build error(s); fix attempt {}/{}",
+                errors.len(),
+                attempt,
+                fixer.max_iterations()
+            );
+            match self.request_fixes(task, goal, &errors, context).await {
+                Ok((fixes, tokens)) => {
+                    outcome.tokens_used += tokens;
+                    outcome.model_calls += 1;
+                    self.apply_fixes(task, &errors, fixes).await;
+                }
+                Err(e) => {
+                    warn!("No fix from the model: {}", e);
+                    break errors;
+                }
+            }
+        };
+
+        let listed: Vec<String> = errors.iter().map(analysis::checker::format_diagnostic).collect();
+        outcome.message = format!(
+            "{} new build error(s) after {} fix attempt(s):\n{}",
+            errors.len(),
+            attempt,
+            listed.join("\n")
+        );
+        if let Some(state) = self.compile_states.lock().get_mut(&task.id) {
+            state.outstanding = errors;
+        }
+        outcome
+    }
+
+    /// Show the model the new errors and the files they are in
+    async fn request_fixes(
+        &self,
+        task: &super::Task,
+        goal: &str,
+        errors: &[analysis::Diagnostic],
+        context: &intelligence::Context,
+    ) -> Result<(Vec<compile_fix::FileFix>, u32)> {
+        let no_model = || Error::Internal("No model available to fix build errors".to_string());
+        if self.intelligence.is_none() {
+            return Err(no_model());
+        }
+
+        let mut files = Vec::new();
+        for path in compile_fix::affected_files(errors) {
+            let read = PlanStep {
+                description: format!("Read {}", path),
+                tool: Some("filesystem".to_string()),
+                parameters: serde_json::json!({
+                    "operation": "read",
+                    "path": workspace_of(task).join(&path).to_string_lossy(),
+                }),
+                expected_output: String::new(),
+                timeout_seconds: 30,
+            };
+            if let StepResult::Success(data) = self.run_step(&task.id, &read).await {
+                let content = data.get("content").and_then(|c| c.as_str()).unwrap_or_default();
+                files.push((path, content.to_string()));
+            }
+        }
+
+        let prompt = compile_fix::build_prompt(goal, errors, &files);
+        let routed = self
+            .routed_engine(self.category(&task.id), RouteStage::Execution)
+            .ok_or_else(no_model)?;
+        let result = match self.generate(&task.id, &routed, context, &prompt).await {
+            Ok(result) => result,
+            Err(e) => {
+                self.record_route(&routed, false);
+                return Err(e);
+            }
+        };
+        let fixes = compile_fix::parse_fixes(&result.content);
+        self.record_route(&routed, fixes.is_ok());
+        Ok((fixes?, result.tokens_used))
+    }
+
+    /// Write the model's fixes, only to files that had errors
+    async fn apply_fixes(
+        &self,
+        task: &super::Task,
+        errors: &[analysis::Diagnostic],
+        fixes: Vec<compile_fix::FileFix>,
+    ) {
+        let affected = compile_fix::affected_files(errors);
+        for fix in fixes {
+            if !affected.contains(&fix.path) {
+                warn!("Ignoring fix for {}, which had no errors", fix.path);
+                continue;
+            }
+            let write = PlanStep {
+                description: format!("Fix build errors in {}", fix.path),
+                tool: Some("filesystem".to_string()),
+                parameters: serde_json::json!({
+                    "operation": "write",
+                    "path": workspace_of(task).join(&fix.path).to_string_lossy(),
+                    "content": fix.content,
+                }),
+                expected_output: String::new(),
+                timeout_seconds: 30,
+            };
+            if let StepResult::Failure(e) = self.run_step(&task.id, &write).await {
+                warn!("Could not apply fix to {}: {}", fix.path, e);
+            }
+        }
+    }
+
+    /// Execute a single plan step
+    async fn run_step(&self, task_id: &TaskId, step: &PlanStep) -> StepResult {
+        let Some(tool_name) = &step.tool else {
+            // No tool specified, treat as informational step
+            return StepResult::Success(serde_json::json!({"info": step.description}));
+        };
+
+        let started = std::time::Instant::now();
+        let result = self.call_tool(task_id, tool_name, step).await;
+        self.events.emit(EventKind::ToolCalled {
+            task_id: *task_id,
+            tool: tool_name.clone(),
+            parameters: events::redact_parameters(&step.parameters),
+            success: matches!(result, StepResult::Success(_)),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+        result
+    }
+
+    /// Run a step's tool call inside the task's scope, worktree and budget
+    async fn call_tool(&self, task_id: &TaskId, tool_name: &str, step: &PlanStep) -> StepResult {
+        let Some(tools) = &self.tools else {
+            return StepResult::Failure("Tools not available".to_string());
+        };
+
+        // Delegated children stay inside their scope
+        if let Some(scope) = &self.scope {
+            let takes_path = tools
+                .list_tools()
+                .into_iter()
+                .find(|tool| tool.name() == tool_name)
+                .is_some_and(delegation::takes_path);
+            if let Err(e) = scope.check(tool_name, &step.parameters, takes_path) {
+                return StepResult::Failure(e.to_string());
+            }
+        }
+
+        // Isolated tasks work inside their worktree
+        let mut parameters = step.parameters.clone();
+        if let Some(worktree) = self.worktrees.lock().get(task_id) {
+            parameters = worktree.scope(parameters);
+        }
+
+        // Risky calls pause here for approval; waiting does not count against the step timeout
+        let parameters = match tools.authorize(tool_name, parameters).await {
+            Ok(parameters) => parameters,
+            Err(e) => return StepResult::Failure(e.to_string()),
+        };
+
+        // A step never outlives the task's time budget
+        let mut timeout = tokio::time::Duration::from_secs(step.timeout_seconds);
+        if let Some(budget) = self.budget(task_id) {
+            budget.charge_tool();
+            if let Some(remaining) = budget.remaining_time() {
+                timeout = timeout.min(remaining);
+            }
+        }
+
+        match tokio::time::timeout(timeout, tools.execute_authorized(tool_name, parameters)).await {
+            Ok(Ok(result)) if result.success => StepResult::Success(result.data),
+            Ok(Ok(result)) => StepResult::Failure(result.data.to_string()),
+            Ok(Err(e)) => StepResult::Failure(e.to_string()),
+            Err(_) => StepResult::Timeout,
+        }
+    }
+
+    /// Ask the model how to proceed after an observation
+    ///
+    /// Falls back to continuing on success and retrying on failure when no
+    /// model is configured or its answer cannot be parsed.
+    #[allow(clippy::too_many_arguments)]
+    async fn think(
+        &self,
+        task_id: &TaskId,
+        goal: &str,
+        steps: &[PlanStep],
+        current: usize,
+        observation: &intelligence::ToolOutput,
+        can_retry: bool,
+        error: &str,
+        context: &intelligence::Context,
+    ) -> (Option<String>, ReactDecision, u32) {
+        let fallback = || react::fallback_decision(observation.success, can_retry, error);
+
+        // An exhausted budget allows no more model calls
+        if self.budget(task_id).is_some_and(|b| b.exhausted().is_some()) {
+            return (None, fallback(), 0);
+        }
+        let Some(routed) = self.routed_engine(self.category(task_id), RouteStage::Execution) else {
+            return (None, fallback(), 0);
+        };
+
+        let prompt = react::build_prompt(goal, steps, current, observation);
+        match self.generate(task_id, &routed, context, &prompt).await {
+            Ok(result) => {
+                let thought = react::parse_thought(&result.content);
+                self.record_route(&routed, thought.is_ok());
+                match thought {
+                    Ok(thought) => (thought.reasoning, thought.decision, result.tokens_used),
+                    Err(e) => {
+                        warn!("Could not parse model decision: {}. Using default.", e);
+                        (None, fallback(), result.tokens_used)
+                    }
+                }
+            }
+            Err(e) => {
+                self.record_route(&routed, false);
+                warn!("Model unavailable for step decision: {}. Using default.", e);
+                (None, fallback(), 0)
+            }
+        }
+    }
+
     /// Validate results with retry
     async fn validate_results_with_retry(
         &self,
@@ -696,27 +2002,62 @@ impl Orchestrator {
         let checkpoint = TaskCheckpoint {
             id: uuid::Uuid::new_v4().to_string(),
             task_id: task.id,
+            task: task.clone(),
             stage,
             current_step: 0,
+            intent: None,
+            context: None,
+            plan: None,
+            goal: None,
+            htn_plan: None,
+            completed_steps: Vec::new(),
+            execution: None,
             created_at: common::chrono::Utc::now(),
             completed_at: None,
         };
 
-        self.checkpoint_store.write().await.add(checkpoint.clone());
+        let mut store = self.checkpoint_store.write().await;
+        store.add(checkpoint.clone());
+        store.persist(&checkpoint.id).await?;
+        self.events.emit(EventKind::StageChanged {
+            task_id: task.id,
+            stage,
+        });
         Ok(checkpoint)
     }
 
+    /// Find an unfinished checkpoint to resume a task from
+    async fn find_resumable_checkpoint(&self, task_id: &TaskId) -> Option<TaskCheckpoint> {
+        self.checkpoint_store
+            .read()
+            .await
+            .latest_unfinished(task_id)
+            .cloned()
+    }
+
+    /// Apply a change to a checkpoint and persist it
+    async fn record_checkpoint<F>(&self, checkpoint: &TaskCheckpoint, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut TaskCheckpoint),
+    {
+        let mut store = self.checkpoint_store.write().await;
+        store.modify(&checkpoint.id, f);
+        store.persist(&checkpoint.id).await
+    }
+
     /// Update checkpoint stage
     async fn update_checkpoint(
         &self,
         checkpoint: &TaskCheckpoint,
         stage: PipelineStage,
     ) -> Result<()> {
-        self.checkpoint_store
-            .write()
-            .await
-            .update_stage(&checkpoint.id, stage);
-        Ok(())
+        self.events.emit(EventKind::StageChanged {
+            task_id: checkpoint.task_id,
+            stage,
+        });
+        let mut store = self.checkpoint_store.write().await;
+        store.update_stage(&checkpoint.id, stage);
+        store.persist(&checkpoint.id).await
     }
 
     /// Update checkpoint step
@@ -725,20 +2066,66 @@ impl Orchestrator {
         checkpoint: &TaskCheckpoint,
         step: usize,
     ) -> Result<()> {
+        let mut store = self.checkpoint_store.write().await;
+        store.update_step(&checkpoint.id, step);
+        store.persist(&checkpoint.id).await
+    }
+
+    /// Step index a checkpoint last reached
+    pub(crate) async fn checkpoint_step(&self, id: &str) -> usize {
         self.checkpoint_store
-            .write()
+            .read()
             .await
-            .update_step(&checkpoint.id, step);
-        Ok(())
+            .get(id)
+            .map(|cp| cp.current_step)
+            .unwrap_or(0)
+    }
+
+    /// Replace a checkpoint's plan after replanning
+    ///
+    /// The new plan starts with the steps that already completed, so they
+    /// stay completed and execution resumes after them.
+    pub(crate) async fn restart_checkpoint_with_plan(
+        &self,
+        checkpoint: &TaskCheckpoint,
+        plan: ActionPlan,
+        htn_plan: planning::Plan,
+    ) -> Result<TaskCheckpoint> {
+        let mut store = self.checkpoint_store.write().await;
+        store.modify(&checkpoint.id, |cp| {
+            cp.plan = Some(plan);
+            cp.htn_plan = Some(htn_plan);
+            cp.current_step = cp.completed_steps.len();
+        });
+        store.persist(&checkpoint.id).await?;
+        store
+            .get(&checkpoint.id)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("Checkpoint not found: {}", checkpoint.id)))
     }
 
     /// Mark checkpoint as complete
     async fn complete_checkpoint(&self, checkpoint: &TaskCheckpoint) -> Result<()> {
-        self.checkpoint_store
-            .write()
-            .await
-            .complete(&checkpoint.id);
-        Ok(())
+        self.events.emit(EventKind::StageChanged {
+            task_id: checkpoint.task_id,
+            stage: PipelineStage::Completed,
+        });
+        let mut store = self.checkpoint_store.write().await;
+        store.complete(&checkpoint.id);
+        // Completed tasks never need resuming, so drop the on-disk copy
+        store.remove_persisted(&checkpoint.id).await
+    }
+
+    /// Mark checkpoint as failed
+    async fn fail_checkpoint(&self, checkpoint: &TaskCheckpoint) -> Result<()> {
+        self.events.emit(EventKind::StageChanged {
+            task_id: checkpoint.task_id,
+            stage: PipelineStage::Failed,
+        });
+        let mut store = self.checkpoint_store.write().await;
+        store.fail(&checkpoint.id);
+        // A failed run is retried from scratch, so its checkpoint is not kept
+        store.remove_persisted(&checkpoint.id).await
     }
 
     /// Convert execution data to artifact
@@ -784,6 +2171,20 @@ impl Module for Orchestrator {
             warn!("No tools configured");
         }
 
+        // A dry run must not resume real work or leave checkpoints behind
+        if self.is_dry_run() {
+            self.checkpoint_store = Arc::new(RwLock::new(CheckpointStore::new()));
+            return Ok(());
+        }
+
+        // Restore checkpoints left behind by a previous run
+        let mut store = self.checkpoint_store.write().await;
+        store.load().await?;
+        let interrupted = store.interrupted().len();
+        if interrupted > 0 {
+            info!("Found {} interrupted task(s) with resumable checkpoints", interrupted);
+        }
+
         Ok(())
     }
 
@@ -848,25 +2249,65 @@ pub enum PipelineStage {
 }
 
 /// Task checkpoint for recovery
+///
+/// Besides the current stage, a checkpoint carries the output of every
+/// finished stage so an interrupted task can pick up where it stopped.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskCheckpoint {
     pub id: String,
     pub task_id: TaskId,
+    pub task: super::Task,
     pub stage: PipelineStage,
     pub current_step: usize,
+    #[serde(default)]
+    pub intent: Option<intelligence::Intent>,
+    #[serde(default)]
+    pub context: Option<intelligence::Context>,
+    #[serde(default)]
+    pub plan: Option<ActionPlan>,
+    #[serde(default)]
+    pub goal: Option<Goal>,
+    #[serde(default)]
+    pub htn_plan: Option<planning::Plan>,
+    #[serde(default)]
+    pub completed_steps: Vec<CompletedStep>,
+    #[serde(default)]
+    pub execution: Option<ExecutionResult>,
     pub created_at: common::chrono::DateTime<common::chrono::Utc>,
     pub completed_at: Option<common::chrono::DateTime<common::chrono::Utc>>,
 }
 
+/// Plan step that finished successfully before a checkpoint was written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletedStep {
+    pub index: usize,
+    pub tool: Option<String>,
+    pub output: serde_json::Value,
+    pub completed_at: common::chrono::DateTime<common::chrono::Utc>,
+}
+
 /// Checkpoint store
+///
+/// Checkpoints are always kept in memory; when a storage path is set each
+/// one is also written to `{storage_path}/{id}.json` on every update.
 pub struct CheckpointStore {
     checkpoints: Vec<TaskCheckpoint>,
+    storage_path: Option<PathBuf>,
 }
 
 impl CheckpointStore {
     pub fn new() -> Self {
         Self {
             checkpoints: Vec::new(),
+            storage_path: None,
+        }
+    }
+
+    /// Create a store that persists checkpoints to disk
+    pub fn with_storage(storage_path: PathBuf) -> Self {
+        Self {
+            checkpoints: Vec::new(),
+            storage_path: Some(storage_path),
         }
     }
 
@@ -875,27 +2316,139 @@ impl CheckpointStore {
     }
 
     pub fn update_stage(&mut self, id: &str, stage: PipelineStage) {
-        if let Some(cp) = self.checkpoints.iter_mut().find(|c| c.id == id) {
-            cp.stage = stage;
-        }
+        self.modify(id, |cp| cp.stage = stage);
     }
 
     pub fn update_step(&mut self, id: &str, step: usize) {
-        if let Some(cp) = self.checkpoints.iter_mut().find(|c| c.id == id) {
-            cp.current_step = step;
-        }
+        self.modify(id, |cp| cp.current_step = step);
     }
 
     pub fn complete(&mut self, id: &str) {
-        if let Some(cp) = self.checkpoints.iter_mut().find(|c| c.id == id) {
+        self.modify(id, |cp| {
             cp.stage = PipelineStage::Completed;
             cp.completed_at = Some(common::chrono::Utc::now());
+        });
+    }
+
+    pub fn fail(&mut self, id: &str) {
+        self.modify(id, |cp| {
+            cp.stage = PipelineStage::Failed;
+            cp.completed_at = Some(common::chrono::Utc::now());
+        });
+    }
+
+    /// Apply an arbitrary change to a checkpoint
+    pub fn modify<F>(&mut self, id: &str, f: F)
+    where
+        F: FnOnce(&mut TaskCheckpoint),
+    {
+        if let Some(cp) = self.checkpoints.iter_mut().find(|c| c.id == id) {
+            f(cp);
         }
     }
 
     pub fn get(&self, id: &str) -> Option<&TaskCheckpoint> {
         self.checkpoints.iter().find(|c| c.id == id)
     }
+
+    /// Most recent unfinished checkpoint for a task; failed runs are not resumed
+    pub fn latest_unfinished(&self, task_id: &TaskId) -> Option<&TaskCheckpoint> {
+        self.checkpoints
+            .iter()
+            .filter(|c| {
+                c.task_id == *task_id
+                    && c.completed_at.is_none()
+                    && !matches!(c.stage, PipelineStage::Completed | PipelineStage::Failed)
+            })
+            .max_by_key(|c| c.created_at)
+    }
+
+    /// Checkpoints that stopped mid-pipeline without completing or failing
+    pub fn interrupted(&self) -> Vec<&TaskCheckpoint> {
+        self.checkpoints
+            .iter()
+            .filter(|c| {
+                c.completed_at.is_none()
+                    && !matches!(c.stage, PipelineStage::Completed | PipelineStage::Failed)
+            })
+            .collect()
+    }
+
+    /// Write a checkpoint to disk
+    pub async fn persist(&self, id: &str) -> Result<()> {
+        let (Some(storage_path), Some(checkpoint)) = (&self.storage_path, self.get(id)) else {
+            return Ok(());
+        };
+
+        tokio::fs::create_dir_all(storage_path).await?;
+
+        // Write to a temporary file first so a crash mid-write never leaves
+        // a truncated checkpoint behind
+        let path = storage_path.join(format!("{}.json", id));
+        let tmp_path = storage_path.join(format!("{}.json.tmp", id));
+        let json = serde_json::to_string_pretty(checkpoint)?;
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        debug!("Persisted checkpoint {} at stage {:?}", id, checkpoint.stage);
+        Ok(())
+    }
+
+    /// Remove a checkpoint's file from disk
+    pub async fn remove_persisted(&self, id: &str) -> Result<()> {
+        if let Some(storage_path) = &self.storage_path {
+            let path = storage_path.join(format!("{}.json", id));
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Load persisted checkpoints from disk
+    pub async fn load(&mut self) -> Result<usize> {
+        let Some(storage_path) = self.storage_path.clone() else {
+            return Ok(0);
+        };
+
+        let mut entries = match tokio::fs::read_dir(&storage_path).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut loaded = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let json = tokio::fs::read_to_string(&path).await?;
+            match serde_json::from_str::<TaskCheckpoint>(&json) {
+                // Failed checkpoints left by earlier runs are pruned
+                Ok(checkpoint) if checkpoint.stage == PipelineStage::Failed => {
+                    if let Err(e) = tokio::fs::remove_file(&path).await {
+                        warn!("Failed to prune failed checkpoint {:?}: {}", path, e);
+                    }
+                }
+                Ok(checkpoint) => loaded.push(checkpoint),
+                Err(e) => warn!("Skipping unreadable checkpoint {:?}: {}", path, e),
+            }
+        }
+
+        let count = loaded.len();
+        for checkpoint in loaded {
+            if self.get(&checkpoint.id).is_none() {
+                self.checkpoints.push(checkpoint);
+            }
+        }
+
+        debug!("Loaded {} checkpoint(s) from {:?}", count, storage_path);
+        Ok(count)
+    }
 }
 
 impl Default for CheckpointStore {
@@ -964,6 +2517,14 @@ pub enum StepResult {
     Timeout,
 }
 
+/// Workspace a task runs in
+fn workspace_of(task: &super::Task) -> PathBuf {
+    task.context
+        .workspace_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
 /// Execution result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
@@ -974,6 +2535,9 @@ pub struct ExecutionResult {
     pub api_calls: u32,
     pub tools_used: Vec<String>,
     pub retries: u32,
+    /// Set when the task's budget ran out before the plan finished
+    #[serde(default)]
+    pub exhausted: Option<String>,
 }
 
 /// Validation result
@@ -1055,4 +2619,861 @@ mod tests {
             intelligence::IntentCategory::Analysis
         );
     }
+
+    #[tokio::test]
+    async fn test_replanning_keeps_completed_steps() {
+        let step = |id: &str, tool: Option<&str>| {
+            planning::Task::Primitive(planning::PrimitiveTask {
+                id: id.to_string(),
+                name: id.to_string(),
+                description: id.to_string(),
+                tool: tool.map(str::to_string),
+                tool_args: serde_json::json!({}),
+                preconditions: vec![],
+                effects: vec![],
+                estimated_cost: Default::default(),
+            })
+        };
+        let method = |name: &str, time_ms, subtasks| planning::Method {
+            name: name.to_string(),
+            preconditions: vec![],
+            subtasks,
+            ordering: planning::OrderingConstraint::Sequential,
+            cost: planning::Cost { time_ms, ..Default::default() },
+        };
+        let mut planner = HTNPlanner::new();
+        planner.register_task(planning::CompoundTask {
+            id: "run_tests".to_string(),
+            name: "run_tests".to_string(),
+            description: "Run the test suite".to_string(),
+            methods: vec![
+                method("fast", 100, vec![step("prepare", None), step("nextest", Some("nextest"))]),
+                method("slow", 10_000, vec![step("prepare", None), step("report", None)]),
+            ],
+        });
+        let orchestrator = Orchestrator::new()
+            .with_retry_policy(RetryPolicy {
+                retry_on_failure: false,
+                ..Default::default()
+            })
+            .with_planner(planner);
+
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Testing,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: "run the tests".to_string(),
+        };
+        let task = crate::Task::new("run the tests");
+        let goal = Goal::from_intent(&intent, &task.description);
+        let state = WorldState::default();
+        let plan = orchestrator.plan_executor.planner().plan(&goal, &state, &[]).await.unwrap();
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+
+        // The tool-less step survives the failed method and is not run again
+        let mut context = intelligence::Context::default();
+        let result = orchestrator
+            .plan_executor
+            .execute_with_replanning(&goal, &plan, &orchestrator, &checkpoint, &mut context, &state, &[])
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.retries, 1);
+        let output = result.output.unwrap().summary;
+        assert!(output.contains("Step 1 restored from checkpoint"), "{}", output);
+        assert!(output.contains("Step 2: report"), "{}", output);
+        let ran: Vec<&str> = result.execution_history.iter().map(|s| s.task_id.as_str()).collect();
+        assert_eq!(ran, ["prepare", "nextest", "report"]);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_persisted_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = crate::Task::new("Analyze this file");
+
+        // Simulate a run that crashed after the first of two steps
+        let mut store = CheckpointStore::with_storage(dir.path().to_path_buf());
+        let checkpoint = TaskCheckpoint {
+            id: "interrupted".to_string(),
+            task_id: task.id,
+            task: task.clone(),
+            stage: PipelineStage::Execution,
+            current_step: 1,
+            intent: Some(intelligence::Intent {
+                category: intelligence::IntentCategory::Analysis,
+                confidence: 1.0,
+                parameters: Default::default(),
+                raw_input: task.description.clone(),
+            }),
+            context: Some(intelligence::Context::default()),
+            plan: Some(ActionPlan {
+                steps: vec![
+                    PlanStep {
+                        description: "first".to_string(),
+                        tool: None,
+                        parameters: Default::default(),
+                        expected_output: String::new(),
+                        timeout_seconds: 10,
+                    },
+                    PlanStep {
+                        description: "second".to_string(),
+                        tool: None,
+                        parameters: Default::default(),
+                        expected_output: String::new(),
+                        timeout_seconds: 10,
+                    },
+                ],
+                intent_category: intelligence::IntentCategory::Analysis,
+                estimated_tokens: 0,
+            }),
+            goal: None,
+            htn_plan: None,
+            completed_steps: vec![CompletedStep {
+                index: 0,
+                tool: None,
+                output: serde_json::json!({"info": "first"}),
+                completed_at: common::chrono::Utc::now(),
+            }],
+            execution: None,
+            created_at: common::chrono::Utc::now(),
+            completed_at: None,
+        };
+        store.add(checkpoint);
+        store.persist("interrupted").await.unwrap();
+
+        let mut orchestrator = Orchestrator::new().with_checkpoint_dir(dir.path());
+        orchestrator.initialize().await.unwrap();
+
+        let interrupted = orchestrator.interrupted_tasks().await;
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].id, task.id);
+
+        let result = orchestrator.process_task(task).await.unwrap();
+        assert!(result.output.contains("Step 1 restored from checkpoint"));
+        assert!(result.output.contains("Step 2: second"));
+
+        // Completed checkpoints are removed from disk
+        assert!(!dir.path().join("interrupted.json").exists());
+        assert!(orchestrator.interrupted_tasks().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_leaves_checkpoints_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let interrupted = crate::Task::new("Analyze this file");
+        let mut store = CheckpointStore::with_storage(dir.path().to_path_buf());
+        store.add(TaskCheckpoint {
+            id: "interrupted".to_string(),
+            task_id: interrupted.id,
+            task: interrupted.clone(),
+            stage: PipelineStage::IntentParsing,
+            current_step: 0,
+            intent: None,
+            context: None,
+            plan: None,
+            goal: None,
+            htn_plan: None,
+            completed_steps: Vec::new(),
+            execution: None,
+            created_at: common::chrono::Utc::now(),
+            completed_at: None,
+        });
+        store.persist("interrupted").await.unwrap();
+        let saved = std::fs::read_to_string(dir.path().join("interrupted.json")).unwrap();
+
+        let tools = tools::ToolFramework::new().with_overlay(Arc::new(tools::overlay::Overlay::new()));
+        let mut orchestrator = Orchestrator::new()
+            .with_tools(Arc::new(tools))
+            .with_checkpoint_dir(dir.path());
+        orchestrator.initialize().await.unwrap();
+
+        // Real interrupted work is neither picked up nor consumed
+        assert!(orchestrator.interrupted_tasks().await.is_empty());
+        orchestrator.process_task(crate::Task::new("Explain the parser")).await.unwrap();
+        let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(files, ["interrupted.json"]);
+        assert_eq!(std::fs::read_to_string(dir.path().join("interrupted.json")).unwrap(), saved);
+    }
+
+    #[tokio::test]
+    async fn test_failed_checkpoints_are_not_resumed() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = crate::Task::new("Analyze this file");
+        let checkpoint = |id: &str, stage| TaskCheckpoint {
+            id: id.to_string(),
+            task_id: task.id,
+            task: task.clone(),
+            stage,
+            current_step: 0,
+            intent: None,
+            context: None,
+            plan: None,
+            goal: None,
+            htn_plan: None,
+            completed_steps: Vec::new(),
+            execution: None,
+            created_at: common::chrono::Utc::now(),
+            completed_at: None,
+        };
+
+        let mut store = CheckpointStore::with_storage(dir.path().to_path_buf());
+        store.add(checkpoint("running", PipelineStage::Execution));
+        assert!(store.latest_unfinished(&task.id).is_some());
+        store.fail("running");
+        assert!(store.latest_unfinished(&task.id).is_none());
+
+        // Failed checkpoints persisted by earlier runs are pruned on load
+        store.add(checkpoint("failed", PipelineStage::Failed));
+        store.persist("failed").await.unwrap();
+        let mut loaded = CheckpointStore::with_storage(dir.path().to_path_buf());
+        assert_eq!(loaded.load().await.unwrap(), 0);
+        assert!(!dir.path().join("failed.json").exists());
+        assert!(loaded.latest_unfinished(&task.id).is_none());
+    }
+
+    /// Gateway that replays canned responses in order
+    struct ScriptedGateway {
+        responses: std::sync::Mutex<std::collections::VecDeque<String>>,
+    }
+
+    #[async_trait]
+    impl intelligence::gateway::LlmGateway for ScriptedGateway {
+        async fn initialize(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn generate(&self, _prompt: &str) -> Result<intelligence::GenerationResult> {
+            let content = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| Error::Internal("script exhausted".to_string()))?;
+            Ok(intelligence::GenerationResult {
+                content,
+                tokens_used: 7,
+                model: "scripted".to_string(),
+                finish_reason: "stop".to_string(),
+            })
+        }
+
+        async fn generate_stream(&self, _prompt: &str) -> Result<intelligence::gateway::StreamResult> {
+            Err(Error::Internal("streaming not supported".to_string()))
+        }
+
+        async fn list_models(&self) -> Result<Vec<intelligence::gateway::ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    /// Gateway that finishes every step and keeps the prompts it was sent
+    struct RecordingGateway {
+        prompts: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl intelligence::gateway::LlmGateway for RecordingGateway {
+        async fn initialize(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn generate(&self, prompt: &str) -> Result<intelligence::GenerationResult> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(intelligence::GenerationResult {
+                content: r#"{"thought": "done", "decision": "finish", "summary": "done"}"#.to_string(),
+                tokens_used: 1,
+                model: "recording".to_string(),
+                finish_reason: "stop".to_string(),
+            })
+        }
+
+        async fn generate_stream(&self, _prompt: &str) -> Result<intelligence::gateway::StreamResult> {
+            Err(Error::Internal("streaming not supported".to_string()))
+        }
+
+        async fn list_models(&self) -> Result<Vec<intelligence::gateway::ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn info_step(description: &str) -> PlanStep {
+        PlanStep {
+            description: description.to_string(),
+            tool: None,
+            parameters: Default::default(),
+            expected_output: String::new(),
+            timeout_seconds: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_experiment_arms_run_with_their_own_treatment() {
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let gateway = RecordingGateway { prompts: prompts.clone() };
+        let mut config = agent_config::AgentConfig::default();
+        config.llm.temperature = 0.5;
+        let orchestrator = Orchestrator::new()
+            .with_config(config)
+            .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(Box::new(gateway))));
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Analysis,
+            confidence: 1.0,
+            parameters: HashMap::new(),
+            raw_input: "Analyze this file".to_string(),
+        };
+        let plan = ActionPlan {
+            steps: vec![info_step("look")],
+            intent_category: intelligence::IntentCategory::Analysis,
+            estimated_tokens: 0,
+        };
+
+        let control = Treatment {
+            prompt: Some("Be brief.".to_string()),
+            config: None,
+        };
+        let variant = Treatment {
+            prompt: Some("Think step by step.".to_string()),
+            config: Some(serde_json::json!({"temperature": 0.2})),
+        };
+        let mut contexts = Vec::new();
+        for treatment in [control, variant] {
+            let mut task = crate::Task::new("Analyze this file");
+            task.context
+                .metadata
+                .insert("treatment".to_string(), serde_json::to_value(&treatment).unwrap());
+            let mut context = orchestrator.gather_context(&task, &intent).await.unwrap();
+            let checkpoint = orchestrator
+                .create_checkpoint(&task, PipelineStage::Execution)
+                .await
+                .unwrap();
+            orchestrator
+                .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+                .await
+                .unwrap();
+            contexts.push(context);
+        }
+
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].starts_with("Be brief.\n\n"));
+        assert!(prompts[1].starts_with("Think step by step.\n\n"));
+        assert_eq!(contexts[0].system_context.config["temperature"], 0.5);
+        assert_eq!(contexts[1].system_context.config["temperature"], 0.2);
+    }
+
+    #[tokio::test]
+    async fn test_react_loop_finishes_early_and_records_trajectory() {
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(
+                vec![
+                    r#"{"thought": "looks fine", "decision": "continue"}"#.to_string(),
+                    r#"{"thought": "goal reached", "decision": "finish", "summary": "done early"}"#.to_string(),
+                ]
+                .into(),
+            ),
+        };
+        let orchestrator = Orchestrator::new().with_intelligence(Arc::new(
+            intelligence::IntelligenceEngine::new(Box::new(gateway)),
+        ));
+
+        let task = crate::Task::new("Analyze this file");
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let plan = ActionPlan {
+            steps: vec![info_step("first"), info_step("second"), info_step("third")],
+            intent_category: intelligence::IntentCategory::Analysis,
+            estimated_tokens: 0,
+        };
+
+        let mut context = intelligence::Context::default();
+        let result = orchestrator
+            .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.tokens_used, 14);
+        assert!(result.summary.contains("done early"));
+        assert!(!result.summary.contains("third"));
+
+        let trajectory = &context.execution_context.trajectory;
+        assert_eq!(trajectory.len(), 2);
+        assert_eq!(trajectory[0].decision, "continue");
+        assert_eq!(trajectory[1].thought.as_deref(), Some("goal reached"));
+        assert_eq!(context.execution_context.tool_outputs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_reported_as_events() {
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(
+                vec![r#"{"thought": "no tools here", "decision": "finish", "summary": "gave up"}"#.to_string()].into(),
+            ),
+        };
+        let events = EventBus::new();
+        let mut received = events.subscribe();
+        let orchestrator = Orchestrator::new()
+            .with_events(events)
+            .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(Box::new(gateway))));
+
+        let task = crate::Task::new("Analyze this file");
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let read = PlanStep {
+            tool: Some("filesystem".to_string()),
+            parameters: serde_json::json!({"operation": "read", "path": "src/lib.rs"}),
+            ..info_step("read the file")
+        };
+        let plan = ActionPlan {
+            steps: vec![read],
+            intent_category: intelligence::IntentCategory::Analysis,
+            estimated_tokens: 0,
+        };
+        let mut context = intelligence::Context::default();
+        orchestrator
+            .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+            .await
+            .unwrap();
+
+        let kinds: Vec<EventKind> = std::iter::from_fn(|| received.try_recv().ok())
+            .map(|event| event.kind)
+            .collect();
+        assert!(matches!(
+            kinds[0],
+            EventKind::StageChanged { stage: PipelineStage::Execution, .. }
+        ));
+        assert!(matches!(
+            &kinds[1],
+            EventKind::ToolCalled { tool, success: false, .. } if tool == "filesystem"
+        ));
+        assert!(matches!(
+            &kinds[2],
+            EventKind::TokensStreamed { tokens: 7, content, .. } if content.contains("gave up")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_react_loop_respects_step_limit() {
+        let mut config = agent_config::AgentConfig::default();
+        config.agent.react.max_steps = 1;
+        let orchestrator = Orchestrator::new().with_config(config);
+
+        let task = crate::Task::new("Analyze this file");
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let plan = ActionPlan {
+            steps: vec![info_step("first"), info_step("second")],
+            intent_category: intelligence::IntentCategory::Analysis,
+            estimated_tokens: 0,
+        };
+
+        let mut context = intelligence::Context::default();
+        let result = orchestrator
+            .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.summary.contains("step limit of 1 reached"));
+        assert_eq!(context.execution_context.trajectory.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_integration_keeps_success_and_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git").current_dir(repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "agent@example.com"]);
+        git(&["config", "user.name", "agent"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "init"]);
+
+        let mut config = agent_config::AgentConfig::default();
+        config.tools.git.enabled = true;
+        config.tools.git.worktree_isolation = true;
+        let orchestrator = Orchestrator::new()
+            .with_config(config)
+            .with_tools(Arc::new(tools::ToolFramework::new()));
+        let mut task = crate::Task::new("edit a");
+        task.context.workspace_path = Some(repo.to_path_buf());
+        let worktree = orchestrator.open_worktree(&task).await.unwrap().unwrap();
+
+        // The original tree moves on in a conflicting way
+        std::fs::write(worktree.root.join("a.txt"), "two\n").unwrap();
+        std::fs::write(repo.join("a.txt"), "three\n").unwrap();
+        git(&["commit", "-q", "-am", "conflict"]);
+
+        let result = crate::TaskResult {
+            task_id: task.id,
+            success: true,
+            output: "edited".to_string(),
+            artifacts: Vec::new(),
+            completed_at: common::chrono::Utc::now(),
+            execution_time_ms: 0,
+            metrics: Default::default(),
+            usage: Default::default(),
+            exhausted: None,
+            integration_error: None,
+        };
+        let result = orchestrator.close_worktree(&task, &worktree, Ok(result)).await.unwrap();
+        assert!(result.success);
+        assert!(result.integration_error.is_some());
+        assert!(result.output.contains(&worktree.branch));
+        assert_eq!(std::fs::read_to_string(worktree.root.join("a.txt")).unwrap(), "two\n");
+    }
+
+    #[test]
+    fn test_child_with_another_model_keeps_recording_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let recording = dir.path().join("calls.jsonl");
+        let mut config = agent_config::AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        config.llm.record_to = Some(recording.to_string_lossy().into_owned());
+        let spec = DelegationSpec::new().with_model("other");
+
+        // The child's own gateway records to the same file
+        let parent = Orchestrator::new().with_config(config.clone());
+        parent.child(&spec, ToolScope::default()).unwrap();
+        assert!(recording.exists());
+
+        // A replay stays one stream, answered through the parent's engine
+        config.llm.record_to = None;
+        config.llm.replay_from = Some(recording.to_string_lossy().into_owned());
+        let engine = Arc::new(intelligence::IntelligenceEngine::new(Box::new(RecordingGateway {
+            prompts: Default::default(),
+        })));
+        let parent = Orchestrator::new().with_config(config).with_intelligence(engine.clone());
+        let child = parent.child(&spec, ToolScope::default()).unwrap();
+        assert!(Arc::ptr_eq(child.intelligence.as_ref().unwrap(), &engine));
+    }
+
+    #[tokio::test]
+    async fn test_model_calls_are_bounded_by_the_task_budget() {
+        let orchestrator = Orchestrator::new();
+        let task = crate::Task::new("Analyze this file").with_budget(intelligence::ResourceLimits {
+            timeout_seconds: 1,
+            ..Default::default()
+        });
+        let budget = orchestrator.open_budget(&task);
+
+        // A call is cut off when the task's time runs out
+        let stalled = orchestrator
+            .within_budget(&task.id, std::future::pending::<Result<()>>())
+            .await;
+        assert!(matches!(stalled, Err(Error::Timeout(_))));
+
+        // and refused once any limit is used up
+        budget.set_limits(intelligence::ResourceLimits {
+            max_llm_calls: 1,
+            ..Default::default()
+        });
+        assert!(orchestrator.within_budget(&task.id, async { Ok(()) }).await.is_ok());
+        budget.charge_llm("scripted", 7);
+        let refused = orchestrator.within_budget(&task.id, async { Ok(()) }).await;
+        assert!(matches!(refused, Err(Error::Execution(message)) if message.contains("LLM call budget")));
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_budget_winds_down_with_partial_result() {
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(
+                vec![r#"{"thought": "step one done", "decision": "continue"}"#.to_string()].into(),
+            ),
+        };
+        let orchestrator = Orchestrator::new().with_intelligence(Arc::new(
+            intelligence::IntelligenceEngine::new(Box::new(gateway)),
+        ));
+
+        let task = crate::Task::new("Analyze this file").with_budget(intelligence::ResourceLimits {
+            max_llm_calls: 1,
+            ..Default::default()
+        });
+        let budget = orchestrator.open_budget(&task);
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let plan = ActionPlan {
+            steps: vec![info_step("first"), info_step("second"), info_step("third")],
+            intent_category: intelligence::IntentCategory::Analysis,
+            estimated_tokens: 0,
+        };
+
+        let mut context = intelligence::Context::default();
+        let execution = orchestrator
+            .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+            .await
+            .unwrap();
+        assert!(!execution.success);
+        assert_eq!(execution.exhausted.as_deref(), Some("LLM call budget of 1 used up"));
+        assert_eq!(context.execution_context.trajectory.len(), 1);
+
+        let start = common::chrono::Utc::now();
+        let reason = execution.exhausted.clone().unwrap();
+        let result = orchestrator
+            .wind_down(&task, &checkpoint, start, reason, Some(execution), &budget)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.exhausted.is_some());
+        assert!(result.output.contains("Step 1: first"));
+        assert!(!result.output.contains("third"));
+        assert_eq!(result.usage.llm_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_broken_edit_is_fixed_by_the_model() {
+        // Relative, since the filesystem tool rejects absolute paths
+        let dir = tempfile::Builder::new().tempdir_in(".").unwrap();
+        let workspace = dir
+            .path()
+            .strip_prefix(std::env::current_dir().unwrap())
+            .unwrap()
+            .to_path_buf();
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[package]\nname = \"scratch\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::write(workspace.join("src/lib.rs"), "pub fn answer() -> u32 { 42 }\n").unwrap();
+
+        let fixed = "pub fn answer() -> u32 { 41 + 1 }\n";
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(
+                vec![
+                    serde_json::json!({"files": [{"path": "src/lib.rs", "content": fixed}]}).to_string(),
+                    r#"{"thought": "build is green", "decision": "continue"}"#.to_string(),
+                ]
+                .into(),
+            ),
+        };
+        let mut config = agent_config::AgentConfig::default();
+        config.tools.check.max_fix_iterations = 1;
+        let orchestrator = Orchestrator::new()
+            .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(Box::new(gateway))))
+            .with_tools(Arc::new(tools::ToolFramework::new()))
+            .with_config(config);
+
+        let task = crate::Task::new("Make answer compute its result").with_context(crate::TaskContext {
+            workspace_path: Some(workspace.clone()),
+            ..Default::default()
+        });
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let plan = ActionPlan {
+            steps: vec![PlanStep {
+                description: "Rewrite answer".to_string(),
+                tool: Some("filesystem".to_string()),
+                parameters: serde_json::json!({
+                    "operation": "write",
+                    "path": workspace.join("src/lib.rs").to_string_lossy(),
+                    "content": "pub fn answer() -> u32 { \"42\" }\n",
+                }),
+                expected_output: String::new(),
+                timeout_seconds: 30,
+            }],
+            intent_category: intelligence::IntentCategory::CodeModification,
+            estimated_tokens: 0,
+        };
+
+        let mut context = intelligence::Context::default();
+        let result = orchestrator
+            .execute_plan_with_checkpoint(&plan, &checkpoint, &mut context)
+            .await
+            .unwrap();
+
+        assert!(result.success, "{}", result.summary);
+        assert!(result.summary.contains("Build check passed"), "{}", result.summary);
+        assert_eq!(result.tokens_used, 14);
+        assert_eq!(std::fs::read_to_string(workspace.join("src/lib.rs")).unwrap(), fixed);
+    }
+
+    #[tokio::test]
+    async fn test_planning_calls_follow_the_routing_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = r#"{"steps": [{"description": "Write it", "parameters": {}, "expected_output": "done", "timeout_seconds": 10}]}"#;
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(vec![plan.to_string(); 3].into()),
+        };
+        let mut config = agent_config::AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        config.llm.model = "scripted".to_string();
+        config.llm.routing.adaptive = agent_config::AdaptiveRoutingConfig {
+            enabled: true,
+            exploration_rate: 0.0,
+            min_samples: 1,
+            table_path: dir.path().join("routing.json"),
+        };
+        // The mock gateway's answers are not plans
+        config.llm.routing.providers = vec![agent_config::ProviderRoute {
+            provider: "mock".to_string(),
+            model: "mock".to_string(),
+            weight: 1.0,
+            cost_per_1k_input: 0.0,
+            cost_per_1k_output: 0.0,
+            avg_latency_ms: 0,
+            priority: 1,
+            enabled: true,
+        }];
+        let router = Arc::new(ModelRouter::from_config(&config).unwrap());
+        let orchestrator = Orchestrator::new()
+            .with_config(config)
+            .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(Box::new(gateway))))
+            .with_router(router.clone());
+
+        let task = crate::Task::new("Write a parser");
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::CodeGeneration,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: task.description.clone(),
+        };
+        let context = intelligence::Context::default();
+        for _ in 0..3 {
+            orchestrator.generate_plan(&task.id, &intent, &context).await.unwrap();
+        }
+
+        let table = router.table();
+        let calls = |model: &str| {
+            let route = router.candidates().iter().find(|r| r.model == model).unwrap();
+            let entry = table
+                .get(intelligence::IntentCategory::CodeGeneration, RouteStage::Planning, route)
+                .unwrap();
+            (entry.calls, entry.successes)
+        };
+        assert_eq!(calls("scripted"), (2, 2));
+        assert_eq!(calls("mock"), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_is_traced_as_one_task() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        // Relative, since the filesystem tool rejects absolute paths
+        let workspace = tempfile::Builder::new().tempdir_in(".").unwrap();
+        let notes = workspace
+            .path()
+            .strip_prefix(std::env::current_dir().unwrap())
+            .unwrap()
+            .join("notes.txt");
+        std::fs::write(&notes, "traced\n").unwrap();
+
+        let gateway = ScriptedGateway {
+            responses: std::sync::Mutex::new(
+                vec![r#"{"thought": "read it", "decision": "finish", "summary": "notes read"}"#.to_string()].into(),
+            ),
+        };
+        let orchestrator = Orchestrator::new()
+            .with_checkpoint_dir(dir.path())
+            .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(Box::new(gateway))))
+            .with_tools(Arc::new(tools::ToolFramework::new()));
+
+        // Start at execution with a one-step plan reading the notes
+        let task = crate::Task::new("Read the notes");
+        let checkpoint = orchestrator
+            .create_checkpoint(&task, PipelineStage::Execution)
+            .await
+            .unwrap();
+        let read = PlanStep {
+            tool: Some("filesystem".to_string()),
+            parameters: serde_json::json!({"operation": "read", "path": notes.to_string_lossy()}),
+            ..info_step("read the notes")
+        };
+        let description = task.description.clone();
+        orchestrator
+            .record_checkpoint(&checkpoint, move |cp| {
+                cp.intent = Some(intelligence::Intent {
+                    category: intelligence::IntentCategory::Analysis,
+                    confidence: 1.0,
+                    parameters: Default::default(),
+                    raw_input: description,
+                });
+                cp.context = Some(intelligence::Context::default());
+                cp.plan = Some(ActionPlan {
+                    steps: vec![read],
+                    intent_category: intelligence::IntentCategory::Analysis,
+                    estimated_tokens: 0,
+                });
+            })
+            .await
+            .unwrap();
+
+        let file = dir.path().join("spans.jsonl");
+        let exporter = Arc::new(otel::SpanExporter::new(otel::ExportTarget::File(file.clone()), "agent"));
+        let layer = otel::TraceLayer::new();
+        layer.install(exporter.clone()).unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let result = orchestrator.process_task(task.clone()).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        exporter.flush().await.unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&file).unwrap().trim()).unwrap();
+        let spans = request["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap().clone();
+        let named = |name: &str| spans.iter().find(|span| span["name"] == name).cloned();
+        let attribute = |span: &serde_json::Value, key: &str| {
+            span["attributes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|kv| kv["key"] == key)
+                .map(|kv| kv["value"].clone())
+        };
+
+        let root = named("process_task").unwrap();
+        assert!(spans.iter().all(|span| span["traceId"] == root["traceId"]));
+        assert_eq!(attribute(&root, "task.id").unwrap()["stringValue"], task.id.to_string());
+
+        // Restored stages are not run again
+        assert!(named("stage planning").is_none());
+        let execution = named("stage execution").unwrap();
+        for stage in ["stage execution", "stage validation", "stage knowledge_update"] {
+            assert_eq!(named(stage).unwrap()["parentSpanId"], root["spanId"], "{}", stage);
+        }
+
+        let tool = named("execute_tool filesystem").unwrap();
+        assert_eq!(tool["parentSpanId"], execution["spanId"]);
+        assert_eq!(attribute(&tool, "gen_ai.tool.name").unwrap()["stringValue"], "filesystem");
+        assert_ne!(tool["status"]["code"], 2);
+
+        let chat = named("chat scripted").unwrap();
+        assert_eq!(chat["parentSpanId"], execution["spanId"]);
+        assert_eq!(attribute(&chat, "gen_ai.usage.total_tokens").unwrap()["intValue"], "7");
+        assert!(attribute(&chat, otel::COST_ATTRIBUTE).unwrap()["doubleValue"].is_f64());
+    }
 }
@@ -0,0 +1,801 @@
+//! OpenTelemetry-compatible tracing of the task pipeline.
+//!
+//! [`TraceLayer`] is a `tracing` layer that turns spans into OTLP spans.
+//! Each task is one trace: a `process_task` root with a child span per
+//! pipeline stage, and below those a span per model call and tool call.
+//! Attributes follow the OpenTelemetry semantic conventions (`gen_ai.*` for
+//! model and tool calls, `error.type` for failures). As with
+//! `tracing-opentelemetry`, the `otel.name`, `otel.kind`, `otel.status_code`
+//! and `otel.status_message` fields rename a span, set its kind and mark
+//! its status rather than becoming attributes.
+//!
+//! Finished spans are buffered and a [`SpanExporter`] sends them as
+//! OTLP/HTTP JSON to a collector's `/v1/traces`, or appends the same
+//! export requests to a file, one per line.
+
+use crate::orchestrator::PipelineStage;
+use crate::routing::ModelRoute;
+use agent_config::TracingConfig;
+use common::{Error, Result};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tracing::field::{Empty, Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{warn, Instrument, Level, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Finished spans held for export; newer ones are dropped beyond this
+const MAX_PENDING: usize = 4096;
+
+/// Longest a single export request may take
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Instrumentation scope reported with every span
+const SCOPE: &str = "coding-agent";
+
+/// Attribute holding what a model call cost in USD
+pub const COST_ATTRIBUTE: &str = "gen_ai.usage.cost";
+
+/// OTLP span kinds
+const KIND_INTERNAL: u8 = 1;
+const KIND_SERVER: u8 = 2;
+const KIND_CLIENT: u8 = 3;
+const KIND_PRODUCER: u8 = 4;
+const KIND_CONSUMER: u8 = 5;
+
+/// OTLP status codes
+const STATUS_OK: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+/// Root span of one task
+pub fn task_span(task: &crate::Task) -> Span {
+    tracing::info_span!(
+        "process_task",
+        task.id = %task.id,
+        task.priority = ?task.priority,
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        error.type = Empty,
+    )
+}
+
+/// Span covering one pipeline stage
+pub fn stage_span(stage: PipelineStage) -> Span {
+    let name = serde_json::to_value(stage)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+    tracing::info_span!(
+        "pipeline_stage",
+        otel.name = %format!("stage {}", name),
+        pipeline.stage = %name,
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        error.type = Empty,
+    )
+}
+
+/// Run one pipeline stage in its own span, marking it failed on error
+pub async fn in_stage<T>(stage: PipelineStage, work: impl Future<Output = Result<T>>) -> Result<T> {
+    let span = stage_span(stage);
+    let result = work.instrument(span.clone()).await;
+    if let Err(e) = &result {
+        record_error(&span, e);
+    }
+    result
+}
+
+/// Span for one model call; `route` names the model asked for, if known
+pub fn chat_span(route: Option<&ModelRoute>) -> Span {
+    let span = tracing::info_span!(
+        "chat",
+        otel.kind = "client",
+        gen_ai.operation.name = "chat",
+        gen_ai.system = Empty,
+        gen_ai.request.model = Empty,
+        gen_ai.response.model = Empty,
+        gen_ai.response.finish_reasons = Empty,
+        gen_ai.usage.total_tokens = Empty,
+        gen_ai.usage.cost = Empty,
+        otel.name = Empty,
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        error.type = Empty,
+    );
+    if let Some(route) = route {
+        span.record("otel.name", format!("chat {}", route.model).as_str());
+        span.record("gen_ai.system", route.provider.as_str());
+        span.record("gen_ai.request.model", route.model.as_str());
+    }
+    span
+}
+
+/// Record a finished model call on its span
+pub fn record_generation(span: &Span, result: &Result<intelligence::GenerationResult>) {
+    match result {
+        Ok(result) => {
+            span.record("otel.name", format!("chat {}", result.model).as_str());
+            span.record("gen_ai.response.model", result.model.as_str());
+            span.record("gen_ai.response.finish_reasons", result.finish_reason.as_str());
+            span.record("gen_ai.usage.total_tokens", result.tokens_used);
+        }
+        Err(e) => record_error(span, e),
+    }
+}
+
+/// Mark a span failed with `error`
+pub fn record_error(span: &Span, error: &Error) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", error.to_string().as_str());
+    span.record("error.type", error.kind());
+}
+
+/// A span as sent to the collector
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpanRecord {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: String,
+    kind: u8,
+    #[serde(serialize_with = "as_string")]
+    start_time_unix_nano: u64,
+    #[serde(serialize_with = "as_string")]
+    end_time_unix_nano: u64,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+impl SpanRecord {
+    fn new(trace_id: String, parent_span_id: Option<String>, name: &str) -> Self {
+        Self {
+            trace_id,
+            span_id: random_id(8),
+            parent_span_id,
+            name: name.to_string(),
+            kind: KIND_INTERNAL,
+            start_time_unix_nano: now_nanos(),
+            end_time_unix_nano: 0,
+            attributes: Vec::new(),
+            status: Status::default(),
+        }
+    }
+
+    /// Apply a recorded field, honouring the `otel.*` conventions
+    fn set(&mut self, key: &str, value: AnyValue) {
+        if let AnyValue::String(text) = &value {
+            match key {
+                "otel.name" => {
+                    self.name = text.clone();
+                    return;
+                }
+                "otel.kind" => {
+                    self.kind = match text.to_ascii_lowercase().as_str() {
+                        "server" => KIND_SERVER,
+                        "client" => KIND_CLIENT,
+                        "producer" => KIND_PRODUCER,
+                        "consumer" => KIND_CONSUMER,
+                        _ => KIND_INTERNAL,
+                    };
+                    return;
+                }
+                "otel.status_code" => {
+                    self.status.code = match text.to_ascii_uppercase().as_str() {
+                        "OK" => STATUS_OK,
+                        "ERROR" => STATUS_ERROR,
+                        _ => 0,
+                    };
+                    return;
+                }
+                "otel.status_message" => {
+                    self.status.message = Some(text.clone());
+                    return;
+                }
+                _ => {}
+            }
+        }
+        match self.attributes.iter_mut().find(|kv| kv.key == key) {
+            Some(kv) => kv.value = value,
+            None => self.attributes.push(KeyValue {
+                key: key.to_string(),
+                value,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+/// OTLP attribute value; 64-bit integers travel as strings in JSON
+#[derive(Debug, Clone, PartialEq, Serialize)]
+enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    #[serde(rename = "boolValue")]
+    Bool(bool),
+    #[serde(rename = "intValue")]
+    Int(String),
+    #[serde(rename = "doubleValue")]
+    Double(f64),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Status {
+    code: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+/// Body of an OTLP/HTTP trace export
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRequest<'a> {
+    resource_spans: [ResourceSpans<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans<'a> {
+    resource: Resource<'a>,
+    scope_spans: [ScopeSpans<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct Resource<'a> {
+    attributes: &'a [KeyValue],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeSpans<'a> {
+    scope: Scope,
+    spans: &'a [SpanRecord],
+}
+
+#[derive(Serialize)]
+struct Scope {
+    name: &'static str,
+    version: &'static str,
+}
+
+fn as_string<S: Serializer>(value: &u64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Copies span fields into a [`SpanRecord`]
+struct FieldVisitor<'a>(&'a mut SpanRecord);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.set(field.name(), AnyValue::String(value.to_string()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.set(field.name(), AnyValue::Bool(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.set(field.name(), AnyValue::Int(value.to_string()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.set(field.name(), AnyValue::Int(value.to_string()));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.set(field.name(), AnyValue::Double(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.set(field.name(), AnyValue::String(format!("{:?}", value)));
+    }
+}
+
+/// `tracing` layer recording spans for OTLP export
+///
+/// Spans are only recorded once an exporter is installed, so the layer can
+/// be part of the subscriber before the configuration is loaded. Debug and
+/// trace spans, such as the HTTP client's own, are left out.
+#[derive(Clone, Default)]
+pub struct TraceLayer {
+    exporter: Arc<OnceLock<Arc<SpanExporter>>>,
+}
+
+impl TraceLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send finished spans to `exporter` from now on
+    pub fn install(&self, exporter: Arc<SpanExporter>) -> Result<()> {
+        self.exporter
+            .set(exporter)
+            .map_err(|_| Error::Config("A span exporter is already installed".to_string()))
+    }
+
+    /// Start exporting as `config` asks
+    ///
+    /// Returns the exporter, to be flushed on shutdown, or `None` when
+    /// tracing is disabled. Must be called within a Tokio runtime.
+    pub fn start(&self, config: &TracingConfig) -> Result<Option<Arc<SpanExporter>>> {
+        let Some(exporter) = SpanExporter::from_config(config) else {
+            return Ok(None);
+        };
+        let exporter = Arc::new(exporter);
+        self.install(exporter.clone())?;
+        exporter.spawn();
+        Ok(Some(exporter))
+    }
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if self.exporter.get().is_none() || *attrs.metadata().level() > Level::INFO {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        // Hang off the nearest recorded ancestor, or start a new trace
+        let parent = span.scope().skip(1).find_map(|ancestor| {
+            ancestor
+                .extensions()
+                .get::<SpanRecord>()
+                .map(|record| (record.trace_id.clone(), record.span_id.clone()))
+        });
+        let (trace_id, parent_span_id) = match parent {
+            Some((trace_id, span_id)) => (trace_id, Some(span_id)),
+            None => (random_id(16), None),
+        };
+
+        let mut record = SpanRecord::new(trace_id, parent_span_id, attrs.metadata().name());
+        attrs.record(&mut FieldVisitor(&mut record));
+        span.extensions_mut().insert(record);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(record) = extensions.get_mut::<SpanRecord>() {
+            values.record(&mut FieldVisitor(record));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let (Some(exporter), Some(span)) = (self.exporter.get(), ctx.span(&id)) else {
+            return;
+        };
+        let Some(mut record) = span.extensions_mut().remove::<SpanRecord>() else {
+            return;
+        };
+        record.end_time_unix_nano = now_nanos();
+        exporter.push(record);
+    }
+}
+
+/// Where exported spans go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// OTLP/HTTP traces endpoint
+    Http(String),
+    /// File receiving one JSON export request per line
+    File(PathBuf),
+}
+
+/// Batches finished spans and sends them to an [`ExportTarget`]
+pub struct SpanExporter {
+    target: ExportTarget,
+    resource: Vec<KeyValue>,
+    interval: Duration,
+    pending: parking_lot::Mutex<Vec<SpanRecord>>,
+    /// Spans dropped because the buffer was full, reported on the next flush
+    dropped: AtomicUsize,
+    client: reqwest::Client,
+}
+
+impl SpanExporter {
+    /// Export to `target`, reporting the agent as `service_name`
+    ///
+    /// An HTTP target may be a collector's base URL; `/v1/traces` is added
+    /// when missing.
+    pub fn new(target: ExportTarget, service_name: &str) -> Self {
+        let target = match target {
+            ExportTarget::Http(endpoint) if !endpoint.ends_with("/v1/traces") => {
+                ExportTarget::Http(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+            }
+            target => target,
+        };
+        let resource = vec![
+            KeyValue {
+                key: "service.name".to_string(),
+                value: AnyValue::String(service_name.to_string()),
+            },
+            KeyValue {
+                key: "service.version".to_string(),
+                value: AnyValue::String(env!("CARGO_PKG_VERSION").to_string()),
+            },
+        ];
+        Self {
+            target,
+            resource,
+            interval: Duration::from_secs(5),
+            pending: parking_lot::Mutex::new(Vec::new()),
+            dropped: AtomicUsize::new(0),
+            client: reqwest::Client::builder()
+                .timeout(EXPORT_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Exporter for `config`, preferring the endpoint over the file
+    pub fn from_config(config: &TracingConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let target = match (&config.endpoint, &config.file) {
+            (Some(endpoint), _) => ExportTarget::Http(endpoint.clone()),
+            (None, Some(file)) => ExportTarget::File(file.clone()),
+            (None, None) => return None,
+        };
+        Some(
+            Self::new(target, &config.service_name)
+                .with_interval(Duration::from_secs(config.export_interval_seconds)),
+        )
+    }
+
+    /// How often [`SpanExporter::spawn`] exports buffered spans
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn target(&self) -> &ExportTarget {
+        &self.target
+    }
+
+    fn push(&self, span: SpanRecord) {
+        let mut pending = self.pending.lock();
+        if pending.len() < MAX_PENDING {
+            pending.push(span);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Export every buffered span now
+    ///
+    /// Spans that fail to export are kept for the next attempt, as long as
+    /// the buffer has room for them.
+    pub async fn flush(&self) -> Result<()> {
+        let spans = std::mem::take(&mut *self.pending.lock());
+        let result = if spans.is_empty() { Ok(()) } else { self.export(&spans).await };
+        if result.is_err() {
+            let mut pending = self.pending.lock();
+            let newer = std::mem::replace(&mut *pending, spans);
+            pending.extend(newer);
+            if pending.len() > MAX_PENDING {
+                self.dropped.fetch_add(pending.len() - MAX_PENDING, Ordering::Relaxed);
+                pending.truncate(MAX_PENDING);
+            }
+        }
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Dropped {} spans while the export buffer was full", dropped);
+        }
+        result
+    }
+
+    async fn export(&self, spans: &[SpanRecord]) -> Result<()> {
+        let request = ExportRequest {
+            resource_spans: [ResourceSpans {
+                resource: Resource {
+                    attributes: &self.resource,
+                },
+                scope_spans: [ScopeSpans {
+                    scope: Scope {
+                        name: SCOPE,
+                        version: env!("CARGO_PKG_VERSION"),
+                    },
+                    spans,
+                }],
+            }],
+        };
+        let body = serde_json::to_vec(&request)?;
+
+        match &self.target {
+            ExportTarget::Http(url) => {
+                let response = self
+                    .client
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body)
+                    .send()
+                    .await
+                    .map_err(|e| Error::ExternalService(format!("Span export to {} failed: {}", url, e)))?;
+                if !response.status().is_success() {
+                    return Err(Error::ExternalService(format!(
+                        "Span export to {} failed with {}",
+                        url,
+                        response.status()
+                    )));
+                }
+            }
+            ExportTarget::File(path) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                let mut line = body;
+                line.push(b'\n');
+                file.write_all(&line).await?;
+                file.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export buffered spans every interval until the runtime shuts down
+    pub fn spawn(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let exporter = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(exporter.interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = exporter.flush().await {
+                    warn!("{}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Random lowercase hex id of `bytes` bytes
+fn random_id(bytes: usize) -> String {
+    let mut id = uuid::Uuid::new_v4().simple().to_string();
+    id.truncate(bytes * 2);
+    id
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tokio::io::AsyncReadExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Stand-in collector returning each request's path and JSON body
+    async fn collector() -> (String, tokio::sync::mpsc::UnboundedReceiver<(String, Value)>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (requests, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        // Headers, then as many body bytes as they announce
+                        let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            match stream.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                            }
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&buffer[..end]).to_string();
+                        let length: usize = head
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                name.eq_ignore_ascii_case("content-length")
+                                    .then(|| value.trim().parse().ok())?
+                            })
+                            .unwrap_or(0);
+                        while buffer.len() < end + 4 + length {
+                            match stream.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                            }
+                        }
+                        let body: Vec<u8> = buffer.drain(..end + 4 + length).skip(end + 4).collect();
+                        let path = head.split_whitespace().nth(1).unwrap_or_default().to_string();
+                        let _ = requests.send((path, serde_json::from_slice(&body).unwrap()));
+                        let reply = b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 2\r\n\r\n{}";
+                        if stream.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (endpoint, received)
+    }
+
+    fn spans(request: &Value) -> Vec<Value> {
+        request["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    fn attribute<'a>(span: &'a Value, key: &str) -> Option<&'a Value> {
+        span["attributes"]
+            .as_array()?
+            .iter()
+            .find(|kv| kv["key"] == key)
+            .map(|kv| &kv["value"])
+    }
+
+    fn named<'a>(spans: &'a [Value], name: &str) -> &'a Value {
+        spans.iter().find(|span| span["name"] == name).unwrap()
+    }
+
+    #[test]
+    fn test_otel_fields_set_name_kind_and_status() {
+        let mut record = SpanRecord::new(random_id(16), None, "chat");
+        assert_eq!(record.trace_id.len(), 32);
+        assert_eq!(record.span_id.len(), 16);
+
+        record.set("otel.name", AnyValue::String("chat gpt-4".to_string()));
+        record.set("otel.kind", AnyValue::String("client".to_string()));
+        record.set("otel.status_code", AnyValue::String("ERROR".to_string()));
+        record.set("gen_ai.usage.total_tokens", AnyValue::Int("10".to_string()));
+        record.set("gen_ai.usage.total_tokens", AnyValue::Int("12".to_string()));
+
+        assert_eq!(record.name, "chat gpt-4");
+        assert_eq!(record.kind, KIND_CLIENT);
+        assert_eq!(record.status.code, STATUS_ERROR);
+        assert_eq!(record.attributes.len(), 1);
+        assert_eq!(record.attributes[0].value, AnyValue::Int("12".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_spans_export_to_collector_as_otlp_json() {
+        let (endpoint, mut received) = collector().await;
+        let layer = TraceLayer::new();
+        let exporter = Arc::new(SpanExporter::new(ExportTarget::Http(endpoint), "agent-under-test"));
+        layer.install(exporter.clone()).unwrap();
+        assert!(layer.install(exporter.clone()).is_err());
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let task = crate::Task::new("Trace me");
+        let route = ModelRoute {
+            provider: "openrouter".to_string(),
+            model: "gpt-4".to_string(),
+        };
+        {
+            let _task = task_span(&task).entered();
+            let stage = stage_span(PipelineStage::Planning);
+            let _stage = stage.enter();
+            let chat = chat_span(Some(&route));
+            record_generation(
+                &chat,
+                &Ok(intelligence::GenerationResult {
+                    content: String::new(),
+                    tokens_used: 42,
+                    model: "gpt-4-0613".to_string(),
+                    finish_reason: "stop".to_string(),
+                }),
+            );
+            chat.record(COST_ATTRIBUTE, 0.25);
+            drop(chat);
+            record_error(&stage, &Error::Validation("no plan".to_string()));
+        }
+        exporter.flush().await.unwrap();
+
+        let (path, request) = received.recv().await.unwrap();
+        assert_eq!(path, "/v1/traces");
+        let resource = &request["resourceSpans"][0]["resource"];
+        assert_eq!(attribute(resource, "service.name").unwrap()["stringValue"], "agent-under-test");
+
+        let spans = spans(&request);
+        assert_eq!(spans.len(), 3);
+        let root = named(&spans, "process_task");
+        let stage = named(&spans, "stage planning");
+        let chat = named(&spans, "chat gpt-4-0613");
+        assert!(spans.iter().all(|span| span["traceId"] == root["traceId"]));
+        assert!(root.get("parentSpanId").is_none());
+        assert_eq!(stage["parentSpanId"], root["spanId"]);
+        assert_eq!(chat["parentSpanId"], stage["spanId"]);
+        assert_eq!(attribute(root, "task.id").unwrap()["stringValue"], task.id.to_string());
+
+        assert_eq!(stage["status"]["code"], 2);
+        assert_eq!(attribute(stage, "error.type").unwrap()["stringValue"], "validation");
+        assert_eq!(attribute(stage, "pipeline.stage").unwrap()["stringValue"], "planning");
+
+        assert_eq!(chat["kind"], 3);
+        assert_eq!(attribute(chat, "gen_ai.system").unwrap()["stringValue"], "openrouter");
+        assert_eq!(attribute(chat, "gen_ai.request.model").unwrap()["stringValue"], "gpt-4");
+        assert_eq!(attribute(chat, "gen_ai.response.model").unwrap()["stringValue"], "gpt-4-0613");
+        assert_eq!(attribute(chat, "gen_ai.usage.total_tokens").unwrap()["intValue"], "42");
+        assert_eq!(attribute(chat, COST_ATTRIBUTE).unwrap()["doubleValue"], 0.25);
+        assert!(attribute(chat, "otel.kind").is_none());
+        let start: u64 = chat["startTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        let end: u64 = chat["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        assert!(start > 0 && end >= start);
+
+        // Nothing left to send
+        exporter.flush().await.unwrap();
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spans_export_to_file_one_request_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = TracingConfig {
+            enabled: true,
+            file: Some(dir.path().join("traces/spans.jsonl")),
+            ..Default::default()
+        };
+        let exporter = Arc::new(SpanExporter::from_config(&config).unwrap());
+        assert!(matches!(exporter.target(), ExportTarget::File(_)));
+        let layer = TraceLayer::new();
+        layer.install(exporter.clone()).unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        // Debug spans are skipped
+        tracing::debug_span!("hidden").in_scope(|| tracing::info_span!("first").in_scope(|| {}));
+        exporter.flush().await.unwrap();
+        tracing::info_span!("second").in_scope(|| {});
+        exporter.flush().await.unwrap();
+
+        let written = std::fs::read_to_string(dir.path().join("traces/spans.jsonl")).unwrap();
+        let names: Vec<String> = written
+            .lines()
+            .flat_map(|line| spans(&serde_json::from_str(line).unwrap()))
+            .map(|span| span["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        assert!(SpanExporter::from_config(&TracingConfig::default()).is_none());
+
+        // A failed export keeps its spans for the next flush
+        std::fs::write(dir.path().join("blocked"), "").unwrap();
+        let exporter = Arc::new(SpanExporter::new(
+            ExportTarget::File(dir.path().join("blocked/spans.jsonl")),
+            "agent-under-test",
+        ));
+        let layer = TraceLayer::new();
+        layer.install(exporter.clone()).unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        tracing::info_span!("retried").in_scope(|| {});
+        assert!(exporter.flush().await.is_err());
+        assert_eq!(exporter.pending.lock().len(), 1);
+        std::fs::remove_file(dir.path().join("blocked")).unwrap();
+        exporter.flush().await.unwrap();
+        assert!(exporter.pending.lock().is_empty());
+        let written = std::fs::read_to_string(dir.path().join("blocked/spans.jsonl")).unwrap();
+        assert_eq!(spans(&serde_json::from_str(written.trim()).unwrap())[0]["name"], "retried");
+    }
+}
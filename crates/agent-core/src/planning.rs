@@ -10,14 +10,15 @@
 //!
 //! Key features:
 //! - Goal decomposition into primitive tasks
-//! - Method selection based on world state
+//! - Cost-aware best-first search over method alternatives
 //! - Dynamic replanning on failure
 //! - Plan validation and optimization
 
-use crate::orchestrator::Orchestrator;
+use crate::orchestrator::{ActionPlan, Orchestrator, PlanStep, TaskCheckpoint};
+use agent_config::PlanningSettings;
 use common::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use tracing::{debug, info, warn};
 
 /// A goal that needs to be achieved
@@ -37,6 +38,32 @@ pub struct Goal {
     pub deadline: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+impl Goal {
+    /// Derive a planning goal from a parsed intent
+    pub fn from_intent(intent: &intelligence::Intent, description: &str) -> Self {
+        use intelligence::IntentCategory;
+
+        let goal_type = match intent.category {
+            IntentCategory::CodeGeneration => GoalType::CodeGeneration,
+            IntentCategory::CodeModification => GoalType::CodeModification,
+            IntentCategory::Analysis => GoalType::Analysis,
+            IntentCategory::Testing => GoalType::Testing,
+            IntentCategory::Documentation => GoalType::Documentation,
+            IntentCategory::Optimization => GoalType::Optimization,
+            other => GoalType::Custom(other.to_string()),
+        };
+
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            description: description.to_string(),
+            goal_type,
+            parameters: intent.parameters.clone(),
+            priority: 0,
+            deadline: None,
+        }
+    }
+}
+
 /// Types of goals
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -137,9 +164,28 @@ pub struct Condition {
     /// Condition type
     pub condition_type: ConditionType,
     /// Condition expression
+    #[serde(default)]
     pub expression: String,
 }
 
+impl Condition {
+    /// Key identifying the condition in the world state and probe caches
+    ///
+    /// Workspace conditions without an expression are keyed by their type
+    /// alone (e.g. `tests_pass`), so effects can target them by that name.
+    pub fn key(&self) -> String {
+        let kind = serde_json::to_value(&self.condition_type)
+            .ok()
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default();
+        if self.expression.is_empty() {
+            kind
+        } else {
+            format!("{}:{}", kind, self.expression)
+        }
+    }
+}
+
 /// Types of conditions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -152,10 +198,28 @@ pub enum ConditionType {
     ToolAvailable,
     /// State predicate
     StatePredicate,
+    /// Working tree has no uncommitted changes (optionally limited to a path)
+    GitClean,
+    /// Workspace compiles
+    Compiles,
+    /// Tests pass (optionally filtered by name)
+    TestsPass,
+    /// Symbol is defined in the workspace
+    SymbolExists,
     /// Custom condition
     Custom,
 }
 
+impl ConditionType {
+    /// Whether conditions of this type are meaningless without an expression
+    pub fn requires_expression(&self) -> bool {
+        !matches!(
+            self,
+            ConditionType::GitClean | ConditionType::Compiles | ConditionType::TestsPass
+        )
+    }
+}
+
 /// An effect of executing a task
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Effect {
@@ -164,6 +228,7 @@ pub struct Effect {
     /// What is affected
     pub target: String,
     /// New value/state
+    #[serde(default)]
     pub value: serde_json::Value,
 }
 
@@ -185,6 +250,7 @@ pub enum EffectType {
 
 /// Cost of executing a task or method
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct Cost {
     /// Time cost in milliseconds (estimated)
     pub time_ms: u64,
@@ -194,6 +260,8 @@ pub struct Cost {
     pub api_calls: u32,
     /// Monetary cost
     pub monetary: f64,
+    /// Estimated probability of failure (0.0 - 1.0)
+    pub risk: f64,
 }
 
 impl Cost {
@@ -204,6 +272,8 @@ impl Cost {
             tokens: self.tokens + other.tokens,
             api_calls: self.api_calls + other.api_calls,
             monetary: self.monetary + other.monetary,
+            // Either step failing fails the plan
+            risk: 1.0 - (1.0 - self.risk) * (1.0 - other.risk),
         }
     }
 
@@ -213,11 +283,14 @@ impl Cost {
         let token_weight = 0.4;
         let api_weight = 0.2;
         let monetary_weight = 0.1;
+        // A certain failure weighs about as much as half a minute of work
+        let risk_weight = 10.0;
 
         time_weight * self.time_ms as f64 / 1000.0
             + token_weight * self.tokens as f64 / 1000.0
             + api_weight * self.api_calls as f64
             + monetary_weight * self.monetary * 100.0
+            + risk_weight * self.risk
     }
 }
 
@@ -245,6 +318,36 @@ pub struct PlanMetadata {
     pub algorithm: String,
     /// Number of backtracks during planning
     pub backtracks: u32,
+    /// Method that introduced each primitive task, keyed by task id
+    #[serde(default)]
+    pub task_methods: HashMap<String, String>,
+    /// Weighted search cost, including method overhead and risk
+    #[serde(default)]
+    pub score: f64,
+}
+
+impl Plan {
+    /// Convert to the step list executed by the orchestrator
+    pub fn to_action_plan(&self, intent_category: intelligence::IntentCategory) -> ActionPlan {
+        let steps = self
+            .tasks
+            .iter()
+            .map(|task| PlanStep {
+                description: task.description.clone(),
+                tool: task.tool.clone(),
+                parameters: task.tool_args.clone(),
+                expected_output: format!("{} completed", task.name),
+                // Allow generous headroom over the estimate
+                timeout_seconds: (task.estimated_cost.time_ms.div_ceil(1000) * 3).max(60),
+            })
+            .collect();
+
+        ActionPlan {
+            steps,
+            intent_category,
+            estimated_tokens: self.total_cost.tokens,
+        }
+    }
 }
 
 /// World state for planning
@@ -276,10 +379,39 @@ impl WorldState {
             ConditionType::StatePredicate => {
                 self.predicates.get(&condition.expression).copied().unwrap_or(false)
             }
+            ConditionType::GitClean
+            | ConditionType::Compiles
+            | ConditionType::TestsPass
+            | ConditionType::SymbolExists => {
+                // Unprobed workspace conditions are not assumed to hold
+                self.predicates.get(&condition.key()).copied().unwrap_or(false)
+            }
             ConditionType::Custom => {
-                // Custom conditions always pass in basic implementation
-                true
+                // Custom conditions pass unless an evaluator says otherwise
+                self.predicates.get(&condition.key()).copied().unwrap_or(true)
+            }
+        }
+    }
+
+    /// Record the observed value of a condition
+    pub fn record(&mut self, condition: &Condition, holds: bool) {
+        let set = match condition.condition_type {
+            ConditionType::FileExists => &mut self.files,
+            ConditionType::DirectoryExists => &mut self.directories,
+            ConditionType::ToolAvailable => &mut self.available_tools,
+            ConditionType::StatePredicate => {
+                self.predicates.insert(condition.expression.clone(), holds);
+                return;
+            }
+            _ => {
+                self.predicates.insert(condition.key(), holds);
+                return;
             }
+        };
+        if holds {
+            set.insert(condition.expression.clone());
+        } else {
+            set.remove(&condition.expression);
         }
     }
 
@@ -308,6 +440,7 @@ impl WorldState {
 }
 
 /// HTN Planner
+#[derive(Clone)]
 pub struct HTNPlanner {
     /// Task library - known compound tasks and their methods
     task_library: HashMap<String, CompoundTask>,
@@ -315,6 +448,10 @@ pub struct HTNPlanner {
     max_depth: usize,
     /// Maximum backtracks allowed
     max_backtracks: u32,
+    /// Search budget per goal
+    budget: PlanningSettings,
+    /// Description keywords that select a library task, as (keyword, task) pairs
+    keywords: Vec<(String, String)>,
 }
 
 impl HTNPlanner {
@@ -324,6 +461,8 @@ impl HTNPlanner {
             task_library: HashMap::new(),
             max_depth: 10,
             max_backtracks: 100,
+            budget: PlanningSettings::default(),
+            keywords: Vec::new(),
         };
         planner.initialize_default_tasks();
         planner
@@ -331,12 +470,65 @@ impl HTNPlanner {
 
     /// Initialize default compound tasks
     fn initialize_default_tasks(&mut self) {
-        // Add default task decompositions here
-        // For example, "RefactorCode" might decompose into:
-        // 1. Analyze code
-        // 2. Identify refactoring opportunities
-        // 3. Apply refactoring
-        // 4. Verify changes
+        // Reviewing the working tree is fully covered by the git tool, so
+        // questions about repository state need no LLM round-trip. Other
+        // analysis is planned by the model.
+        self.register_task(CompoundTask {
+            id: "review_working_tree".to_string(),
+            name: "review_working_tree".to_string(),
+            description: "Inspect the working tree and pending changes".to_string(),
+            methods: vec![Method {
+                name: "review_working_tree".to_string(),
+                preconditions: vec![Condition {
+                    condition_type: ConditionType::ToolAvailable,
+                    expression: "git".to_string(),
+                }],
+                subtasks: vec![
+                    Task::Primitive(PrimitiveTask {
+                        id: "git_status".to_string(),
+                        name: "git_status".to_string(),
+                        description: "Show repository status".to_string(),
+                        tool: Some("git".to_string()),
+                        tool_args: serde_json::json!({"operation": "status", "path": "."}),
+                        preconditions: vec![],
+                        effects: vec![],
+                        estimated_cost: Cost {
+                            time_ms: 500,
+                            ..Default::default()
+                        },
+                    }),
+                    Task::Primitive(PrimitiveTask {
+                        id: "git_diff".to_string(),
+                        name: "git_diff".to_string(),
+                        description: "Show uncommitted changes".to_string(),
+                        tool: Some("git".to_string()),
+                        tool_args: serde_json::json!({"operation": "diff", "path": "."}),
+                        preconditions: vec![],
+                        effects: vec![],
+                        estimated_cost: Cost {
+                            time_ms: 500,
+                            ..Default::default()
+                        },
+                    }),
+                ],
+                ordering: OrderingConstraint::Sequential,
+                cost: Cost {
+                    time_ms: 1000,
+                    ..Default::default()
+                },
+            }],
+        });
+        let keywords: Vec<String> = ["git status", "working tree", "uncommitted", "pending changes"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        self.register_keywords("review_working_tree", &keywords);
+    }
+
+    /// Set the search budget per goal
+    pub fn with_budget(mut self, budget: PlanningSettings) -> Self {
+        self.budget = budget;
+        self
     }
 
     /// Register a compound task
@@ -344,40 +536,455 @@ impl HTNPlanner {
         self.task_library.insert(task.name.clone(), task);
     }
 
+    /// Route task descriptions containing any of the keywords to a library task
+    pub fn register_keywords(&mut self, task_name: &str, keywords: &[String]) {
+        for keyword in keywords {
+            self.keywords
+                .push((keyword.to_lowercase(), task_name.to_string()));
+        }
+    }
+
+    /// Look up a compound task by name
+    pub fn task(&self, name: &str) -> Option<&CompoundTask> {
+        self.task_library.get(name)
+    }
+
+    /// Preconditions reachable from the task achieving a goal
+    ///
+    /// These are the conditions worth probing before planning for the goal.
+    pub fn conditions_for(&self, goal: &Goal) -> Vec<Condition> {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<&CompoundTask> = self.task(Self::goal_task_name(goal)).into_iter().collect();
+
+        let mut push = |condition: &Condition, conditions: &mut Vec<Condition>| {
+            if seen.insert(condition.key()) {
+                conditions.push(condition.clone());
+            }
+        };
+
+        while let Some(compound) = stack.pop() {
+            if !visited.insert(compound.name.clone()) {
+                continue;
+            }
+            for method in &compound.methods {
+                for condition in &method.preconditions {
+                    push(condition, &mut conditions);
+                }
+                for subtask in &method.subtasks {
+                    match subtask {
+                        Task::Primitive(primitive) => {
+                            for condition in &primitive.preconditions {
+                                push(condition, &mut conditions);
+                            }
+                        }
+                        Task::Compound(nested) => {
+                            stack.push(nested);
+                        }
+                    }
+                }
+            }
+        }
+
+        conditions
+    }
+
+    /// Names of all known compound tasks
+    pub fn task_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.task_library.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Build the goal for a task, preferring library tasks whose keywords match
+    pub fn goal_for(&self, intent: &intelligence::Intent, description: &str) -> Goal {
+        let mut goal = Goal::from_intent(intent, description);
+        let lower = description.to_lowercase();
+
+        if let Some((keyword, task_name)) = self
+            .keywords
+            .iter()
+            .find(|(keyword, _)| lower.contains(keyword.as_str()))
+        {
+            debug!("Keyword '{}' selects library task {}", keyword, task_name);
+            goal.goal_type = GoalType::Custom(task_name.clone());
+        }
+
+        goal
+    }
+
+    /// Whether the task library has a decomposition for this goal
+    pub fn covers(&self, goal: &Goal) -> bool {
+        self.task_library.contains_key(Self::goal_task_name(goal))
+    }
+
     /// Create a plan to achieve a goal
     pub async fn plan(
         &self,
         goal: &Goal,
         initial_state: &WorldState,
         available_tools: &[String],
+    ) -> Result<Plan> {
+        self.plan_excluding(goal, initial_state, available_tools, &HashSet::new())
+            .await
+    }
+
+    /// Create a new plan after a failure, avoiding the method that failed
+    pub async fn replan(
+        &self,
+        goal: &Goal,
+        state: &WorldState,
+        available_tools: &[String],
+        failed_plan: &Plan,
+        analysis: &FailureAnalysis,
+        excluded_methods: &mut HashSet<String>,
+    ) -> Result<Plan> {
+        info!("Replanning after failure: {:?}", analysis.error_type);
+
+        let failed_task = analysis
+            .failed_step
+            .as_ref()
+            .and_then(|id| failed_plan.tasks.iter().find(|t| &t.id == id));
+
+        let mut tools: Vec<String> = available_tools.to_vec();
+        let mut state = state.clone();
+
+        if let Some(task) = failed_task {
+            if matches!(analysis.error_type, FailureType::ToolUnavailable) {
+                if let Some(tool) = &task.tool {
+                    debug!("Dropping unavailable tool from world state: {}", tool);
+                    tools.retain(|t| t != tool);
+                    state.available_tools.remove(tool);
+                }
+            }
+
+            if let Some(method) = failed_plan.metadata.task_methods.get(&task.id) {
+                debug!("Excluding failed method: {}", method);
+                excluded_methods.insert(method.clone());
+            }
+        }
+
+        self.plan_excluding(goal, &state, &tools, excluded_methods).await
+    }
+
+    /// Plan while skipping the given methods
+    async fn plan_excluding(
+        &self,
+        goal: &Goal,
+        initial_state: &WorldState,
+        available_tools: &[String],
+        excluded_methods: &HashSet<String>,
     ) -> Result<Plan> {
         info!("Planning for goal: {}", goal.description);
 
+        // Executing needs only the cheapest plan, so stop at the first one
+        let search = self
+            .search_blocking(goal, initial_state, available_tools, excluded_methods, 0)
+            .await;
+        let PlanSearch { plans, failure, expansions, .. } = search;
+
+        match plans.into_iter().next() {
+            Some(plan) => {
+                info!(
+                    "Plan created with {} tasks (cost {:.2}, {} nodes expanded)",
+                    plan.tasks.len(),
+                    plan.metadata.score,
+                    expansions
+                );
+                Ok(plan)
+            }
+            None => {
+                let e = failure.unwrap_or_else(|| common::Error::Internal("No plan found".to_string()));
+                warn!("Planning failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Plan a goal and report which method was chosen for every compound task
+    pub async fn explain(
+        &self,
+        goal: &Goal,
+        initial_state: &WorldState,
+        available_tools: &[String],
+    ) -> PlanExplanation {
+        let search = self
+            .search_blocking(goal, initial_state, available_tools, &HashSet::new(), self.budget.alternatives)
+            .await;
+        let mut plans = search.plans.into_iter();
+        let plan = plans.next();
+        let error = match (&plan, search.failure) {
+            (None, Some(e)) => Some(e.to_string()),
+            _ => None,
+        };
+
+        PlanExplanation {
+            goal: goal.description.clone(),
+            task: Self::goal_task_name(goal).to_string(),
+            covered: self.covers(goal),
+            decisions: search.decisions,
+            plan,
+            runner_ups: plans.collect(),
+            expansions: search.expansions,
+            budget_exhausted: search.budget_exhausted,
+            error,
+        }
+    }
+
+    /// Run [`search`](Self::search) on a blocking thread
+    ///
+    /// The search is CPU-bound for up to the planning time budget, which
+    /// would otherwise stall the async worker it runs on.
+    async fn search_blocking(
+        &self,
+        goal: &Goal,
+        initial_state: &WorldState,
+        available_tools: &[String],
+        excluded_methods: &HashSet<String>,
+        alternatives: usize,
+    ) -> PlanSearch {
+        let planner = self.clone();
+        let goal = goal.clone();
+        let state = initial_state.clone();
+        let tools = available_tools.to_vec();
+        let excluded = excluded_methods.clone();
+        let search = tokio::task::spawn_blocking(move || {
+            planner.search_with_alternatives(&goal, &state, &tools, &excluded, alternatives)
+        })
+        .await;
+        search.unwrap_or_else(|e| PlanSearch {
+            plans: Vec::new(),
+            decisions: Vec::new(),
+            expansions: 0,
+            dead_ends: 0,
+            budget_exhausted: false,
+            failure: Some(common::Error::Internal(format!("Planning task failed: {}", e))),
+        })
+    }
+
+    /// Best-first search over method alternatives
+    ///
+    /// Nodes are ordered by the cost of the choices made so far plus an
+    /// optimistic estimate of the remaining agenda (A*), so the first
+    /// complete plan is the cheapest. The search then continues for the
+    /// configured number of runner-up plans or until the budget runs out.
+    pub fn search(
+        &self,
+        goal: &Goal,
+        initial_state: &WorldState,
+        available_tools: &[String],
+        excluded_methods: &HashSet<String>,
+    ) -> PlanSearch {
+        let alternatives = self.budget.alternatives;
+        self.search_with_alternatives(goal, initial_state, available_tools, excluded_methods, alternatives)
+    }
+
+    fn search_with_alternatives(
+        &self,
+        goal: &Goal,
+        initial_state: &WorldState,
+        available_tools: &[String],
+        excluded_methods: &HashSet<String>,
+        alternatives: usize,
+    ) -> PlanSearch {
+        let started = std::time::Instant::now();
         let mut state = initial_state.clone();
         for tool in available_tools {
             state.available_tools.insert(tool.clone());
         }
 
-        let root_task = self.goal_to_task(goal);
-        let mut plan_builder = PlanBuilder::new(goal.id.clone());
+        let root = AgendaItem {
+            task: self.goal_to_task(goal),
+            method: None,
+            depth: 0,
+        };
+        let estimate = self.estimate_task(&root.task, excluded_methods);
+        let mut frontier = BinaryHeap::new();
+        let mut seq = 0usize;
+        frontier.push(QueuedNode {
+            priority: estimate,
+            seq,
+            node: SearchNode {
+                agenda: vec![root],
+                state,
+                builder: PlanBuilder::new(goal.id.clone()),
+                cost: 0.0,
+                estimate,
+            },
+        });
+
+        let mut search = PlanSearch {
+            plans: Vec::new(),
+            decisions: Vec::new(),
+            expansions: 0,
+            dead_ends: 0,
+            budget_exhausted: false,
+            failure: None,
+        };
 
-        match self.decompose_task(&root_task, &state, &mut plan_builder, 0).await {
-            Ok(()) => {
-                let plan = plan_builder.build();
-                info!("Plan created with {} tasks", plan.tasks.len());
-                Ok(plan)
+        while let Some(QueuedNode { mut node, .. }) = frontier.pop() {
+            if search.plans.len() > alternatives {
+                break;
             }
-            Err(e) => {
-                warn!("Planning failed: {}", e);
-                Err(e)
+            if search.expansions >= self.budget.max_expansions
+                || started.elapsed().as_millis() as u64 >= self.budget.max_time_ms
+                || search.dead_ends >= self.max_backtracks
+            {
+                debug!("Planning budget exhausted after {} expansions", search.expansions);
+                search.budget_exhausted = true;
+                break;
             }
+            search.expansions += 1;
+
+            let Some(item) = node.agenda.pop() else {
+                // Empty agenda: every task has been decomposed
+                if search.plans.is_empty() {
+                    search.decisions = node.builder.decisions.clone();
+                }
+                node.builder.backtracks = search.dead_ends;
+                let mut plan = node.builder.build();
+                plan.metadata.score = node.cost;
+                search.plans.push(plan);
+                continue;
+            };
+
+            match self.expand(node, item, excluded_methods) {
+                Expansion::Children(children) => {
+                    for child in children {
+                        seq += 1;
+                        frontier.push(QueuedNode {
+                            priority: child.cost + child.estimate,
+                            seq,
+                            node: child,
+                        });
+                    }
+                }
+                Expansion::DeadEnd { error, decisions } => {
+                    search.dead_ends += 1;
+                    // Explain the dead end that got furthest when nothing succeeds
+                    if search.plans.is_empty() && decisions.len() >= search.decisions.len() {
+                        search.decisions = decisions;
+                    }
+                    search.failure = Some(error);
+                }
+            }
+        }
+
+        if search.plans.is_empty() && search.budget_exhausted {
+            search.failure = Some(common::Error::Timeout(format!(
+                "Planning budget exhausted after {} expansions",
+                search.expansions
+            )));
         }
+
+        search
     }
 
-    /// Convert a goal to a task
-    fn goal_to_task(&self, goal: &Goal) -> Task {
-        // Map goal types to compound tasks
-        let task_name = match &goal.goal_type {
+    /// Expand the next agenda item of a search node
+    fn expand(
+        &self,
+        mut node: SearchNode,
+        item: AgendaItem,
+        excluded_methods: &HashSet<String>,
+    ) -> Expansion {
+        node.estimate = (node.estimate - self.estimate_task(&item.task, excluded_methods)).max(0.0);
+
+        if item.depth > self.max_depth {
+            return Expansion::DeadEnd {
+                error: common::Error::Internal("Maximum planning depth exceeded".to_string()),
+                decisions: node.builder.decisions,
+            };
+        }
+
+        match item.task {
+            Task::Primitive(primitive) => {
+                if let Some(condition) = primitive.preconditions.iter().find(|c| !node.state.satisfies(c)) {
+                    return Expansion::DeadEnd {
+                        error: common::Error::Validation(format!(
+                            "Precondition not satisfied: {:?}",
+                            condition
+                        )),
+                        decisions: node.builder.decisions,
+                    };
+                }
+                for effect in &primitive.effects {
+                    node.state.apply_effect(effect);
+                }
+                node.cost += primitive.estimated_cost.weighted_score();
+                node.builder.add_task(primitive, item.method.as_deref());
+                Expansion::Children(vec![node])
+            }
+            Task::Compound(compound) => {
+                let candidates = self.evaluate_methods(&compound, &node.state, excluded_methods);
+                let applicable: Vec<usize> = (0..candidates.len())
+                    .filter(|&i| candidates[i].is_applicable())
+                    .collect();
+
+                if applicable.is_empty() {
+                    let mut decisions = node.builder.decisions;
+                    decisions.push(MethodDecision {
+                        task: compound.name.clone(),
+                        depth: item.depth,
+                        candidates,
+                        chosen: None,
+                    });
+                    return Expansion::DeadEnd {
+                        error: common::Error::Internal(format!(
+                            "No applicable method for task: {}",
+                            compound.name
+                        )),
+                        decisions,
+                    };
+                }
+
+                let children = applicable
+                    .into_iter()
+                    .map(|i| {
+                        let method = &compound.methods[i];
+                        let mut child = node.clone();
+                        child.builder.decisions.push(MethodDecision {
+                            task: compound.name.clone(),
+                            depth: item.depth,
+                            candidates: candidates.clone(),
+                            chosen: Some(method.name.clone()),
+                        });
+                        // A method's own cost is overhead on top of its subtasks
+                        child.cost += candidates[i].cost;
+                        for subtask in method.subtasks.iter().rev() {
+                            child.estimate += self.estimate_task(subtask, excluded_methods);
+                            child.agenda.push(AgendaItem {
+                                task: subtask.clone(),
+                                method: Some(method.name.clone()),
+                                depth: item.depth + 1,
+                            });
+                        }
+                        child
+                    })
+                    .collect();
+
+                Expansion::Children(children)
+            }
+        }
+    }
+
+    /// Optimistic cost of a task: its own cost, or its cheapest method's
+    fn estimate_task(&self, task: &Task, excluded_methods: &HashSet<String>) -> f64 {
+        match task {
+            Task::Primitive(primitive) => primitive.estimated_cost.weighted_score(),
+            Task::Compound(compound) => compound
+                .methods
+                .iter()
+                .filter(|m| !excluded_methods.contains(&m.name))
+                .map(|m| m.cost.weighted_score())
+                .reduce(f64::min)
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// Name of the compound task a goal maps to
+    fn goal_task_name(goal: &Goal) -> &str {
+        match &goal.goal_type {
             GoalType::CodeGeneration => "generate_code",
             GoalType::CodeModification => "modify_code",
             GoalType::Analysis => "analyze_code",
@@ -387,7 +994,13 @@ impl HTNPlanner {
             GoalType::BugFix => "fix_bug",
             GoalType::Refactoring => "refactor_code",
             GoalType::Custom(name) => name.as_str(),
-        };
+        }
+    }
+
+    /// Convert a goal to a task
+    fn goal_to_task(&self, goal: &Goal) -> Task {
+        // Map goal types to compound tasks
+        let task_name = Self::goal_task_name(goal);
 
         if let Some(compound) = self.task_library.get(task_name) {
             Task::Compound(compound.clone())
@@ -406,70 +1019,247 @@ impl HTNPlanner {
         }
     }
 
-    /// Decompose a task into primitive tasks
-    async fn decompose_task(
+    /// Score every method of a compound task against the current state
+    fn evaluate_methods(
         &self,
-        task: &Task,
+        task: &CompoundTask,
         state: &WorldState,
-        builder: &mut PlanBuilder,
-        depth: usize,
-    ) -> Result<()> {
-        if depth > self.max_depth {
-            return Err(common::Error::Internal(
-                "Maximum planning depth exceeded".to_string()
-            ));
+        excluded_methods: &HashSet<String>,
+    ) -> Vec<MethodCandidate> {
+        task.methods
+            .iter()
+            .map(|method| MethodCandidate {
+                method: method.name.clone(),
+                cost: method.cost.weighted_score(),
+                unmet_preconditions: method
+                    .preconditions
+                    .iter()
+                    .filter(|c| !state.satisfies(c))
+                    .cloned()
+                    .collect(),
+                excluded: excluded_methods.contains(&method.name),
+            })
+            .collect()
+    }
+}
+
+/// Pending task in a search node's agenda
+#[derive(Clone)]
+struct AgendaItem {
+    task: Task,
+    /// Method that introduced the task
+    method: Option<String>,
+    depth: usize,
+}
+
+/// Partial plan explored during search
+#[derive(Clone)]
+struct SearchNode {
+    /// Tasks still to decompose, next task last
+    agenda: Vec<AgendaItem>,
+    /// World state after the tasks planned so far
+    state: WorldState,
+    builder: PlanBuilder,
+    /// Weighted cost of the choices made so far
+    cost: f64,
+    /// Optimistic cost of the remaining agenda
+    estimate: f64,
+}
+
+/// Outcome of expanding a search node
+enum Expansion {
+    Children(Vec<SearchNode>),
+    DeadEnd {
+        error: common::Error,
+        decisions: Vec<MethodDecision>,
+    },
+}
+
+/// Frontier entry, ordered so the cheapest node pops first
+struct QueuedNode {
+    priority: f64,
+    seq: usize,
+    node: SearchNode,
+}
+
+impl PartialEq for QueuedNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for QueuedNode {}
+
+impl PartialOrd for QueuedNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedNode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // BinaryHeap is a max-heap: invert so lower priority (and earlier nodes on ties) pop first
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Result of searching for plans
+#[derive(Debug)]
+pub struct PlanSearch {
+    /// Valid plans found, cheapest first
+    pub plans: Vec<Plan>,
+    /// Method decisions behind the cheapest plan, or the furthest dead end
+    pub decisions: Vec<MethodDecision>,
+    /// Search nodes expanded
+    pub expansions: usize,
+    /// Branches abandoned
+    pub dead_ends: u32,
+    /// Whether the budget ran out before the search finished
+    pub budget_exhausted: bool,
+    /// Why the last branch failed
+    pub failure: Option<common::Error>,
+}
+
+impl PlanSearch {
+    /// Cheapest valid plan
+    pub fn best(&self) -> Option<&Plan> {
+        self.plans.first()
+    }
+
+    /// Valid plans other than the cheapest
+    pub fn runner_ups(&self) -> &[Plan] {
+        self.plans.get(1..).unwrap_or_default()
+    }
+}
+
+/// How a method fared during method selection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodCandidate {
+    pub method: String,
+    pub cost: f64,
+    pub unmet_preconditions: Vec<Condition>,
+    pub excluded: bool,
+}
+
+impl MethodCandidate {
+    /// Whether the method could be chosen
+    pub fn is_applicable(&self) -> bool {
+        !self.excluded && self.unmet_preconditions.is_empty()
+    }
+}
+
+/// Method selection for one compound task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodDecision {
+    pub task: String,
+    pub depth: usize,
+    pub candidates: Vec<MethodCandidate>,
+    pub chosen: Option<String>,
+}
+
+/// Explanation of how a goal was decomposed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanExplanation {
+    pub goal: String,
+    pub task: String,
+    pub covered: bool,
+    pub decisions: Vec<MethodDecision>,
+    pub plan: Option<Plan>,
+    /// Next cheapest plans, cheapest first
+    #[serde(default)]
+    pub runner_ups: Vec<Plan>,
+    #[serde(default)]
+    pub expansions: usize,
+    #[serde(default)]
+    pub budget_exhausted: bool,
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for PlanExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Goal: {}", self.goal)?;
+        if !self.covered {
+            writeln!(f, "No method library covers '{}'; the LLM planner would be used.", self.task)?;
         }
 
-        match task {
-            Task::Primitive(primitive) => {
-                // Check preconditions
-                for condition in &primitive.preconditions {
-                    if !state.satisfies(condition) {
-                        return Err(common::Error::Validation(
-                            format!("Precondition not satisfied: {:?}", condition)
-                        ));
-                    }
-                }
-                builder.add_task(primitive.clone());
-                Ok(())
+        for decision in &self.decisions {
+            let indent = "  ".repeat(decision.depth);
+            writeln!(f, "{}{}:", indent, decision.task)?;
+            for candidate in &decision.candidates {
+                let marker = if decision.chosen.as_deref() == Some(candidate.method.as_str()) {
+                    "✓"
+                } else {
+                    " "
+                };
+                let reason = if candidate.excluded {
+                    "excluded after an earlier failure".to_string()
+                } else if !candidate.unmet_preconditions.is_empty() {
+                    let unmet: Vec<String> = candidate
+                        .unmet_preconditions
+                        .iter()
+                        .map(|c| format!("{:?} {}", c.condition_type, c.expression))
+                        .collect();
+                    format!("unmet: {}", unmet.join(", "))
+                } else if marker == "✓" {
+                    "chosen".to_string()
+                } else {
+                    "applicable alternative".to_string()
+                };
+                writeln!(
+                    f,
+                    "{}  {} {} (cost {:.2}) - {}",
+                    indent, marker, candidate.method, candidate.cost, reason
+                )?;
             }
-            Task::Compound(compound) => {
-                // Find applicable method
-                let method = self.select_method(compound, state).ok_or_else(|| {
-                    common::Error::Internal(
-                        format!("No applicable method for task: {}", compound.name)
-                    )
-                })?;
-
-                // Decompose subtasks
-                for subtask in &method.subtasks {
-                    Box::pin(self.decompose_task(subtask, state, builder, depth + 1)).await?;
-                }
+        }
 
-                Ok(())
+        match (&self.plan, &self.error) {
+            (Some(plan), _) => {
+                writeln!(f, "Plan ({} steps, cost {:.2}):", plan.tasks.len(), plan.metadata.score)?;
+                for (i, task) in plan.tasks.iter().enumerate() {
+                    let tool = task.tool.as_deref().unwrap_or("-");
+                    writeln!(f, "  {}. {} [{}] {}", i + 1, task.name, tool, task.description)?;
+                }
             }
+            (None, Some(error)) => writeln!(f, "Planning failed: {}", error)?,
+            (None, None) => {}
         }
-    }
-
-    /// Select the best applicable method for a compound task
-    fn select_method(&self, task: &CompoundTask, state: &WorldState) -> Option<Method> {
-        let mut best_method: Option<Method> = None;
-        let mut best_cost = f64::MAX;
-
-        for method in &task.methods {
-            // Check if preconditions are satisfied
-            let applicable = method.preconditions.iter().all(|c| state.satisfies(c));
 
-            if applicable {
-                let cost = method.cost.weighted_score();
-                if cost < best_cost {
-                    best_cost = cost;
-                    best_method = Some(method.clone());
+        if !self.runner_ups.is_empty() {
+            writeln!(f, "Runner-up plans:")?;
+            for (i, plan) in self.runner_ups.iter().enumerate() {
+                let mut methods: Vec<&str> = Vec::new();
+                for task in &plan.tasks {
+                    if let Some(method) = plan.metadata.task_methods.get(&task.id) {
+                        if !methods.contains(&method.as_str()) {
+                            methods.push(method);
+                        }
+                    }
                 }
+                writeln!(
+                    f,
+                    "  {}. cost {:.2}, {} steps via {}",
+                    i + 1,
+                    plan.metadata.score,
+                    plan.tasks.len(),
+                    methods.join(", ")
+                )?;
             }
         }
 
-        best_method
+        writeln!(f, "Search expanded {} node(s)", self.expansions)?;
+        if self.budget_exhausted {
+            if self.plan.is_some() {
+                writeln!(f, "Planning budget exhausted; runner-ups may be incomplete")?;
+            } else {
+                writeln!(f, "Planning budget exhausted")?;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -480,11 +1270,14 @@ impl Default for HTNPlanner {
 }
 
 /// Builder for constructing plans
+#[derive(Clone)]
 struct PlanBuilder {
     goal_id: String,
     tasks: Vec<PrimitiveTask>,
     total_cost: Cost,
     backtracks: u32,
+    task_methods: HashMap<String, String>,
+    decisions: Vec<MethodDecision>,
 }
 
 impl PlanBuilder {
@@ -494,11 +1287,16 @@ impl PlanBuilder {
             tasks: vec![],
             total_cost: Cost::default(),
             backtracks: 0,
+            task_methods: HashMap::new(),
+            decisions: Vec::new(),
         }
     }
 
-    fn add_task(&mut self, task: PrimitiveTask) {
+    fn add_task(&mut self, task: PrimitiveTask, method: Option<&str>) {
         self.total_cost = self.total_cost.add(&task.estimated_cost);
+        if let Some(method) = method {
+            self.task_methods.insert(task.id.clone(), method.to_string());
+        }
         self.tasks.push(task);
     }
 
@@ -512,12 +1310,15 @@ impl PlanBuilder {
                 goal_id: self.goal_id,
                 algorithm: "htn".to_string(),
                 backtracks: self.backtracks,
+                task_methods: self.task_methods,
+                score: 0.0,
             },
         }
     }
 }
 
 /// Plan executor with replanning capabilities
+#[derive(Clone)]
 pub struct PlanExecutor {
     planner: HTNPlanner,
     max_retries: u32,
@@ -532,111 +1333,217 @@ impl PlanExecutor {
         }
     }
 
+    /// Set the planner used for planning and replanning
+    pub fn with_planner(mut self, planner: HTNPlanner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    /// Set the maximum number of replanning attempts
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Planner backing this executor
+    pub fn planner(&self) -> &HTNPlanner {
+        &self.planner
+    }
+
     /// Execute a plan with replanning on failure
+    ///
+    /// Each attempt runs through the orchestrator. When a step fails, the
+    /// failure is analyzed and the goal is re-decomposed from the updated
+    /// world state without the method that failed.
+    #[allow(clippy::too_many_arguments)]
     pub async fn execute_with_replanning(
         &self,
+        goal: &Goal,
         plan: &Plan,
         orchestrator: &Orchestrator,
+        checkpoint: &TaskCheckpoint,
+        context: &mut intelligence::Context,
         initial_state: &WorldState,
+        available_tools: &[String],
     ) -> Result<ExecutionResult> {
         info!("Executing plan with replanning: {}", plan.id);
 
         let mut current_plan = plan.clone();
+        let mut current_checkpoint = checkpoint.clone();
         let mut state = initial_state.clone();
         let mut execution_history = vec![];
+        let mut excluded_methods = HashSet::new();
         let mut retries = 0;
+        // Leading steps of the current plan already in the history
+        let mut recorded = 0;
 
         loop {
-            match self.execute_plan(&current_plan, orchestrator, &mut state).await {
+            let action_plan = current_plan.to_action_plan(context_category(&current_checkpoint));
+            match orchestrator
+                .execute_plan_with_checkpoint(&action_plan, &current_checkpoint, context)
+                .await
+            {
                 Ok(result) => {
                     info!("Plan executed successfully");
+                    for task in current_plan.tasks.iter().skip(recorded) {
+                        self.record_step(&mut execution_history, task, StepResult::Success);
+                        for effect in &task.effects {
+                            state.apply_effect(effect);
+                        }
+                        orchestrator.observe_effects(&current_checkpoint.task.id, &task.effects);
+                    }
                     return Ok(ExecutionResult {
-                        success: true,
+                        success: result.success,
                         final_state: state,
                         execution_history,
                         retries,
+                        output: Some(result),
+                        error: None,
                     });
                 }
                 Err(e) => {
                     warn!("Plan execution failed: {}", e);
 
+                    // Steps before the failing one completed; keep their effects
+                    let failed_index = orchestrator.checkpoint_step(&current_checkpoint.id).await;
+                    for (i, task) in current_plan.tasks.iter().enumerate().skip(recorded) {
+                        if i < failed_index {
+                            self.record_step(&mut execution_history, task, StepResult::Success);
+                            for effect in &task.effects {
+                                state.apply_effect(effect);
+                            }
+                            orchestrator.observe_effects(&current_checkpoint.task.id, &task.effects);
+                        } else if i == failed_index {
+                            self.record_step(&mut execution_history, task, StepResult::Failure(e.to_string()));
+                        }
+                    }
+
                     if retries >= self.max_retries {
                         return Ok(ExecutionResult {
                             success: false,
                             final_state: state,
                             execution_history,
                             retries,
+                            output: None,
+                            error: Some(e.to_string()),
                         });
                     }
 
+                    // Re-probe what the executed steps may have changed
+                    orchestrator
+                        .ground_world_state(&current_checkpoint.task, goal, &mut state)
+                        .await;
+
                     // Analyze failure and replan
                     let failure_analysis = self.analyze_failure(&e, &execution_history).await?;
-                    current_plan = self.replan(&current_plan, &failure_analysis, &state).await?;
+                    let replanned = match self
+                        .planner
+                        .replan(
+                            goal,
+                            &state,
+                            available_tools,
+                            &current_plan,
+                            &failure_analysis,
+                            &mut excluded_methods,
+                        )
+                        .await
+                    {
+                        Ok(plan) => plan,
+                        Err(replan_err) => {
+                            warn!("No alternative plan available: {}", replan_err);
+                            return Ok(ExecutionResult {
+                                success: false,
+                                final_state: state,
+                                execution_history,
+                                retries,
+                                output: None,
+                                error: Some(e.to_string()),
+                            });
+                        }
+                    };
                     retries += 1;
 
+                    // Keep the steps that completed; only the rest is replanned
+                    let done = current_plan.tasks[..failed_index.min(current_plan.tasks.len())].to_vec();
+                    let mut remaining = replanned;
+                    remaining.tasks.retain(|t| !done.iter().any(|d| d.id == t.id));
+                    recorded = done.len();
+                    remaining.tasks.splice(0..0, done);
+                    current_plan = remaining;
+
+                    current_checkpoint = orchestrator
+                        .restart_checkpoint_with_plan(
+                            &current_checkpoint,
+                            current_plan.to_action_plan(context_category(&current_checkpoint)),
+                            current_plan.clone(),
+                        )
+                        .await?;
+
                     info!("Replanning attempt {}/{}", retries, self.max_retries);
                 }
             }
         }
     }
 
-    /// Execute a plan
-    async fn execute_plan(
-        &self,
-        plan: &Plan,
-        _orchestrator: &Orchestrator,
-        state: &mut WorldState,
-    ) -> Result<()> {
-        for task in &plan.tasks {
-            debug!("Executing task: {}", task.name);
-
-            // In full implementation, this would:
-            // 1. Execute the task using the orchestrator
-            // 2. Handle the result
-            // 3. Update the world state with effects
-
-            // Apply effects
-            for effect in &task.effects {
-                state.apply_effect(effect);
-            }
-        }
-
-        Ok(())
+    fn record_step(&self, history: &mut Vec<ExecutionStep>, task: &PrimitiveTask, result: StepResult) {
+        history.push(ExecutionStep {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            result,
+            timestamp: chrono::Utc::now(),
+        });
     }
 
     /// Analyze a failure to determine replanning strategy
-    async fn analyze_failure(
+    pub async fn analyze_failure(
         &self,
-        _error: &common::Error,
+        error: &common::Error,
         history: &[ExecutionStep],
     ) -> Result<FailureAnalysis> {
-        // In full implementation, this would use an LLM to analyze
-        // what went wrong and how to fix it
+        let message = error.to_string();
+        let error_type = match error {
+            common::Error::Timeout(_) => FailureType::Timeout,
+            common::Error::NotFound(_) => FailureType::ToolUnavailable,
+            _ if message.contains("Tool not found") || message.contains("Tools not available") => {
+                FailureType::ToolUnavailable
+            }
+            _ if message.contains("timed out") => FailureType::Timeout,
+            _ if message.contains("Precondition not satisfied") => FailureType::PreconditionFailure,
+            common::Error::Execution(_) | common::Error::PermissionDenied(_) => {
+                FailureType::ExecutionError
+            }
+            _ => FailureType::Unknown,
+        };
+
+        let failed_step = history
+            .iter()
+            .rev()
+            .find(|s| matches!(s.result, StepResult::Failure(_)))
+            .map(|s| s.task_id.clone());
+
+        let suggested_fix = match error_type {
+            FailureType::ToolUnavailable => "Choose a method that does not need the missing tool",
+            FailureType::Timeout => "Choose a cheaper method or split the work",
+            FailureType::PreconditionFailure => "Choose a method whose preconditions hold",
+            FailureType::ExecutionError | FailureType::Unknown => "Try an alternative method",
+        }
+        .to_string();
 
         Ok(FailureAnalysis {
-            error_type: FailureType::ExecutionError,
-            failed_step: history.last().map(|s| s.task_id.clone()),
-            suggested_fix: "Retry with modified parameters".to_string(),
+            error_type,
+            failed_step,
+            suggested_fix,
         })
     }
+}
 
-    /// Create a new plan based on failure analysis
-    async fn replan(
-        &self,
-        original_plan: &Plan,
-        analysis: &FailureAnalysis,
-        _current_state: &WorldState,
-    ) -> Result<Plan> {
-        info!("Replanning after failure: {:?}", analysis.error_type);
-
-        // In full implementation, this would:
-        // 1. Modify the original plan based on failure analysis
-        // 2. Or create a completely new plan
-        // 3. Ensure the new plan addresses the failure cause
-
-        // For now, return the original plan (placeholder)
-        Ok(original_plan.clone())
-    }
+/// Intent category recorded for the task behind a checkpoint
+fn context_category(checkpoint: &TaskCheckpoint) -> intelligence::IntentCategory {
+    checkpoint
+        .intent
+        .as_ref()
+        .map(|i| i.category)
+        .unwrap_or(checkpoint.task.intent.category)
 }
 
 impl Default for PlanExecutor {
@@ -656,6 +1563,10 @@ pub struct ExecutionResult {
     pub execution_history: Vec<ExecutionStep>,
     /// Number of replanning retries
     pub retries: u32,
+    /// Orchestrator output of the final successful attempt
+    pub output: Option<crate::orchestrator::ExecutionResult>,
+    /// Last error when execution did not succeed
+    pub error: Option<String>,
 }
 
 /// A step in execution history
@@ -739,6 +1650,7 @@ mod tests {
             tokens: 500,
             api_calls: 2,
             monetary: 0.01,
+            risk: 0.0,
         };
 
         let cost2 = Cost {
@@ -746,6 +1658,7 @@ mod tests {
             tokens: 1000,
             api_calls: 4,
             monetary: 0.02,
+            risk: 0.0,
         };
 
         let total = cost1.add(&cost2);
@@ -771,11 +1684,219 @@ mod tests {
                 tokens: 500,
                 api_calls: 1,
                 monetary: 0.01,
+                risk: 0.0,
             },
-        });
+        }, None);
 
         let plan = builder.build();
         assert_eq!(plan.tasks.len(), 1);
         assert_eq!(plan.total_cost.time_ms, 1000);
     }
+
+    fn tool_task(id: &str, tool: &str) -> Task {
+        Task::Primitive(PrimitiveTask {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: format!("Run {}", tool),
+            tool: Some(tool.to_string()),
+            tool_args: serde_json::json!({}),
+            preconditions: vec![],
+            effects: vec![],
+            estimated_cost: Cost::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_replan_avoids_failed_method() {
+        let mut planner = HTNPlanner::new();
+        planner.register_task(CompoundTask {
+            id: "run_tests".to_string(),
+            name: "run_tests".to_string(),
+            description: "Run the test suite".to_string(),
+            methods: vec![
+                Method {
+                    name: "fast".to_string(),
+                    preconditions: vec![],
+                    subtasks: vec![tool_task("nextest", "nextest")],
+                    ordering: OrderingConstraint::Sequential,
+                    cost: Cost { time_ms: 100, ..Default::default() },
+                },
+                Method {
+                    name: "slow".to_string(),
+                    preconditions: vec![],
+                    subtasks: vec![tool_task("cargo_test", "cargo")],
+                    ordering: OrderingConstraint::Sequential,
+                    cost: Cost { time_ms: 10_000, ..Default::default() },
+                },
+            ],
+        });
+
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Testing,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: "run the tests".to_string(),
+        };
+        let goal = Goal::from_intent(&intent, "run the tests");
+        assert!(planner.covers(&goal));
+
+        let state = WorldState::default();
+        let plan = planner.plan(&goal, &state, &[]).await.unwrap();
+        assert_eq!(plan.tasks[0].id, "nextest");
+        assert_eq!(plan.metadata.task_methods.get("nextest").map(String::as_str), Some("fast"));
+
+        let executor = PlanExecutor::new();
+        let history = vec![ExecutionStep {
+            task_id: "nextest".to_string(),
+            task_name: "nextest".to_string(),
+            result: StepResult::Failure("Tool not found: nextest".to_string()),
+            timestamp: chrono::Utc::now(),
+        }];
+        let error = common::Error::Execution("Step 1 failed: Not found: Tool not found: nextest".to_string());
+        let analysis = executor.analyze_failure(&error, &history).await.unwrap();
+        assert!(matches!(analysis.error_type, FailureType::ToolUnavailable));
+
+        let mut excluded = HashSet::new();
+        let replanned = planner
+            .replan(&goal, &state, &[], &plan, &analysis, &mut excluded)
+            .await
+            .unwrap();
+        assert_eq!(replanned.tasks[0].id, "cargo_test");
+        assert!(excluded.contains("fast"));
+    }
+
+    fn costed_task(id: &str, time_ms: u64, risk: f64) -> Task {
+        Task::Primitive(PrimitiveTask {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: format!("Run {}", id),
+            tool: None,
+            tool_args: serde_json::json!({}),
+            preconditions: vec![],
+            effects: vec![],
+            estimated_cost: Cost { time_ms, risk, ..Default::default() },
+        })
+    }
+
+    fn ship_planner() -> HTNPlanner {
+        let mut planner = HTNPlanner::new();
+        planner.register_task(CompoundTask {
+            id: "ship".to_string(),
+            name: "ship".to_string(),
+            description: "Ship the change".to_string(),
+            methods: vec![
+                // Cheapest method up front, but its step is slow and risky
+                Method {
+                    name: "yolo".to_string(),
+                    preconditions: vec![],
+                    subtasks: vec![costed_task("push", 60_000, 0.5)],
+                    ordering: OrderingConstraint::Sequential,
+                    cost: Cost::default(),
+                },
+                Method {
+                    name: "careful".to_string(),
+                    preconditions: vec![],
+                    subtasks: vec![costed_task("test", 2_000, 0.0), costed_task("merge", 1_000, 0.0)],
+                    ordering: OrderingConstraint::Sequential,
+                    cost: Cost { time_ms: 1_000, ..Default::default() },
+                },
+            ],
+        });
+        planner
+    }
+
+    fn ship_goal() -> Goal {
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Unknown,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: String::new(),
+        };
+        let mut goal = Goal::from_intent(&intent, "ship it");
+        goal.goal_type = GoalType::Custom("ship".to_string());
+        goal
+    }
+
+    #[tokio::test]
+    async fn test_search_prefers_cheapest_plan_over_cheapest_method() {
+        let planner = ship_planner();
+        let goal = ship_goal();
+
+        let search = planner.search(&goal, &WorldState::default(), &[], &HashSet::new());
+        let best = search.best().unwrap();
+        let ids: Vec<&str> = best.tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["test", "merge"]);
+        assert!(best.metadata.score < search.runner_ups()[0].metadata.score);
+        assert_eq!(search.runner_ups()[0].tasks[0].id, "push");
+
+        let explanation = planner.explain(&goal, &WorldState::default(), &[]).await;
+        assert_eq!(explanation.runner_ups.len(), 1);
+        assert!(explanation.to_string().contains("Runner-up plans:"));
+    }
+
+    #[test]
+    fn test_risk_combines_as_probability_of_any_failure() {
+        let risky = Cost { risk: 0.5, ..Default::default() };
+        let total = risky.add(&risky).add(&risky);
+        assert!((total.risk - 0.875).abs() < 1e-9);
+        assert_eq!(Cost::default().add(&risky).risk, 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_planning_stops_at_the_cheapest_plan() {
+        let planner = ship_planner();
+        let goal = ship_goal();
+
+        let full = planner.search(&goal, &WorldState::default(), &[], &HashSet::new());
+        let plan = planner.plan(&goal, &WorldState::default(), &[]).await.unwrap();
+        assert_eq!(plan.tasks[0].id, "test");
+        let first = planner
+            .search_blocking(&goal, &WorldState::default(), &[], &HashSet::new(), 0)
+            .await;
+        assert_eq!(first.plans.len(), 1);
+        assert!(first.expansions < full.expansions);
+    }
+
+    #[tokio::test]
+    async fn test_search_respects_budget() {
+        let planner = ship_planner().with_budget(PlanningSettings {
+            max_expansions: 1,
+            ..Default::default()
+        });
+
+        let search = planner.search(&ship_goal(), &WorldState::default(), &[], &HashSet::new());
+        assert!(search.budget_exhausted);
+        assert!(search.best().is_none());
+        assert!(matches!(search.failure, Some(common::Error::Timeout(_))));
+    }
+
+    #[test]
+    fn test_uncovered_goal() {
+        let planner = HTNPlanner::new();
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Documentation,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: String::new(),
+        };
+        assert!(!planner.covers(&Goal::from_intent(&intent, "write docs")));
+    }
+
+    #[test]
+    fn test_only_repository_state_questions_use_the_git_review() {
+        let planner = HTNPlanner::new();
+        let intent = intelligence::Intent {
+            category: intelligence::IntentCategory::Analysis,
+            confidence: 1.0,
+            parameters: Default::default(),
+            raw_input: String::new(),
+        };
+
+        let goal = planner.goal_for(&intent, "Find the memory leak in the parser");
+        assert!(!planner.covers(&goal));
+
+        let goal = planner.goal_for(&intent, "Summarize the uncommitted changes");
+        assert!(matches!(&goal.goal_type, GoalType::Custom(name) if name == "review_working_tree"));
+        assert!(planner.covers(&goal));
+    }
 }
@@ -0,0 +1,160 @@
+//! Observe-think-act support for plan execution.
+//!
+//! After every action the model is shown the observation together with the
+//! remaining plan and decides how execution should proceed.
+
+use common::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::orchestrator::PlanStep;
+
+/// Decision taken after observing the outcome of an action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ReactDecision {
+    /// Move on to the next planned step
+    Continue,
+    /// Run the same step again
+    Retry,
+    /// Replace the remaining steps with a new sequence
+    Revise { steps: Vec<PlanStep> },
+    /// The task is done; stop executing
+    Finish { summary: String },
+    /// Give up on the task
+    Abort { reason: String },
+}
+
+impl ReactDecision {
+    /// Short label used in logs and the trajectory
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReactDecision::Continue => "continue",
+            ReactDecision::Retry => "retry",
+            ReactDecision::Revise { .. } => "revise",
+            ReactDecision::Finish { .. } => "finish",
+            ReactDecision::Abort { .. } => "abort",
+        }
+    }
+}
+
+/// Model response to an observation
+#[derive(Debug, Clone)]
+pub struct Thought {
+    pub reasoning: Option<String>,
+    pub decision: ReactDecision,
+}
+
+/// Build the prompt asking the model what to do after an observation
+pub fn build_prompt(
+    goal: &str,
+    steps: &[PlanStep],
+    current: usize,
+    observation: &intelligence::ToolOutput,
+) -> String {
+    let remaining: Vec<String> = steps
+        .iter()
+        .enumerate()
+        .skip(current + 1)
+        .map(|(i, s)| format!("{}. {}", i + 1, s.description))
+        .collect();
+
+    format!(
+        "You are executing a task step by step.\n\
+        Task: {}\n\
+        Step {} ({}) produced this observation:\n\
+        success: {}\n\
+        output: {}\n\n\
+        Remaining steps:\n{}\n\n\
+        Decide what to do next. Respond with a JSON object containing:\n\
+        - thought: string explaining your reasoning\n\
+        - decision: one of \"continue\", \"retry\", \"revise\", \"finish\", \"abort\"\n\
+        - steps: array of new steps replacing the remaining ones (only for \"revise\"), \
+        each with description, tool, parameters, expected_output, timeout_seconds\n\
+        - summary: string (only for \"finish\")\n\
+        - reason: string (only for \"abort\")",
+        goal,
+        current + 1,
+        steps.get(current).map(|s| s.description.as_str()).unwrap_or(""),
+        observation.success,
+        common::utils::truncate(&observation.output.to_string(), 2000),
+        if remaining.is_empty() {
+            "(none)".to_string()
+        } else {
+            remaining.join("\n")
+        },
+    )
+}
+
+/// Parse the model's response into a thought and decision
+pub fn parse_thought(content: &str) -> Result<Thought> {
+    let start = content
+        .find('{')
+        .ok_or_else(|| Error::Validation("No JSON object in model response".to_string()))?;
+    let end = content
+        .rfind('}')
+        .filter(|end| *end > start)
+        .ok_or_else(|| Error::Validation("Unterminated JSON object in model response".to_string()))?;
+
+    let value: serde_json::Value = serde_json::from_str(&content[start..=end])?;
+    let reasoning = value
+        .get("thought")
+        .and_then(|t| t.as_str())
+        .map(|t| t.to_string());
+    let decision: ReactDecision = serde_json::from_value(value)?;
+
+    Ok(Thought { reasoning, decision })
+}
+
+/// Decision used when no model is available or its response is unusable
+pub fn fallback_decision(success: bool, can_retry: bool, error: &str) -> ReactDecision {
+    if success {
+        ReactDecision::Continue
+    } else if can_retry {
+        ReactDecision::Retry
+    } else {
+        ReactDecision::Abort {
+            reason: error.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thought_in_prose() {
+        let content = "Sure.\n```json\n{\"thought\": \"all good\", \"decision\": \"finish\", \"summary\": \"done\"}\n```";
+        let thought = parse_thought(content).unwrap();
+
+        assert_eq!(thought.reasoning.as_deref(), Some("all good"));
+        assert!(matches!(thought.decision, ReactDecision::Finish { ref summary } if summary == "done"));
+    }
+
+    #[test]
+    fn test_parse_revision() {
+        let content = r#"{"decision": "revise", "steps": [{"description": "run tests", "tool": "test", "parameters": {}, "expected_output": "pass", "timeout_seconds": 30}]}"#;
+        let thought = parse_thought(content).unwrap();
+
+        match thought.decision {
+            ReactDecision::Revise { steps } => {
+                assert_eq!(steps.len(), 1);
+                assert_eq!(steps[0].tool.as_deref(), Some("test"));
+            }
+            other => panic!("unexpected decision: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_decision() {
+        assert!(parse_thought(r#"{"decision": "dance"}"#).is_err());
+        assert!(parse_thought("no json here").is_err());
+    }
+
+    #[test]
+    fn test_fallback_decision() {
+        assert!(matches!(fallback_decision(true, false, ""), ReactDecision::Continue));
+        assert!(matches!(fallback_decision(false, true, "boom"), ReactDecision::Retry));
+        assert!(matches!(fallback_decision(false, false, "boom"), ReactDecision::Abort { .. }));
+    }
+}
@@ -0,0 +1,314 @@
+//! Model selection per intent category and pipeline stage.
+//!
+//! The routing table records, for each intent category, stage and model,
+//! how many calls were made, how many gave a usable answer and how long
+//! they took. Each candidate first gets `min_samples` calls; after that the
+//! best measured model is used, except for an `exploration_rate` share of
+//! calls that go to the least-tried candidate so new and recovering models
+//! keep being measured. The table is saved as JSON between runs.
+
+use agent_config::{AdaptiveRoutingConfig, AgentConfig};
+use common::Result;
+use intelligence::IntentCategory;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Success rates this close to the best count as a tie, broken by latency
+const SUCCESS_MARGIN: f64 = 0.05;
+
+/// Pipeline stage a model call belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteStage {
+    Intent,
+    Planning,
+    Execution,
+    Evaluation,
+}
+
+/// A model and the provider serving it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelRoute {
+    pub provider: String,
+    pub model: String,
+}
+
+impl std::fmt::Display for ModelRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.provider, self.model)
+    }
+}
+
+/// Measured outcomes of one model for one category and stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteEntry {
+    pub category: IntentCategory,
+    pub stage: RouteStage,
+    #[serde(flatten)]
+    pub route: ModelRoute,
+    pub calls: u32,
+    pub successes: u32,
+    pub total_latency_ms: u64,
+    /// Calls this model got as exploration rather than on merit
+    pub explorations: u32,
+}
+
+impl RouteEntry {
+    pub fn success_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.calls as f64
+        }
+    }
+
+    pub fn mean_latency_ms(&self) -> u64 {
+        self.total_latency_ms.checked_div(self.calls as u64).unwrap_or(0)
+    }
+}
+
+/// Measurements for every category, stage and model seen so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingTable {
+    pub entries: Vec<RouteEntry>,
+}
+
+impl RoutingTable {
+    /// Read a saved table; a missing file is an empty table
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the table, replacing the file in one step
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension(format!("{}.tmp", common::uuid::Uuid::new_v4()));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, category: IntentCategory, stage: RouteStage, route: &ModelRoute) -> Option<&RouteEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.category == category && e.stage == stage && e.route == *route)
+    }
+
+    fn entry(&mut self, category: IntentCategory, stage: RouteStage, route: &ModelRoute) -> &mut RouteEntry {
+        let index = match self
+            .entries
+            .iter()
+            .position(|e| e.category == category && e.stage == stage && e.route == *route)
+        {
+            Some(index) => index,
+            None => {
+                self.entries.push(RouteEntry {
+                    category,
+                    stage,
+                    route: route.clone(),
+                    calls: 0,
+                    successes: 0,
+                    total_latency_ms: 0,
+                    explorations: 0,
+                });
+                self.entries.len() - 1
+            }
+        };
+        &mut self.entries[index]
+    }
+}
+
+/// Chooses a model for each call from the routing table
+pub struct ModelRouter {
+    candidates: Vec<ModelRoute>,
+    settings: AdaptiveRoutingConfig,
+    table: parking_lot::Mutex<RoutingTable>,
+}
+
+impl ModelRouter {
+    /// Route between `candidates`; the first is used when nothing is measured
+    pub fn new(candidates: Vec<ModelRoute>, settings: AdaptiveRoutingConfig) -> Self {
+        Self {
+            candidates,
+            settings,
+            table: parking_lot::Mutex::new(RoutingTable::default()),
+        }
+    }
+
+    /// Route between the configured model and the enabled provider routes
+    ///
+    /// Routes are tried in priority order, so the ranking kept by
+    /// `ModelAssigner` decides which new models are measured first. The
+    /// saved table is loaded if there is one.
+    pub fn from_config(config: &AgentConfig) -> Result<Self> {
+        let mut candidates = vec![ModelRoute {
+            provider: config.llm.provider.clone(),
+            model: config.llm.model.clone(),
+        }];
+        let mut routes: Vec<_> = config.llm.routing.providers.iter().filter(|r| r.enabled).collect();
+        routes.sort_by_key(|r| r.priority);
+        for route in routes {
+            let route = ModelRoute {
+                provider: route.provider.clone(),
+                model: route.model.clone(),
+            };
+            if !candidates.contains(&route) {
+                candidates.push(route);
+            }
+        }
+
+        let router = Self::new(candidates, config.llm.routing.adaptive.clone());
+        *router.table.lock() = RoutingTable::load(&router.settings.table_path)?;
+        Ok(router)
+    }
+
+    pub fn candidates(&self) -> &[ModelRoute] {
+        &self.candidates
+    }
+
+    /// Model to use for the next call in this category and stage
+    pub fn select(&self, category: IntentCategory, stage: RouteStage) -> ModelRoute {
+        let mut table = self.table.lock();
+        let calls = |route: &ModelRoute| table.get(category, stage, route).map_or(0, |e| e.calls);
+
+        // New candidates are measured before anything is ranked
+        if let Some(route) = self
+            .candidates
+            .iter()
+            .filter(|route| calls(route) < self.settings.min_samples)
+            .min_by_key(|route| calls(route))
+        {
+            return route.clone();
+        }
+
+        let Some(best) = self.best(&table, category, stage) else {
+            return self.candidates[0].clone();
+        };
+
+        let (total, explored) = self
+            .candidates
+            .iter()
+            .filter_map(|route| table.get(category, stage, route))
+            .fold((0u64, 0u64), |(c, x), e| (c + e.calls as u64, x + e.explorations as u64));
+        if (explored + 1) as f64 <= self.settings.exploration_rate * (total + 1) as f64 {
+            if let Some(route) = self
+                .candidates
+                .iter()
+                .filter(|route| **route != best)
+                .min_by_key(|route| calls(route))
+                .cloned()
+            {
+                table.entry(category, stage, &route).explorations += 1;
+                return route;
+            }
+        }
+        best
+    }
+
+    /// Highest success rate, with the faster model winning near-ties
+    fn best(&self, table: &RoutingTable, category: IntentCategory, stage: RouteStage) -> Option<ModelRoute> {
+        let measured: Vec<&RouteEntry> = self
+            .candidates
+            .iter()
+            .filter_map(|route| table.get(category, stage, route))
+            .filter(|e| e.calls > 0)
+            .collect();
+        let top = measured.iter().map(|e| e.success_rate()).fold(f64::NAN, f64::max);
+        measured
+            .into_iter()
+            .filter(|e| e.success_rate() >= top - SUCCESS_MARGIN)
+            .min_by_key(|e| e.mean_latency_ms())
+            .map(|e| e.route.clone())
+    }
+
+    /// Record how a call went
+    pub fn record(
+        &self,
+        category: IntentCategory,
+        stage: RouteStage,
+        route: &ModelRoute,
+        success: bool,
+        latency: Duration,
+    ) {
+        let mut table = self.table.lock();
+        let entry = table.entry(category, stage, route);
+        entry.calls += 1;
+        entry.successes += success as u32;
+        entry.total_latency_ms += latency.as_millis() as u64;
+    }
+
+    pub fn table(&self) -> RoutingTable {
+        self.table.lock().clone()
+    }
+
+    /// Persist the table for the next run
+    pub fn save(&self) -> Result<()> {
+        let table = self.table();
+        table.save(&self.settings.table_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(model: &str) -> ModelRoute {
+        ModelRoute {
+            provider: "openrouter".to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_router_measures_ranks_and_explores() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = AdaptiveRoutingConfig {
+            enabled: true,
+            exploration_rate: 0.2,
+            min_samples: 2,
+            table_path: dir.path().join("routing.json"),
+        };
+        let router = ModelRouter::new(vec![route("default"), route("fast")], settings.clone());
+        let (category, stage) = (IntentCategory::CodeGeneration, RouteStage::Planning);
+
+        // Both candidates are tried before either is ranked
+        let mut warmup = Vec::new();
+        for _ in 0..4 {
+            let chosen = router.select(category, stage);
+            let success = chosen.model == "fast";
+            router.record(category, stage, &chosen, success, Duration::from_millis(100));
+            warmup.push(chosen.model);
+        }
+        assert_eq!(warmup.iter().filter(|m| *m == "default").count(), 2);
+
+        // Mostly the better model, with a share of calls kept for the other
+        let mut picks = Vec::new();
+        for _ in 0..20 {
+            let chosen = router.select(category, stage);
+            let success = chosen.model == "fast";
+            router.record(category, stage, &chosen, success, Duration::from_millis(100));
+            picks.push(chosen.model);
+        }
+        let explored = picks.iter().filter(|m| *m == "default").count();
+        assert!((3..=6).contains(&explored), "{:?}", picks);
+
+        // Other categories and stages are measured separately
+        assert_eq!(router.select(IntentCategory::Testing, RouteStage::Planning).model, "default");
+
+        // A new model is measured first; measurements survive a restart
+        router.save().unwrap();
+        let restarted = ModelRouter::new(vec![route("default"), route("fast"), route("new")], settings);
+        *restarted.table.lock() = RoutingTable::load(&dir.path().join("routing.json")).unwrap();
+        assert_eq!(restarted.select(category, stage).model, "new");
+        let fast = restarted.table().get(category, stage, &route("fast")).cloned().unwrap();
+        assert_eq!(fast.success_rate(), 1.0);
+        assert_eq!(fast.mean_latency_ms(), 100);
+    }
+}
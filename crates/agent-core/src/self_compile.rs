@@ -2,22 +2,175 @@
 //!
 //! This module enables the agent to compile its own source code and restart
 //! with the new binary, enabling true self-modification and improvement.
+//!
+//! A new binary replaces the running one blue/green style: it is first
+//! started side by side in probe mode, and only a healthy probe leads to the
+//! old process exec'ing it with a [`Handoff`] of its queue and control
+//! socket. Every attempt is appended to `.agent/swaps.jsonl`.
 
+use crate::Task;
+use common::chrono::{DateTime, Utc};
 use common::{Error, Result};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 use tracing::{debug, error, info, warn};
 
+/// Environment variable pointing a swapped-in process at its handoff file
+pub const HANDOFF_ENV: &str = "CODING_AGENT_HANDOFF";
+
+const HANDOFF_FILE: &str = ".agent/handoff.json";
+const SWAP_LOG: &str = ".agent/swaps.jsonl";
+/// Source snapshots are extracted here
+const SNAPSHOT_DIR: &str = ".agent/build";
+/// Built binaries are kept here, each next to its `BuildInfo`
+const BUILDS_DIR: &str = ".agent/builds";
+
+/// What went into a binary the agent built
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub commit: String,
+    /// Git blob hash of the `Cargo.lock` dependencies were resolved from
+    pub cargo_lock: Option<String>,
+    pub profile: String,
+    pub build_args: Vec<String>,
+    pub built_at: DateTime<Utc>,
+    /// Git blob hash of the binary, which identifies it wherever it is copied
+    pub binary: String,
+}
+
+/// A backed-up binary and, if the agent built it, what went into it
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub modified: std::time::SystemTime,
+    pub build: Option<BuildInfo>,
+}
+
+/// What `coding-agent probe` prints as its last line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeReport {
+    pub healthy: bool,
+    pub version: String,
+    pub elapsed_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The daemon's control listener, left open across exec
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlHandoff {
+    pub fd: i32,
+    pub token: String,
+}
+
+/// What a swapped-in process takes over from the one it replaces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Handoff {
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub control: Option<ControlHandoff>,
+    /// Recorded by the new process once it has taken over
+    pub swap: SwapRecord,
+}
+
+impl Handoff {
+    pub fn new(tasks: Vec<Task>, control: Option<ControlHandoff>, mut swap: SwapRecord) -> Self {
+        swap.outcome = SwapOutcome::HandedOff;
+        swap.tasks_handed_off = tasks.len();
+        Self { tasks, control, swap }
+    }
+
+    /// Write the handoff so only the current user can read it
+    fn save(&self, path: &Path) -> Result<()> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let _ = std::fs::remove_file(path);
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Take over the handoff at `path`, left by the process this one replaced
+    ///
+    /// The file is removed so a later restart does not adopt it again.
+    pub fn take(path: &Path) -> Result<Option<Self>> {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let _ = std::fs::remove_file(path);
+        Ok(Some(serde_json::from_slice(&contents)?))
+    }
+}
+
+/// How a swap attempt ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapOutcome {
+    /// The old process exec'd the new binary
+    HandedOff,
+    /// The new binary failed its health probe; the old one kept running
+    ProbeFailed,
+    /// The new binary was healthy but could not take over
+    Failed,
+}
+
+/// One attempt to swap in a new binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapRecord {
+    pub at: DateTime<Utc>,
+    pub binary: PathBuf,
+    pub from_version: String,
+    pub outcome: SwapOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe: Option<ProbeReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub tasks_handed_off: usize,
+}
+
+impl SwapRecord {
+    pub fn new(binary: &Path, outcome: SwapOutcome, probe: Option<ProbeReport>) -> Self {
+        Self {
+            at: Utc::now(),
+            binary: binary.to_path_buf(),
+            from_version: env!("CARGO_PKG_VERSION").to_string(),
+            outcome,
+            probe,
+            error: None,
+            tasks_handed_off: 0,
+        }
+    }
+
+    pub fn with_error(mut self, error: &Error) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
 /// Self-compilation manager
 pub struct SelfCompiler {
     config: agent_config::SelfCompileConfig,
     project_root: PathBuf,
     backup_dir: PathBuf,
     current_binary: PathBuf,
+    probe_args: Vec<String>,
 }
 
 impl SelfCompiler {
@@ -32,9 +185,20 @@ impl SelfCompiler {
             project_root,
             backup_dir,
             current_binary,
+            probe_args: Vec::new(),
         }
     }
 
+    /// Arguments passed before `probe` when starting a new binary, e.g. its config file
+    pub fn with_probe_args(mut self, args: Vec<String>) -> Self {
+        self.set_probe_args(args);
+        self
+    }
+
+    pub fn set_probe_args(&mut self, args: Vec<String>) {
+        self.probe_args = args;
+    }
+
     /// Initialize the self-compiler
     pub async fn initialize(&self) -> Result<()> {
         // Ensure backup directory exists
@@ -63,23 +227,112 @@ impl SelfCompiler {
 
         info!("Starting self-compilation...");
 
-        // Create backup of current binary
-        self.backup_current_binary().await?;
+        let new_binary = self.build().await?;
+
+        info!("Self-compilation completed successfully: {:?}", new_binary);
+        Ok(new_binary)
+    }
+
+    /// Build and verify `HEAD` without touching backups or the running one
+    pub async fn build(&self) -> Result<PathBuf> {
+        self.build_rev("HEAD").await
+    }
 
-        // Build the project
-        let new_binary = self.build_project().await?;
+    /// Build and verify a commit from a snapshot of its tracked files
+    ///
+    /// Uncommitted edits never reach the build, and the dedicated target
+    /// directory keeps it apart from developer builds. The binary is kept in
+    /// `.agent/builds` next to a [`BuildInfo`] describing it.
+    pub async fn build_rev(&self, rev: &str) -> Result<PathBuf> {
+        let root = tokio::fs::canonicalize(&self.project_root).await?;
+        let commit = git(&root, &["rev-parse", "--verify", &format!("{}^{{commit}}", rev)]).await?;
+        info!("Building {} from a snapshot of {}", rev, commit);
+
+        let snapshot = self.snapshot(&root, &commit).await?;
+        let built = self.build_project(&root, &snapshot, &commit).await;
+        if let Err(e) = tokio::fs::remove_dir_all(&snapshot).await {
+            warn!("Failed to remove snapshot {:?}: {}", snapshot, e);
+        }
+        let new_binary = built?;
 
         // Verify the new binary if configured
         if self.config.verify_before_restart {
             self.verify_binary(&new_binary).await?;
         }
 
-        info!("Self-compilation completed successfully: {:?}", new_binary);
         Ok(new_binary)
     }
 
-    /// Build the project using cargo
-    async fn build_project(&self) -> Result<PathBuf> {
+    /// Extract the tracked files of `commit` into a fresh directory
+    async fn snapshot(&self, root: &Path, commit: &str) -> Result<PathBuf> {
+        let name = format!("src-{}-{}", &commit[..12], &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let dir = root.join(SNAPSHOT_DIR).join(&name);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let archive = root.join(SNAPSHOT_DIR).join(format!("{}.tar", name));
+        git(root, &["archive", "--format=tar", "-o", &archive.to_string_lossy(), commit]).await?;
+        let extracted = Command::new("tar")
+            .arg("-xf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&dir)
+            .output()
+            .await;
+        let _ = tokio::fs::remove_file(&archive).await;
+        let extracted = extracted?;
+        if !extracted.status.success() {
+            return Err(Error::Internal(format!(
+                "Could not extract snapshot of {}: {}",
+                commit,
+                String::from_utf8_lossy(&extracted.stderr).trim()
+            )));
+        }
+
+        // An untracked lock file is taken from the working tree so dependencies match
+        let lock = dir.join("Cargo.lock");
+        if !lock.exists() && root.join("Cargo.lock").exists() {
+            tokio::fs::copy(root.join("Cargo.lock"), &lock).await?;
+        }
+
+        debug!("Snapshot of {} in {:?}", commit, dir);
+        Ok(dir)
+    }
+
+    /// Run the configured test suite in the project root
+    pub async fn run_tests(&self) -> Result<()> {
+        let (program, args) = self
+            .config
+            .test_command
+            .split_first()
+            .ok_or_else(|| Error::Config("No test command configured".to_string()))?;
+
+        info!("Running test suite in {:?}", self.project_root);
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .current_dir(&self.project_root)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let test_timeout = Duration::from_secs(self.config.test_timeout_seconds);
+        let output = timeout(test_timeout, cmd.output())
+            .await
+            .map_err(|_| Error::Timeout("Test suite timed out".to_string()))??;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+            Err(Error::Validation(format!(
+                "Test suite failed:\n{}",
+                lines[lines.len().saturating_sub(20)..].join("\n")
+            )))
+        }
+    }
+
+    /// Build a source snapshot using cargo and keep the binary with its build info
+    async fn build_project(&self, root: &Path, source: &Path, commit: &str) -> Result<PathBuf> {
         let mut cmd = Command::new("cargo");
         cmd.arg("build")
             .arg("--bin")
@@ -103,9 +356,13 @@ impl SelfCompiler {
             cmd.arg(arg);
         }
 
-        cmd.current_dir(&self.project_root)
+        // Self-builds share one target directory that nothing else writes to
+        let target_dir = root.join(&self.config.target_dir);
+        cmd.current_dir(source)
+            .env("CARGO_TARGET_DIR", &target_dir)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         debug!("Running: {:?}", cmd);
 
@@ -116,19 +373,18 @@ impl SelfCompiler {
             Ok(Ok(output)) => {
                 if output.status.success() {
                     // Determine the output binary path
-                    let target_dir = self.project_root.join("target");
-                    let binary_path = match self.config.build_profile.as_str() {
+                    let built = match self.config.build_profile.as_str() {
                         "release" => target_dir.join("release/coding-agent"),
                         "dev" => target_dir.join("debug/coding-agent"),
                         profile => target_dir.join(format!("{}/coding-agent", profile)),
                     };
 
-                    if binary_path.exists() {
-                        Ok(binary_path)
+                    if built.exists() {
+                        self.keep_build(root, source, commit, &built).await
                     } else {
                         Err(Error::Internal(format!(
                             "Binary not found at expected path: {:?}",
-                            binary_path
+                            built
                         )))
                     }
                 } else {
@@ -151,6 +407,38 @@ impl SelfCompiler {
         }
     }
 
+    /// Copy a fresh binary out of the shared target directory and record its build
+    async fn keep_build(&self, root: &Path, source: &Path, commit: &str, built: &Path) -> Result<PathBuf> {
+        let builds = root.join(BUILDS_DIR);
+        tokio::fs::create_dir_all(&builds).await?;
+        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+        let kept = builds.join(format!("coding-agent_{}_{}", &commit[..12], timestamp));
+        tokio::fs::copy(built, &kept).await?;
+
+        let lock = source.join("Cargo.lock");
+        let info = BuildInfo {
+            commit: commit.to_string(),
+            cargo_lock: if lock.exists() { Some(hash_object(&lock).await?) } else { None },
+            profile: self.config.build_profile.clone(),
+            build_args: self.config.build_args.clone(),
+            built_at: Utc::now(),
+            binary: hash_object(&kept).await?,
+        };
+        tokio::fs::write(kept.with_extension("json"), serde_json::to_vec_pretty(&info)?).await?;
+
+        prune(&builds, self.config.backup_count).await?;
+        Ok(kept)
+    }
+
+    /// Build info recorded next to the running binary when it was installed
+    ///
+    /// Ignored if the binary has since been replaced by something else.
+    async fn current_build_info(&self) -> Option<BuildInfo> {
+        let info = read_build_info(&self.current_binary).await?;
+        let hash = hash_object(&self.current_binary).await.ok()?;
+        (info.binary == hash).then_some(info)
+    }
+
     /// Create a backup of the current binary
     async fn backup_current_binary(&self) -> Result<PathBuf> {
         if !self.current_binary.exists() {
@@ -159,14 +447,22 @@ impl SelfCompiler {
         }
 
         // Create backup with timestamp
+        tokio::fs::create_dir_all(&self.backup_dir).await?;
         let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
         let backup_name = format!("coding-agent_{}", timestamp);
         let backup_path = self.backup_dir.join(&backup_name);
 
         // Copy current binary to backup
         tokio::fs::copy(&self.current_binary, &backup_path).await?;
-        
-        info!("Created backup: {:?}", backup_path);
+
+        // Say what the binary is, if the agent built it
+        match self.current_build_info().await {
+            Some(info) => {
+                tokio::fs::write(backup_path.with_extension("json"), serde_json::to_vec_pretty(&info)?).await?;
+                info!("Created backup: {:?} (commit {})", backup_path, info.commit);
+            }
+            None => info!("Created backup: {:?}", backup_path),
+        }
 
         // Clean up old backups
         self.cleanup_old_backups().await?;
@@ -176,35 +472,7 @@ impl SelfCompiler {
 
     /// Clean up old backups, keeping only the most recent N
     async fn cleanup_old_backups(&self) -> Result<()> {
-        let mut entries = tokio::fs::read_dir(&self.backup_dir).await?;
-        let mut backups: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
-
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file() && path.file_name().unwrap_or_default().to_string_lossy().starts_with("coding-agent_") {
-                if let Ok(metadata) = entry.metadata().await {
-                    if let Ok(modified) = metadata.modified() {
-                        backups.push((path, modified));
-                    }
-                }
-            }
-        }
-
-        // Sort by modification time (newest first)
-        backups.sort_by(|a, b| b.1.cmp(&a.1));
-
-        // Remove excess backups
-        if backups.len() > self.config.backup_count {
-            for (path, _) in &backups[self.config.backup_count..] {
-                if let Err(e) = tokio::fs::remove_file(path).await {
-                    warn!("Failed to remove old backup {:?}: {}", path, e);
-                } else {
-                    debug!("Removed old backup: {:?}", path);
-                }
-            }
-        }
-
-        Ok(())
+        prune(&self.backup_dir, self.config.backup_count).await
     }
 
     /// Verify that a binary is valid and can be executed
@@ -239,54 +507,153 @@ impl SelfCompiler {
         }
     }
 
-    /// Restart the agent with a new binary
-    /// 
-    /// This function will replace the current binary and restart the process.
-    /// Note: This will not return if successful - the process will be replaced.
-    #[allow(unreachable_code)]
-    pub async fn restart_with_new_binary(&self, new_binary: &Path) -> Result<()> {
+    /// Start `binary` side by side in probe mode and check that it is healthy
+    ///
+    /// The probe loads this agent's config, initializes every module and
+    /// runs a smoke task against the mock gateway. It runs in a scratch
+    /// directory so it cannot touch the daemon's checkpoints or ledgers.
+    pub async fn probe(&self, binary: &Path) -> Result<ProbeReport> {
+        if self.config.verify_before_restart {
+            self.verify_binary(binary).await?;
+        }
+
+        info!("Probing binary: {:?}", binary);
+        let scratch = self
+            .project_root
+            .join(".agent/probes")
+            .join(uuid::Uuid::new_v4().simple().to_string());
+        tokio::fs::create_dir_all(&scratch).await?;
+
+        let mut cmd = Command::new(binary);
+        cmd.args(&self.probe_args)
+            .arg("probe")
+            .current_dir(&scratch)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let probe_timeout = Duration::from_secs(self.config.probe_timeout_seconds);
+        let output = timeout(probe_timeout, cmd.output()).await;
+        let _ = tokio::fs::remove_dir_all(&scratch).await;
+        let output = output.map_err(|_| {
+            Error::Timeout(format!(
+                "Health probe did not finish within {} seconds",
+                self.config.probe_timeout_seconds
+            ))
+        })??;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let report = stdout
+            .lines()
+            .rev()
+            .find_map(|line| serde_json::from_str::<ProbeReport>(line).ok());
+        match report {
+            Some(report) if report.healthy && output.status.success() => {
+                info!("Health probe passed in {}ms (version {})", report.elapsed_ms, report.version);
+                Ok(report)
+            }
+            Some(report) => Err(Error::Validation(format!(
+                "Health probe failed: {}",
+                report.error.unwrap_or_else(|| "unhealthy".to_string())
+            ))),
+            None => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let lines: Vec<&str> = stderr.lines().collect();
+                Err(Error::Validation(format!(
+                    "Health probe exited with {:?} and no report:\n{}",
+                    output.status.code(),
+                    lines[lines.len().saturating_sub(20)..].join("\n")
+                )))
+            }
+        }
+    }
+
+    /// Back up the running binary and copy a probed one over it
+    ///
+    /// Returns the backup, or `None` if there was nothing to replace.
+    pub async fn install(&self, new_binary: &Path) -> Result<Option<PathBuf>> {
         if !self.config.auto_restart {
             info!("Auto-restart is disabled. New binary available at: {:?}", new_binary);
             return Err(Error::Cancelled);
         }
 
-        info!("Restarting with new binary: {:?}", new_binary);
+        if std::fs::canonicalize(new_binary).ok() == std::fs::canonicalize(&self.current_binary).ok() {
+            return Ok(None);
+        }
 
-        // Verify the new binary
-        if self.config.verify_before_restart {
-            self.verify_binary(new_binary).await?;
+        let backup = self.backup_current_binary().await?;
+        self.replace_current_binary(new_binary).await?;
+        info!("Replaced current binary with new version");
+        self.record_installed(new_binary).await?;
+        Ok(Some(backup).filter(|backup| !backup.as_os_str().is_empty()))
+    }
+
+    /// Put back the binary [`SelfCompiler::install`] replaced
+    pub async fn restore(&self, backup: &Path) -> Result<()> {
+        self.replace_current_binary(backup).await?;
+        self.record_installed(backup).await?;
+        info!("Restored {:?} from {:?}", self.current_binary, backup);
+        Ok(())
+    }
+
+    /// Keep the build info of an installed binary next to it, or drop stale info
+    async fn record_installed(&self, source: &Path) -> Result<()> {
+        let sidecar = self.current_binary.with_extension("json");
+        match tokio::fs::read(source.with_extension("json")).await {
+            Ok(info) => tokio::fs::write(&sidecar, info).await?,
+            Err(_) => {
+                let _ = tokio::fs::remove_file(&sidecar).await;
+            }
         }
+        Ok(())
+    }
 
-        // Get current executable path
-        let current_exe = std::env::current_exe()
-            .map_err(|e| Error::Io(e))?;
+    async fn replace_current_binary(&self, source: &Path) -> Result<()> {
+        // Resolved before the swap; afterwards the running binary reads as deleted
+        let current_exe = &self.current_binary;
 
-        // Copy new binary over current binary
-        // On Unix, we can replace a running binary
+        // A running binary cannot be written to, but it can be renamed over
         #[cfg(unix)]
         {
-            tokio::fs::copy(new_binary, &current_exe).await?;
-            info!("Replaced current binary with new version");
+            let staged = current_exe.with_extension("new");
+            tokio::fs::copy(source, &staged).await?;
+            tokio::fs::rename(&staged, current_exe).await?;
         }
 
         #[cfg(windows)]
         {
             // On Windows, we need to rename the old binary first
             let old_binary = current_exe.with_extension("old");
-            tokio::fs::rename(&current_exe, &old_binary).await?;
-            tokio::fs::copy(new_binary, &current_exe).await?;
+            tokio::fs::rename(current_exe, &old_binary).await?;
+            tokio::fs::copy(source, current_exe).await?;
             tokio::fs::remove_file(&old_binary).await?;
         }
 
+        Ok(())
+    }
+
+    /// Replace this process with the installed binary, passing it `handoff`
+    ///
+    /// Only returns if the new process could not be started; the caller
+    /// still owns everything in the handoff.
+    #[allow(unreachable_code)]
+    pub fn hand_off(&self, handoff: &Handoff) -> Error {
+        let path = self.project_root.join(HANDOFF_FILE);
+        if let Err(e) = handoff.save(&path) {
+            return e;
+        }
+
         // Prepare to restart
-        info!("Executing restart...");
+        info!("Handing off {} queued task(s) to the new binary", handoff.tasks.len());
 
         // Get current process arguments
         let args: Vec<String> = std::env::args().collect();
 
         // Spawn new process
-        let mut cmd = std::process::Command::new(&current_exe);
+        let mut cmd = std::process::Command::new(&self.current_binary);
         cmd.args(&args[1..])
+            .env(HANDOFF_ENV, &path)
             .stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
@@ -294,19 +661,57 @@ impl SelfCompiler {
         // On Unix, use exec to replace current process
         #[cfg(unix)]
         {
-            use std::os::unix::process::CommandExt;
             let error = cmd.exec();
-            return Err(Error::Io(error));
+            let _ = std::fs::remove_file(&path);
+            return Error::Io(error);
         }
 
         // On Windows, spawn and exit
         #[cfg(windows)]
         {
-            let _ = cmd.spawn()?;
+            if let Err(e) = cmd.spawn() {
+                return Error::Io(e);
+            }
             std::process::exit(0);
         }
     }
 
+    /// Append a swap attempt to `.agent/swaps.jsonl`
+    pub async fn record_swap(&self, record: &SwapRecord) {
+        let path = self.project_root.join(SWAP_LOG);
+        let append = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            file.write_all(&line).await?;
+            Result::Ok(())
+        };
+        if let Err(e) = append.await {
+            warn!("Failed to record swap attempt: {}", e);
+        }
+    }
+
+    /// Every recorded swap attempt, oldest first
+    pub async fn swap_history(&self) -> Result<Vec<SwapRecord>> {
+        let path = self.project_root.join(SWAP_LOG);
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
     /// Rollback to the previous version
     pub async fn rollback(&self) -> Result<()> {
         if !self.config.rollback_on_failure {
@@ -317,27 +722,10 @@ impl SelfCompiler {
         info!("Initiating rollback...");
 
         // Find the most recent backup
-        let mut entries = tokio::fs::read_dir(&self.backup_dir).await?;
-        let mut backups: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
-
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file() && path.file_name().unwrap_or_default().to_string_lossy().starts_with("coding-agent_") {
-                if let Ok(metadata) = entry.metadata().await {
-                    if let Ok(modified) = metadata.modified() {
-                        backups.push((path, modified));
-                    }
-                }
-            }
-        }
-
-        if backups.is_empty() {
+        let backups = scan_binaries(&self.backup_dir).await?;
+        let Some((latest_backup, _)) = backups.first() else {
             return Err(Error::NotFound("No backups available for rollback".to_string()));
-        }
-
-        // Sort by modification time (newest first)
-        backups.sort_by(|a, b| b.1.cmp(&a.1));
-        let latest_backup = &backups[0].0;
+        };
 
         info!("Rolling back to: {:?}", latest_backup);
 
@@ -352,25 +740,13 @@ impl SelfCompiler {
         Ok(())
     }
 
-    /// Get the list of available backups
-    pub async fn list_backups(&self) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
-        let mut entries = tokio::fs::read_dir(&self.backup_dir).await?;
-        let mut backups: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
-
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file() && path.file_name().unwrap_or_default().to_string_lossy().starts_with("coding-agent_") {
-                if let Ok(metadata) = entry.metadata().await {
-                    if let Ok(modified) = metadata.modified() {
-                        backups.push((path, modified));
-                    }
-                }
-            }
+    /// Get the list of available backups, newest first, with what each binary is
+    pub async fn list_backups(&self) -> Result<Vec<Backup>> {
+        let mut backups = Vec::new();
+        for (path, modified) in scan_binaries(&self.backup_dir).await? {
+            let build = read_build_info(&path).await;
+            backups.push(Backup { path, modified, build });
         }
-
-        // Sort by modification time (newest first)
-        backups.sort_by(|a, b| b.1.cmp(&a.1));
-
         Ok(backups)
     }
 
@@ -381,6 +757,75 @@ impl SelfCompiler {
     }
 }
 
+/// Binaries named `coding-agent_*` in `dir`, newest first
+async fn scan_binaries(dir: &Path) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut binaries: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_binary = path.file_name().unwrap_or_default().to_string_lossy().starts_with("coding-agent_")
+            && path.extension().is_none();
+        if path.is_file() && is_binary {
+            if let Ok(metadata) = entry.metadata().await {
+                if let Ok(modified) = metadata.modified() {
+                    binaries.push((path, modified));
+                }
+            }
+        }
+    }
+
+    binaries.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    Ok(binaries)
+}
+
+/// Remove all but the newest `keep` binaries in `dir`, with their build info
+async fn prune(dir: &Path, keep: usize) -> Result<()> {
+    for (path, _) in scan_binaries(dir).await?.iter().skip(keep) {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            warn!("Failed to remove old binary {:?}: {}", path, e);
+        } else {
+            let _ = tokio::fs::remove_file(path.with_extension("json")).await;
+            debug!("Removed old binary: {:?}", path);
+        }
+    }
+    Ok(())
+}
+
+async fn read_build_info(binary: &Path) -> Option<BuildInfo> {
+    let contents = tokio::fs::read(binary.with_extension("json")).await.ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Git blob hash of a file's contents; works outside a repository
+async fn hash_object(path: &Path) -> Result<String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    git(dir, &["hash-object", &path.to_string_lossy()]).await
+}
+
+/// Run git in `dir` and return its trimmed output
+async fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(Error::ExternalService(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -391,4 +836,174 @@ mod tests {
         let compiler = SelfCompiler::new(config, "/tmp/test");
         assert!(compiler.is_enabled());
     }
+
+    #[tokio::test]
+    async fn test_probe_gates_swap_and_is_recorded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let healthy = script(
+            "healthy",
+            r#"[ "$1 $2 $3" = "--config agent.toml probe" ] || exit 2
+echo 'log line'
+echo '{"healthy":true,"version":"9.9.9","elapsed_ms":5}'"#,
+        );
+        let unhealthy = script(
+            "unhealthy",
+            r#"echo '{"healthy":false,"version":"9.9.9","elapsed_ms":5,"error":"smoke task failed"}'; exit 1"#,
+        );
+        let hanging = script("hanging", "sleep 30");
+
+        let config = agent_config::SelfCompileConfig {
+            verify_before_restart: false,
+            probe_timeout_seconds: 1,
+            ..Default::default()
+        };
+        let compiler = SelfCompiler::new(config, dir.path())
+            .with_probe_args(vec!["--config".to_string(), "agent.toml".to_string()]);
+
+        let report = compiler.probe(&healthy).await.unwrap();
+        assert_eq!(report.version, "9.9.9");
+        let err = compiler.probe(&unhealthy).await.unwrap_err();
+        assert!(err.to_string().contains("smoke task failed"));
+        assert!(matches!(compiler.probe(&hanging).await, Err(Error::Timeout(_))));
+        // Probes run in scratch directories that are cleaned up
+        assert_eq!(std::fs::read_dir(dir.path().join(".agent/probes")).unwrap().count(), 0);
+
+        compiler
+            .record_swap(&SwapRecord::new(&unhealthy, SwapOutcome::ProbeFailed, None).with_error(&err))
+            .await;
+        compiler
+            .record_swap(&SwapRecord::new(&healthy, SwapOutcome::HandedOff, Some(report)))
+            .await;
+        let history = compiler.swap_history().await.unwrap();
+        assert_eq!(
+            history.iter().map(|r| r.outcome).collect::<Vec<_>>(),
+            vec![SwapOutcome::ProbeFailed, SwapOutcome::HandedOff]
+        );
+        assert!(history[0].error.as_deref().unwrap().contains("smoke task failed"));
+    }
+
+    #[tokio::test]
+    async fn test_build_uses_committed_snapshot_and_records_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git").current_dir(repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        std::fs::write(
+            repo.join("Cargo.toml"),
+            "[package]\nname = \"coding-agent\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(repo.join("src")).unwrap();
+        std::fs::write(repo.join("src/main.rs"), "fn main() { println!(\"coding-agent 0.1.0\"); }\n").unwrap();
+        git(&["init", "-q"]);
+        git(&["config", "user.email", "agent@example.com"]);
+        git(&["config", "user.name", "agent"]);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "init"]);
+        let head = git(&["rev-parse", "HEAD"]);
+
+        // A half-applied edit in the working tree must not reach the build
+        std::fs::write(repo.join("src/main.rs"), "fn main() {").unwrap();
+
+        let config = agent_config::SelfCompileConfig {
+            build_profile: "dev".to_string(),
+            build_args: vec!["--offline".to_string()],
+            ..Default::default()
+        };
+        let mut compiler = SelfCompiler::new(config, repo);
+        let binary = compiler.build().await.unwrap();
+        assert!(binary.starts_with(repo.canonicalize().unwrap().join(BUILDS_DIR)));
+        assert!(repo.join(".agent/build/target").is_dir());
+        assert!(!repo.join("target").exists());
+        // Snapshots are removed once built; only the target directory stays
+        let left: Vec<_> = std::fs::read_dir(repo.join(SNAPSHOT_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, vec![std::ffi::OsString::from("target")]);
+
+        let info = read_build_info(&binary).await.unwrap();
+        assert_eq!(info.commit, head);
+        assert_eq!(info.build_args, vec!["--offline".to_string()]);
+        assert!(info.cargo_lock.is_some());
+
+        // A backup of a binary the agent built says what it is
+        compiler.current_binary = binary.clone();
+        compiler.initialize().await.unwrap();
+        compiler.backup_current_binary().await.unwrap();
+        let backups = compiler.list_backups().await.unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].build.as_ref(), Some(&info));
+    }
+
+    #[test]
+    fn test_handoff_is_taken_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("handoff.json");
+        let tasks = vec![Task::new("first"), Task::new("second")];
+        let swap = SwapRecord::new(Path::new("/bin/new"), SwapOutcome::Failed, None);
+        Handoff::new(tasks, None, swap).save(&path).unwrap();
+
+        let handoff = Handoff::take(&path).unwrap().unwrap();
+        assert_eq!(handoff.tasks.len(), 2);
+        assert_eq!(handoff.swap.outcome, SwapOutcome::HandedOff);
+        assert_eq!(handoff.swap.tasks_handed_off, 2);
+        assert!(!path.exists());
+        assert!(Handoff::take(&path).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_install_backs_up_and_restores() {
+        let dir = tempfile::tempdir().unwrap();
+        let (current, new) = (dir.path().join("coding-agent"), dir.path().join("candidate"));
+        std::fs::write(&current, "old").unwrap();
+        std::fs::write(&new, "new").unwrap();
+
+        let config = agent_config::SelfCompileConfig {
+            auto_restart: true,
+            ..Default::default()
+        };
+        let mut compiler = SelfCompiler::new(config, dir.path());
+        compiler.current_binary = current.clone();
+
+        // The candidate's build info is installed with it
+        let info = BuildInfo {
+            commit: "abc123".to_string(),
+            cargo_lock: None,
+            profile: "release".to_string(),
+            build_args: Vec::new(),
+            built_at: Utc::now(),
+            binary: hash_object(&new).await.unwrap(),
+        };
+        std::fs::write(new.with_extension("json"), serde_json::to_vec(&info).unwrap()).unwrap();
+
+        let backup = compiler.install(&new).await.unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "old");
+        assert!(read_build_info(&backup).await.is_none());
+        assert_eq!(read_build_info(&current).await, Some(info.clone()));
+        assert!(compiler.install(&current).await.unwrap().is_none());
+
+        // Backing it up again keeps what it is, wherever it came from
+        let other = dir.path().join("other");
+        std::fs::write(&other, "other").unwrap();
+        let second = compiler.install(&other).await.unwrap().unwrap();
+        assert_eq!(read_build_info(&second).await, Some(info));
+        assert!(read_build_info(&current).await.is_none());
+
+        compiler.restore(&second).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "new");
+        assert!(read_build_info(&current).await.is_some());
+    }
 }
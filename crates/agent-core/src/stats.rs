@@ -0,0 +1,169 @@
+//! Small statistics helpers for comparing experiment arms.
+
+/// Mean and unbiased variance of a sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Self {
+        let n = values.len();
+        if n == 0 {
+            return Self { n, mean: 0.0, variance: 0.0 };
+        }
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let variance = if n > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        Self { n, mean, variance }
+    }
+}
+
+/// Result of Welch's unequal-variance t-test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WelchTest {
+    /// Positive when `b` has the larger mean
+    pub t: f64,
+    pub df: f64,
+    /// Two-sided p-value
+    pub p_value: f64,
+}
+
+/// Welch's t-test of `b` against `a`; needs at least two values on each side
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Option<WelchTest> {
+    let (a, b) = (Summary::of(a), Summary::of(b));
+    if a.n < 2 || b.n < 2 {
+        return None;
+    }
+
+    let (va, vb) = (a.variance / a.n as f64, b.variance / b.n as f64);
+    let diff = b.mean - a.mean;
+    if va + vb == 0.0 {
+        // Both samples are constant: either identical or certainly different
+        return Some(if diff == 0.0 {
+            WelchTest { t: 0.0, df: (a.n + b.n - 2) as f64, p_value: 1.0 }
+        } else {
+            WelchTest { t: diff.signum() * f64::INFINITY, df: (a.n + b.n - 2) as f64, p_value: 0.0 }
+        });
+    }
+
+    let t = diff / (va + vb).sqrt();
+    let df = (va + vb).powi(2) / (va.powi(2) / (a.n - 1) as f64 + vb.powi(2) / (b.n - 1) as f64);
+    Some(WelchTest { t, df, p_value: t_two_sided_p(t, df) })
+}
+
+/// Two-sided tail probability of Student's t distribution
+pub fn t_two_sided_p(t: f64, df: f64) -> f64 {
+    if !t.is_finite() {
+        return 0.0;
+    }
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).clamp(0.0, 1.0)
+}
+
+/// Regularized incomplete beta function I_x(a, b)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction for the incomplete beta function (modified Lentz)
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+/// Natural log of the gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection keeps the approximation in its accurate range
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_t_distribution_and_welch() {
+        // Closed forms: Cauchy for one degree of freedom, 1 - t/sqrt(2+t²) for two
+        assert!((t_two_sided_p(1.0, 1.0) - 0.5).abs() < 1e-9);
+        assert!((t_two_sided_p(2.0, 2.0) - (1.0 - 2.0 / 6f64.sqrt())).abs() < 1e-9);
+        assert!((t_two_sided_p(1.959_964, 1e7) - 0.05).abs() < 1e-5);
+        assert_eq!(t_two_sided_p(0.0, 5.0), 1.0);
+
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let b = [2.0, 4.0, 6.0, 8.0, 10.0];
+        let test = welch_t_test(&a, &b).unwrap();
+        assert!((test.t - 1.897_366_596).abs() < 1e-6);
+        assert!((test.df - 5.882_352_941).abs() < 1e-6);
+        assert!(test.p_value > 0.05 && test.p_value < 0.15);
+
+        assert!(welch_t_test(&a, &[1.0]).is_none());
+        assert_eq!(welch_t_test(&[1.0, 1.0], &[1.0, 1.0]).unwrap().p_value, 1.0);
+        assert_eq!(welch_t_test(&[0.0, 0.0], &[1.0, 1.0]).unwrap().p_value, 0.0);
+    }
+}
@@ -0,0 +1,432 @@
+//! Per-task workspace isolation with git worktrees.
+//!
+//! When enabled, each task runs in its own worktree on a scratch branch.
+//! Successful work is committed there and merged or cherry-picked back into
+//! the original working tree; failed attempts are thrown away with the
+//! worktree. All git operations go through the tool framework's `GitTool`.
+
+use agent_config::{GitIntegration, GitToolConfig};
+use common::{Error, Result};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::delegation::PATH_ARGUMENTS;
+
+/// Scratch worktree a task runs in
+#[derive(Debug, Clone)]
+pub struct TaskWorktree {
+    /// Repository the worktree belongs to
+    pub repo: PathBuf,
+    /// Worktree directory, relative to the agent's working directory
+    pub root: PathBuf,
+    /// Directory passed to `git worktree`, relative to `repo`
+    pub relative: String,
+    pub branch: String,
+}
+
+impl TaskWorktree {
+    /// Point every path argument of a tool call at the worktree
+    ///
+    /// Relative and absolute paths inside the repository are rewritten;
+    /// paths outside it, or already in the worktree, are left alone.
+    pub fn scope(&self, mut parameters: Value) -> Value {
+        self.scope_value(&mut parameters);
+        parameters
+    }
+
+    fn scope_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if PATH_ARGUMENTS.contains(&key.as_str()) {
+                        match value {
+                            Value::String(path) => self.scope_string(path),
+                            Value::Array(items) => items.iter_mut().for_each(|item| {
+                                if let Value::String(path) = item {
+                                    self.scope_string(path);
+                                }
+                            }),
+                            _ => {}
+                        }
+                    }
+                    self.scope_value(value);
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.scope_value(item)),
+            _ => {}
+        }
+    }
+
+    fn scope_string(&self, path: &mut String) {
+        if let Some(scoped) = self.scoped_path(path) {
+            *path = scoped;
+        }
+    }
+
+    fn scoped_path(&self, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        // Relative paths compare as written against a relative repository,
+        // anything else by where it resolves
+        let inside = if path.is_relative() && self.repo.is_relative() {
+            normalize(path).strip_prefix(normalize(&self.repo)).ok()?.to_path_buf()
+        } else {
+            absolute(path)?.strip_prefix(absolute(&self.repo)?).ok()?.to_path_buf()
+        };
+        if inside.starts_with("..") || inside.starts_with(&self.relative) {
+            return None;
+        }
+        let root = if path.is_absolute() { absolute(&self.root)? } else { self.root.clone() };
+        if inside.as_os_str().is_empty() {
+            return Some(path_arg(&root));
+        }
+        Some(path_arg(&root.join(inside)))
+    }
+}
+
+/// Creates, integrates and removes task worktrees
+pub struct WorktreeManager {
+    settings: GitToolConfig,
+}
+
+impl WorktreeManager {
+    pub fn new(settings: GitToolConfig) -> Self {
+        Self { settings }
+    }
+
+    /// Create a worktree for a task on a fresh scratch branch
+    ///
+    /// Returns `None` if the workspace is not a git repository. A worktree
+    /// left behind by an interrupted run of the same task is reused.
+    pub async fn create(
+        &self,
+        tools: &tools::ToolFramework,
+        task: &super::Task,
+        workspace: Option<&Path>,
+    ) -> Result<Option<TaskWorktree>> {
+        let repo = normalize(workspace.unwrap_or(Path::new(".")));
+        let repo_arg = path_arg(&repo);
+
+        if git(tools, serde_json::json!({"operation": "worktree_list", "path": repo_arg}))
+            .await
+            .is_err()
+        {
+            warn!("{} is not a git repository; running task in place", repo_arg);
+            return Ok(None);
+        }
+
+        let id = task.id.0.simple().to_string();
+        let name = format!("task-{}", &id[..8]);
+        let worktree = TaskWorktree {
+            root: repo.join(&self.settings.worktree_dir).join(&name),
+            relative: format!("{}/{}", self.settings.worktree_dir.trim_end_matches('/'), name),
+            branch: format!("agent/{}", name),
+            repo,
+        };
+
+        if worktree.root.exists() {
+            info!("Reusing worktree {} for task {:?}", worktree.root.display(), task.id);
+            return Ok(Some(worktree));
+        }
+
+        // Keep task worktrees out of the main tree's status
+        let dir = worktree.repo.join(&self.settings.worktree_dir);
+        tokio::fs::create_dir_all(&dir).await?;
+        let ignore = dir.join(".gitignore");
+        if !ignore.exists() {
+            tokio::fs::write(&ignore, "*\n").await?;
+        }
+
+        git(
+            tools,
+            serde_json::json!({
+                "operation": "worktree_add",
+                "path": repo_arg,
+                "worktree": worktree.relative,
+                "branch": worktree.branch,
+            }),
+        )
+        .await?;
+        info!("Task {:?} isolated in {} on {}", task.id, worktree.root.display(), worktree.branch);
+        Ok(Some(worktree))
+    }
+
+    /// Commit the task's changes and bring them into the original tree
+    ///
+    /// On success the worktree and branch are removed. If integration fails
+    /// they are kept so the conflict can be resolved by hand.
+    pub async fn integrate(
+        &self,
+        tools: &tools::ToolFramework,
+        worktree: &TaskWorktree,
+        message: &str,
+    ) -> Result<()> {
+        let root = path_arg(&worktree.root);
+        let status = git(tools, serde_json::json!({"operation": "status", "path": root})).await?;
+        let dirty = ["staged", "unstaged", "untracked"].iter().any(|key| {
+            status
+                .get(*key)
+                .and_then(|v| v.as_array())
+                .is_some_and(|files| !files.is_empty())
+        });
+        if dirty {
+            let message = format!("{} {}", self.settings.commit_prefix, message);
+            git(
+                tools,
+                serde_json::json!({"operation": "commit", "path": root, "message": message.trim()}),
+            )
+            .await?;
+        }
+
+        let strategy = match self.settings.integration {
+            GitIntegration::Merge => "merge",
+            GitIntegration::CherryPick => "cherry_pick",
+        };
+        git(
+            tools,
+            serde_json::json!({
+                "operation": "merge",
+                "path": path_arg(&worktree.repo),
+                "branch": worktree.branch,
+                "strategy": strategy,
+            }),
+        )
+        .await
+        .map_err(|e| {
+            Error::Execution(format!(
+                "Could not integrate {} (kept in {}): {}",
+                worktree.branch,
+                worktree.root.display(),
+                e
+            ))
+        })?;
+        info!("Integrated {} into {} ({})", worktree.branch, path_arg(&worktree.repo), strategy);
+
+        // The work is in; a leftover worktree is only clutter
+        if let Err(e) = self.remove(tools, worktree).await {
+            warn!("Failed to remove worktree {}: {}", worktree.root.display(), e);
+        }
+        Ok(())
+    }
+
+    /// Throw away a failed task's worktree and branch
+    pub async fn discard(&self, tools: &tools::ToolFramework, worktree: &TaskWorktree) -> Result<()> {
+        debug!("Discarding worktree {}", worktree.root.display());
+        self.remove(tools, worktree).await
+    }
+
+    async fn remove(&self, tools: &tools::ToolFramework, worktree: &TaskWorktree) -> Result<()> {
+        git(
+            tools,
+            serde_json::json!({
+                "operation": "worktree_remove",
+                "path": path_arg(&worktree.repo),
+                "worktree": worktree.relative,
+                "branch": worktree.branch,
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// Discards a task's worktree if the task stops before closing it
+///
+/// A task whose future is dropped, such as an aborted or timed-out
+/// sub-agent, never gets to integrate or discard its worktree itself.
+pub(crate) struct WorktreeGuard {
+    open: Option<(WorktreeManager, Arc<tools::ToolFramework>, Arc<TaskWorktree>)>,
+}
+
+impl WorktreeGuard {
+    pub(crate) fn new(manager: WorktreeManager, tools: Arc<tools::ToolFramework>, worktree: Arc<TaskWorktree>) -> Self {
+        Self {
+            open: Some((manager, tools, worktree)),
+        }
+    }
+
+    /// The task closed its worktree; leave it alone
+    pub(crate) fn disarm(mut self) {
+        self.open = None;
+    }
+}
+
+impl Drop for WorktreeGuard {
+    fn drop(&mut self) {
+        let Some((manager, tools, worktree)) = self.open.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("Leaving worktree {} of a stopped task behind", worktree.root.display());
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(e) = manager.discard(&tools, &worktree).await {
+                warn!("Failed to remove worktree {}: {}", worktree.root.display(), e);
+            }
+        });
+    }
+}
+
+/// Run a git operation, turning an unsuccessful result into an error
+pub(crate) async fn git(tools: &tools::ToolFramework, args: Value) -> Result<Value> {
+    let result = tools.execute("git", args).await?;
+    if result.success {
+        Ok(result.data)
+    } else {
+        Err(Error::ExternalService(
+            result.data.as_str().map(str::to_string).unwrap_or_else(|| result.data.to_string()),
+        ))
+    }
+}
+
+/// Drop `.` segments and resolve `..` so paths compare by their real components
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// A path resolved against the working directory and normalized
+fn absolute(path: &Path) -> Option<PathBuf> {
+    if path.is_absolute() {
+        Some(normalize(path))
+    } else {
+        Some(normalize(&std::env::current_dir().ok()?.join(path)))
+    }
+}
+
+fn path_arg(path: &Path) -> String {
+    if path.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        path.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn test_scope_rewrites_paths_into_worktree() {
+        let worktree = TaskWorktree {
+            repo: PathBuf::from("project"),
+            root: PathBuf::from("project/.agent/worktrees/task-1"),
+            relative: ".agent/worktrees/task-1".to_string(),
+            branch: "agent/task-1".to_string(),
+        };
+
+        let scoped = worktree.scope(serde_json::json!({"operation": "read", "path": "./project/src/lib.rs"}));
+        assert_eq!(scoped["path"], "project/.agent/worktrees/task-1/src/lib.rs");
+        let outside = worktree.scope(serde_json::json!({"path": "other/file.rs"}));
+        assert_eq!(outside["path"], "other/file.rs");
+        let url = worktree.scope(serde_json::json!({"method": "GET", "url": "http://x"}));
+        assert!(url.get("path").is_none());
+
+        let scoped = worktree.scope(serde_json::json!({
+            "paths": ["project/a.rs", "other/b.rs"],
+            "file": "project/src/../Cargo.toml",
+            "cwd": "project",
+            "query": "project/a.rs",
+        }));
+        assert_eq!(scoped["paths"][0], "project/.agent/worktrees/task-1/a.rs");
+        assert_eq!(scoped["paths"][1], "other/b.rs");
+        assert_eq!(scoped["file"], "project/.agent/worktrees/task-1/Cargo.toml");
+        assert_eq!(scoped["cwd"], "project/.agent/worktrees/task-1");
+        assert_eq!(scoped["query"], "project/a.rs");
+
+        // Already scoped and escaping paths stay as they are
+        let again = worktree.scope(scoped.clone());
+        assert_eq!(again, scoped);
+        let escaping = worktree.scope(serde_json::json!({"path": "project/../other/c.rs"}));
+        assert_eq!(escaping["path"], "project/../other/c.rs");
+    }
+
+    #[test]
+    fn test_scope_with_absolute_repository() {
+        let cwd = std::env::current_dir().unwrap();
+        let repo = cwd.join("project");
+        let worktree = TaskWorktree {
+            root: repo.join(".agent/worktrees/task-1"),
+            relative: ".agent/worktrees/task-1".to_string(),
+            branch: "agent/task-1".to_string(),
+            repo: repo.clone(),
+        };
+        let root = worktree.root.display().to_string();
+
+        let absolute = worktree.scope(serde_json::json!({"path": repo.join("src/lib.rs")}));
+        assert_eq!(absolute["path"], format!("{}/src/lib.rs", root));
+        let relative = worktree.scope(serde_json::json!({"files": ["project/src/main.rs"]}));
+        assert_eq!(relative["files"][0], format!("{}/src/main.rs", root));
+        let outside = worktree.scope(serde_json::json!({"path": "/elsewhere/lib.rs"}));
+        assert_eq!(outside["path"], "/elsewhere/lib.rs");
+    }
+
+    #[tokio::test]
+    async fn test_worktree_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        run(repo, &["init", "-q", "-b", "main"]);
+        run(repo, &["config", "user.email", "agent@example.com"]);
+        run(repo, &["config", "user.name", "agent"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run(repo, &["add", "-A"]);
+        run(repo, &["commit", "-q", "-m", "init"]);
+
+        let tools = tools::ToolFramework::new();
+        let manager = WorktreeManager::new(GitToolConfig::default());
+
+        // Successful task: changes come back as a merge
+        let task = super::super::Task::new("edit a");
+        let worktree = manager.create(&tools, &task, Some(repo)).await.unwrap().unwrap();
+        std::fs::write(worktree.root.join("a.txt"), "two\n").unwrap();
+        assert_eq!(std::fs::read_to_string(repo.join("a.txt")).unwrap(), "one\n");
+        manager.integrate(&tools, &worktree, "edit a").await.unwrap();
+        assert_eq!(std::fs::read_to_string(repo.join("a.txt")).unwrap(), "two\n");
+        assert!(!worktree.root.exists());
+        assert!(!run(repo, &["branch"]).contains(&worktree.branch));
+        assert!(run(repo, &["status", "--porcelain"]).is_empty());
+
+        // Failed task: nothing reaches the original tree
+        let task = super::super::Task::new("break a");
+        let worktree = manager.create(&tools, &task, Some(repo)).await.unwrap().unwrap();
+        std::fs::write(worktree.root.join("a.txt"), "broken\n").unwrap();
+        manager.discard(&tools, &worktree).await.unwrap();
+        assert_eq!(std::fs::read_to_string(repo.join("a.txt")).unwrap(), "two\n");
+        assert!(!worktree.root.exists());
+
+        // Stopped task: dropping its guard removes the worktree
+        let task = super::super::Task::new("abort a");
+        let worktree = Arc::new(manager.create(&tools, &task, Some(repo)).await.unwrap().unwrap());
+        let guard = WorktreeGuard::new(manager, Arc::new(tools), worktree.clone());
+        drop(guard);
+        for _ in 0..100 {
+            if !worktree.root.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!worktree.root.exists());
+        assert!(!run(repo, &["branch"]).contains(&worktree.branch));
+    }
+}
@@ -0,0 +1,455 @@
+//! Grounding of planner world state in the workspace.
+//!
+//! HTN preconditions are checked against a [`WorldState`]. Condition
+//! evaluators probe the workspace to fill it in. Results are cached for a
+//! planning session and dropped when a tool effect may have changed them.
+
+use common::{async_trait, Error, Result};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::planning::{Condition, ConditionType, Effect, EffectType, WorldState};
+
+/// Decides conditions by probing the workspace
+#[async_trait]
+pub trait ConditionEvaluator: Send + Sync {
+    /// Evaluator name, used in logs
+    fn name(&self) -> &str;
+
+    /// Whether this evaluator can decide the condition
+    fn handles(&self, condition: &Condition) -> bool;
+
+    /// Whether a cached result for the condition may be stale after the effect
+    fn invalidated_by(&self, condition: &Condition, effect: &Effect) -> bool;
+
+    /// Probe the workspace rooted at `root`
+    async fn evaluate(&self, condition: &Condition, root: &Path) -> Result<bool>;
+}
+
+/// Whether an effect may have touched the workspace contents
+fn changes_workspace(effect: &Effect) -> bool {
+    matches!(
+        effect.effect_type,
+        EffectType::CreateFile | EffectType::ModifyFile | EffectType::DeleteFile | EffectType::SideEffect
+    )
+}
+
+/// Checks whether files and directories exist
+pub struct FileProbe;
+
+#[async_trait]
+impl ConditionEvaluator for FileProbe {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn handles(&self, condition: &Condition) -> bool {
+        matches!(
+            condition.condition_type,
+            ConditionType::FileExists | ConditionType::DirectoryExists
+        )
+    }
+
+    fn invalidated_by(&self, condition: &Condition, effect: &Effect) -> bool {
+        match effect.effect_type {
+            EffectType::SideEffect => true,
+            EffectType::CreateFile | EffectType::ModifyFile | EffectType::DeleteFile => {
+                let target = Path::new(&effect.target);
+                let probed = Path::new(&condition.expression);
+                target.starts_with(probed) || probed.starts_with(target)
+            }
+            EffectType::UpdateState => false,
+        }
+    }
+
+    async fn evaluate(&self, condition: &Condition, root: &Path) -> Result<bool> {
+        let metadata = match tokio::fs::metadata(root.join(&condition.expression)).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        Ok(match condition.condition_type {
+            ConditionType::DirectoryExists => metadata.is_dir(),
+            _ => metadata.is_file(),
+        })
+    }
+}
+
+/// Checks that the git working tree has no uncommitted changes
+pub struct GitCleanProbe;
+
+#[async_trait]
+impl ConditionEvaluator for GitCleanProbe {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn handles(&self, condition: &Condition) -> bool {
+        matches!(condition.condition_type, ConditionType::GitClean)
+    }
+
+    fn invalidated_by(&self, _condition: &Condition, effect: &Effect) -> bool {
+        changes_workspace(effect)
+    }
+
+    async fn evaluate(&self, condition: &Condition, root: &Path) -> Result<bool> {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(root).arg("status").arg("--porcelain");
+        if !condition.expression.is_empty() {
+            cmd.arg("--").arg(&condition.expression);
+        }
+
+        let output = cmd.output().await.map_err(Error::Io)?;
+        if !output.status.success() {
+            return Err(Error::Execution(format!(
+                "git status failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout.iter().all(|b| b.is_ascii_whitespace()))
+    }
+}
+
+/// Decides a condition by whether a command exits successfully
+///
+/// The condition expression, when present, is appended as a final argument
+/// (e.g. a test name filter).
+pub struct CommandProbe {
+    name: String,
+    condition_type: ConditionType,
+    program: String,
+    args: Vec<String>,
+    timeout_seconds: u64,
+}
+
+impl CommandProbe {
+    /// Create a probe running `program` with `args`
+    pub fn new(
+        name: impl Into<String>,
+        condition_type: ConditionType,
+        program: impl Into<String>,
+        args: &[&str],
+    ) -> Self {
+        Self {
+            name: name.into(),
+            condition_type,
+            program: program.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            timeout_seconds: 600,
+        }
+    }
+
+    /// Probe for `compiles` using `cargo check`
+    pub fn compiles() -> Self {
+        Self::new("compile", ConditionType::Compiles, "cargo", &["check", "--quiet", "--workspace"])
+    }
+
+    /// Probe for `tests_pass` using `cargo test`
+    pub fn tests_pass() -> Self {
+        Self::new("test", ConditionType::TestsPass, "cargo", &["test", "--quiet", "--workspace"])
+    }
+
+    /// Set the command timeout
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = seconds;
+        self
+    }
+}
+
+#[async_trait]
+impl ConditionEvaluator for CommandProbe {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handles(&self, condition: &Condition) -> bool {
+        std::mem::discriminant(&condition.condition_type) == std::mem::discriminant(&self.condition_type)
+    }
+
+    fn invalidated_by(&self, _condition: &Condition, effect: &Effect) -> bool {
+        changes_workspace(effect)
+    }
+
+    async fn evaluate(&self, condition: &Condition, root: &Path) -> Result<bool> {
+        let mut cmd = Command::new(&self.program);
+        cmd.current_dir(root).args(&self.args).kill_on_drop(true);
+        if !condition.expression.is_empty() {
+            cmd.arg(&condition.expression);
+        }
+
+        let output = tokio::time::timeout(
+            tokio::time::Duration::from_secs(self.timeout_seconds),
+            cmd.output(),
+        )
+        .await
+        .map_err(|_| Error::Timeout(format!("{} probe timed out", self.name)))?
+        .map_err(Error::Io)?;
+
+        Ok(output.status.success())
+    }
+}
+
+/// Checks symbol presence through the analysis engine
+pub struct SymbolProbe {
+    analysis: Arc<analysis::AnalysisEngine>,
+}
+
+impl SymbolProbe {
+    pub fn new(analysis: Arc<analysis::AnalysisEngine>) -> Self {
+        Self { analysis }
+    }
+}
+
+#[async_trait]
+impl ConditionEvaluator for SymbolProbe {
+    fn name(&self) -> &str {
+        "symbol"
+    }
+
+    fn handles(&self, condition: &Condition) -> bool {
+        matches!(condition.condition_type, ConditionType::SymbolExists)
+    }
+
+    fn invalidated_by(&self, _condition: &Condition, effect: &Effect) -> bool {
+        changes_workspace(effect)
+    }
+
+    async fn evaluate(&self, condition: &Condition, _root: &Path) -> Result<bool> {
+        let symbols = self.analysis.workspace_symbol(&condition.expression).await?;
+        Ok(symbols.iter().any(|s| s.name == condition.expression))
+    }
+}
+
+/// Set of condition evaluators used to ground world state
+#[derive(Clone)]
+pub struct WorldProbe {
+    evaluators: Vec<Arc<dyn ConditionEvaluator>>,
+}
+
+impl WorldProbe {
+    /// Create a probe with the built-in file, git, compile and test evaluators
+    pub fn new() -> Self {
+        Self::empty()
+            .with_evaluator(Arc::new(FileProbe))
+            .with_evaluator(Arc::new(GitCleanProbe))
+            .with_evaluator(Arc::new(CommandProbe::compiles()))
+            .with_evaluator(Arc::new(CommandProbe::tests_pass()))
+    }
+
+    /// Create a probe without any evaluators
+    pub fn empty() -> Self {
+        Self { evaluators: Vec::new() }
+    }
+
+    /// Add an evaluator; later evaluators take precedence for the conditions they handle
+    pub fn with_evaluator(mut self, evaluator: Arc<dyn ConditionEvaluator>) -> Self {
+        self.evaluators.insert(0, evaluator);
+        self
+    }
+
+    /// Add symbol presence checks backed by the analysis engine
+    pub fn with_analysis(self, analysis: Arc<analysis::AnalysisEngine>) -> Self {
+        self.with_evaluator(Arc::new(SymbolProbe::new(analysis)))
+    }
+
+    /// Start a planning session probing the workspace at `root`
+    pub fn session(&self, root: impl Into<PathBuf>) -> ProbeSession {
+        ProbeSession {
+            root: root.into(),
+            evaluators: self.evaluators.clone(),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for WorldProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached outcome of a probe
+struct CachedProbe {
+    condition: Condition,
+    evaluator: usize,
+    holds: bool,
+}
+
+/// Probe results for one planning session
+pub struct ProbeSession {
+    root: PathBuf,
+    evaluators: Vec<Arc<dyn ConditionEvaluator>>,
+    cache: RwLock<HashMap<String, CachedProbe>>,
+}
+
+impl ProbeSession {
+    /// Workspace root being probed
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of cached probe results
+    pub fn cached(&self) -> usize {
+        self.cache.read().len()
+    }
+
+    /// Evaluate a condition, reusing the cached result when there is one
+    ///
+    /// Returns `None` if no evaluator handles the condition. A failing probe
+    /// counts as the condition not holding.
+    pub async fn evaluate(&self, condition: &Condition) -> Option<bool> {
+        let key = condition.key();
+        if let Some(cached) = self.cache.read().get(&key) {
+            return Some(cached.holds);
+        }
+
+        let index = self.evaluators.iter().position(|e| e.handles(condition))?;
+        let evaluator = &self.evaluators[index];
+        let holds = match evaluator.evaluate(condition, &self.root).await {
+            Ok(holds) => holds,
+            Err(e) => {
+                warn!("{} probe failed for {}: {}", evaluator.name(), key, e);
+                false
+            }
+        };
+        debug!("Probed {} with {}: {}", key, evaluator.name(), holds);
+
+        self.cache.write().insert(
+            key,
+            CachedProbe {
+                condition: condition.clone(),
+                evaluator: index,
+                holds,
+            },
+        );
+        Some(holds)
+    }
+
+    /// Record probed values of the given conditions in `state`
+    ///
+    /// Conditions no evaluator handles are left as they are. Returns the
+    /// number of conditions recorded.
+    pub async fn ground(&self, state: &mut WorldState, conditions: &[Condition]) -> usize {
+        let mut grounded = 0;
+        for condition in conditions {
+            if let Some(holds) = self.evaluate(condition).await {
+                state.record(condition, holds);
+                grounded += 1;
+            }
+        }
+        grounded
+    }
+
+    /// Drop cached results the effect may have changed
+    ///
+    /// Returns the number of results dropped.
+    pub fn invalidate(&self, effect: &Effect) -> usize {
+        let mut cache = self.cache.write();
+        let before = cache.len();
+        cache.retain(|key, cached| {
+            let stale = (matches!(effect.effect_type, EffectType::UpdateState) && effect.target == *key)
+                || self.evaluators[cached.evaluator].invalidated_by(&cached.condition, effect);
+            !stale
+        });
+        before - cache.len()
+    }
+
+    /// Drop all cached results
+    pub fn clear(&self) {
+        self.cache.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(condition_type: ConditionType, expression: &str) -> Condition {
+        Condition {
+            condition_type,
+            expression: expression.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ground_and_invalidate_file_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+
+        let session = WorldProbe::new().session(dir.path());
+        let conditions = vec![
+            condition(ConditionType::FileExists, "Cargo.toml"),
+            condition(ConditionType::DirectoryExists, "src"),
+            condition(ConditionType::FileExists, "README.md"),
+        ];
+
+        let mut state = WorldState::default();
+        assert_eq!(session.ground(&mut state, &conditions).await, 3);
+        assert!(state.satisfies(&conditions[0]));
+        assert!(state.satisfies(&conditions[1]));
+        assert!(!state.satisfies(&conditions[2]));
+        assert_eq!(session.cached(), 3);
+
+        // Creating the file only invalidates the probe that looked for it
+        std::fs::write(dir.path().join("README.md"), "hello").unwrap();
+        let created = Effect {
+            effect_type: EffectType::CreateFile,
+            target: "README.md".to_string(),
+            value: serde_json::Value::Null,
+        };
+        assert_eq!(session.invalidate(&created), 1);
+
+        session.ground(&mut state, &conditions).await;
+        assert!(state.satisfies(&conditions[2]));
+    }
+
+    #[tokio::test]
+    async fn test_custom_evaluator_takes_precedence() {
+        struct Always(bool);
+
+        #[async_trait]
+        impl ConditionEvaluator for Always {
+            fn name(&self) -> &str {
+                "always"
+            }
+            fn handles(&self, condition: &Condition) -> bool {
+                matches!(condition.condition_type, ConditionType::TestsPass | ConditionType::Custom)
+            }
+            fn invalidated_by(&self, _condition: &Condition, effect: &Effect) -> bool {
+                changes_workspace(effect)
+            }
+            async fn evaluate(&self, _condition: &Condition, _root: &Path) -> Result<bool> {
+                Ok(self.0)
+            }
+        }
+
+        let session = WorldProbe::new()
+            .with_evaluator(Arc::new(Always(false)))
+            .session(".");
+        let tests_pass = condition(ConditionType::TestsPass, "");
+        let custom = condition(ConditionType::Custom, "reviewed");
+
+        let mut state = WorldState::default();
+        assert!(state.satisfies(&custom));
+        session.ground(&mut state, &[tests_pass.clone(), custom.clone()]).await;
+        assert!(!state.satisfies(&tests_pass));
+        assert!(!state.satisfies(&custom));
+
+        // Unhandled conditions are left alone
+        let unknown = condition(ConditionType::SymbolExists, "main");
+        assert_eq!(session.evaluate(&unknown).await, None);
+
+        let edited = Effect {
+            effect_type: EffectType::ModifyFile,
+            target: "src/lib.rs".to_string(),
+            value: serde_json::Value::Null,
+        };
+        assert_eq!(session.invalidate(&edited), 2);
+        assert_eq!(session.cached(), 0);
+    }
+}
@@ -0,0 +1,314 @@
+//! Build checker integration.
+//!
+//! Runs a language's checker (e.g. `cargo check --message-format=json`) in
+//! a workspace and parses its output into [`Diagnostic`]s. Cargo's JSON
+//! messages are understood natively; other tools are parsed from the
+//! common `path:line:col: severity: message` line format.
+
+use super::{Diagnostic, DiagnosticSeverity, Location};
+use common::{Error, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+use tracing::debug;
+
+/// How a checker reports its findings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// `cargo --message-format=json`
+    CargoJson,
+    /// One `path:line[:col]: [severity:] message` per line
+    Lines,
+}
+
+/// A checker command
+#[derive(Debug, Clone)]
+pub struct Checker {
+    program: String,
+    args: Vec<String>,
+    format: OutputFormat,
+    timeout_seconds: u64,
+}
+
+impl Checker {
+    pub fn new(program: impl Into<String>, args: Vec<String>, format: OutputFormat) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            format,
+            timeout_seconds: 600,
+        }
+    }
+
+    /// `cargo check` with JSON diagnostics
+    pub fn cargo() -> Self {
+        Self::new(
+            "cargo",
+            vec!["check".to_string(), "--message-format=json".to_string()],
+            OutputFormat::CargoJson,
+        )
+    }
+
+    /// Set the command timeout
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = seconds;
+        self
+    }
+
+    /// Run the checker in `root`
+    ///
+    /// A checker that fails without reporting any diagnostic is treated as
+    /// unable to run rather than as a failed check.
+    pub async fn run(&self, root: &Path) -> Result<CheckReport> {
+        debug!("Running {} {:?} in {}", self.program, self.args, root.display());
+        let output = tokio::time::timeout(
+            tokio::time::Duration::from_secs(self.timeout_seconds),
+            Command::new(&self.program)
+                .current_dir(root)
+                .args(&self.args)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| Error::Timeout(format!("{} timed out", self.program)))?
+        .map_err(Error::Io)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let diagnostics = match self.format {
+            OutputFormat::CargoJson => parse_cargo_json(&stdout),
+            OutputFormat::Lines => parse_lines(&format!("{}\n{}", stdout, stderr), &self.program),
+        };
+
+        let report = CheckReport {
+            success: output.status.success(),
+            diagnostics,
+        };
+        if !report.success && report.errors().next().is_none() {
+            return Err(Error::Execution(format!(
+                "{} failed without diagnostics: {}",
+                self.program,
+                stderr.trim()
+            )));
+        }
+        Ok(report)
+    }
+}
+
+/// Outcome of one checker run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckReport {
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckReport {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == DiagnosticSeverity::Error)
+    }
+
+    /// Errors that are not in `baseline`
+    ///
+    /// Errors are matched by file, code and message; line numbers are
+    /// ignored since edits elsewhere in a file shift them.
+    pub fn new_errors(&self, baseline: &[Diagnostic]) -> Vec<Diagnostic> {
+        self.errors()
+            .filter(|error| {
+                !baseline.iter().any(|old| {
+                    old.severity == DiagnosticSeverity::Error
+                        && old.location.path == error.location.path
+                        && old.code == error.code
+                        && old.message == error.message
+                })
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Render a diagnostic as `path:line:col: severity[code]: message`
+pub fn format_diagnostic(diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        DiagnosticSeverity::Error => "error",
+        DiagnosticSeverity::Warning => "warning",
+        DiagnosticSeverity::Information => "info",
+        DiagnosticSeverity::Hint => "hint",
+    };
+    let code = diagnostic
+        .code
+        .as_ref()
+        .map(|c| format!("[{}]", c))
+        .unwrap_or_default();
+    format!(
+        "{}:{}:{}: {}{}: {}",
+        diagnostic.location.path.display(),
+        diagnostic.location.line_start,
+        diagnostic.location.column_start,
+        severity,
+        code,
+        diagnostic.message
+    )
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    level: String,
+    message: String,
+    code: Option<CompilerCode>,
+    #[serde(default)]
+    spans: Vec<CompilerSpan>,
+}
+
+#[derive(Deserialize)]
+struct CompilerCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct CompilerSpan {
+    file_name: String,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+}
+
+/// Parse `cargo --message-format=json` output
+///
+/// Messages without a source location, such as the final "aborting due to
+/// previous errors", are skipped.
+pub fn parse_cargo_json(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|msg| msg.reason == "compiler-message")
+        .filter_map(|msg| {
+            let message = msg.message?;
+            let span = message
+                .spans
+                .iter()
+                .find(|s| s.is_primary)
+                .or_else(|| message.spans.first())?;
+            let severity = match message.level.as_str() {
+                "error" | "error: internal compiler error" => DiagnosticSeverity::Error,
+                "warning" => DiagnosticSeverity::Warning,
+                "note" => DiagnosticSeverity::Information,
+                _ => DiagnosticSeverity::Hint,
+            };
+            Some(Diagnostic {
+                severity,
+                message: message.message,
+                location: Location {
+                    path: PathBuf::from(&span.file_name),
+                    line_start: span.line_start,
+                    line_end: span.line_end,
+                    column_start: span.column_start,
+                    column_end: span.column_end,
+                },
+                code: message.code.map(|c| c.code),
+                source: "cargo".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `path:line[:col]: [severity[code]:] message` lines
+///
+/// Lines without a severity are reported as errors.
+pub fn parse_lines(output: &str, source: &str) -> Vec<Diagnostic> {
+    static LINE_REGEX: OnceLock<Regex> = OnceLock::new();
+    let line_regex = LINE_REGEX.get_or_init(|| {
+        Regex::new(
+            r"^(?P<path>[^:\s][^:]*):(?P<line>\d+)(?::(?P<col>\d+))?:\s*(?:(?P<sev>error|warning|note|info|hint)(?:\[(?P<code>[^\]]+)\])?:)?\s*(?P<msg>.+)$",
+        )
+        .unwrap()
+    });
+
+    output
+        .lines()
+        .filter_map(|line| line_regex.captures(line.trim_end()))
+        .map(|caps| {
+            let line: u32 = caps["line"].parse().unwrap_or(0);
+            let column: u32 = caps.name("col").and_then(|c| c.as_str().parse().ok()).unwrap_or(0);
+            let severity = match caps.name("sev").map(|s| s.as_str()) {
+                Some("warning") => DiagnosticSeverity::Warning,
+                Some("note") | Some("info") => DiagnosticSeverity::Information,
+                Some("hint") => DiagnosticSeverity::Hint,
+                _ => DiagnosticSeverity::Error,
+            };
+            Diagnostic {
+                severity,
+                message: caps["msg"].to_string(),
+                location: Location {
+                    path: PathBuf::from(&caps["path"]),
+                    line_start: line,
+                    line_end: line,
+                    column_start: column,
+                    column_end: column,
+                },
+                code: caps.name("code").map(|c| c.as_str().to_string()),
+                source: source.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARGO_OUTPUT: &str = r#"{"reason":"compiler-artifact","package_id":"dep 0.1.0"}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"level":"error","message":"mismatched types","code":{"code":"E0308","explanation":null},"spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":5,"column_end":10,"is_primary":true}],"rendered":"error[E0308]: mismatched types"}}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"level":"warning","message":"unused variable: `x`","code":null,"spans":[{"file_name":"src/main.rs","line_start":7,"line_end":7,"column_start":9,"column_end":10,"is_primary":true}],"rendered":""}}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"level":"error","message":"aborting due to 1 previous error","code":null,"spans":[],"rendered":""}}
+{"reason":"build-finished","success":false}"#;
+
+    #[test]
+    fn test_parse_cargo_json() {
+        let diagnostics = parse_cargo_json(CARGO_OUTPUT);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("E0308"));
+        assert_eq!(diagnostics[0].location.path, PathBuf::from("src/lib.rs"));
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+        assert_eq!(
+            format_diagnostic(&diagnostics[0]),
+            "src/lib.rs:3:5: error[E0308]: mismatched types"
+        );
+    }
+
+    #[test]
+    fn test_parse_lines_and_new_errors() {
+        let output = "main.go:12:3: undefined: foo\n\
+                      src/app.ts:4: warning: unused import\n\
+                      Found 1 error.";
+        let diagnostics = parse_lines(output, "go");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[0].location.column_start, 3);
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+
+        let baseline = parse_cargo_json(CARGO_OUTPUT);
+        let mut shifted = baseline.clone();
+        shifted[0].location.line_start = 9;
+        let report = CheckReport {
+            success: false,
+            diagnostics: shifted.into_iter().chain(diagnostics).collect(),
+        };
+        let new = report.new_errors(&baseline);
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].message, "undefined: foo");
+    }
+}
@@ -12,6 +12,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+pub mod checker;
 pub mod lsp;
 pub mod semantic;
 
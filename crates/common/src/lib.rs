@@ -53,6 +53,25 @@ pub enum Error {
     Cancelled,
 }
 
+impl Error {
+    /// Short, stable name of the error's variant, for metrics and traces
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Serialization(_) => "serialization",
+            Error::Config(_) => "config",
+            Error::Validation(_) => "validation",
+            Error::NotFound(_) => "not_found",
+            Error::PermissionDenied(_) => "permission_denied",
+            Error::Timeout(_) => "timeout",
+            Error::Internal(_) => "internal",
+            Error::ExternalService(_) => "external_service",
+            Error::Execution(_) => "execution",
+            Error::Cancelled => "cancelled",
+        }
+    }
+}
+
 /// Unique identifier for tasks
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct TaskId(pub uuid::Uuid);
@@ -5,7 +5,8 @@
 
 use common::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Main configuration structure (sensitive fields are redacted in debug output)
 #[derive(Clone, Serialize, Deserialize)]
@@ -31,6 +32,14 @@ pub struct AgentConfig {
 
     /// Telemetry and survey configuration
     pub telemetry: TelemetryConfig,
+
+    /// Control API served in daemon mode
+    #[serde(default)]
+    pub control: ControlConfig,
+
+    /// Span export for the task pipeline
+    #[serde(default)]
+    pub tracing: TracingConfig,
 }
 
 impl std::fmt::Debug for AgentConfig {
@@ -43,6 +52,8 @@ impl std::fmt::Debug for AgentConfig {
             .field("tools", &self.tools)
             .field("self_compile", &self.self_compile)
             .field("telemetry", &DebugRedactedTelemetryConfig(&self.telemetry))
+            .field("control", &self.control)
+            .field("tracing", &self.tracing)
             .finish()
     }
 }
@@ -58,7 +69,9 @@ impl<'a> std::fmt::Debug for DebugRedactedLlmConfig<'a> {
             .field("model", &self.0.model)
             .field("temperature", &self.0.temperature)
             .field("max_tokens", &self.0.max_tokens)
-            .field("providers", &DebugRedactedLlmProviders(&self.0.providers));
+            .field("providers", &DebugRedactedLlmProviders(&self.0.providers))
+            .field("record_to", &self.0.record_to)
+            .field("replay_from", &self.0.replay_from);
         
         lsp.finish()
     }
@@ -138,6 +151,65 @@ impl Default for AgentConfig {
             tools: ToolConfig::default(),
             self_compile: SelfCompileConfig::default(),
             telemetry: TelemetryConfig::default(),
+            control: ControlConfig::default(),
+            tracing: TracingConfig::default(),
+        }
+    }
+}
+
+/// Local control API for a running daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Serve the API in daemon mode
+    pub enabled: bool,
+    /// Unix socket path, or a loopback `host:port` to listen on over TCP
+    pub listen: String,
+    /// Token clients must present; a fresh one is written here on startup
+    pub token_file: PathBuf,
+}
+
+impl ControlConfig {
+    /// TCP address to listen on, or `None` for a Unix socket
+    pub fn tcp_addr(&self) -> Option<std::net::SocketAddr> {
+        self.listen.parse().ok()
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: ".agent/control.sock".to_string(),
+            token_file: PathBuf::from(".agent/control.token"),
+        }
+    }
+}
+
+/// OpenTelemetry span export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// Export spans for tasks, model calls and tool calls
+    pub enabled: bool,
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318`
+    pub endpoint: Option<String>,
+    /// Append OTLP JSON export requests to this file instead
+    pub file: Option<PathBuf>,
+    /// `service.name` reported for the agent
+    pub service_name: String,
+    /// How often buffered spans are exported
+    pub export_interval_seconds: u64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            file: None,
+            service_name: "coding-agent".to_string(),
+            export_interval_seconds: 5,
         }
     }
 }
@@ -218,6 +290,9 @@ impl AgentConfig {
     /// - CODING_AGENT_LLM_PROVIDER - Override LLM provider
     /// - CODING_AGENT_LLM_TEMPERATURE - Override temperature
     /// - CODING_AGENT_LLM_MAX_TOKENS - Override max tokens
+    /// - CODING_AGENT_LLM_RECORD - Record model exchanges to a file
+    /// - CODING_AGENT_LLM_REPLAY - Replay model exchanges from a file
+    /// - CODING_AGENT_ROUTING_ADAPTIVE - Enable/disable per-intent model selection
     /// - CODING_AGENT_AGENT_LOG_LEVEL - Override log level
     /// - CODING_AGENT_AGENT_IMPROVEMENT_INTERVAL - Override improvement interval
     /// - CODING_AGENT_AGENT_MAX_CONCURRENT_TASKS - Override max concurrent tasks
@@ -270,6 +345,14 @@ impl AgentConfig {
             }
         }
 
+        if let Ok(val) = env::var("CODING_AGENT_LLM_RECORD") {
+            self.llm.record_to = Some(val);
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_LLM_REPLAY") {
+            self.llm.replay_from = Some(val);
+        }
+
         if let Ok(val) = env::var("CODING_AGENT_LLM_BASE_URL") {
             // Apply to the current provider's base_url
             match self.llm.provider.as_str() {
@@ -383,6 +466,48 @@ impl AgentConfig {
             }
         }
 
+        if let Ok(val) = env::var("CODING_AGENT_REACT_MAX_STEPS") {
+            if let Ok(steps) = val.parse::<u32>() {
+                self.agent.react.max_steps = steps;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_REACT_MAX_TOKENS") {
+            if let Ok(tokens) = val.parse::<u32>() {
+                self.agent.react.max_tokens = tokens;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_PLANNING_MAX_EXPANSIONS") {
+            if let Ok(expansions) = val.parse::<usize>() {
+                self.agent.planning.max_expansions = expansions;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_PLANNING_MAX_TIME_MS") {
+            if let Ok(ms) = val.parse::<u64>() {
+                self.agent.planning.max_time_ms = ms;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_BUDGET_MAX_TOKENS") {
+            if let Ok(tokens) = val.parse::<u32>() {
+                self.agent.budget.default.max_tokens = tokens;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_BUDGET_MAX_SECONDS") {
+            if let Ok(seconds) = val.parse::<u64>() {
+                self.agent.budget.default.max_seconds = seconds;
+            }
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_BUDGET_MAX_COST_USD") {
+            if let Ok(cost) = val.parse::<f64>() {
+                self.agent.budget.default.max_cost_usd = cost;
+            }
+        }
+
         if let Ok(val) = env::var("CODING_AGENT_AGENT_NAME") {
             self.agent.name = val;
         }
@@ -424,6 +549,12 @@ impl AgentConfig {
             }
         }
 
+        if let Ok(val) = env::var("CODING_AGENT_APPROVAL_TIMEOUT_SECONDS") {
+            if let Ok(seconds) = val.parse::<u64>() {
+                self.safety.approval_timeout_seconds = seconds;
+            }
+        }
+
         // Tool Configuration overrides
         if let Ok(val) = env::var("CODING_AGENT_GIT_ENABLED") {
             self.tools.git.enabled = val.parse().unwrap_or(true);
@@ -433,6 +564,33 @@ impl AgentConfig {
             self.tools.git.auto_commit = val.parse().unwrap_or(false);
         }
 
+        if let Ok(val) = env::var("CODING_AGENT_GIT_WORKTREE_ISOLATION") {
+            self.tools.git.worktree_isolation = val.parse().unwrap_or(false);
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_CONTROL_ENABLED") {
+            self.control.enabled = val.parse().unwrap_or(false);
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_CONTROL_LISTEN") {
+            self.control.listen = val;
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_TRACING_ENDPOINT") {
+            self.tracing.enabled = true;
+            self.tracing.endpoint = Some(val);
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_CHECK_ENABLED") {
+            self.tools.check.enabled = val.parse().unwrap_or(true);
+        }
+
+        if let Ok(val) = env::var("CODING_AGENT_CHECK_MAX_FIX_ITERATIONS") {
+            if let Ok(iterations) = val.parse::<u32>() {
+                self.tools.check.max_fix_iterations = iterations;
+            }
+        }
+
         if let Ok(val) = env::var("CODING_AGENT_TEST_ENABLED") {
             self.tools.test.enabled = val.parse().unwrap_or(true);
         }
@@ -465,6 +623,10 @@ impl AgentConfig {
             self.llm.routing.strategy = val.parse().unwrap_or(RoutingStrategy::Fallback);
         }
 
+        if let Ok(val) = env::var("CODING_AGENT_ROUTING_ADAPTIVE") {
+            self.llm.routing.adaptive.enabled = val.parse().unwrap_or(false);
+        }
+
         if let Ok(val) = env::var("CODING_AGENT_COST_BUDGET_PER_HOUR") {
             if let Ok(budget) = val.parse::<f64>() {
                 self.llm.routing.cost_budget_per_hour = budget;
@@ -660,8 +822,16 @@ impl AgentConfig {
             )));
         }
 
+        if self.llm.record_to.is_some() && self.llm.replay_from.is_some() {
+            return Err(Error::Validation(
+                "llm.record_to and llm.replay_from cannot both be set".to_string(),
+            ));
+        }
+
         // Check API key for non-local providers
         match self.llm.provider.as_str() {
+            // A replayed run never contacts the provider
+            _ if self.llm.replay_from.is_some() => {}
             "anthropic" => {
                 if self.llm.providers.anthropic.api_key.is_empty()
                     || self.llm.providers.anthropic.api_key == "${ANTHROPIC_API_KEY}"
@@ -979,6 +1149,18 @@ impl AgentConfig {
             )));
         }
 
+        if self.agent.react.max_steps == 0 {
+            return Err(Error::Validation(
+                "react.max_steps must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.agent.planning.max_expansions == 0 {
+            return Err(Error::Validation(
+                "planning.max_expansions must be greater than 0".to_string(),
+            ));
+        }
+
         // Validate log level
         let valid_log_levels = ["trace", "debug", "info", "warn", "error"];
         if !valid_log_levels.contains(&self.agent.log_level.as_str()) {
@@ -1037,6 +1219,12 @@ impl AgentConfig {
             ));
         }
 
+        if !self.safety.require_approval_for.is_empty() && self.safety.approval_timeout_seconds == 0 {
+            return Err(Error::Validation(
+                "approval_timeout_seconds must be greater than 0".to_string(),
+            ));
+        }
+
         // Validate forbidden commands
         if self.safety.forbidden_commands.is_empty() {
             tracing::warn!("No forbidden commands configured. This may be a security risk.");
@@ -1052,6 +1240,17 @@ impl AgentConfig {
             );
         }
 
+        // Tools only accept relative paths, so task worktrees must live inside the workspace
+        let worktree_dir = Path::new(&self.tools.git.worktree_dir);
+        if self.tools.git.worktree_isolation
+            && (worktree_dir.is_absolute()
+                || worktree_dir.components().any(|c| matches!(c, std::path::Component::ParentDir)))
+        {
+            return Err(Error::Validation(
+                "tools.git.worktree_dir must be a relative path inside the workspace".to_string(),
+            ));
+        }
+
         // Validate self-compile configuration
         if self.self_compile.backup_count == 0 {
             return Err(Error::Validation(
@@ -1065,6 +1264,24 @@ impl AgentConfig {
             ));
         }
 
+        if self.self_compile.test_command.is_empty() {
+            return Err(Error::Validation(
+                "self_compile.test_command cannot be empty".to_string(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.self_compile.max_benchmark_regression) {
+            return Err(Error::Validation(
+                "self_compile.max_benchmark_regression must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
+        if self.self_compile.probe_timeout_seconds == 0 {
+            return Err(Error::Validation(
+                "self_compile.probe_timeout_seconds must be greater than 0".to_string(),
+            ));
+        }
+
         // Validate routing cost budget
         if self.llm.routing.cost_budget_per_hour < 0.0 {
             return Err(Error::Validation(
@@ -1072,12 +1289,100 @@ impl AgentConfig {
             ));
         }
 
+        let adaptive = &self.llm.routing.adaptive;
+        if !(0.0..=1.0).contains(&adaptive.exploration_rate) {
+            return Err(Error::Validation(format!(
+                "llm.routing.adaptive.exploration_rate must be between 0.0 and 1.0, got {}",
+                adaptive.exploration_rate
+            )));
+        }
+
+        if self.tools.check.enabled && self.tools.check.command.is_empty() {
+            return Err(Error::Validation(
+                "tools.check.command cannot be empty".to_string(),
+            ));
+        }
+
+        let mut mcp_names = std::collections::HashSet::new();
+        for server in &self.tools.mcp_servers {
+            if server.name.is_empty()
+                || !server.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(Error::Validation(format!(
+                    "MCP server name must be non-empty and use only letters, digits, '-' and '_', got '{}'",
+                    server.name
+                )));
+            }
+            if !mcp_names.insert(server.name.as_str()) {
+                return Err(Error::Validation(format!(
+                    "Duplicate MCP server name: {}",
+                    server.name
+                )));
+            }
+            if server.command.is_empty() {
+                return Err(Error::Validation(format!(
+                    "MCP server {} has no command",
+                    server.name
+                )));
+            }
+        }
+
+        // The control API is local only
+        if self.control.listen.is_empty() {
+            return Err(Error::Validation(
+                "control.listen cannot be empty".to_string(),
+            ));
+        }
+        if self.control.tcp_addr().is_some_and(|addr| !addr.ip().is_loopback()) {
+            return Err(Error::Validation(format!(
+                "control.listen must be a Unix socket path or a loopback address, got {}",
+                self.control.listen
+            )));
+        }
+
+        // Spans need somewhere to go
+        if self.tracing.enabled {
+            if self.tracing.endpoint.is_none() && self.tracing.file.is_none() {
+                return Err(Error::Validation(
+                    "tracing needs an endpoint or a file".to_string(),
+                ));
+            }
+            if self.tracing.export_interval_seconds == 0 {
+                return Err(Error::Validation(
+                    "tracing.export_interval_seconds must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        // Validate task budgets
+        let budgets = &self.agent.budget;
+        if budgets.cost_per_1k_tokens < 0.0 {
+            return Err(Error::Validation(
+                "budget.cost_per_1k_tokens cannot be negative".to_string(),
+            ));
+        }
+        for (name, budget) in std::iter::once(("default", &budgets.default))
+            .chain(budgets.per_intent.iter().map(|(k, v)| (k.as_str(), v)))
+        {
+            if budget.max_cost_usd < 0.0 {
+                return Err(Error::Validation(format!(
+                    "max_cost_usd of the '{}' budget cannot be negative",
+                    name
+                )));
+            }
+        }
+
         Ok(())
     }
 
     /// Get the API key for the current provider
     pub fn current_api_key(&self) -> &str {
-        match self.llm.provider.as_str() {
+        self.api_key_for(&self.llm.provider)
+    }
+
+    /// API key configured for `provider`, empty if there is none
+    pub fn api_key_for(&self, provider: &str) -> &str {
+        match provider {
             "anthropic" => &self.llm.providers.anthropic.api_key,
             "openai" => &self.llm.providers.openai.api_key,
             "ollama" => &self.llm.providers.ollama.api_key,
@@ -1129,6 +1434,12 @@ pub struct AgentSettings {
     pub max_concurrent_tasks: usize,
     pub log_level: String,
     pub self_improvement: SelfImprovementSettings,
+    #[serde(default)]
+    pub react: ReactSettings,
+    #[serde(default)]
+    pub planning: PlanningSettings,
+    #[serde(default)]
+    pub budget: BudgetSettings,
 }
 
 impl Default for AgentSettings {
@@ -1140,6 +1451,9 @@ impl Default for AgentSettings {
             max_concurrent_tasks: 4,
             log_level: "info".to_string(),
             self_improvement: SelfImprovementSettings::default(),
+            react: ReactSettings::default(),
+            planning: PlanningSettings::default(),
+            budget: BudgetSettings::default(),
         }
     }
 }
@@ -1164,6 +1478,88 @@ impl Default for SelfImprovementSettings {
     }
 }
 
+/// Limits for the observe-think-act execution loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactSettings {
+    /// Maximum number of loop iterations per task
+    pub max_steps: u32,
+    /// Maximum tokens the loop may spend deciding on next actions
+    pub max_tokens: u32,
+}
+
+impl Default for ReactSettings {
+    fn default() -> Self {
+        Self {
+            max_steps: 20,
+            max_tokens: 32_000,
+        }
+    }
+}
+
+/// Budget for the HTN planner's search over method alternatives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanningSettings {
+    /// Maximum search nodes expanded per goal
+    pub max_expansions: usize,
+    /// Maximum wall-clock time spent searching per goal
+    pub max_time_ms: u64,
+    /// Runner-up plans kept for explanation after the cheapest one
+    pub alternatives: usize,
+}
+
+impl Default for PlanningSettings {
+    fn default() -> Self {
+        Self {
+            max_expansions: 2_000,
+            max_time_ms: 2_000,
+            alternatives: 2,
+        }
+    }
+}
+
+/// Resource budgets applied to each task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetSettings {
+    /// Budget for tasks whose intent has no entry in `per_intent`
+    pub default: TaskBudget,
+    /// Budgets keyed by intent category, e.g. `code_generation`
+    pub per_intent: HashMap<String, TaskBudget>,
+    /// Price in USD per 1K tokens for models without a routing entry
+    pub cost_per_1k_tokens: f64,
+}
+
+impl Default for BudgetSettings {
+    fn default() -> Self {
+        Self {
+            default: TaskBudget::default(),
+            per_intent: HashMap::new(),
+            cost_per_1k_tokens: 0.01,
+        }
+    }
+}
+
+impl BudgetSettings {
+    /// Budget for an intent category
+    pub fn for_intent(&self, category: &str) -> &TaskBudget {
+        self.per_intent.get(category).unwrap_or(&self.default)
+    }
+}
+
+/// Limits on what a single task may consume (0 = unlimited)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskBudget {
+    pub max_tokens: u32,
+    pub max_llm_calls: u32,
+    pub max_tool_calls: u32,
+    /// Wall-clock time for the whole task
+    pub max_seconds: u64,
+    /// Estimated spend in USD
+    pub max_cost_usd: f64,
+}
+
 /// LLM provider configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
@@ -1177,6 +1573,12 @@ pub struct LlmConfig {
     pub providers: ProviderConfigs,
     #[serde(default)]
     pub routing: RoutingConfig,
+    /// Append every model exchange to this JSON lines file
+    #[serde(default)]
+    pub record_to: Option<String>,
+    /// Answer model calls from a file written with `record_to` instead of the provider
+    #[serde(default)]
+    pub replay_from: Option<String>,
 }
 
 impl Default for LlmConfig {
@@ -1189,6 +1591,8 @@ impl Default for LlmConfig {
             fallback: FallbackConfig::default(),
             providers: ProviderConfigs::default(),
             routing: RoutingConfig::default(),
+            record_to: None,
+            replay_from: None,
         }
     }
 }
@@ -1395,6 +1799,7 @@ impl std::str::FromStr for RoutingStrategy {
 
 /// Provider routing configuration with cost/latency balancing
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RoutingConfig {
     /// Routing strategy
     pub strategy: RoutingStrategy,
@@ -1410,6 +1815,8 @@ pub struct RoutingConfig {
     pub health_check_interval: u64,
     /// Enable automatic failover
     pub auto_failover: bool,
+    /// Per-intent, per-stage model selection from measured outcomes
+    pub adaptive: AdaptiveRoutingConfig,
 }
 
 impl Default for RoutingConfig {
@@ -1443,6 +1850,34 @@ impl Default for RoutingConfig {
             health_check_enabled: true,
             health_check_interval: 60,
             auto_failover: true,
+            adaptive: AdaptiveRoutingConfig::default(),
+        }
+    }
+}
+
+/// Runtime model selection per intent category and pipeline stage
+///
+/// Candidates are the configured model plus every enabled route in
+/// `llm.routing.providers`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveRoutingConfig {
+    pub enabled: bool,
+    /// Share of calls sent to the least-tried candidate instead of the best
+    pub exploration_rate: f64,
+    /// Calls each candidate gets for a category and stage before it is ranked
+    pub min_samples: u32,
+    /// Where the measured routing table is kept between runs
+    pub table_path: PathBuf,
+}
+
+impl Default for AdaptiveRoutingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            exploration_rate: 0.1,
+            min_samples: 3,
+            table_path: PathBuf::from(".agent/routing.json"),
         }
     }
 }
@@ -1515,6 +1950,13 @@ pub struct SafetyConfig {
     pub forbidden_commands: Vec<String>,
     #[serde(default)]
     pub require_approval_for: Vec<String>,
+    /// Seconds to wait for an approval decision before denying
+    #[serde(default = "default_approval_timeout")]
+    pub approval_timeout_seconds: u64,
+}
+
+fn default_approval_timeout() -> u64 {
+    300
 }
 
 fn default_protected_paths() -> Vec<String> {
@@ -1532,6 +1974,7 @@ impl Default for SafetyConfig {
             max_file_size_mb: 10,
             forbidden_commands: vec!["rm -rf /".to_string(), "dd if=/dev/zero".to_string()],
             require_approval_for: vec!["delete".to_string(), "modify_protected".to_string(), "git_push".to_string()],
+            approval_timeout_seconds: default_approval_timeout(),
         }
     }
 }
@@ -1541,6 +1984,11 @@ impl Default for SafetyConfig {
 pub struct ToolConfig {
     pub git: GitToolConfig,
     pub test: TestToolConfig,
+    #[serde(default)]
+    pub check: CheckToolConfig,
+    /// External MCP servers whose tools are imported at startup
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServerConfig>,
 }
 
 impl Default for ToolConfig {
@@ -1548,10 +1996,88 @@ impl Default for ToolConfig {
         Self {
             git: GitToolConfig::default(),
             test: TestToolConfig::default(),
+            check: CheckToolConfig::default(),
+            mcp_servers: Vec::new(),
         }
     }
 }
 
+/// An MCP server launched over stdio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Prefix of the imported tool names (`<name>__<tool>`)
+    pub name: String,
+    /// Program and arguments
+    pub command: Vec<String>,
+    /// Extra environment variables for the server
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// How long a request may take before the server is restarted
+    #[serde(default = "default_mcp_timeout")]
+    pub timeout_seconds: u64,
+    /// Restarts allowed after the server crashes or hangs
+    #[serde(default = "default_mcp_max_restarts")]
+    pub max_restarts: u32,
+}
+
+fn default_mcp_timeout() -> u64 {
+    60
+}
+
+fn default_mcp_max_restarts() -> u32 {
+    3
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Build check run after the agent edits source files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckToolConfig {
+    pub enabled: bool,
+    /// Checker command and arguments, run in the workspace root
+    pub command: Vec<String>,
+    /// How the checker reports diagnostics
+    pub format: CheckOutputFormat,
+    /// File extensions whose edits trigger a check
+    pub extensions: Vec<String>,
+    /// Model-driven fix attempts before the edit counts as failed
+    pub max_fix_iterations: u32,
+    pub timeout_seconds: u64,
+}
+
+impl Default for CheckToolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            command: vec![
+                "cargo".to_string(),
+                "check".to_string(),
+                "--message-format=json".to_string(),
+            ],
+            format: CheckOutputFormat::default(),
+            extensions: vec!["rs".to_string()],
+            max_fix_iterations: 3,
+            timeout_seconds: 600,
+        }
+    }
+}
+
+/// Diagnostic output format of a checker
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckOutputFormat {
+    /// `cargo --message-format=json`
+    #[default]
+    CargoJson,
+    /// `path:line:col: severity: message`, as printed by most compilers and linters
+    Lines,
+}
+
 /// Git tool configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitToolConfig {
@@ -1559,6 +2085,15 @@ pub struct GitToolConfig {
     pub auto_commit: bool,
     #[serde(default)]
     pub commit_prefix: String,
+    /// Run each task in its own git worktree on a scratch branch
+    #[serde(default)]
+    pub worktree_isolation: bool,
+    /// Directory holding task worktrees, relative to the workspace
+    #[serde(default = "default_worktree_dir")]
+    pub worktree_dir: String,
+    /// How a successful task branch is brought back
+    #[serde(default)]
+    pub integration: GitIntegration,
 }
 
 impl Default for GitToolConfig {
@@ -1567,10 +2102,28 @@ impl Default for GitToolConfig {
             enabled: true,
             auto_commit: false,
             commit_prefix: "[agent]".to_string(),
+            worktree_isolation: false,
+            worktree_dir: default_worktree_dir(),
+            integration: GitIntegration::default(),
         }
     }
 }
 
+fn default_worktree_dir() -> String {
+    ".agent/worktrees".to_string()
+}
+
+/// How task branches are integrated into the original working tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitIntegration {
+    /// Merge the task branch
+    #[default]
+    Merge,
+    /// Cherry-pick the task branch's commits
+    CherryPick,
+}
+
 /// Test tool configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestToolConfig {
@@ -1607,6 +2160,9 @@ pub struct SelfCompileConfig {
     /// Additional cargo build arguments
     #[serde(default)]
     pub build_args: Vec<String>,
+    /// Cargo target directory for self-builds, relative to the project root
+    #[serde(default = "default_build_target_dir")]
+    pub target_dir: PathBuf,
     /// Timeout for compilation in seconds
     #[serde(default)]
     pub compile_timeout_seconds: u64,
@@ -1616,6 +2172,37 @@ pub struct SelfCompileConfig {
     /// Rollback on restart failure
     #[serde(default)]
     pub rollback_on_failure: bool,
+    /// Test suite a code change must pass before it is promoted
+    #[serde(default = "default_test_command")]
+    pub test_command: Vec<String>,
+    /// Timeout for the test suite in seconds
+    #[serde(default = "default_test_timeout")]
+    pub test_timeout_seconds: u64,
+    /// Benchmark suite run against each promoted code change
+    #[serde(default)]
+    pub benchmark_suite: Option<PathBuf>,
+    /// Drop in benchmark pass rate tolerated before a change is reverted
+    #[serde(default)]
+    pub max_benchmark_regression: f64,
+    /// Time a new binary's health probe may take before the swap is refused
+    #[serde(default = "default_probe_timeout")]
+    pub probe_timeout_seconds: u64,
+}
+
+fn default_test_command() -> Vec<String> {
+    vec!["cargo".to_string(), "test".to_string(), "--workspace".to_string()]
+}
+
+fn default_test_timeout() -> u64 {
+    900
+}
+
+fn default_probe_timeout() -> u64 {
+    120
+}
+
+fn default_build_target_dir() -> PathBuf {
+    PathBuf::from(".agent/build/target")
 }
 
 impl Default for SelfCompileConfig {
@@ -1626,9 +2213,15 @@ impl Default for SelfCompileConfig {
             backup_count: 5,
             build_profile: "release".to_string(),
             build_args: vec![],
+            target_dir: default_build_target_dir(),
             compile_timeout_seconds: 300,
             verify_before_restart: true,
             rollback_on_failure: true,
+            test_command: default_test_command(),
+            test_timeout_seconds: default_test_timeout(),
+            benchmark_suite: None,
+            max_benchmark_regression: 0.0,
+            probe_timeout_seconds: default_probe_timeout(),
         }
     }
 }
@@ -1721,6 +2314,87 @@ mod tests {
         assert!(config.llm.routing.max_latency_ms > 0);
     }
 
+    #[test]
+    fn test_budget_for_intent() {
+        let mut config = AgentConfig::default();
+        assert_eq!(config.agent.budget.default, TaskBudget::default());
+
+        let testing = TaskBudget {
+            max_tool_calls: 50,
+            max_cost_usd: 0.5,
+            ..Default::default()
+        };
+        config.agent.budget.per_intent.insert("testing".to_string(), testing.clone());
+        assert_eq!(config.agent.budget.for_intent("testing"), &testing);
+        assert_eq!(config.agent.budget.for_intent("analysis"), &TaskBudget::default());
+    }
+
+    #[test]
+    fn test_control_listen_must_be_local() {
+        let mut config = AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        assert!(config.control.tcp_addr().is_none());
+        assert!(config.validate().is_ok());
+
+        config.control.listen = "127.0.0.1:7878".to_string();
+        assert!(config.control.tcp_addr().is_some());
+        assert!(config.validate().is_ok());
+
+        config.control.listen = "0.0.0.0:7878".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tracing_needs_a_destination() {
+        let mut config = AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        config.tracing.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.tracing.endpoint = Some("http://localhost:4318".to_string());
+        assert!(config.validate().is_ok());
+
+        config.tracing.export_interval_seconds = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_mcp_servers_need_unique_names_and_commands() {
+        let mut config = AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        let server: McpServerConfig = toml::from_str(
+            r#"
+            name = "jira"
+            command = ["jira-mcp", "--stdio"]
+            "#,
+        )
+        .unwrap();
+        assert!(server.enabled);
+        assert_eq!(server.timeout_seconds, 60);
+
+        config.tools.mcp_servers.push(server.clone());
+        assert!(config.validate().is_ok());
+
+        config.tools.mcp_servers.push(server);
+        assert!(config.validate().is_err());
+
+        config.tools.mcp_servers.pop();
+        config.tools.mcp_servers[0].command.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_adaptive_routing_exploration_rate_is_bounded() {
+        let mut config = AgentConfig::default();
+        config.llm.provider = "ollama".to_string();
+        assert!(!config.llm.routing.adaptive.enabled);
+
+        config.llm.routing.adaptive.exploration_rate = 1.5;
+        assert!(config.validate().is_err());
+        config.llm.routing.adaptive.exploration_rate = 0.0;
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_self_compile_defaults() {
         let config = AgentConfig::default();
@@ -1728,6 +2402,8 @@ mod tests {
         assert!(config.self_compile.auto_restart);
         assert_eq!(config.self_compile.build_profile, "release");
         assert!(config.self_compile.backup_count > 0);
+        assert_eq!(config.self_compile.test_command, ["cargo", "test", "--workspace"]);
+        assert!(config.self_compile.benchmark_suite.is_none());
     }
 
     #[test]
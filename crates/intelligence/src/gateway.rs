@@ -681,6 +681,173 @@ impl LlmGateway for MockGateway {
     }
 }
 
+/// One recorded model call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub prompt: String,
+    pub content: String,
+    pub tokens_used: u32,
+    pub model: String,
+    pub finish_reason: String,
+}
+
+/// Appends every exchange with the wrapped gateway to a JSON lines file
+pub struct RecordingGateway {
+    inner: Box<dyn LlmGateway>,
+    file: std::sync::Mutex<std::fs::File>,
+}
+
+impl RecordingGateway {
+    pub fn new(inner: Box<dyn LlmGateway>, path: impl AsRef<std::path::Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            inner,
+            file: std::sync::Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl LlmGateway for RecordingGateway {
+    async fn initialize(&mut self) -> Result<()> {
+        self.inner.initialize().await
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<super::GenerationResult> {
+        use std::io::Write;
+
+        let result = self.inner.generate(prompt).await?;
+        let mut line = serde_json::to_vec(&Exchange {
+            prompt: prompt.to_string(),
+            content: result.content.clone(),
+            tokens_used: result.tokens_used,
+            model: result.model.clone(),
+            finish_reason: result.finish_reason.clone(),
+        })?;
+        line.push(b'\n');
+        self.file
+            .lock()
+            .map_err(|_| Error::Internal("Recording file lock poisoned".to_string()))?
+            .write_all(&line)?;
+        Ok(result)
+    }
+
+    async fn generate_stream(&self, prompt: &str) -> Result<StreamResult> {
+        // Recorded as a whole so a replay can return it in one piece
+        let result = self.generate(prompt).await?;
+        let stream = futures::stream::iter(vec![Ok(StreamChunk {
+            content: result.content,
+            is_finished: true,
+        })]);
+        Ok(Box::pin(stream))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+}
+
+/// Answers model calls with previously recorded exchanges, in order
+///
+/// Prompts are not matched against the recording since they embed task ids
+/// and timestamps; a run that makes more calls than were recorded fails.
+pub struct ReplayGateway {
+    exchanges: std::sync::Mutex<std::collections::VecDeque<Exchange>>,
+}
+
+impl ReplayGateway {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            exchanges: std::sync::Mutex::new(exchanges.into()),
+        }
+    }
+
+    /// Load a file written by [`RecordingGateway`]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let exchanges = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<Vec<Exchange>, _>>()?;
+        Ok(Self::new(exchanges))
+    }
+
+    /// Recorded exchanges not yet replayed
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().map(|e| e.len()).unwrap_or(0)
+    }
+}
+
+#[async_trait]
+impl LlmGateway for ReplayGateway {
+    async fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn generate(&self, _prompt: &str) -> Result<super::GenerationResult> {
+        let exchange = self
+            .exchanges
+            .lock()
+            .map_err(|_| Error::Internal("Replay lock poisoned".to_string()))?
+            .pop_front()
+            .ok_or_else(|| Error::ExternalService("Recording has no more exchanges to replay".to_string()))?;
+        Ok(super::GenerationResult {
+            content: exchange.content,
+            tokens_used: exchange.tokens_used,
+            model: exchange.model,
+            finish_reason: exchange.finish_reason,
+        })
+    }
+
+    async fn generate_stream(&self, prompt: &str) -> Result<StreamResult> {
+        let result = self.generate(prompt).await?;
+        let stream = futures::stream::iter(vec![Ok(StreamChunk {
+            content: result.content,
+            is_finished: true,
+        })]);
+        Ok(Box::pin(stream))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        Ok(Vec::new())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Record `gateway`'s exchanges to `record_to`, or replace it with a replay of `replay_from`
+pub fn with_recording(
+    gateway: Box<dyn LlmGateway>,
+    record_to: Option<&str>,
+    replay_from: Option<&str>,
+) -> Result<Box<dyn LlmGateway>> {
+    if let Some(path) = replay_from {
+        return Ok(Box::new(ReplayGateway::load(path)?));
+    }
+    if let Some(path) = record_to {
+        return Ok(Box::new(RecordingGateway::new(gateway, path)?));
+    }
+    Ok(gateway)
+}
+
 /// Gateway factory for creating appropriate gateway instances
 pub struct GatewayFactory {
     client: reqwest::Client,
@@ -757,4 +924,22 @@ mod tests {
         let init_result = gateway.initialize().await;
         assert!(init_result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let recorder = RecordingGateway::new(Box::new(MockGateway::new()), &path).unwrap();
+        let first = recorder.generate("write some code").await.unwrap();
+        recorder.generate("make a plan").await.unwrap();
+
+        let replay = ReplayGateway::load(&path).unwrap();
+        assert_eq!(replay.remaining(), 2);
+        let replayed = replay.generate("a different prompt").await.unwrap();
+        assert_eq!(replayed.content, first.content);
+        assert_eq!(replayed.tokens_used, first.tokens_used);
+        replay.generate("make a plan").await.unwrap();
+        assert!(replay.generate("one too many").await.is_err());
+    }
 }
\ No newline at end of file
@@ -137,6 +137,9 @@ pub struct ExecutionContext {
     pub previous_actions: Vec<Action>,
     pub tool_outputs: Vec<ToolOutput>,
     pub error_messages: Vec<String>,
+    /// Full observe-think-act history of the current task
+    #[serde(default)]
+    pub trajectory: Vec<TrajectoryStep>,
 }
 
 /// System-related context
@@ -148,10 +151,19 @@ pub struct SystemContext {
 }
 
 /// Resource limits
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+///
+/// A zero value leaves that resource unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ResourceLimits {
     pub max_tokens: u32,
     pub timeout_seconds: u64,
+    #[serde(default)]
+    pub max_llm_calls: u32,
+    #[serde(default)]
+    pub max_tool_calls: u32,
+    /// Estimated spend in USD
+    #[serde(default)]
+    pub max_cost_usd: f64,
 }
 
 /// Action record
@@ -171,6 +183,17 @@ pub struct ToolOutput {
     pub success: bool,
 }
 
+/// One iteration of the observe-think-act loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrajectoryStep {
+    pub iteration: u32,
+    pub thought: Option<String>,
+    pub action: Option<Action>,
+    pub observation: Option<ToolOutput>,
+    pub decision: String,
+    pub tokens_used: u32,
+}
+
 /// Generation result from LLM
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerationResult {
@@ -0,0 +1,738 @@
+//! Human approval for risky tool calls.
+//!
+//! Calls matching `SafetyConfig::require_approval_for` are held with a diff or
+//! command preview until someone approves, denies or edits them. Requests
+//! that are not decided in time are denied, and every decision is appended
+//! to an audit trail.
+
+use common::chrono::{DateTime, Utc};
+use common::{Error, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, oneshot};
+use tracing::{info, warn};
+
+use crate::diff::unified_diff;
+
+/// Default directory for pending requests, inbox decisions and the audit trail
+pub const DEFAULT_APPROVALS_DIR: &str = ".agent/approvals";
+
+/// Default directory for task worktrees, relative to the workspace
+const DEFAULT_WORKTREE_DIR: &str = ".agent/worktrees";
+
+/// Largest file whose contents are included in a preview
+const MAX_PREVIEW_BYTES: u64 = 256 * 1024;
+
+/// A tool call waiting for a decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub tool: String,
+    /// Entry of `require_approval_for` that matched the call
+    pub rule: String,
+    pub args: Value,
+    /// Diff or command preview shown to the reviewer
+    pub preview: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Reviewer's answer to an approval request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run the call as requested
+    Approve,
+    /// Do not run the call
+    Deny {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Run the call with these arguments instead
+    Edit { args: Value },
+    /// Nobody decided in time; treated as a denial
+    TimedOut,
+}
+
+impl ApprovalDecision {
+    /// Whether the call may run
+    pub fn is_approved(&self) -> bool {
+        matches!(self, ApprovalDecision::Approve | ApprovalDecision::Edit { .. })
+    }
+}
+
+/// Audit trail entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub request: ApprovalRequest,
+    pub decision: ApprovalDecision,
+    pub decided_by: String,
+    pub decided_at: DateTime<Utc>,
+}
+
+/// Decision dropped into the inbox by another process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InboxDecision {
+    id: String,
+    #[serde(flatten)]
+    decision: ApprovalDecision,
+    decided_by: String,
+}
+
+/// Which tool calls need approval
+///
+/// Rules are the entries of `SafetyConfig::require_approval_for`:
+/// - `delete`: any `delete` operation
+/// - `modify_protected`: writes, edits or deletes under a protected path
+/// - `<tool>_<operation>`, e.g. `git_push`
+/// - `<tool>`: every call to that tool
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicy {
+    rules: Vec<String>,
+    protected_paths: Vec<glob::Pattern>,
+    /// Where task worktrees live, relative to the workspace
+    worktree_dir: PathBuf,
+    timeout: Duration,
+}
+
+impl ApprovalPolicy {
+    /// Build the policy from the safety configuration
+    pub fn from_config(safety: &agent_config::SafetyConfig) -> Self {
+        let protected_paths = safety
+            .protected_paths
+            .iter()
+            .filter_map(|p| match glob::Pattern::new(p) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!("Ignoring invalid protected path pattern '{}': {}", p, e);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            rules: safety.require_approval_for.clone(),
+            protected_paths,
+            worktree_dir: PathBuf::from(DEFAULT_WORKTREE_DIR),
+            timeout: Duration::from_secs(safety.approval_timeout_seconds),
+        }
+    }
+
+    /// Set where task worktrees live, so paths inside them are protected too
+    pub fn with_worktree_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.worktree_dir = dir.into();
+        self
+    }
+
+    /// Set how long to wait for a decision
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long requests wait for a decision
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// The first rule requiring approval for a call, if any
+    pub fn matching_rule(&self, tool: &str, args: &Value) -> Option<String> {
+        let operation = args.get("operation").and_then(|v| v.as_str()).unwrap_or("");
+        let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+
+        self.rules
+            .iter()
+            .find(|rule| match rule.as_str() {
+                "delete" => operation == "delete",
+                "modify_protected" => {
+                    matches!(operation, "write" | "edit" | "delete") && self.is_protected(path)
+                }
+                rule => rule == tool || *rule == format!("{}_{}", tool, operation),
+            })
+            .cloned()
+    }
+
+    /// Whether a path is covered by a protected glob
+    ///
+    /// Globs are relative to the workspace, while a call may name the file
+    /// through `..`, absolutely, or inside a task worktree.
+    fn is_protected(&self, path: &str) -> bool {
+        self.workspace_forms(path).iter().any(|path| {
+            let path = path.to_string_lossy();
+            self.protected_paths.iter().any(|p| p.matches(&path))
+        })
+    }
+
+    /// The ways a path may be written relative to its workspace root
+    fn workspace_forms(&self, path: &str) -> Vec<PathBuf> {
+        let mut path = normalize(Path::new(path));
+        if path.is_absolute() {
+            let cwd = std::env::current_dir().map(|cwd| normalize(&cwd)).unwrap_or_default();
+            match path.strip_prefix(&cwd) {
+                Ok(relative) => path = relative.to_path_buf(),
+                // The workspace root is unknown, so any tail may be relative to it
+                Err(_) => {
+                    let components: Vec<Component> = path.components().collect();
+                    return (1..components.len()).map(|i| components[i..].iter().collect()).collect();
+                }
+            }
+        }
+
+        // Paths inside `<worktree_dir>/<name>/` are relative to that worktree
+        let components: Vec<Component> = path.components().collect();
+        let dir: Vec<Component> = self.worktree_dir.components().collect();
+        let mut forms = vec![path.clone()];
+        for start in 0..components.len() {
+            let rest = start + dir.len() + 1;
+            if rest < components.len() && components[start..].starts_with(&dir) {
+                forms.push(components[rest..].iter().collect());
+            }
+        }
+        forms
+    }
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self::from_config(&agent_config::SafetyConfig::default())
+    }
+}
+
+/// A held call and the channel its decision is sent on
+struct PendingApproval {
+    request: ApprovalRequest,
+    responder: oneshot::Sender<(ApprovalDecision, String)>,
+}
+
+/// Holds tool calls that need approval until they are decided
+///
+/// Reviewers either subscribe in-process (interactive mode) or, when the gate
+/// has a storage directory and its inbox is being watched, decide from
+/// another process with `coding-agent approvals`. Without any reviewer,
+/// matching calls are denied immediately.
+pub struct ApprovalGate {
+    policy: ApprovalPolicy,
+    pending: Mutex<HashMap<String, PendingApproval>>,
+    notifier: broadcast::Sender<ApprovalRequest>,
+    /// Watchers that see requests but cannot decide them
+    observers: broadcast::Sender<ApprovalRequest>,
+    audit: Mutex<Vec<ApprovalRecord>>,
+    storage: Option<PathBuf>,
+    inbox_watched: AtomicBool,
+}
+
+impl ApprovalGate {
+    pub fn new(policy: ApprovalPolicy) -> Self {
+        let (notifier, _) = broadcast::channel(64);
+        let (observers, _) = broadcast::channel(64);
+        Self {
+            policy,
+            pending: Mutex::new(HashMap::new()),
+            notifier,
+            observers,
+            audit: Mutex::new(Vec::new()),
+            storage: None,
+            inbox_watched: AtomicBool::new(false),
+        }
+    }
+
+    /// Persist pending requests and the audit trail under `dir`
+    pub fn with_storage(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.storage = Some(dir.into());
+        self
+    }
+
+    pub fn policy(&self) -> &ApprovalPolicy {
+        &self.policy
+    }
+
+    /// Receive requests as they are raised
+    pub fn subscribe(&self) -> broadcast::Receiver<ApprovalRequest> {
+        self.notifier.subscribe()
+    }
+
+    /// Receive requests as they are raised without counting as a reviewer
+    pub fn observe(&self) -> broadcast::Receiver<ApprovalRequest> {
+        self.observers.subscribe()
+    }
+
+    /// Requests waiting for a decision, oldest first
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        let mut requests: Vec<ApprovalRequest> =
+            self.pending.lock().values().map(|p| p.request.clone()).collect();
+        requests.sort_by_key(|r| r.requested_at);
+        requests
+    }
+
+    /// Decisions made so far in this process
+    pub fn audit_trail(&self) -> Vec<ApprovalRecord> {
+        self.audit.lock().clone()
+    }
+
+    fn has_reviewer(&self) -> bool {
+        self.notifier.receiver_count() > 0 || self.inbox_watched.load(Ordering::Relaxed)
+    }
+
+    /// Hold a tool call until it is decided
+    ///
+    /// Returns the arguments to run the call with: unchanged for calls that
+    /// need no approval or were approved, or the reviewer's edited version.
+    pub async fn authorize(&self, tool: &str, args: Value) -> Result<Value> {
+        let Some(rule) = self.policy.matching_rule(tool, &args) else {
+            return Ok(args);
+        };
+
+        let request = ApprovalRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            tool: tool.to_string(),
+            rule,
+            preview: preview(tool, &args).await,
+            args,
+            requested_at: Utc::now(),
+        };
+
+        let (decision, decided_by) = if self.has_reviewer() {
+            self.wait_for_decision(&request).await
+        } else {
+            warn!("No reviewer available for '{}' call requiring approval", tool);
+            (
+                ApprovalDecision::Deny {
+                    reason: Some("no reviewer available".to_string()),
+                },
+                "system".to_string(),
+            )
+        };
+
+        self.record(ApprovalRecord {
+            request: request.clone(),
+            decision: decision.clone(),
+            decided_by,
+            decided_at: Utc::now(),
+        })
+        .await;
+
+        match decision {
+            ApprovalDecision::Approve => Ok(request.args),
+            ApprovalDecision::Edit { args } => Ok(args),
+            ApprovalDecision::Deny { reason } => Err(Error::PermissionDenied(format!(
+                "Tool '{}' call requiring approval ({}) was denied: {}",
+                tool,
+                request.rule,
+                reason.unwrap_or_else(|| "no reason given".to_string())
+            ))),
+            ApprovalDecision::TimedOut => Err(Error::PermissionDenied(format!(
+                "Tool '{}' call requiring approval ({}) was not approved within {}s",
+                tool,
+                request.rule,
+                self.policy.timeout.as_secs()
+            ))),
+        }
+    }
+
+    async fn wait_for_decision(&self, request: &ApprovalRequest) -> (ApprovalDecision, String) {
+        let (responder, decision) = oneshot::channel();
+        self.pending.lock().insert(
+            request.id.clone(),
+            PendingApproval {
+                request: request.clone(),
+                responder,
+            },
+        );
+        if let Err(e) = self.persist_pending(request).await {
+            warn!("Failed to persist approval request {}: {}", request.id, e);
+        }
+
+        info!(
+            "Tool call '{}' awaiting approval ({}): request {}",
+            request.tool, request.rule, request.id
+        );
+        let _ = self.notifier.send(request.clone());
+        let _ = self.observers.send(request.clone());
+
+        let outcome = match tokio::time::timeout(self.policy.timeout, decision).await {
+            Ok(Ok(outcome)) => outcome,
+            _ => {
+                self.pending.lock().remove(&request.id);
+                (ApprovalDecision::TimedOut, "timeout".to_string())
+            }
+        };
+
+        if let Some(dir) = &self.storage {
+            let _ = tokio::fs::remove_file(dir.join("pending").join(format!("{}.json", request.id))).await;
+        }
+        outcome
+    }
+
+    /// Decide a pending request
+    pub fn decide(&self, id: &str, decision: ApprovalDecision, decided_by: &str) -> Result<()> {
+        let pending = self
+            .pending
+            .lock()
+            .remove(id)
+            .ok_or_else(|| Error::NotFound(format!("No pending approval request: {}", id)))?;
+
+        pending
+            .responder
+            .send((decision, decided_by.to_string()))
+            .map_err(|_| Error::Internal(format!("Approval request {} is no longer waiting", id)))
+    }
+
+    /// Apply decisions submitted through the storage inbox
+    ///
+    /// Returns the number of decisions applied.
+    pub async fn poll_inbox(&self) -> Result<usize> {
+        let Some(dir) = &self.storage else {
+            return Ok(0);
+        };
+        let inbox = dir.join("decisions");
+        let mut entries = match tokio::fs::read_dir(&inbox).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        let mut applied = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let content = tokio::fs::read_to_string(&path).await?;
+            match serde_json::from_str::<InboxDecision>(&content) {
+                Ok(submitted) => match self.decide(&submitted.id, submitted.decision, &submitted.decided_by) {
+                    Ok(()) => applied += 1,
+                    Err(e) => warn!("Ignoring inbox decision {}: {}", path.display(), e),
+                },
+                Err(e) => warn!("Ignoring malformed inbox decision {}: {}", path.display(), e),
+            }
+            tokio::fs::remove_file(&path).await?;
+        }
+
+        Ok(applied)
+    }
+
+    /// Poll the storage inbox in the background, making the gate accept
+    /// decisions from other processes
+    pub fn spawn_inbox_watcher(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        self.inbox_watched.store(true, Ordering::Relaxed);
+        let gate = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = gate.poll_inbox().await {
+                    warn!("Failed to read approval inbox: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn persist_pending(&self, request: &ApprovalRequest) -> Result<()> {
+        let Some(dir) = &self.storage else {
+            return Ok(());
+        };
+        let pending_dir = dir.join("pending");
+        tokio::fs::create_dir_all(&pending_dir).await?;
+        let content = serde_json::to_string_pretty(request)?;
+        let tmp = pending_dir.join(format!("{}.json.tmp", request.id));
+        tokio::fs::write(&tmp, content).await?;
+        tokio::fs::rename(&tmp, pending_dir.join(format!("{}.json", request.id))).await?;
+        Ok(())
+    }
+
+    async fn record(&self, record: ApprovalRecord) {
+        info!(
+            "Approval {} for '{}' ({}): {:?} by {}",
+            record.request.id, record.request.tool, record.request.rule, record.decision, record.decided_by
+        );
+        if let Some(dir) = &self.storage {
+            if let Err(e) = append_audit(dir, &record).await {
+                warn!("Failed to write approval audit trail: {}", e);
+            }
+        }
+        self.audit.lock().push(record);
+    }
+}
+
+async fn append_audit(dir: &Path, record: &ApprovalRecord) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("audit.jsonl"))
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+/// Requests persisted by a running agent under `dir`
+pub async fn list_pending(dir: &Path) -> Result<Vec<ApprovalRequest>> {
+    let mut entries = match tokio::fs::read_dir(dir.join("pending")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::Io(e)),
+    };
+
+    let mut requests = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let content = tokio::fs::read_to_string(&path).await?;
+        requests.push(serde_json::from_str::<ApprovalRequest>(&content)?);
+    }
+    requests.sort_by_key(|r| r.requested_at);
+    Ok(requests)
+}
+
+/// Submit a decision for a running agent to pick up from its inbox
+pub async fn submit_decision(dir: &Path, id: &str, decision: ApprovalDecision, decided_by: &str) -> Result<()> {
+    if !dir.join("pending").join(format!("{}.json", id)).exists() {
+        return Err(Error::NotFound(format!("No pending approval request: {}", id)));
+    }
+
+    let inbox = dir.join("decisions");
+    tokio::fs::create_dir_all(&inbox).await?;
+    let submitted = InboxDecision {
+        id: id.to_string(),
+        decision,
+        decided_by: decided_by.to_string(),
+    };
+    let tmp = inbox.join(format!("{}.json.tmp", id));
+    tokio::fs::write(&tmp, serde_json::to_string(&submitted)?).await?;
+    tokio::fs::rename(&tmp, inbox.join(format!("{}.json", id))).await?;
+    Ok(())
+}
+
+/// Audit trail written under `dir`
+pub async fn read_audit_trail(dir: &Path) -> Result<Vec<ApprovalRecord>> {
+    let content = match tokio::fs::read_to_string(dir.join("audit.jsonl")).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::Io(e)),
+    };
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(Error::from))
+        .collect()
+}
+
+/// Describe what a call would do
+async fn preview(tool: &str, args: &Value) -> String {
+    let operation = args.get("operation").and_then(|v| v.as_str()).unwrap_or("");
+    let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+
+    match (tool, operation) {
+        ("filesystem", "write") => {
+            let new = args.get("content").and_then(|v| v.as_str()).unwrap_or("");
+            let old = read_for_preview(path).await.unwrap_or_default();
+            let diff = unified_diff(path, &old, new);
+            if diff.is_empty() {
+                format!("write {} (no changes)", path)
+            } else {
+                diff
+            }
+        }
+        ("filesystem", "delete") => match read_for_preview(path).await {
+            Some(old) => unified_diff(path, &old, ""),
+            None => format!("delete {}", path),
+        },
+        ("git", operation) => {
+            let dir = if path.is_empty() { "." } else { path };
+            let mut preview = format!("$ git {}   (in {})", operation, dir);
+            if operation == "push" {
+                // Show what would leave the machine
+                if let Ok(output) = tokio::process::Command::new("git")
+                    .current_dir(dir)
+                    .args(["log", "--oneline", "@{upstream}..HEAD"])
+                    .output()
+                    .await
+                {
+                    if output.status.success() {
+                        preview.push_str("\ncommits to push:\n");
+                        preview.push_str(&String::from_utf8_lossy(&output.stdout));
+                    }
+                }
+            }
+            preview
+        }
+        _ => format!(
+            "{} {}",
+            tool,
+            serde_json::to_string_pretty(args).unwrap_or_else(|_| args.to_string())
+        ),
+    }
+}
+
+async fn read_for_preview(path: &str) -> Option<String> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_PREVIEW_BYTES {
+        return None;
+    }
+    tokio::fs::read_to_string(path).await.ok()
+}
+
+/// Drop `.` segments and resolve `..` where a parent is known
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gate(timeout_ms: u64) -> Arc<ApprovalGate> {
+        let policy = ApprovalPolicy::default().with_timeout(Duration::from_millis(timeout_ms));
+        Arc::new(ApprovalGate::new(policy))
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let policy = ApprovalPolicy::default();
+
+        let delete = serde_json::json!({"operation": "delete", "path": "src/old.rs"});
+        assert_eq!(policy.matching_rule("filesystem", &delete).as_deref(), Some("delete"));
+
+        let protected = serde_json::json!({"operation": "write", "path": ".agent/safety/rules.toml"});
+        assert_eq!(policy.matching_rule("filesystem", &protected).as_deref(), Some("modify_protected"));
+
+        let push = serde_json::json!({"operation": "push", "path": "."});
+        assert_eq!(policy.matching_rule("git", &push).as_deref(), Some("git_push"));
+
+        let write = serde_json::json!({"operation": "write", "path": "src/lib.rs"});
+        assert_eq!(policy.matching_rule("filesystem", &write), None);
+    }
+
+    #[test]
+    fn test_protected_paths_in_other_forms() {
+        let policy = ApprovalPolicy::default().with_worktree_dir("scratch/trees");
+        let protected = |path: &str| {
+            let args = serde_json::json!({"operation": "write", "path": path});
+            policy.matching_rule("filesystem", &args).as_deref() == Some("modify_protected")
+        };
+
+        assert!(protected("./.agent/safety/rules.toml"));
+        assert!(protected("src/../.agent/safety/rules.toml"));
+        let cwd = std::env::current_dir().unwrap();
+        assert!(protected(&cwd.join(".agent/safety/rules.toml").display().to_string()));
+        assert!(protected("/elsewhere/repo/.agent/core/agent.toml"));
+        assert!(protected("scratch/trees/task-1/.agent/safety/rules.toml"));
+        assert!(protected("repo/scratch/trees/task-1/.agent/auth/keys"));
+
+        assert!(!protected("src/.agent/safety.rs"));
+        assert!(!protected(".agent/worktrees/task-1/src/lib.rs"));
+        assert!(!protected(&cwd.join("src/lib.rs").display().to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_denied_without_reviewer() {
+        let gate = gate(1000);
+        let args = serde_json::json!({"operation": "push", "path": "."});
+        // Observers do not count as reviewers
+        let _observer = gate.observe();
+
+        let result = gate.authorize("git", args).await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+
+        let trail = gate.audit_trail();
+        assert_eq!(trail.len(), 1);
+        assert_eq!(trail[0].decided_by, "system");
+
+        // Calls outside the policy pass straight through
+        let status = serde_json::json!({"operation": "status", "path": "."});
+        assert!(gate.authorize("git", status).await.is_ok());
+        assert_eq!(gate.audit_trail().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_timeout() {
+        let gate = gate(200);
+        let mut requests = gate.subscribe();
+        // Keeps a reviewer attached after the first one leaves
+        let _idle_reviewer = gate.subscribe();
+
+        let reviewer = Arc::clone(&gate);
+        let review = tokio::spawn(async move {
+            let request = requests.recv().await.unwrap();
+            assert!(request.preview.contains("git push"));
+            let edited = serde_json::json!({"operation": "status", "path": "."});
+            reviewer
+                .decide(&request.id, ApprovalDecision::Edit { args: edited }, "alice")
+                .unwrap();
+        });
+
+        let args = serde_json::json!({"operation": "push", "path": "."});
+        let approved = gate.authorize("git", args.clone()).await.unwrap();
+        assert_eq!(approved["operation"], "status");
+        review.await.unwrap();
+
+        // Nobody answers the second request
+        let result = gate.authorize("git", args).await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+
+        let trail = gate.audit_trail();
+        assert_eq!(trail[0].decided_by, "alice");
+        assert!(matches!(trail[1].decision, ApprovalDecision::TimedOut));
+        assert!(gate.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_inbox_decisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let gate = Arc::new(
+            ApprovalGate::new(ApprovalPolicy::default().with_timeout(Duration::from_secs(5)))
+                .with_storage(dir.path()),
+        );
+        let watcher = gate.spawn_inbox_watcher(Duration::from_millis(20));
+
+        let args = serde_json::json!({"operation": "delete", "path": "missing.txt"});
+        let waiting = {
+            let gate = Arc::clone(&gate);
+            tokio::spawn(async move { gate.authorize("filesystem", args).await })
+        };
+
+        let request = loop {
+            if let Some(request) = list_pending(dir.path()).await.unwrap().pop() {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        submit_decision(
+            dir.path(),
+            &request.id,
+            ApprovalDecision::Deny { reason: Some("keep it".to_string()) },
+            "bob",
+        )
+        .await
+        .unwrap();
+
+        let result = waiting.await.unwrap();
+        assert!(matches!(result, Err(Error::PermissionDenied(ref m)) if m.contains("keep it")));
+        watcher.abort();
+
+        let trail = read_audit_trail(dir.path()).await.unwrap();
+        assert_eq!(trail.len(), 1);
+        assert_eq!(trail[0].decided_by, "bob");
+        assert!(list_pending(dir.path()).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! Line-based unified diffs for change previews.
+
+/// Context lines shown around each change
+const CONTEXT: usize = 3;
+
+/// Above this many line comparisons the diff degrades to a full replacement
+const MAX_COMPARISONS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Render a unified diff between two versions of a file
+///
+/// Returns an empty string when the contents are identical.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    render(&format!("a/{}", path), &format!("b/{}", path), old, new)
+}
+
+/// Render a diff that can create or delete the file
+///
+/// A missing side is labelled `/dev/null` so the result applies with `git apply`.
+pub fn file_diff(path: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let label = |side: &str, content: Option<&str>| match content {
+        Some(_) => format!("{}/{}", side, path),
+        None => "/dev/null".to_string(),
+    };
+    render(
+        &label("a", old),
+        &label("b", new),
+        old.unwrap_or_default(),
+        new.unwrap_or_default(),
+    )
+}
+
+fn render(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    // Lines keep their terminator so a missing final newline counts as a change
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&a, &b);
+    if ops.iter().all(|(op, _)| *op == DiffOp::Equal) {
+        return String::new();
+    }
+
+    // Old and new line positions before each op
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_line, mut new_line) = (0usize, 0usize);
+    for (op, _) in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            DiffOp::Equal => {
+                old_line += 1;
+                new_line += 1;
+            }
+            DiffOp::Delete => old_line += 1,
+            DiffOp::Insert => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != DiffOp::Equal)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut i = 0;
+    while i < changed.len() {
+        let start = changed[i].saturating_sub(CONTEXT);
+        let mut last = changed[i];
+        // Merge changes whose context would overlap into one hunk
+        while i + 1 < changed.len() && changed[i + 1] <= last + 2 * CONTEXT + 1 {
+            i += 1;
+            last = changed[i];
+        }
+        let end = (last + CONTEXT + 1).min(ops.len());
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+        for (op, line) in &ops[start..end] {
+            let prefix = match op {
+                DiffOp::Equal => ' ',
+                DiffOp::Delete => '-',
+                DiffOp::Insert => '+',
+            };
+            out.push(prefix);
+            match line.strip_suffix('\n') {
+                Some(text) => {
+                    out.push_str(text);
+                    out.push('\n');
+                }
+                None => {
+                    out.push_str(line);
+                    out.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+        i += 1;
+    }
+
+    out
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Longest-common-subsequence line diff
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(DiffOp, &'a str)> {
+    if a.len().saturating_mul(b.len()) > MAX_COMPARISONS {
+        return a
+            .iter()
+            .map(|l| (DiffOp::Delete, *l))
+            .chain(b.iter().map(|l| (DiffOp::Insert, *l)))
+            .collect();
+    }
+
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            ops.push((DiffOp::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push((DiffOp::Delete, a[i]));
+            i += 1;
+        } else {
+            ops.push((DiffOp::Insert, b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[i..].iter().map(|l| (DiffOp::Delete, *l)));
+    ops.extend(b[j..].iter().map(|l| (DiffOp::Insert, *l)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\nk\n";
+        let diff = unified_diff("src/lib.rs", old, new);
+
+        assert!(diff.starts_with("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+        assert!(diff.contains("-d\n+D\n"));
+        assert!(diff.contains("+k\n"));
+        // Changes six lines apart share one hunk
+        assert_eq!(diff.matches("@@ -").count(), 1);
+        assert!(diff.contains("@@ -1,10 +1,11 @@"));
+
+        assert_eq!(unified_diff("same.rs", old, old), "");
+        assert!(unified_diff("new.rs", "", "x\n").contains("@@ -0,0 +1 @@\n+x\n"));
+
+        let created = file_diff("new.rs", None, Some("x\n"));
+        assert!(created.starts_with("--- /dev/null\n+++ b/new.rs\n"));
+        let deleted = file_diff("old.rs", Some("x\n"), None);
+        assert!(deleted.starts_with("--- a/old.rs\n+++ /dev/null\n"));
+
+        // A missing trailing newline is marked, and adding one is a change
+        let added = unified_diff("eof.rs", "a\nb", "a\nb\n");
+        assert!(added.contains("-b\n\\ No newline at end of file\n+b\n"));
+        let appended = unified_diff("eof.rs", "a\n", "a\nb");
+        assert!(appended.ends_with("+b\n\\ No newline at end of file\n"));
+    }
+}
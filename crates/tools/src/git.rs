@@ -16,14 +16,14 @@ impl Tool for GitTool {
     }
 
     fn description(&self) -> &str {
-        "Git operations including status, diff, commit, branch, and push"
+        "Git operations including status, diff, commit, branch, push, merge, and worktrees"
     }
 
     fn parameters(&self) -> Vec<Parameter> {
         vec![
             Parameter {
                 name: "operation".to_string(),
-                description: "Operation to perform: status, diff, commit, branch, log, push".to_string(),
+                description: "Operation to perform: status, diff, commit, branch, log, push, merge, revert, worktree_add, worktree_remove, worktree_list".to_string(),
                 required: true,
                 parameter_type: ParameterType::Enum(vec![
                     "status".to_string(),
@@ -32,6 +32,11 @@ impl Tool for GitTool {
                     "branch".to_string(),
                     "log".to_string(),
                     "push".to_string(),
+                    "merge".to_string(),
+                    "revert".to_string(),
+                    "worktree_add".to_string(),
+                    "worktree_remove".to_string(),
+                    "worktree_list".to_string(),
                 ]),
                 default: None,
             },
@@ -56,6 +61,41 @@ impl Tool for GitTool {
                 parameter_type: ParameterType::Array(Box::new(ParameterType::String)),
                 default: None,
             },
+            Parameter {
+                name: "branch".to_string(),
+                description: "Branch to create, merge or delete".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: None,
+            },
+            Parameter {
+                name: "commit".to_string(),
+                description: "Commit to revert".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: None,
+            },
+            Parameter {
+                name: "worktree".to_string(),
+                description: "Worktree directory, relative to the repository".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: None,
+            },
+            Parameter {
+                name: "base".to_string(),
+                description: "Commit a new worktree branch starts from".to_string(),
+                required: false,
+                parameter_type: ParameterType::String,
+                default: Some(Value::String("HEAD".to_string())),
+            },
+            Parameter {
+                name: "strategy".to_string(),
+                description: "How merge brings the branch in: merge or cherry_pick".to_string(),
+                required: false,
+                parameter_type: ParameterType::Enum(vec!["merge".to_string(), "cherry_pick".to_string()]),
+                default: Some(Value::String("merge".to_string())),
+            },
         ]
     }
 
@@ -104,6 +144,24 @@ impl Tool for GitTool {
             "branch" => self.branch(path).await,
             "log" => self.log(path).await,
             "push" => self.push(path).await,
+            "merge" => {
+                let branch = str_arg(args, "branch")?;
+                let strategy = args.get("strategy").and_then(|v| v.as_str()).unwrap_or("merge");
+                self.merge(path, branch, strategy).await
+            }
+            "revert" => self.revert(path, str_arg(args, "commit")?).await,
+            "worktree_add" => {
+                let worktree = str_arg(args, "worktree")?;
+                let branch = str_arg(args, "branch")?;
+                let base = args.get("base").and_then(|v| v.as_str()).unwrap_or("HEAD");
+                self.worktree_add(path, worktree, branch, base).await
+            }
+            "worktree_remove" => {
+                let worktree = str_arg(args, "worktree")?;
+                let branch = args.get("branch").and_then(|v| v.as_str());
+                self.worktree_remove(path, worktree, branch).await
+            }
+            "worktree_list" => self.worktree_list(path).await,
             _ => Err(Error::Validation(format!("Unknown operation: {}", operation))),
         }
     }
@@ -118,14 +176,8 @@ impl Tool for GitTool {
         Ok(())
     }
 
-    fn is_safe(&self, args: &Value) -> bool {
-        // Push operations require explicit approval
-        if let Some(op) = args.get("operation").and_then(|v| v.as_str()) {
-            if op == "push" {
-                // TODO: Check if approval is granted
-                return false;
-            }
-        }
+    fn is_safe(&self, _args: &Value) -> bool {
+        // Pushes are held for approval by the framework (`git_push` rule)
         true
     }
 }
@@ -265,4 +317,129 @@ impl GitTool {
             "output": stdout.to_string(),
         }))
     }
-}
\ No newline at end of file
+
+    /// Bring a branch into the current one, aborting cleanly on conflicts
+    async fn merge(&self, path: &str, branch: &str, strategy: &str) -> Result<Value> {
+        match strategy {
+            "merge" => {
+                if let Err(e) = self.run_git(path, &["merge", "--no-ff", "--no-edit", branch]).await {
+                    let _ = self.run_git(path, &["merge", "--abort"]).await;
+                    return Err(e);
+                }
+                Ok(serde_json::json!({
+                    "success": true,
+                    "strategy": strategy,
+                    "branch": branch,
+                }))
+            }
+            "cherry_pick" => {
+                let range = format!("HEAD..{}", branch);
+                let output = self.run_git(path, &["rev-list", "--reverse", &range]).await?;
+                let commits: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|l| l.to_string())
+                    .collect();
+
+                if !commits.is_empty() {
+                    let mut args = vec!["cherry-pick"];
+                    args.extend(commits.iter().map(|c| c.as_str()));
+                    if let Err(e) = self.run_git(path, &args).await {
+                        let _ = self.run_git(path, &["cherry-pick", "--abort"]).await;
+                        return Err(e);
+                    }
+                }
+                Ok(serde_json::json!({
+                    "success": true,
+                    "strategy": strategy,
+                    "branch": branch,
+                    "commits": commits,
+                }))
+            }
+            _ => Err(Error::Validation(format!("Unknown merge strategy: {}", strategy))),
+        }
+    }
+
+    /// Undo a commit with a new one; merges are reverted against their first parent
+    async fn revert(&self, path: &str, commit: &str) -> Result<Value> {
+        let output = self.run_git(path, &["rev-list", "--parents", "-n", "1", commit]).await?;
+        let parents = String::from_utf8_lossy(&output.stdout).split_whitespace().count().saturating_sub(1);
+
+        let mut args = vec!["revert", "--no-edit"];
+        if parents > 1 {
+            args.extend(["-m", "1"]);
+        }
+        args.push(commit);
+        if let Err(e) = self.run_git(path, &args).await {
+            let _ = self.run_git(path, &["revert", "--abort"]).await;
+            return Err(e);
+        }
+
+        Ok(serde_json::json!({
+            "success": true,
+            "reverted": commit,
+        }))
+    }
+
+    async fn worktree_add(&self, path: &str, worktree: &str, branch: &str, base: &str) -> Result<Value> {
+        self.run_git(path, &["worktree", "add", "-b", branch, worktree, base]).await?;
+        let output = self.run_git(path, &["rev-parse", base]).await?;
+        let base_commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        Ok(serde_json::json!({
+            "success": true,
+            "worktree": worktree,
+            "branch": branch,
+            "base": base_commit,
+        }))
+    }
+
+    /// Remove a worktree and optionally delete its branch
+    async fn worktree_remove(&self, path: &str, worktree: &str, branch: Option<&str>) -> Result<Value> {
+        if self.run_git(path, &["worktree", "remove", "--force", worktree]).await.is_err() {
+            // Directory already gone; drop the stale registration
+            self.run_git(path, &["worktree", "prune"]).await?;
+        }
+        if let Some(branch) = branch {
+            self.run_git(path, &["branch", "-D", branch]).await?;
+        }
+
+        Ok(serde_json::json!({
+            "success": true,
+            "worktree": worktree,
+        }))
+    }
+
+    async fn worktree_list(&self, path: &str) -> Result<Value> {
+        let output = self.run_git(path, &["worktree", "list", "--porcelain"]).await?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let worktrees: Vec<Value> = stdout
+            .split("\n\n")
+            .filter(|block| !block.trim().is_empty())
+            .map(|block| {
+                let field = |key: &str| {
+                    block
+                        .lines()
+                        .find_map(|l| l.strip_prefix(key))
+                        .map(|v| v.trim().to_string())
+                };
+                serde_json::json!({
+                    "path": field("worktree "),
+                    "head": field("HEAD "),
+                    "branch": field("branch ").map(|b| b.trim_start_matches("refs/heads/").to_string()),
+                })
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "success": true,
+            "worktrees": worktrees,
+        }))
+    }
+}
+
+fn str_arg<'a>(args: &'a Value, name: &str) -> Result<&'a str> {
+    args.get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| Error::Validation(format!("Missing {} parameter", name)))
+}
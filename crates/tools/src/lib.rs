@@ -7,10 +7,18 @@ use common::{async_trait, Error, Module, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::Instrument;
 
+pub mod approval;
+pub mod diff;
 pub mod filesystem;
 pub mod git;
+pub mod jsonrpc;
 pub mod learning;
+pub mod mcp;
+pub mod mcp_client;
+pub mod overlay;
 pub mod search;
 pub mod http;
 
@@ -19,6 +27,8 @@ pub struct ToolFramework {
     registry: ToolRegistry,
     execution_engine: ExecutionEngine,
     sandbox: Sandbox,
+    approval: Arc<approval::ApprovalGate>,
+    overlay: Option<Arc<overlay::Overlay>>,
 }
 
 impl ToolFramework {
@@ -27,6 +37,8 @@ impl ToolFramework {
             registry: ToolRegistry::new(),
             execution_engine: ExecutionEngine::new(),
             sandbox: Sandbox::new(),
+            approval: Arc::new(approval::ApprovalGate::new(approval::ApprovalPolicy::default())),
+            overlay: None,
         };
         framework.register_builtin_tools();
         framework
@@ -41,25 +53,103 @@ impl ToolFramework {
         self.registry.register(Box::new(http::HttpTool));
     }
 
+    /// Set the gate holding calls that need human approval
+    pub fn with_approval_gate(mut self, gate: Arc<approval::ApprovalGate>) -> Self {
+        self.approval = gate;
+        self
+    }
+
+    /// Gate holding calls that need human approval
+    pub fn approval_gate(&self) -> &Arc<approval::ApprovalGate> {
+        &self.approval
+    }
+
+    /// Redirect side effects to a dry-run overlay
+    pub fn with_overlay(mut self, overlay: Arc<overlay::Overlay>) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Dry-run overlay, if side effects are being redirected
+    pub fn overlay(&self) -> Option<&Arc<overlay::Overlay>> {
+        self.overlay.as_ref()
+    }
+
     /// Execute a tool by name
     pub async fn execute(&self, tool_name: &str, args: Value) -> Result<ToolResult> {
-        let tool = self.registry.get(tool_name)?;
+        let args = self.authorize(tool_name, args).await?;
+        self.execute_authorized(tool_name, args).await
+    }
+
+    /// Check a call and hold it for approval if the policy requires it
+    ///
+    /// Returns the arguments to execute with, which a reviewer may have edited.
+    pub async fn authorize(&self, tool_name: &str, args: Value) -> Result<Value> {
+        self.check(tool_name, &args)?;
+        // Nothing to approve when the call only touches the overlay
+        if self.overlay.is_some() && overlay::Overlay::captures(tool_name, &args) {
+            return Ok(args);
+        }
+        self.approval.authorize(tool_name, args).await
+    }
+
+    /// Execute a call that has already passed [`ToolFramework::authorize`]
+    ///
+    /// The call runs in an `execute_tool` span carrying the GenAI semantic
+    /// convention attributes.
+    pub async fn execute_authorized(&self, tool_name: &str, args: Value) -> Result<ToolResult> {
+        let span = tracing::info_span!(
+            "execute_tool",
+            otel.name = %format!("execute_tool {}", tool_name),
+            gen_ai.operation.name = "execute_tool",
+            gen_ai.tool.name = %tool_name,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+            error.type = tracing::field::Empty,
+        );
+        let result = async {
+            let tool = self.check(tool_name, &args)?;
+
+            // Execute in sandbox
+            self.sandbox.execute(|| async {
+                match &self.overlay {
+                    Some(overlay) => self.execution_engine.execute(&overlay.wrap(tool), &args).await,
+                    None => self.execution_engine.execute(tool, &args).await,
+                }
+            }).await
+        }
+        .instrument(span.clone())
+        .await;
+
+        let failure = match &result {
+            Ok(result) if result.success => None,
+            Ok(result) => Some(("tool_error", result.data.to_string())),
+            Err(e) => Some((e.kind(), e.to_string())),
+        };
+        if let Some((kind, message)) = failure {
+            span.record("otel.status_code", "ERROR");
+            span.record("otel.status_message", message.as_str());
+            span.record("error.type", kind);
+        }
+        result
+    }
+
+    /// Validate arguments and run the tool's safety check
+    fn check(&self, tool_name: &str, args: &Value) -> Result<&dyn Tool> {
+        let tool = self.registry.get(tool_name)?.as_ref();
 
         // Validate arguments
-        tool.validate(&args)?;
+        tool.validate(args)?;
 
         // Check safety
-        if !tool.is_safe(&args) {
+        if !tool.is_safe(args) {
             return Err(Error::PermissionDenied(format!(
                 "Tool '{}' execution blocked by safety check",
                 tool_name
             )));
         }
 
-        // Execute in sandbox
-        self.sandbox.execute(|| async {
-            self.execution_engine.execute(tool.as_ref(), &args).await
-        }).await
+        Ok(tool)
     }
 
     /// Get available tools
@@ -71,6 +161,19 @@ impl ToolFramework {
     pub fn register_tool(&mut self, tool: Box<dyn Tool>) {
         self.registry.register(tool);
     }
+
+    /// Launch external MCP servers and register their tools
+    ///
+    /// Returns how many tools were imported; servers that fail to start are
+    /// skipped.
+    pub async fn import_mcp_servers(&mut self, servers: &[agent_config::McpServerConfig]) -> usize {
+        let imported = mcp_client::import_servers(servers).await;
+        let count = imported.len();
+        for tool in imported {
+            self.registry.register(Box::new(tool));
+        }
+        count
+    }
 }
 
 #[async_trait]
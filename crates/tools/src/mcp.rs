@@ -0,0 +1,286 @@
+//! Model Context Protocol server.
+//!
+//! Serves the tool framework to editors and other agents over stdio, one
+//! JSON-RPC 2.0 message per line. Every registered [`Tool`] is advertised
+//! with an input schema derived from [`Tool::parameters`], and calls go
+//! through [`ToolFramework::execute`], so validation, safety checks and
+//! approvals apply exactly as they do for the agent itself.
+//!
+//! Requests are answered in order; a call held for approval delays the
+//! responses behind it.
+
+use crate::jsonrpc::{Request, Response, RpcError, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR};
+use crate::{Parameter, ParameterType, Tool, ToolFramework};
+use common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, warn};
+
+/// Protocol revision implemented here
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// A tool as advertised by `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolDescriptor {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub input_schema: Value,
+}
+
+impl ToolDescriptor {
+    pub fn from_tool(tool: &dyn Tool) -> Self {
+        Self {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            input_schema: input_schema(&tool.parameters()),
+        }
+    }
+}
+
+/// JSON schema for a tool's arguments
+pub fn input_schema(parameters: &[Parameter]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for parameter in parameters {
+        let mut schema = type_schema(&parameter.parameter_type);
+        schema["description"] = Value::String(parameter.description.clone());
+        if let Some(default) = &parameter.default {
+            schema["default"] = default.clone();
+        }
+        properties.insert(parameter.name.clone(), schema);
+        if parameter.required {
+            required.push(Value::String(parameter.name.clone()));
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// JSON schema for a single parameter type
+pub fn type_schema(parameter_type: &ParameterType) -> Value {
+    match parameter_type {
+        ParameterType::String => json!({"type": "string"}),
+        ParameterType::Integer => json!({"type": "integer"}),
+        ParameterType::Float => json!({"type": "number"}),
+        ParameterType::Boolean => json!({"type": "boolean"}),
+        ParameterType::Array(item) => json!({"type": "array", "items": type_schema(item)}),
+        ParameterType::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(name, field)| (name.clone(), type_schema(field)))
+                .collect();
+            json!({"type": "object", "properties": properties})
+        }
+        ParameterType::Enum(values) => json!({"type": "string", "enum": values}),
+    }
+}
+
+/// Serves a [`ToolFramework`] to MCP clients
+pub struct McpServer {
+    tools: Arc<ToolFramework>,
+    name: String,
+    version: String,
+}
+
+impl McpServer {
+    pub fn new(tools: Arc<ToolFramework>) -> Self {
+        Self {
+            tools,
+            name: "coding-agent".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Set the name and version reported to clients
+    pub fn with_server_info(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.name = name.into();
+        self.version = version.into();
+        self
+    }
+
+    /// Answer messages from `input` on `output` until `input` ends
+    pub async fn serve<R, W>(&self, input: R, mut output: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = BufReader::new(input).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_line(&line).await {
+                let mut bytes = serde_json::to_vec(&response)?;
+                bytes.push(b'\n');
+                output.write_all(&bytes).await?;
+                output.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one message; notifications and stray responses get no reply
+    pub async fn handle_line(&self, line: &str) -> Option<Response> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                return Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, e.to_string())),
+                ))
+            }
+        };
+        if message.get("method").is_none() && message.get("id").is_some() {
+            debug!("Ignoring response from MCP client");
+            return None;
+        }
+
+        let request: Request = match serde_json::from_value(message) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                ))
+            }
+        };
+        let Some(id) = request.id.clone() else {
+            debug!("MCP notification: {}", request.method);
+            return None;
+        };
+        Some(Response::new(id, self.dispatch(&request.method, request.params).await))
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> std::result::Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {"tools": {"listChanged": false}},
+                "serverInfo": {"name": self.name, "version": self.version},
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => {
+                let mut tools: Vec<ToolDescriptor> = self
+                    .tools
+                    .list_tools()
+                    .into_iter()
+                    .map(ToolDescriptor::from_tool)
+                    .collect();
+                tools.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(json!({"tools": tools}))
+            }
+            "tools/call" => self.call_tool(params).await,
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+
+    /// Run a tool; failures the client should see are results with `isError`
+    async fn call_tool(&self, params: Value) -> std::result::Result<Value, RpcError> {
+        let name = params
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        match self.tools.execute(name, arguments).await {
+            Ok(result) => Ok(call_result(&result.data, !result.success)),
+            Err(Error::NotFound(message)) => Err(RpcError::new(INVALID_PARAMS, message)),
+            Err(e) => {
+                warn!("MCP call to {} refused: {}", name, e);
+                Ok(call_result(&Value::String(e.to_string()), true))
+            }
+        }
+    }
+}
+
+/// `tools/call` result carrying `data` as text
+fn call_result(data: &Value, is_error: bool) -> Value {
+    let text = match data {
+        Value::String(text) => text.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    };
+    json!({
+        "content": [{"type": "text", "text": text}],
+        "isError": is_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_input_schema() {
+        let tools = ToolFramework::new();
+        let search = tools.list_tools().into_iter().find(|t| t.name() == "search").unwrap();
+        let schema = input_schema(&search.parameters());
+
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["operation"]["type"], "string");
+        assert_eq!(schema["properties"]["operation"]["enum"][0], "grep");
+        assert!(schema["required"].as_array().unwrap().contains(&json!("pattern")));
+        assert_eq!(
+            type_schema(&ParameterType::Array(Box::new(ParameterType::Integer))),
+            json!({"type": "array", "items": {"type": "integer"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scripted_stdio_session() {
+        let server = McpServer::new(Arc::new(ToolFramework::new()));
+        let (client, server_end) = tokio::io::duplex(64 * 1024);
+        let (server_read, server_write) = tokio::io::split(server_end);
+        let serving = tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let script = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": PROTOCOL_VERSION, "capabilities": {}, "clientInfo": {"name": "test", "version": "0"}}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "filesystem", "arguments": {"operation": "read", "path": "/etc/passwd"}}}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "missing", "arguments": {}}}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "resources/list"}),
+        ];
+        for message in &script {
+            client_write.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+        }
+        client_write.write_all(b"not json\n").await.unwrap();
+        client_write.shutdown().await.unwrap();
+
+        let mut lines = tokio::io::BufReader::new(client_read).lines();
+        let mut responses = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            responses.push(serde_json::from_str::<Value>(&line).unwrap());
+        }
+        serving.await.unwrap().unwrap();
+
+        // The notification gets no reply
+        assert_eq!(responses.len(), 6);
+        assert_eq!(responses[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let tools = responses[1]["result"]["tools"].as_array().unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["filesystem", "git", "http", "search"]);
+        assert_eq!(tools[0]["inputSchema"]["type"], "object");
+
+        // Absolute paths fail the filesystem tool's safety check
+        assert_eq!(responses[2]["result"]["isError"], true);
+        let text = responses[2]["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("safety check"), "{}", text);
+
+        assert_eq!(responses[3]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[4]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[5]["error"]["code"], PARSE_ERROR);
+    }
+}
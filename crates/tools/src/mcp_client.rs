@@ -0,0 +1,627 @@
+//! Model Context Protocol client.
+//!
+//! Launches external MCP servers over stdio and imports their tools as
+//! [`McpTool`]s, which register like any built-in tool under the name
+//! `<server>__<tool>`. Arguments are checked against each tool's input
+//! schema before they are sent. A request that times out kills its server,
+//! and a server that has exited is restarted on the next call, up to the
+//! configured number of restarts.
+
+use crate::jsonrpc::{Request, Response, RpcError, METHOD_NOT_FOUND};
+use crate::mcp::{ToolDescriptor, PROTOCOL_VERSION};
+use crate::{Parameter, ParameterType, ReturnType, Tool};
+use agent_config::McpServerConfig;
+use common::{async_trait, Error, Result};
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Separator between the server and tool parts of an imported tool's name
+pub const NAME_SEPARATOR: &str = "__";
+
+/// Paths no remote tool is handed in a path argument
+const SENSITIVE_PATHS: &[&str] = &[".ssh/", ".gnupg/", ".aws/"];
+
+/// Argument names that hold paths besides `*path`, `*file` and `*dir` ones
+const PATH_NAMES: &[&str] = &["cwd", "root", "directory", "source", "destination"];
+
+/// Schema `format`s that mark a string as a path
+const PATH_FORMATS: &[&str] = &["path", "file-path", "directory-path"];
+
+/// Whether `tool` names a tool imported from an MCP server
+pub fn is_imported(tool: &str) -> bool {
+    tool.contains(NAME_SEPARATOR)
+}
+
+/// A running server process
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Connection {
+    async fn send(&mut self, message: &impl serde::Serialize) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+/// Client for one stdio MCP server
+///
+/// Requests are sent one at a time.
+pub struct McpClient {
+    config: McpServerConfig,
+    connection: Mutex<Option<Connection>>,
+    next_id: AtomicU64,
+    restarts: AtomicU32,
+}
+
+impl McpClient {
+    /// Launch the server and complete the MCP handshake
+    pub async fn start(config: McpServerConfig) -> Result<Arc<Self>> {
+        let client = Self {
+            config,
+            connection: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+            restarts: AtomicU32::new(0),
+        };
+        let connection = client.spawn().await?;
+        *client.connection.lock().await = Some(connection);
+        Ok(Arc::new(client))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Times the server has been restarted
+    pub fn restarts(&self) -> u32 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    async fn spawn(&self) -> Result<Connection> {
+        let (program, args) = self
+            .config
+            .command
+            .split_first()
+            .ok_or_else(|| Error::Config(format!("MCP server {} has no command", self.config.name)))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .envs(&self.config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| Error::Execution("No stdin".to_string()))?;
+        let stdout = child.stdout.take().ok_or_else(|| Error::Execution("No stdout".to_string()))?;
+        let mut connection = Connection {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        };
+
+        let handshake = async {
+            self.exchange(
+                &mut connection,
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "coding-agent", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?
+            .map_err(|e| Error::Execution(format!("MCP initialize failed: {}", e.message)))?;
+            connection
+                .send(&Request::new(None, "notifications/initialized", Value::Null))
+                .await
+        };
+        tokio::time::timeout(self.timeout(), handshake)
+            .await
+            .map_err(|_| Error::Timeout(format!("MCP server {} did not initialize", self.config.name)))??;
+
+        debug!("MCP server {} started", self.config.name);
+        Ok(connection)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.config.timeout_seconds)
+    }
+
+    /// Send a request and wait for its response
+    ///
+    /// A server that has exited is restarted first. One that does not answer
+    /// in time, or breaks the connection, is killed.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let mut guard = self.connection.lock().await;
+        if !guard.as_mut().is_some_and(Connection::is_running) {
+            *guard = None;
+            let restarts = self.restarts.load(Ordering::Relaxed);
+            if restarts >= self.config.max_restarts {
+                return Err(Error::Execution(format!(
+                    "MCP server {} stopped after {} restart(s)",
+                    self.config.name, restarts
+                )));
+            }
+            self.restarts.fetch_add(1, Ordering::Relaxed);
+            warn!("Restarting MCP server {}", self.config.name);
+            *guard = Some(self.spawn().await?);
+        }
+
+        let Some(connection) = guard.as_mut() else {
+            return Err(Error::Execution(format!("MCP server {} is not running", self.config.name)));
+        };
+        match tokio::time::timeout(self.timeout(), self.exchange(connection, method, params)).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(e))) => Err(Error::Execution(format!(
+                "MCP server {}: {}",
+                self.config.name, e.message
+            ))),
+            Ok(Err(e)) => {
+                *guard = None;
+                Err(e)
+            }
+            Err(_) => {
+                // Dropping the connection kills the process
+                *guard = None;
+                Err(Error::Timeout(format!(
+                    "MCP server {} did not answer {} within {}s",
+                    self.config.name, method, self.config.timeout_seconds
+                )))
+            }
+        }
+    }
+
+    /// Send a request on `connection`; the outer error is a broken connection
+    async fn exchange(
+        &self,
+        connection: &mut Connection,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, RpcError>> {
+        let id = json!(self.next_id.fetch_add(1, Ordering::Relaxed));
+        connection
+            .send(&Request::new(Some(id.clone()), method, params))
+            .await?;
+
+        loop {
+            let line = connection.stdout.next_line().await?.ok_or_else(|| {
+                Error::Execution(format!("MCP server {} closed its output", self.config.name))
+            })?;
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                debug!("Ignoring non-JSON output from MCP server {}", self.config.name);
+                continue;
+            };
+
+            if let Some(server_method) = message.get("method").and_then(|m| m.as_str()) {
+                // Requests from the server (sampling, roots) are not supported
+                if let Some(request_id) = message.get("id") {
+                    let refusal = Response::new(
+                        request_id.clone(),
+                        Err(RpcError::new(METHOD_NOT_FOUND, format!("Unsupported method: {}", server_method))),
+                    );
+                    connection.send(&refusal).await?;
+                }
+                continue;
+            }
+
+            if message.get("id") != Some(&id) {
+                continue;
+            }
+            let response: Response = serde_json::from_value(message)?;
+            return Ok(match response.error {
+                Some(error) => Err(error),
+                None => Ok(response.result.unwrap_or(Value::Null)),
+            });
+        }
+    }
+
+    /// Tools the server offers
+    pub async fn list_tools(&self) -> Result<Vec<ToolDescriptor>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<Value> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let mut page = self.request("tools/list", params).await?;
+            let descriptors: Vec<ToolDescriptor> = serde_json::from_value(page["tools"].take())?;
+            tools.extend(descriptors);
+            cursor = page.get("nextCursor").filter(|c| !c.is_null()).cloned();
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call a tool; a result flagged `isError` becomes an error
+    pub async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value> {
+        let result = self
+            .request("tools/call", json!({"name": name, "arguments": arguments}))
+            .await?;
+
+        let content = result["content"].as_array().cloned().unwrap_or_default();
+        let texts: Vec<&str> = content
+            .iter()
+            .filter(|c| c["type"] == "text")
+            .filter_map(|c| c["text"].as_str())
+            .collect();
+
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(Error::Execution(texts.join("\n")));
+        }
+        if let Some(structured) = result.get("structuredContent") {
+            return Ok(structured.clone());
+        }
+        if texts.len() == content.len() {
+            return Ok(Value::String(texts.join("\n")));
+        }
+        Ok(Value::Array(content))
+    }
+}
+
+/// A tool offered by an MCP server
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    remote_name: String,
+    description: String,
+    schema: Value,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, descriptor: ToolDescriptor) -> Self {
+        Self {
+            name: format!("{}{}{}", client.name(), NAME_SEPARATOR, descriptor.name),
+            client,
+            remote_name: descriptor.name,
+            description: descriptor.description,
+            schema: descriptor.input_schema,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        let required: Vec<&str> = self.schema["required"]
+            .as_array()
+            .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+        self.schema["properties"]
+            .as_object()
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(name, schema)| Parameter {
+                        name: name.clone(),
+                        description: schema["description"].as_str().unwrap_or_default().to_string(),
+                        required: required.contains(&name.as_str()),
+                        parameter_type: parameter_type(schema),
+                        default: schema.get("default").cloned(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn returns(&self) -> ReturnType {
+        ReturnType {
+            description: "Tool output".to_string(),
+            return_type: ParameterType::String,
+        }
+    }
+
+    async fn execute(&self, args: &Value) -> Result<Value> {
+        self.client.call_tool(&self.remote_name, args).await
+    }
+
+    fn validate(&self, args: &Value) -> Result<()> {
+        check_schema(&self.schema, args, "arguments").map_err(Error::Validation)
+    }
+
+    fn is_safe(&self, args: &Value) -> bool {
+        // The server's own side effects are for approval rules naming the tool;
+        // path arguments still get the checks the filesystem tool applies
+        match unsafe_argument(&self.schema, args) {
+            Some(value) => {
+                warn!("Refusing {} call with argument {}", self.name, value);
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+/// First path argument that is absolute, leaves the workspace or names a
+/// sensitive path
+///
+/// Arguments are paths when their name or their schema's `format` says so;
+/// free text such as queries, patterns and messages is passed through.
+fn unsafe_argument<'a>(schema: &Value, value: &'a Value) -> Option<&'a str> {
+    match value {
+        Value::Array(items) => items.iter().find_map(|item| unsafe_argument(&schema["items"], item)),
+        Value::Object(fields) => fields.iter().find_map(|(name, field)| {
+            let field_schema = &schema["properties"][name];
+            if is_path_argument(name, field_schema) {
+                unsafe_path(field)
+            } else {
+                unsafe_argument(field_schema, field)
+            }
+        }),
+        _ => None,
+    }
+}
+
+fn is_path_argument(name: &str, schema: &Value) -> bool {
+    let name = name.to_lowercase();
+    ["path", "paths", "file", "files", "filename", "dir", "dirs"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+        || PATH_NAMES.contains(&name.as_str())
+        || schema["format"].as_str().is_some_and(|format| PATH_FORMATS.contains(&format))
+        || schema["items"]["format"].as_str().is_some_and(|format| PATH_FORMATS.contains(&format))
+}
+
+/// A path, or list of paths, that must not be handed to a remote tool
+fn unsafe_path(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(s) => (s.starts_with('/')
+            || s.starts_with('~')
+            || s.contains("../")
+            || s.contains("..\\")
+            || SENSITIVE_PATHS.iter().any(|path| s.contains(path)))
+        .then_some(s.as_str()),
+        Value::Array(items) => items.iter().find_map(unsafe_path),
+        _ => None,
+    }
+}
+
+/// Closest [`ParameterType`] to a JSON schema
+pub fn parameter_type(schema: &Value) -> ParameterType {
+    if let Some(values) = schema["enum"].as_array() {
+        return ParameterType::Enum(
+            values.iter().map(|v| v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string())).collect(),
+        );
+    }
+    match schema_types(schema).first().copied() {
+        Some("integer") => ParameterType::Integer,
+        Some("number") => ParameterType::Float,
+        Some("boolean") => ParameterType::Boolean,
+        Some("array") => ParameterType::Array(Box::new(parameter_type(&schema["items"]))),
+        Some("object") => ParameterType::Object(
+            schema["properties"]
+                .as_object()
+                .map(|p| p.iter().map(|(k, v)| (k.clone(), parameter_type(v))).collect())
+                .unwrap_or_default(),
+        ),
+        _ => ParameterType::String,
+    }
+}
+
+/// Non-null types a schema allows
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(types) => types.iter().filter_map(|t| t.as_str()).filter(|t| *t != "null").collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Check `value` against the subset of JSON schema MCP tools use
+///
+/// Covers `type`, `enum`, `required`, `properties`, `additionalProperties:
+/// false` and `items`; other keywords are left to the server.
+pub fn check_schema(schema: &Value, value: &Value, at: &str) -> std::result::Result<(), String> {
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            return Err(format!("{} must be one of {}", at, schema["enum"]));
+        }
+    }
+
+    let matches = |t: &str| match t {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    };
+    let nullable = schema["type"].as_array().is_some_and(|t| t.contains(&json!("null")));
+    let types = schema_types(schema);
+    let allowed = types.is_empty() || types.iter().any(|t| matches(t)) || (nullable && value.is_null());
+    if !allowed {
+        return Err(format!("{} must be of type {}", at, types.join(" or ")));
+    }
+
+    if let Some(object) = value.as_object() {
+        for name in schema["required"].as_array().into_iter().flatten().filter_map(|n| n.as_str()) {
+            if !object.contains_key(name) {
+                return Err(format!("Missing {} parameter", name));
+            }
+        }
+        let properties = schema["properties"].as_object();
+        for (name, field) in object {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => check_schema(field_schema, field, name)?,
+                None if schema["additionalProperties"] == false => {
+                    return Err(format!("Unknown parameter {}", name));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(schema_items)) = (value.as_array(), schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_schema(schema_items, item, &format!("{}[{}]", at, i))?;
+        }
+    }
+    Ok(())
+}
+
+/// Start the enabled servers and wrap their tools
+///
+/// Servers that fail to start or list their tools are skipped with a warning.
+pub async fn import_servers(servers: &[McpServerConfig]) -> Vec<McpTool> {
+    let mut tools = Vec::new();
+    for config in servers.iter().filter(|s| s.enabled) {
+        let imported = async {
+            let client = McpClient::start(config.clone()).await?;
+            let descriptors = client.list_tools().await?;
+            Ok::<_, Error>(
+                descriptors
+                    .into_iter()
+                    .map(|d| McpTool::new(client.clone(), d))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        match imported.await {
+            Ok(imported) => {
+                info!("Imported {} tool(s) from MCP server {}", imported.len(), config.name);
+                tools.extend(imported);
+            }
+            Err(e) => warn!("Skipping MCP server {}: {}", config.name, e),
+        }
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tiny stdio MCP server: `echo` answers, `crash` exits, `hang` stalls
+    const SCRIPTED_SERVER: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  [ -z "$id" ] && continue
+  case "$line" in
+    *'"initialize"'*) printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"scripted","version":"0"}}}\n' "$id" ;;
+    *'"tools/list"'*) printf '{"jsonrpc":"2.0","method":"notifications/message","params":{}}\n{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo text","inputSchema":{"type":"object","properties":{"text":{"type":"string"},"times":{"type":"integer"}},"required":["text"]}},{"name":"crash","inputSchema":{"type":"object"}},{"name":"hang","inputSchema":{"type":"object"}}]}}\n' "$id" ;;
+    *'"crash"'*) exit 1 ;;
+    *'"hang"'*) sleep 30 ;;
+    *'"tools/call"'*) printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"pong"}],"isError":false}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    fn scripted_config() -> McpServerConfig {
+        McpServerConfig {
+            name: "scripted".to_string(),
+            command: vec!["sh".to_string(), "-c".to_string(), SCRIPTED_SERVER.to_string()],
+            env: Default::default(),
+            enabled: true,
+            timeout_seconds: 2,
+            max_restarts: 2,
+        }
+    }
+
+    #[test]
+    fn test_check_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "mode": {"enum": ["fast", "slow"]},
+                "ids": {"type": "array", "items": {"type": "integer"}},
+            },
+            "required": ["mode"],
+            "additionalProperties": false,
+        });
+        assert!(check_schema(&schema, &json!({"mode": "fast", "ids": [1, 2]}), "arguments").is_ok());
+        assert!(check_schema(&schema, &json!({"ids": [1]}), "arguments").is_err());
+        assert!(check_schema(&schema, &json!({"mode": "warp"}), "arguments").is_err());
+        assert!(check_schema(&schema, &json!({"mode": "fast", "ids": ["a"]}), "arguments").is_err());
+        assert!(check_schema(&schema, &json!({"mode": "fast", "extra": 1}), "arguments").is_err());
+        assert!(matches!(parameter_type(&schema["properties"]["ids"]), ParameterType::Array(_)));
+    }
+
+    #[test]
+    fn test_only_path_arguments_are_checked() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string"},
+                "target": {"type": "string", "format": "path"},
+                "inputs": {"type": "array", "items": {"type": "string", "format": "file-path"}},
+            },
+        });
+
+        // Free text may look like a path
+        let free = json!({"query": "/api/v1 ~user ../up", "message": "see ~/.ssh/config", "regex": "^/usr/"});
+        assert_eq!(unsafe_argument(&schema, &free), None);
+
+        // Path arguments are recognized by name or by format
+        assert_eq!(unsafe_argument(&schema, &json!({"filePath": "/etc/passwd"})), Some("/etc/passwd"));
+        assert_eq!(unsafe_argument(&schema, &json!({"options": {"cwd": "~"}})), Some("~"));
+        assert_eq!(unsafe_argument(&schema, &json!({"target": "../x"})), Some("../x"));
+        assert_eq!(unsafe_argument(&schema, &json!({"inputs": ["a.rs", "home/.ssh/id"]})), Some("home/.ssh/id"));
+        assert_eq!(unsafe_argument(&schema, &json!({"target": "src/lib.rs", "files": ["a.rs"]})), None);
+    }
+
+    #[tokio::test]
+    async fn test_imported_tools_survive_crashes_and_hangs() {
+        let tools = import_servers(&[scripted_config()]).await;
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert_eq!(names, ["scripted__echo", "scripted__crash", "scripted__hang"]);
+
+        let echo = &tools[0];
+        assert!(echo.parameters().iter().any(|p| p.name == "text" && p.required));
+        assert!(echo.validate(&json!({"times": 2})).is_err());
+        assert!(echo.validate(&json!({"text": "hi", "times": "2"})).is_err());
+        assert!(echo.validate(&json!({"text": "hi", "times": 2})).is_ok());
+        assert_eq!(echo.execute(&json!({"text": "ping"})).await.unwrap(), "pong");
+        assert!(echo.is_safe(&json!({"text": "ping", "tags": ["a", "b"]})));
+        assert!(echo.is_safe(&json!({"text": "/etc/passwd"})));
+        assert!(!echo.is_safe(&json!({"text": "hi", "meta": {"file": "../../secret"}})));
+
+        // Dry runs never reach the server
+        let overlay = crate::overlay::Overlay::new();
+        let suppressed = overlay.wrap(echo).execute(&json!({"text": "ping"})).await.unwrap();
+        assert_eq!(suppressed["dry_run"], true);
+        assert_eq!(overlay.suppressed()[0].tool, "scripted__echo");
+        assert_eq!(overlay.suppressed()[0].operation, "echo");
+
+        // The server exits mid-call, then is restarted for the next one
+        assert!(tools[1].execute(&json!({})).await.is_err());
+        assert_eq!(echo.execute(&json!({"text": "ping"})).await.unwrap(), "pong");
+        assert_eq!(echo.client.restarts(), 1);
+
+        // A hung server is killed at the timeout and restarted
+        let hung = tools[2].execute(&json!({})).await;
+        assert!(matches!(hung, Err(Error::Timeout(_))));
+        assert_eq!(echo.execute(&json!({"text": "ping"})).await.unwrap(), "pong");
+        assert_eq!(echo.client.restarts(), 2);
+
+        // Out of restarts
+        assert!(tools[1].execute(&json!({})).await.is_err());
+        assert!(echo.execute(&json!({"text": "ping"})).await.is_err());
+    }
+}
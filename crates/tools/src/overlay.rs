@@ -0,0 +1,434 @@
+//! Dry-run overlay for side-effecting tool calls.
+//!
+//! With an overlay installed, filesystem writes and deletes land in memory
+//! instead of the workspace, and git mutations, non-GET HTTP calls and every
+//! call to an imported MCP tool are recorded without running. Reads of
+//! overlaid files return the pending contents, so later steps behave as if
+//! the changes had been made.
+
+use common::chrono::{DateTime, Utc};
+use common::{async_trait, Error, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::diff::file_diff;
+use crate::mcp_client;
+use crate::{Parameter, ReturnType, Tool};
+
+/// A file as the workspace had it and as the dry run left it
+#[derive(Debug, Clone)]
+struct OverlayFile {
+    /// `None` if the file did not exist
+    original: Option<String>,
+    /// `None` if the dry run deleted it
+    current: Option<String>,
+}
+
+/// How a file would change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// A pending change to one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub diff: String,
+}
+
+/// A side-effecting call that was recorded instead of run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuppressedCall {
+    pub tool: String,
+    pub operation: String,
+    pub args: Value,
+    pub at: DateTime<Utc>,
+}
+
+/// In-memory copy of everything a dry run would have changed
+#[derive(Debug, Default)]
+pub struct Overlay {
+    files: Mutex<BTreeMap<String, OverlayFile>>,
+    suppressed: Mutex<Vec<SuppressedCall>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the overlay takes this call over instead of the tool
+    pub fn captures(tool: &str, args: &Value) -> bool {
+        let operation = operation(tool, args);
+        match tool {
+            "filesystem" => matches!(operation, "write" | "delete"),
+            "git" => matches!(
+                operation,
+                "commit" | "push" | "merge" | "revert" | "worktree_add" | "worktree_remove"
+            ),
+            "http" => !operation.eq_ignore_ascii_case("GET"),
+            // Nothing says what a remote tool changes
+            _ => mcp_client::is_imported(tool),
+        }
+    }
+
+    /// Run a call against the overlay, falling through to the tool for reads
+    pub async fn execute(&self, tool: &dyn Tool, args: &Value) -> Result<Value> {
+        let name = tool.name();
+        let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+
+        match (name, operation(name, args)) {
+            ("filesystem", "write") => {
+                let content = args
+                    .get("content")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::Validation("Missing content parameter".to_string()))?;
+                self.write(path, content).await
+            }
+            ("filesystem", "delete") => self.delete(path).await,
+            ("filesystem", "read") => match self.overlaid(path) {
+                Some(Some(content)) => Ok(serde_json::json!({
+                    "success": true,
+                    "content": content,
+                    "path": path,
+                })),
+                Some(None) => Err(Error::NotFound(format!("{} (deleted in dry run)", path))),
+                None => tool.execute(args).await,
+            },
+            (_, operation) if Self::captures(name, args) => {
+                info!("Dry run: suppressed {} {}", name, operation);
+                self.suppressed.lock().push(SuppressedCall {
+                    tool: name.to_string(),
+                    operation: operation.to_string(),
+                    args: args.clone(),
+                    at: Utc::now(),
+                });
+                Ok(serde_json::json!({
+                    "success": true,
+                    "dry_run": true,
+                    "output": format!("{} {} suppressed by dry run", name, operation),
+                }))
+            }
+            _ => tool.execute(args).await,
+        }
+    }
+
+    async fn write(&self, path: &str, content: &str) -> Result<Value> {
+        let key = normalize(path);
+        self.track(&key).await?;
+        if let Some(file) = self.files.lock().get_mut(&key) {
+            file.current = Some(content.to_string());
+        }
+        debug!("Dry run: wrote {} bytes to {}", content.len(), key);
+
+        Ok(serde_json::json!({
+            "success": true,
+            "path": path,
+            "bytes_written": content.len(),
+            "dry_run": true,
+        }))
+    }
+
+    async fn delete(&self, path: &str) -> Result<Value> {
+        let key = normalize(path);
+        let mut targets = vec![key.clone()];
+        if Path::new(&key).is_dir() {
+            targets = walkdir::WalkDir::new(&key)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| normalize(&entry.path().to_string_lossy()))
+                .collect();
+        }
+        // Files created earlier in the run under a deleted directory go too
+        let prefix = format!("{}/", key);
+        targets.extend(
+            self.files
+                .lock()
+                .keys()
+                .filter(|k| k.starts_with(&prefix))
+                .cloned(),
+        );
+
+        for target in &targets {
+            self.track(target).await?;
+            if let Some(file) = self.files.lock().get_mut(target) {
+                file.current = None;
+            }
+        }
+        debug!("Dry run: deleted {}", key);
+
+        Ok(serde_json::json!({
+            "success": true,
+            "path": path,
+            "dry_run": true,
+        }))
+    }
+
+    /// Record the workspace contents of a file the first time it is touched
+    async fn track(&self, key: &str) -> Result<()> {
+        if self.files.lock().contains_key(key) {
+            return Ok(());
+        }
+        let original = read_if_exists(key).await?;
+        self.files.lock().entry(key.to_string()).or_insert(OverlayFile {
+            current: original.clone(),
+            original,
+        });
+        Ok(())
+    }
+
+    /// Pending contents of a path, if the dry run touched it
+    fn overlaid(&self, path: &str) -> Option<Option<String>> {
+        self.files.lock().get(&normalize(path)).map(|f| f.current.clone())
+    }
+
+    /// File changes in path order, skipping files that ended up unchanged
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.files
+            .lock()
+            .iter()
+            .filter(|(_, file)| file.original != file.current)
+            .map(|(path, file)| FileChange {
+                path: path.clone(),
+                kind: match (&file.original, &file.current) {
+                    (None, _) => ChangeKind::Created,
+                    (_, None) => ChangeKind::Deleted,
+                    _ => ChangeKind::Modified,
+                },
+                diff: file_diff(path, file.original.as_deref(), file.current.as_deref()),
+            })
+            .collect()
+    }
+
+    /// All file changes as one patch that applies with `git apply`
+    pub fn diff(&self) -> String {
+        self.changes().into_iter().map(|c| c.diff).collect()
+    }
+
+    /// Calls recorded instead of run, in order
+    pub fn suppressed(&self) -> Vec<SuppressedCall> {
+        self.suppressed.lock().clone()
+    }
+
+    /// Whether the dry run would have changed anything
+    pub fn is_empty(&self) -> bool {
+        self.changes().is_empty() && self.suppressed.lock().is_empty()
+    }
+
+    /// Write the pending file changes to the workspace
+    ///
+    /// Refuses to apply anything if a file changed on disk since the dry run
+    /// first read it. Suppressed calls are not replayed. Returns the paths
+    /// that were written or deleted.
+    pub async fn apply(&self) -> Result<Vec<String>> {
+        let files: Vec<(String, OverlayFile)> = self
+            .files
+            .lock()
+            .iter()
+            .filter(|(_, file)| file.original != file.current)
+            .map(|(path, file)| (path.clone(), file.clone()))
+            .collect();
+
+        let mut conflicts = Vec::new();
+        for (path, file) in &files {
+            if read_if_exists(path).await? != file.original {
+                conflicts.push(path.as_str());
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(Error::Validation(format!(
+                "Files changed since the dry run: {}",
+                conflicts.join(", ")
+            )));
+        }
+
+        for (path, file) in &files {
+            match &file.current {
+                Some(content) => {
+                    if let Some(parent) = Path::new(path).parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(path, content).await?;
+                }
+                None => tokio::fs::remove_file(path).await?,
+            }
+        }
+        info!("Applied {} dry-run file changes", files.len());
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Wrap a tool so its calls go through the overlay
+    pub(crate) fn wrap<'a>(&'a self, tool: &'a dyn Tool) -> Overlaid<'a> {
+        Overlaid { overlay: self, tool }
+    }
+}
+
+/// A tool whose side effects are redirected to an overlay
+pub(crate) struct Overlaid<'a> {
+    overlay: &'a Overlay,
+    tool: &'a dyn Tool,
+}
+
+#[async_trait]
+impl Tool for Overlaid<'_> {
+    fn name(&self) -> &str {
+        self.tool.name()
+    }
+
+    fn description(&self) -> &str {
+        self.tool.description()
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.tool.parameters()
+    }
+
+    fn returns(&self) -> ReturnType {
+        self.tool.returns()
+    }
+
+    async fn execute(&self, args: &Value) -> Result<Value> {
+        self.overlay.execute(self.tool, args).await
+    }
+
+    fn validate(&self, args: &Value) -> Result<()> {
+        self.tool.validate(args)
+    }
+
+    fn is_safe(&self, args: &Value) -> bool {
+        self.tool.is_safe(args)
+    }
+}
+
+/// Operation named in a call: `operation` for most tools, `method` for HTTP
+/// and the remote tool's name for MCP imports
+fn operation<'a>(tool: &'a str, args: &'a Value) -> &'a str {
+    if let Some((_, remote)) = tool.split_once(mcp_client::NAME_SEPARATOR) {
+        return remote;
+    }
+    let key = if tool == "http" { "method" } else { "operation" };
+    args.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// Key overlay entries by path without `./` segments
+fn normalize(path: &str) -> String {
+    Path::new(path)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .into_owned()
+}
+
+async fn read_if_exists(path: &str) -> Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::FileSystemTool;
+    use crate::git::GitTool;
+
+    #[tokio::test]
+    async fn test_overlay_keeps_workspace_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("lib.rs");
+        std::fs::write(&existing, "fn a() {}\n").unwrap();
+        let existing = existing.to_string_lossy().into_owned();
+        let created = dir.path().join("src/new.rs").to_string_lossy().into_owned();
+
+        let overlay = Overlay::new();
+        let fs = FileSystemTool::new(1024 * 1024);
+        let fs = overlay.wrap(&fs);
+
+        fs.execute(&serde_json::json!({"operation": "write", "path": existing, "content": "fn b() {}\n"}))
+            .await
+            .unwrap();
+        fs.execute(&serde_json::json!({"operation": "write", "path": created, "content": "x\n"}))
+            .await
+            .unwrap();
+
+        // Reads see the pending contents, the disk does not
+        let read = fs
+            .execute(&serde_json::json!({"operation": "read", "path": existing}))
+            .await
+            .unwrap();
+        assert_eq!(read["content"], "fn b() {}\n");
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "fn a() {}\n");
+        assert!(!Path::new(&created).exists());
+
+        let git = GitTool;
+        let commit = overlay
+            .wrap(&git)
+            .execute(&serde_json::json!({"operation": "commit", "path": ".", "message": "wip"}))
+            .await
+            .unwrap();
+        assert_eq!(commit["dry_run"], true);
+        assert_eq!(overlay.suppressed()[0].operation, "commit");
+        let revert = overlay
+            .wrap(&git)
+            .execute(&serde_json::json!({"operation": "revert", "path": ".", "commit": "HEAD"}))
+            .await
+            .unwrap();
+        assert_eq!(revert["dry_run"], true);
+        assert_eq!(overlay.suppressed()[1].operation, "revert");
+
+        let changes = overlay.changes();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().any(|c| c.kind == ChangeKind::Created));
+        assert!(overlay.diff().contains("-fn a() {}\n+fn b() {}\n"));
+
+        let applied = overlay.apply().await.unwrap();
+        assert_eq!(applied.len(), 2);
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "fn b() {}\n");
+        assert_eq!(std::fs::read_to_string(&created).unwrap(), "x\n");
+    }
+
+    #[tokio::test]
+    async fn test_apply_refuses_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let overlay = Overlay::new();
+        let fs = FileSystemTool::new(1024);
+        overlay
+            .wrap(&fs)
+            .execute(&serde_json::json!({"operation": "delete", "path": path}))
+            .await
+            .unwrap();
+        assert!(Path::new(&path).exists());
+        assert_eq!(overlay.changes()[0].kind, ChangeKind::Deleted);
+
+        std::fs::write(&path, "two\n").unwrap();
+        assert!(overlay.apply().await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\n");
+    }
+
+    #[test]
+    fn test_captures() {
+        assert!(Overlay::captures("git", &serde_json::json!({"operation": "push"})));
+        assert!(Overlay::captures("git", &serde_json::json!({"operation": "revert"})));
+        assert!(!Overlay::captures("git", &serde_json::json!({"operation": "status"})));
+        assert!(Overlay::captures("http", &serde_json::json!({"method": "POST"})));
+        assert!(!Overlay::captures("http", &serde_json::json!({"method": "GET"})));
+        assert!(!Overlay::captures("search", &serde_json::json!({})));
+        assert!(Overlay::captures("jira__create_issue", &serde_json::json!({"summary": "x"})));
+    }
+}
@@ -1,97 +1,127 @@
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
-use coding_agent::agent_core::{Agent, Task, TaskPriority, TaskContext};
-use coding_agent::agent_config::AgentConfig;
+
+use agent_core::delegation::DelegationSpec;
+use agent_core::orchestrator::Orchestrator;
+use agent_core::{Agent, Task, TaskPriority};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration
-    let config = AgentConfig::load(None, Default::default()).await?;
+    // Use the mock model so the example runs without an API key
+    let mut config = agent_config::AgentConfig::default();
+    config.llm.provider = "mock".to_string();
 
-    // Initialize agent
-    let mut agent = coding_agent::initialize_agent(config).await?;
+    let gateway = intelligence::gateway::GatewayFactory::new().create("mock", None, config.llm.model.clone())?;
+    let orchestrator = Orchestrator::new()
+        .with_intelligence(Arc::new(intelligence::IntelligenceEngine::new(gateway)))
+        .with_tools(Arc::new(tools::ToolFramework::new()))
+        .with_config(config.clone());
+    let agent = Agent::new(config).with_orchestrator(orchestrator);
 
     println!("Agent initialized successfully");
 
-    // Example 1: Create a parent task with subtasks
-    println!("\n=== Example 1: Parent task with subtasks ===");
-    
-    let parent_task = Task::new("Implement user authentication system")
-        .with_priority(TaskPriority::High);
-    
-    let parent_id = agent.submit_task(parent_task).await?;
-    println!("Created parent task: {}", parent_id);
-
-    // Create subtasks
-    let subtask1 = Task::new("Design database schema for users")
-        .with_priority(TaskPriority::High);
-    let subtask1_id = agent.submit_subtask(parent_id, subtask1).await?;
-    println!("Created subtask 1: {}", subtask1_id);
-
-    let subtask2 = Task::new("Implement user registration API endpoint")
-        .with_priority(TaskPriority::High);
-    let subtask2_id = agent.submit_subtask(parent_id, subtask2).await?;
-    println!("Created subtask 2: {}", subtask2_id);
-
-    let subtask3 = Task::new("Implement login API endpoint")
-        .with_priority(TaskPriority::High);
-    let subtask3_id = agent.submit_subtask(parent_id, subtask3).await?;
-    println!("Created subtask 3: {}", subtask3_id);
-
-    // Check subtasks
-    let subtasks = agent.get_subtasks(parent_id).await;
-    println!("\nParent task has {} subtasks", subtasks.len());
-    for subtask_id in &subtasks {
-        println!("  - Subtask: {}", subtask_id);
-        if let Some(parent) = agent.get_parent_task(*subtask_id).await {
-            println!("    Parent task: {}", parent);
+    // Example 1: Delegate subtasks to scoped sub-agents
+    println!("\n=== Example 1: Scoped sub-agents ===");
+
+    let parent = Task::new("Document the tool framework").with_priority(TaskPriority::High);
+    let parent_id = parent.id;
+    println!("Parent task: {}", parent_id);
+
+    // Reads only, limited to the tools crate, with its own budget
+    let reader = agent
+        .delegate(
+            parent_id,
+            Task::new("Summarize the filesystem tool"),
+            DelegationSpec::new()
+                .with_tools(["filesystem", "search"])
+                .with_paths(["crates/tools"])
+                .with_token_budget(2_000)
+                .with_time_budget(Duration::from_secs(30)),
+        )
+        .await?;
+    println!("Delegated reader sub-agent: {}", reader.task_id);
+
+    // A second sub-agent on a different model
+    let reviewer = agent
+        .delegate(
+            parent_id,
+            Task::new("Review the git tool's error handling"),
+            DelegationSpec::new()
+                .with_tools(["filesystem"])
+                .with_paths(["crates/tools/src/git.rs"])
+                .with_model("mock-model-small")
+                .with_time_budget(Duration::from_secs(30)),
+        )
+        .await?;
+    println!("Delegated reviewer sub-agent: {}", reviewer.task_id);
+
+    for handle in [reader, reviewer] {
+        let result = handle.join().await;
+        println!("\nSub-agent {} finished: {:?} in {}ms", result.task_id, result.status, result.duration_ms);
+        if let Some(task_result) = &result.result {
+            println!("  Success: {}", task_result.success);
+            println!("  Tokens used: {}", task_result.metrics.tokens_used);
+            println!("  Tools used: {:?}", task_result.metrics.tools_used);
         }
     }
 
-    // Check completion status
     let completion_status = agent.get_subtask_completion_status(parent_id).await;
     println!("\nSubtask completion status:");
     for (subtask_id, completed) in &completion_status {
-        println!("  - Subtask {}: {}", subtask_id, if *completed { "Completed" } else { "In Progress" });
+        println!("  - Subtask {}: {}", subtask_id, if *completed { "Completed" } else { "Not completed" });
     }
 
-    println!("\nAll subtasks completed: {}", agent.are_all_subtasks_completed(parent_id).await);
-
-    // Example 2: Create a task with nested subtasks
-    println!("\n=== Example 2: Nested subtasks ===");
-    
-    let grandparent_task = Task::new("Build e-commerce platform")
-        .with_priority(TaskPriority::Critical);
-    
-    let grandparent_id = agent.submit_task(grandparent_task).await?;
-    println!("Created grandparent task: {}", grandparent_id);
-
-    let parent_task2 = Task::new("Implement product catalog system")
-        .with_priority(TaskPriority::High);
-    let parent2_id = agent.submit_subtask(grandparent_id, parent_task2).await?;
-    println!("Created parent task: {}", parent2_id);
-
-    let subtask4 = Task::new("Create product database tables")
-        .with_priority(TaskPriority::High);
-    let subtask4_id = agent.submit_subtask(parent2_id, subtask4).await?;
-    println!("Created nested subtask: {}", subtask4_id);
-
-    let subtask5 = Task::new("Implement product search API")
-        .with_priority(TaskPriority::Normal);
-    let subtask5_id = agent.submit_subtask(parent2_id, subtask5).await?;
-    println!("Created nested subtask: {}", subtask5_id);
-
-    println!("\nParent task {} has {} subtasks", parent2_id, agent.get_subtasks(parent2_id).await.len());
-    println!("Grandparent task {} has {} subtasks", grandparent_id, agent.get_subtasks(grandparent_id).await.len());
+    // Example 2: Nested delegation only ever narrows the scope
+    println!("\n=== Example 2: Nested sub-agents ===");
+
+    let grandparent_id = Task::new("Harden the tools crate").id;
+    let child = agent
+        .delegate(
+            grandparent_id,
+            Task::new("Audit path handling in the tools crate"),
+            DelegationSpec::new()
+                .with_tools(["filesystem", "search"])
+                .with_paths(["crates/tools"]),
+        )
+        .await?;
+    println!("Created child sub-agent: {}", child.task_id);
+
+    // Asking for git or paths outside the parent's scope is not granted
+    let grandchild = agent
+        .delegate(
+            child.task_id,
+            Task::new("Check the filesystem tool for path traversal"),
+            DelegationSpec::new()
+                .with_tools(["filesystem", "git"])
+                .with_paths(["crates/tools/src/filesystem.rs"]),
+        )
+        .await?;
+    println!("Created grandchild sub-agent with filesystem access only: {}", grandchild.task_id);
+    let escape = agent
+        .delegate(
+            child.task_id,
+            Task::new("Edit the main binary"),
+            DelegationSpec::new().with_paths(["src"]),
+        )
+        .await;
+    println!("Delegating outside the parent's paths: {}", if escape.is_err() { "refused" } else { "allowed" });
+
+    // Example 3: Cancelling a parent cascades to all of its sub-agents
+    println!("\n=== Example 3: Cascading cancellation ===");
+
+    let cancelled = agent.cancel_task(grandparent_id).await;
+    println!("Cancelled {} and {} descendant(s)", grandparent_id, cancelled.len() - 1);
+    for handle in [child, grandchild] {
+        let result = handle.join().await;
+        println!("Sub-agent {} finished: {:?}", result.task_id, result.status);
+    }
 
     // Check metrics
     println!("\n=== Current Metrics ===");
     let metrics = agent.get_metrics().await;
-    println!("Tasks submitted: {}", metrics.tasks_submitted);
-    println!("Tasks completed: {}", metrics.tasks_completed);
     println!("Subtasks submitted: {}", metrics.subtasks_submitted);
     println!("Subtasks completed: {}", metrics.subtasks_completed);
-    println!("Success rate: {:.1}%", metrics.success_rate * 100.0);
+    println!("Subtasks failed: {}", metrics.subtasks_failed);
 
     Ok(())
 }
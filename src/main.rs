@@ -1,6 +1,7 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use tracing::{info, warn, error};
+use tracing_subscriber::prelude::*;
 
 /// Self-developing coding agent
 #[derive(Parser, Debug)]
@@ -35,6 +36,207 @@ struct Cli {
     /// Show agent metrics
     #[arg(long)]
     metrics: bool,
+
+    /// Run TASK without touching the workspace and print the changes it would make
+    #[arg(long, requires = "task")]
+    dry_run: bool,
+
+    /// Stream structured agent events to stdout as they happen
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    events: Option<EventFormat>,
+
+    /// Write the event stream to FILE instead of stdout
+    #[arg(long, value_name = "FILE", requires = "events")]
+    events_out: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Event stream encodings
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum EventFormat {
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// Standalone commands
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the HTN plan for a task without executing it
+    Plan {
+        /// Task description
+        description: String,
+
+        /// Show which method was chosen for each task and why
+        #[arg(long)]
+        explain: bool,
+    },
+
+    /// Review tool calls held for approval by a running agent
+    Approvals {
+        #[command(subcommand)]
+        action: ApprovalsAction,
+    },
+
+    /// Audit and undo changes made by self-improvement
+    Improvements {
+        #[command(subcommand)]
+        action: ImprovementsAction,
+    },
+
+    /// Talk to a running daemon through its control API
+    Client {
+        #[command(subcommand)]
+        action: ClientAction,
+    },
+
+    /// Serve the agent's tools to MCP clients over stdin and stdout
+    Mcp,
+
+    /// Run a suite of task fixtures and report pass rates, cost and latency per model
+    Bench {
+        /// Suite directory; each fixture has task.md, verify.sh and repo/
+        suite: std::path::PathBuf,
+
+        /// Models to compare (defaults to the configured model)
+        #[arg(long, value_delimiter = ',')]
+        models: Vec<String>,
+
+        /// Record model exchanges into DIR
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        record: Option<std::path::PathBuf>,
+
+        /// Answer from exchanges recorded in DIR instead of calling the provider
+        #[arg(long, value_name = "DIR")]
+        replay: Option<std::path::PathBuf>,
+
+        /// Also score each task's output with an LLM judge
+        #[arg(long)]
+        judge: bool,
+
+        /// Report format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ReportFormat,
+
+        /// Write the report to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<std::path::PathBuf>,
+
+        /// Seconds each fixture's agent may run
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+    },
+
+    /// Check that this binary starts and runs a task; used before swapping it in
+    #[command(hide = true)]
+    Probe,
+}
+
+/// Benchmark report encodings
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+/// Control API requests
+#[derive(Subcommand, Debug)]
+enum ClientAction {
+    /// Queue a task (uses --workspace if given)
+    Submit {
+        /// Task description
+        description: String,
+
+        /// low, normal, high or critical
+        #[arg(long)]
+        priority: Option<String>,
+    },
+    /// Show the agent's state and queue
+    Status,
+    /// Show one task's status and result
+    Task {
+        /// Task id
+        task_id: String,
+    },
+    /// List tasks submitted since the daemon started
+    Sessions,
+    /// Cancel a task and its subtasks
+    Cancel {
+        /// Task id
+        task_id: String,
+    },
+    /// List tool calls awaiting approval
+    Approvals,
+    /// Let a held call run
+    Approve {
+        /// Request id (or a unique prefix)
+        id: String,
+    },
+    /// Refuse a held call
+    Deny {
+        /// Request id (or a unique prefix)
+        id: String,
+
+        /// Reason recorded in the audit trail
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Show agent metrics
+    Metrics,
+    /// Probe a new binary and, if it is healthy, hand the daemon over to it
+    Swap {
+        /// Path to the new coding-agent binary
+        binary: std::path::PathBuf,
+    },
+}
+
+/// Approval review actions
+#[derive(Subcommand, Debug)]
+enum ApprovalsAction {
+    /// List pending requests with their previews
+    List,
+    /// Let a held call run
+    Approve {
+        /// Request id (or a unique prefix)
+        id: String,
+    },
+    /// Refuse a held call
+    Deny {
+        /// Request id (or a unique prefix)
+        id: String,
+
+        /// Reason recorded in the audit trail
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Run a held call with different arguments
+    Edit {
+        /// Request id (or a unique prefix)
+        id: String,
+
+        /// Replacement arguments as JSON
+        args: String,
+    },
+    /// Show the audit trail of past decisions
+    Audit,
+}
+
+/// Improvement ledger actions
+#[derive(Subcommand, Debug)]
+enum ImprovementsAction {
+    /// List every applied change with its status
+    List,
+    /// Show a change's strategy, rollback data and experiment results
+    Show {
+        /// Change id (or a unique prefix)
+        id: String,
+    },
+    /// Undo a change
+    Revert {
+        /// Change id (or a unique prefix)
+        id: String,
+    },
 }
 
 #[tokio::main]
@@ -52,9 +254,19 @@ async fn main() -> Result<()> {
         )
         .with_target(false)
         .with_thread_ids(true)
-        .with_line_number(true);
+        .with_line_number(true)
+        // Keep stdout clean for the event stream and MCP messages
+        .with_writer(if (cli.events.is_some() && cli.events_out.is_none())
+            || matches!(cli.command, Some(Command::Mcp | Command::Bench { .. } | Command::Probe))
+        {
+            tracing_subscriber::fmt::writer::BoxMakeWriter::new(std::io::stderr)
+        } else {
+            tracing_subscriber::fmt::writer::BoxMakeWriter::new(std::io::stdout)
+        });
 
-    subscriber.init();
+    // Spans are exported once the configuration says where
+    let trace_layer = agent_core::otel::TraceLayer::new();
+    subscriber.finish().with(trace_layer.clone()).init();
 
     info!("Starting coding agent v{}", env!("CARGO_PKG_VERSION"));
 
@@ -62,17 +274,121 @@ async fn main() -> Result<()> {
     let config = load_config(&cli).await?;
     info!("Configuration loaded successfully");
 
+    // Probes run a smoke task that should not show up in real traces
+    let exporter = if matches!(cli.command, Some(Command::Probe)) {
+        None
+    } else {
+        trace_layer.start(&config.tracing)?
+    };
+
+    let outcome = run(cli, config, exporter.clone()).await;
+    if let Some(exporter) = exporter {
+        if let Err(e) = exporter.flush().await {
+            warn!("{}", e);
+        }
+    }
+    outcome
+}
+
+/// Run the command, mode or task selected on the command line
+async fn run(
+    cli: Cli,
+    config: agent_config::AgentConfig,
+    exporter: Option<std::sync::Arc<agent_core::otel::SpanExporter>>,
+) -> Result<()> {
+    if let Some(command) = &cli.command {
+        match command {
+            Command::Plan { description, explain } => {
+                run_plan_command(&config, description, *explain).await?;
+            }
+            Command::Approvals { action } => {
+                run_approvals_command(action).await?;
+            }
+            Command::Improvements { action } => {
+                run_improvements_command(&config, action).await?;
+            }
+            Command::Client { action } => {
+                run_client_command(&config, cli.workspace.as_deref(), action).await?;
+            }
+            Command::Mcp => {
+                run_mcp_server(&config).await?;
+            }
+            Command::Bench {
+                suite,
+                models,
+                record,
+                replay,
+                judge,
+                format,
+                out,
+                timeout,
+            } => {
+                let mode = match (record, replay) {
+                    (Some(dir), _) => Some((agent_core::bench::GatewayMode::Record, dir)),
+                    (_, Some(dir)) => Some((agent_core::bench::GatewayMode::Replay, dir)),
+                    _ => None,
+                };
+                run_bench_command(&cli, &config, suite, models, mode, *judge, *format, out.as_deref(), *timeout)
+                    .await?;
+            }
+            Command::Probe => {
+                run_probe_command(&config).await?;
+            }
+        }
+        return Ok(());
+    }
+
     // Show metrics if requested
     if cli.metrics {
         show_metrics(&config).await?;
         return Ok(());
     }
 
+    // Risky tool calls wait here for a reviewer
+    let approvals = std::sync::Arc::new(
+        tools::approval::ApprovalGate::new(approval_policy(&config))
+            .with_storage(tools::approval::DEFAULT_APPROVALS_DIR),
+    );
+
+    // File changes and side effects of a dry run are kept here
+    let overlay = cli
+        .dry_run
+        .then(|| std::sync::Arc::new(tools::overlay::Overlay::new()));
+
     // Initialize agent
-    let mut agent = initialize_agent(config).await?;
+    let mut agent = initialize_agent(config, approvals.clone(), overlay.clone(), config_args(&cli)?).await?;
+    if let Some(exporter) = exporter {
+        agent = agent.with_span_exporter(exporter);
+    }
     info!("Agent initialized successfully");
 
-    // Handle different modes
+    let event_writer = match cli.events {
+        Some(EventFormat::Jsonl) => Some(spawn_event_writer(&agent, cli.events_out.as_deref()).await?),
+        None => None,
+    };
+
+    run_mode(cli, agent, approvals, overlay).await?;
+
+    // The agent is gone, so the writer only has buffered events left
+    if let Some(writer) = event_writer {
+        match tokio::time::timeout(tokio::time::Duration::from_secs(2), writer).await {
+            Ok(Ok(Err(e))) => warn!("Event stream failed: {}", e),
+            Ok(_) => {}
+            Err(_) => warn!("Event stream did not finish draining"),
+        }
+    }
+
+    info!("Coding agent shutting down");
+    Ok(())
+}
+
+/// Run the agent in the mode selected on the command line
+async fn run_mode(
+    cli: Cli,
+    mut agent: agent_core::Agent,
+    approvals: std::sync::Arc<tools::approval::ApprovalGate>,
+    overlay: Option<std::sync::Arc<tools::overlay::Overlay>>,
+) -> Result<()> {
     if cli.improve {
         info!("Triggering self-improvement cycle");
         agent.trigger_self_improvement().await?;
@@ -90,19 +406,52 @@ async fn main() -> Result<()> {
         }
     } else if cli.daemon {
         info!("Starting in daemon mode");
-        run_daemon_mode(&mut agent).await?;
+        run_daemon_mode(&mut agent, &approvals).await?;
     } else if let Some(task_description) = cli.task {
         info!("Executing single task: {}", task_description);
         run_single_task(&mut agent, &task_description, cli.workspace).await?;
+        if let Some(overlay) = &overlay {
+            // The event stream owns stdout unless it goes to a file
+            let events_on_stdout = cli.events.is_some() && cli.events_out.is_none();
+            report_dry_run(overlay, events_on_stdout).await?;
+        }
     } else {
         info!("Starting interactive mode");
-        run_interactive_mode(agent).await?;
+        run_interactive_mode(agent, approvals).await?;
     }
-
-    info!("Coding agent shutting down");
     Ok(())
 }
 
+/// Write the agent's events as JSON lines to `path`, or stdout
+async fn spawn_event_writer(
+    agent: &agent_core::Agent,
+    path: Option<&str>,
+) -> Result<tokio::task::JoinHandle<common::Result<()>>> {
+    use agent_core::events::write_jsonl;
+
+    let events = agent.subscribe();
+    Ok(match path {
+        Some(path) => {
+            let file = tokio::fs::File::create(path).await?;
+            tokio::spawn(write_jsonl(events, file))
+        }
+        None => tokio::spawn(write_jsonl(events, tokio::io::stdout())),
+    })
+}
+
+/// Arguments giving another agent process this run's config file
+///
+/// Benchmark fixtures run in scratch copies, so the path is made absolute.
+fn config_args(cli: &Cli) -> Result<Vec<String>> {
+    match &cli.config {
+        Some(path) => Ok(vec![
+            "--config".to_string(),
+            std::fs::canonicalize(path)?.display().to_string(),
+        ]),
+        None => Ok(Vec::new()),
+    }
+}
+
 /// Load configuration from file or use defaults
 async fn load_config(cli: &Cli) -> Result<agent_config::AgentConfig> {
     let config_path = cli.config.as_ref().map(std::path::PathBuf::from);
@@ -138,21 +487,20 @@ async fn load_config(cli: &Cli) -> Result<agent_config::AgentConfig> {
 }
 
 /// Initialize the agent with all modules
-async fn initialize_agent(config: agent_config::AgentConfig) -> Result<agent_core::Agent> {
+async fn initialize_agent(
+    config: agent_config::AgentConfig,
+    approvals: std::sync::Arc<tools::approval::ApprovalGate>,
+    overlay: Option<std::sync::Arc<tools::overlay::Overlay>>,
+    config_args: Vec<String>,
+) -> Result<agent_core::Agent> {
     use agent_core::*;
     use std::sync::Arc;
 
-    // Create the agent
-    let mut agent = Agent::new(config.clone());
+    // Create the agent; new binaries are probed with the same config file
+    let mut agent = Agent::new(config.clone()).with_probe_args(config_args.clone());
 
     // Create and configure the intelligence engine
-    let gateway_factory = intelligence::gateway::GatewayFactory::new();
-    let gateway = gateway_factory.create(
-        &config.llm.provider,
-        Some(config.current_api_key().to_string()),
-        config.llm.model.clone(),
-    )?;
-    let intelligence_engine = Arc::new(intelligence::IntelligenceEngine::new(gateway));
+    let intelligence_engine = Arc::new(build_intelligence(&config)?);
 
     // Create and configure the analysis engine
     let analysis_engine = Arc::new(analysis::AnalysisEngine::new());
@@ -161,18 +509,57 @@ async fn initialize_agent(config: agent_config::AgentConfig) -> Result<agent_cor
     let knowledge_engine = Arc::new(knowledge::KnowledgeEngine::new());
 
     // Create and configure the tool framework
-    let tools_framework = Arc::new(tools::ToolFramework::new());
+    let mut tools_framework = tools::ToolFramework::new().with_approval_gate(approvals);
+    if let Some(overlay) = overlay {
+        tools_framework = tools_framework.with_overlay(overlay);
+    }
+    tools_framework.import_mcp_servers(&config.tools.mcp_servers).await;
+    let tools_framework = Arc::new(tools_framework);
+
+    // Load HTN method libraries; invalid libraries abort startup
+    let tool_names: Vec<String> = tools_framework
+        .list_tools()
+        .iter()
+        .map(|t| t.name().to_string())
+        .collect();
+    let planner = build_planner(&config, &tool_names).await?;
 
     // Create and configure orchestrator with all engines
-    let orchestrator = orchestrator::Orchestrator::new()
+    let mut orchestrator = orchestrator::Orchestrator::new()
         .with_config(config.clone())
-        .with_intelligence(intelligence_engine)
-        .with_analysis(analysis_engine)
+        .with_intelligence(intelligence_engine.clone())
+        .with_analysis(analysis_engine.clone())
         .with_knowledge(knowledge_engine)
-        .with_tools(tools_framework);
+        .with_tools(tools_framework.clone())
+        .with_planner(planner)
+        .with_world_probe(world_probe::WorldProbe::new().with_analysis(analysis_engine));
+
+    // Dry runs keep their checkpoints in memory
+    if tools_framework.overlay().is_none() {
+        orchestrator = orchestrator.with_checkpoint_dir(".agent/checkpoints");
+    }
+
+    // Choose models per intent and stage from what worked before
+    if config.llm.routing.adaptive.enabled {
+        let router = routing::ModelRouter::from_config(&config)?;
+        info!("Adaptive routing between {} model(s)", router.candidates().len());
+        orchestrator = orchestrator.with_router(Arc::new(router));
+    }
 
     agent = agent.with_orchestrator(orchestrator);
 
+    // Code changes to the agent itself are built and tested in a worktree first
+    if config.self_compile.enabled {
+        let improver = code_improvement::CodeImprover::new(
+            &config,
+            intelligence_engine,
+            tools_framework,
+            std::env::current_dir()?,
+        )
+        .with_bench_args(config_args);
+        agent = agent.with_code_improver(Arc::new(improver));
+    }
+
     info!("Agent initialization complete");
     
     // Initialize the agent
@@ -181,19 +568,291 @@ async fn initialize_agent(config: agent_config::AgentConfig) -> Result<agent_cor
     Ok(agent)
 }
 
+/// Approval rules from the safety settings, aware of where task worktrees live
+fn approval_policy(config: &agent_config::AgentConfig) -> tools::approval::ApprovalPolicy {
+    tools::approval::ApprovalPolicy::from_config(&config.safety).with_worktree_dir(&config.tools.git.worktree_dir)
+}
+
+/// Create the intelligence engine for the configured provider, recording or replaying if asked
+fn build_intelligence(config: &agent_config::AgentConfig) -> Result<intelligence::IntelligenceEngine> {
+    let gateway = intelligence::gateway::GatewayFactory::new().create(
+        &config.llm.provider,
+        Some(config.current_api_key().to_string()),
+        config.llm.model.clone(),
+    )?;
+    let gateway = intelligence::gateway::with_recording(
+        gateway,
+        config.llm.record_to.as_deref(),
+        config.llm.replay_from.as_deref(),
+    )?;
+    Ok(intelligence::IntelligenceEngine::new(gateway))
+}
+
+/// Create the HTN planner with built-in tasks and user method libraries
+async fn build_planner(
+    config: &agent_config::AgentConfig,
+    tool_names: &[String],
+) -> Result<agent_core::planning::HTNPlanner> {
+    let mut planner =
+        agent_core::planning::HTNPlanner::new().with_budget(config.agent.planning.clone());
+    let loaded = planner
+        .load_method_libraries(agent_core::method_library::DEFAULT_METHODS_DIR, tool_names)
+        .await?;
+    if loaded > 0 {
+        info!("Registered {} task(s) from method libraries", loaded);
+    }
+    Ok(planner)
+}
+
+/// Serve the tool framework, plus the analysis engine, over stdio
+///
+/// Calls that need approval wait for `coding-agent approvals` like the
+/// daemon's do.
+async fn run_mcp_server(config: &agent_config::AgentConfig) -> Result<()> {
+    use std::sync::Arc;
+
+    let approvals = Arc::new(
+        tools::approval::ApprovalGate::new(approval_policy(config))
+            .with_storage(tools::approval::DEFAULT_APPROVALS_DIR),
+    );
+    let inbox_watcher = approvals.spawn_inbox_watcher(std::time::Duration::from_secs(1));
+
+    let mut tools = tools::ToolFramework::new().with_approval_gate(approvals);
+    tools.register_tool(Box::new(agent_core::analysis_tool::AnalysisTool::new(Arc::new(
+        analysis::AnalysisEngine::new(),
+    ))));
+    tools.import_mcp_servers(&config.tools.mcp_servers).await;
+
+    info!("Serving {} tool(s) over MCP", tools.list_tools().len());
+    let server = tools::mcp::McpServer::new(Arc::new(tools))
+        .with_server_info("coding-agent", env!("CARGO_PKG_VERSION"));
+    let result = server.serve(tokio::io::stdin(), tokio::io::stdout()).await;
+
+    inbox_watcher.abort();
+    Ok(result?)
+}
+
+/// Run a benchmark suite, each fixture in its own agent process
+#[allow(clippy::too_many_arguments)]
+async fn run_bench_command(
+    cli: &Cli,
+    config: &agent_config::AgentConfig,
+    suite: &std::path::Path,
+    models: &[String],
+    recordings: Option<(agent_core::bench::GatewayMode, &std::path::PathBuf)>,
+    judge: bool,
+    format: ReportFormat,
+    out: Option<&std::path::Path>,
+    timeout: u64,
+) -> Result<()> {
+    use agent_core::bench::{BenchRunner, GatewayMode};
+
+    let mut runner = BenchRunner::new(std::env::current_exe()?)
+        .with_args(config_args(cli)?)
+        .with_timeout(std::time::Duration::from_secs(timeout));
+
+    if let Some((mode, dir)) = recordings {
+        std::fs::create_dir_all(dir)?;
+        runner = runner.with_recordings(mode, dir.clone());
+    }
+
+    if judge {
+        // The judge's exchanges are recorded and replayed alongside the agents'
+        let mut judge_config = config.clone();
+        match recordings {
+            Some((GatewayMode::Record, dir)) => {
+                let path = dir.join("judge.jsonl");
+                let _ = std::fs::remove_file(&path);
+                judge_config.llm.record_to = Some(path.display().to_string());
+            }
+            Some((GatewayMode::Replay, dir)) => {
+                judge_config.llm.replay_from = Some(dir.join("judge.jsonl").display().to_string());
+            }
+            _ => {}
+        }
+        runner = runner.with_judge(std::sync::Arc::new(build_intelligence(&judge_config)?));
+    }
+
+    let models = if models.is_empty() {
+        vec![config.llm.model.clone()]
+    } else {
+        models.to_vec()
+    };
+    let report = runner.run(suite, &models).await?;
+
+    let rendered = match format {
+        ReportFormat::Json => common::serde_json::to_string_pretty(&report)?,
+        ReportFormat::Markdown => report.to_markdown(),
+    };
+    match out {
+        Some(path) => {
+            std::fs::write(path, rendered)?;
+            info!("Benchmark report written to {}", path.display());
+        }
+        None => println!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Initialize the agent and run a smoke task against the mock gateway
+///
+/// The last stdout line is a `ProbeReport`; a daemon only swaps to this
+/// binary if it says healthy.
+async fn run_probe_command(config: &agent_config::AgentConfig) -> Result<()> {
+    let started = std::time::Instant::now();
+    let outcome = probe_agent(config.clone()).await;
+    let report = agent_core::self_compile::ProbeReport {
+        healthy: outcome.is_ok(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        error: outcome.as_ref().err().map(|e| e.to_string()),
+    };
+    println!("{}", common::serde_json::to_string(&report)?);
+    outcome
+}
+
+async fn probe_agent(mut config: agent_config::AgentConfig) -> Result<()> {
+    use agent_core::events::{Event, EventKind};
+    use agent_core::{Task, TaskPriority};
+
+    // Nothing the probe does may reach a provider, the daemon's socket or the agent's own code
+    config.llm.provider = "mock".to_string();
+    config.llm.record_to = None;
+    config.llm.replay_from = None;
+    config.llm.routing.adaptive.enabled = false;
+    config.control.enabled = false;
+    config.self_compile.enabled = false;
+    config.telemetry.enabled = false;
+
+    let approvals = std::sync::Arc::new(tools::approval::ApprovalGate::new(
+        approval_policy(&config),
+    ));
+    let agent = initialize_agent(config, approvals, None, Vec::new()).await?;
+
+    let mut events = agent.subscribe();
+    let task_id = agent
+        .submit_task(Task::new("Summarize the agent's health").with_priority(TaskPriority::Critical))
+        .await?;
+    let finished = async {
+        loop {
+            match events.recv().await {
+                Ok(Event {
+                    kind: EventKind::TaskFinished { task_id: finished, success, error, .. },
+                    ..
+                }) if finished == task_id => return (success, error),
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                    return (false, Some("event stream closed".to_string()))
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let (success, error) = tokio::select! {
+        result = agent.run() => {
+            result?;
+            (false, Some("agent stopped before the smoke task finished".to_string()))
+        }
+        outcome = finished => outcome,
+    };
+    agent.stop().await;
+    if success {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Smoke task failed: {}",
+            error.unwrap_or_else(|| "no error reported".to_string())
+        ))
+    }
+}
+
+/// Print the HTN plan for a task, optionally explaining method selection
+async fn run_plan_command(
+    config: &agent_config::AgentConfig,
+    description: &str,
+    explain: bool,
+) -> Result<()> {
+    // Methods are validated against the same tools the agent would have
+    let mut tools = tools::ToolFramework::new();
+    tools.import_mcp_servers(&config.tools.mcp_servers).await;
+    let tool_names: Vec<String> = tools
+        .list_tools()
+        .iter()
+        .map(|t| t.name().to_string())
+        .collect();
+
+    let planner = build_planner(config, &tool_names).await?;
+    let intent = intelligence::intent::IntentParser::new().parse(description).await?;
+    let goal = planner.goal_for(&intent, description);
+
+    // Ground preconditions in the current workspace
+    let mut state = agent_core::planning::WorldState {
+        available_tools: tool_names.iter().cloned().collect(),
+        ..Default::default()
+    };
+    let session = agent_core::world_probe::WorldProbe::new().session(".");
+    session.ground(&mut state, &planner.conditions_for(&goal)).await;
+
+    let explanation = planner.explain(&goal, &state, &tool_names).await;
+
+    if explain {
+        print!("{}", explanation);
+    } else if !explanation.covered {
+        println!("No method library covers '{}'; the LLM planner would be used.", explanation.task);
+    } else if let Some(plan) = &explanation.plan {
+        for (i, task) in plan.tasks.iter().enumerate() {
+            println!("{}. {}", i + 1, task.description);
+        }
+    } else if let Some(error) = &explanation.error {
+        println!("Planning failed: {}", error);
+    }
+
+    Ok(())
+}
+
 /// Run the agent in daemon mode (continuous operation)
-async fn run_daemon_mode(agent: &mut agent_core::Agent) -> Result<()> {
+async fn run_daemon_mode(
+    agent: &mut agent_core::Agent,
+    approvals: &std::sync::Arc<tools::approval::ApprovalGate>,
+) -> Result<()> {
     info!("Daemon mode started - Agent will run continuously");
+
+    // Decisions arrive through `coding-agent approvals`
+    let inbox_watcher = approvals.spawn_inbox_watcher(std::time::Duration::from_secs(1));
     
     // Set up signal handlers for graceful shutdown
     let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
     let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
 
-    // Run the agent in a separate task
-    let agent_future = agent.run();
+    // A daemon swapped in by `client swap` takes over its predecessor's queue and socket
+    let mut inherited = None;
+    if let Some(path) = std::env::var_os(agent_core::self_compile::HANDOFF_ENV) {
+        // Not passed on to anything this process starts
+        std::env::remove_var(agent_core::self_compile::HANDOFF_ENV);
+        if let Some(handoff) = agent_core::self_compile::Handoff::take(std::path::Path::new(&path))? {
+            inherited = handoff.control.clone();
+            agent.adopt(handoff).await;
+        }
+    }
+
+    // Opt-in local API for giving the daemon work
+    let control = match (agent.config().control.enabled, inherited) {
+        (true, Some(handoff)) => Some(agent_core::control::ControlServer::inherit(&agent.config().control, handoff)?),
+        (true, None) => Some(agent_core::control::ControlServer::bind(&agent.config().control).await?),
+        (false, _) => None,
+    };
+    let control_future = async {
+        match &control {
+            Some(server) => server.serve(agent, approvals).await,
+            None => std::future::pending().await,
+        }
+    };
 
     tokio::select! {
-        result = agent_future => {
+        result = agent.run() => {
+            result?;
+        }
+        result = control_future => {
             result?;
         }
         _ = sigterm.recv() => {
@@ -206,6 +865,7 @@ async fn run_daemon_mode(agent: &mut agent_core::Agent) -> Result<()> {
         }
     }
 
+    inbox_watcher.abort();
     Ok(())
 }
 
@@ -229,11 +889,33 @@ async fn run_single_task(
     }
 
     // Submit the task
+    let mut events = agent.subscribe();
     let task_id = agent.submit_task(task).await?;
     info!("Task submitted with ID: {}", task_id);
 
+    // Stop the agent once the task has finished
+    let stop_when_finished = async {
+        loop {
+            match events.recv().await {
+                Ok(agent_core::events::Event {
+                    kind: agent_core::events::EventKind::TaskFinished { task_id: finished, .. },
+                    ..
+                }) if finished == task_id => break,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                _ => {}
+            }
+        }
+        agent.stop().await;
+        std::future::pending::<()>().await
+    };
+
     // Run the agent briefly to process the task
-    let run_future = agent.run();
+    let run_future = async {
+        tokio::select! {
+            result = agent.run() => result,
+            _ = stop_when_finished => Ok(()),
+        }
+    };
     let timeout = tokio::time::Duration::from_secs(300); // 5 minute timeout
 
     match tokio::time::timeout(timeout, run_future).await {
@@ -253,8 +935,81 @@ async fn run_single_task(
     Ok(())
 }
 
+/// Print what a dry run would have done and offer to apply the file changes
+///
+/// The report goes to stderr when the event stream is on stdout.
+async fn report_dry_run(overlay: &tools::overlay::Overlay, events_on_stdout: bool) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+    use tools::overlay::ChangeKind;
+
+    let mut out: Box<dyn Write> = if events_on_stdout {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+
+    writeln!(out, "\n=== Dry Run ===")?;
+    if overlay.is_empty() {
+        writeln!(out, "No changes would be made")?;
+        return Ok(());
+    }
+
+    let changes = overlay.changes();
+    if !changes.is_empty() {
+        writeln!(out, "\nFile changes:")?;
+        for change in &changes {
+            let marker = match change.kind {
+                ChangeKind::Created => 'A',
+                ChangeKind::Modified => 'M',
+                ChangeKind::Deleted => 'D',
+            };
+            writeln!(out, "  {} {}", marker, change.path)?;
+        }
+        writeln!(out)?;
+        write!(out, "{}", overlay.diff())?;
+    }
+
+    let suppressed = overlay.suppressed();
+    if !suppressed.is_empty() {
+        writeln!(out, "\nSuppressed commands:")?;
+        for call in &suppressed {
+            writeln!(out, "  {} {} {}", call.tool, call.operation, call.args)?;
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    // Keep the patch so it can be applied later with `git apply`
+    let dir = std::path::Path::new(".agent/dry-run");
+    tokio::fs::create_dir_all(dir).await?;
+    let patch = dir.join(format!(
+        "{}.patch",
+        common::chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    tokio::fs::write(&patch, overlay.diff()).await?;
+    writeln!(out, "\nPatch saved to {}", patch.display())?;
+
+    if std::io::stdin().is_terminal() {
+        write!(out, "Apply these changes now? [y/N] ")?;
+        out.flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            let applied = overlay.apply().await?;
+            writeln!(out, "Applied changes to {} files", applied.len())?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Run interactive mode with REPL
-async fn run_interactive_mode(mut agent: agent_core::Agent) -> Result<()> {
+async fn run_interactive_mode(
+    agent: agent_core::Agent,
+    approvals: std::sync::Arc<tools::approval::ApprovalGate>,
+) -> Result<()> {
     use std::io::{self, Write};
     use std::sync::Arc;
     use tokio::sync::Mutex;
@@ -267,12 +1022,23 @@ async fn run_interactive_mode(mut agent: agent_core::Agent) -> Result<()> {
     
     // Run the agent in a background task
     let agent_handle = tokio::spawn(async move {
-        let mut agent = agent_clone.lock().await;
+        let agent = agent_clone.lock().await;
         if let Err(e) = agent.run().await {
             error!("Agent error: {}", e);
         }
     });
 
+    // Show held tool calls as they come in
+    let mut requests = approvals.subscribe();
+    let approval_printer = tokio::spawn(async move {
+        while let Ok(request) = requests.recv().await {
+            println!("\n{}", format_approval_request(&request));
+            println!("Reply with: approve {id} | deny {id} [reason] | edit {id} <json>", id = &request.id[..8]);
+            print!("agent> ");
+            let _ = io::stdout().flush();
+        }
+    });
+
     // REPL loop for user input
     loop {
         print!("agent> ");
@@ -305,6 +1071,20 @@ async fn run_interactive_mode(mut agent: agent_core::Agent) -> Result<()> {
                 info!("Triggering self-improvement cycle");
                 agent.trigger_self_improvement().await?;
             }
+            "approvals" => {
+                let pending = approvals.pending();
+                if pending.is_empty() {
+                    println!("No tool calls awaiting approval");
+                }
+                for request in &pending {
+                    println!("{}", format_approval_request(request));
+                }
+            }
+            _ if is_approval_command(input) => {
+                if let Err(e) = handle_approval_command(&approvals, input) {
+                    eprintln!("{}", e);
+                }
+            }
             "" => {
                 // Empty line, do nothing
             }
@@ -322,6 +1102,7 @@ async fn run_interactive_mode(mut agent: agent_core::Agent) -> Result<()> {
     }
 
     // Stop the agent and wait for completion
+    approval_printer.abort();
     let mut agent = agent_arc.lock().await;
     agent.shutdown().await?;
     agent_handle.abort();
@@ -336,11 +1117,249 @@ fn print_help() {
     println!("  status   - Show current agent state");
     println!("  metrics  - Show agent performance metrics");
     println!("  improve  - Trigger self-improvement cycle");
+    println!("  approvals               - List tool calls awaiting approval");
+    println!("  approve <id>            - Let a held tool call run");
+    println!("  deny <id> [reason]      - Refuse a held tool call");
+    println!("  edit <id> <json args>   - Run a held tool call with different arguments");
     println!("  exit     - Exit interactive mode");
     println!();
     println!("Any other input will be treated as a task description.");
 }
 
+/// Render an approval request for review
+fn format_approval_request(request: &tools::approval::ApprovalRequest) -> String {
+    format!(
+        "⏸ Approval required [{}] {} ({}) requested at {}\n{}",
+        request.id,
+        request.tool,
+        request.rule,
+        request.requested_at.format("%H:%M:%S"),
+        request.preview.trim_end()
+    )
+}
+
+/// Name recorded in the audit trail for decisions made here
+fn reviewer_name() -> String {
+    std::env::var("USER").unwrap_or_else(|_| "operator".to_string())
+}
+
+/// Whether an interactive input line is an approval decision
+fn is_approval_command(input: &str) -> bool {
+    matches!(input.split_whitespace().next(), Some("approve" | "deny" | "edit"))
+}
+
+/// Parse an approval decision typed as `<verb> <id> [rest]`
+fn parse_approval_decision(input: &str) -> Result<(String, tools::approval::ApprovalDecision)> {
+    use tools::approval::ApprovalDecision;
+
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let verb = parts.next().unwrap_or_default();
+    let id = parts
+        .next()
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Usage: {} <id> ...", verb))?
+        .to_string();
+    let rest = parts.next().map(str::trim).filter(|r| !r.is_empty());
+
+    let decision = match verb {
+        "approve" => ApprovalDecision::Approve,
+        "deny" => ApprovalDecision::Deny {
+            reason: rest.map(str::to_string),
+        },
+        "edit" => {
+            let args = rest.ok_or_else(|| anyhow::anyhow!("Usage: edit <id> <json args>"))?;
+            ApprovalDecision::Edit {
+                args: common::serde_json::from_str(args)?,
+            }
+        }
+        other => anyhow::bail!("Unknown approval command: {}", other),
+    };
+    Ok((id, decision))
+}
+
+/// Find the request an id or unique id prefix refers to
+fn resolve_request_id<'a>(ids: impl Iterator<Item = &'a str>, prefix: &str) -> Result<String> {
+    let matches: Vec<&str> = ids.filter(|id| id.starts_with(prefix)).collect();
+    match matches.as_slice() {
+        [id] => Ok(id.to_string()),
+        [] => anyhow::bail!("No pending approval request matches '{}'", prefix),
+        _ => anyhow::bail!("'{}' matches several pending requests", prefix),
+    }
+}
+
+/// Apply an approval decision typed in interactive mode
+fn handle_approval_command(approvals: &tools::approval::ApprovalGate, input: &str) -> Result<()> {
+    let (prefix, decision) = parse_approval_decision(input)?;
+    let pending = approvals.pending();
+    let id = resolve_request_id(pending.iter().map(|r| r.id.as_str()), &prefix)?;
+    approvals.decide(&id, decision, &reviewer_name())?;
+    println!("Decision recorded for {}", id);
+    Ok(())
+}
+
+/// Review tool calls held by an agent running in another process
+async fn run_approvals_command(action: &ApprovalsAction) -> Result<()> {
+    use tools::approval::{self, ApprovalDecision};
+
+    let dir = std::path::Path::new(approval::DEFAULT_APPROVALS_DIR);
+    let pending = approval::list_pending(dir).await?;
+
+    let (prefix, decision) = match action {
+        ApprovalsAction::List => {
+            if pending.is_empty() {
+                println!("No tool calls awaiting approval");
+            }
+            for request in &pending {
+                println!("{}\n", format_approval_request(request));
+            }
+            return Ok(());
+        }
+        ApprovalsAction::Audit => {
+            for record in approval::read_audit_trail(dir).await? {
+                println!(
+                    "{} {} {} ({}) -> {:?} by {}",
+                    record.decided_at.format("%Y-%m-%d %H:%M:%S"),
+                    record.request.id,
+                    record.request.tool,
+                    record.request.rule,
+                    record.decision,
+                    record.decided_by
+                );
+            }
+            return Ok(());
+        }
+        ApprovalsAction::Approve { id } => (id, ApprovalDecision::Approve),
+        ApprovalsAction::Deny { id, reason } => (id, ApprovalDecision::Deny { reason: reason.clone() }),
+        ApprovalsAction::Edit { id, args } => (
+            id,
+            ApprovalDecision::Edit {
+                args: common::serde_json::from_str(args)?,
+            },
+        ),
+    };
+
+    let id = resolve_request_id(pending.iter().map(|r| r.id.as_str()), prefix)?;
+    approval::submit_decision(dir, &id, decision, &reviewer_name()).await?;
+    println!("Decision submitted for {}", id);
+    Ok(())
+}
+
+/// Audit or undo what the self-improvement loop changed, from its ledger
+async fn run_improvements_command(
+    config: &agent_config::AgentConfig,
+    action: &ImprovementsAction,
+) -> Result<()> {
+    use agent_core::improvement::{AppliedChange, ImprovementEngine};
+    use common::Module;
+    use std::sync::Arc;
+
+    let mut engine = ImprovementEngine::new();
+    engine.initialize().await?;
+    let entries = engine.get_improvement_history().await;
+
+    let prefix = match action {
+        ImprovementsAction::List => {
+            if entries.is_empty() {
+                println!("No improvements have been applied");
+            }
+            for entry in &entries {
+                println!("{}", format_ledger_entry(entry));
+            }
+            return Ok(());
+        }
+        ImprovementsAction::Show { id } | ImprovementsAction::Revert { id } => id,
+    };
+    let matches: Vec<&AppliedChange> = entries
+        .iter()
+        .filter(|e| e.change.id.starts_with(prefix.as_str()))
+        .collect();
+    let entry = match matches.as_slice() {
+        [entry] => *entry,
+        [] => anyhow::bail!("No applied change matches '{}'", prefix),
+        _ => anyhow::bail!("'{}' matches several applied changes", prefix),
+    };
+
+    if let ImprovementsAction::Show { .. } = action {
+        println!("{}", common::serde_json::to_string_pretty(entry)?);
+        return Ok(());
+    }
+
+    // Code changes are reverted with git in the project they were merged into
+    if entry.change.change_type == agent_core::ChangeType::Code {
+        let improver = agent_core::code_improvement::CodeImprover::new(
+            config,
+            Arc::new(build_intelligence(config)?),
+            Arc::new(tools::ToolFramework::new()),
+            std::env::current_dir()?,
+        );
+        engine = engine.with_code_improver(Arc::new(improver));
+    }
+    engine.rollback(&entry.change).await?;
+    println!("Rolled back {}: {}", entry.change.id, entry.change.description);
+    Ok(())
+}
+
+/// One line per ledger entry: id, when, status, kind and what it was for
+fn format_ledger_entry(entry: &agent_core::improvement::AppliedChange) -> String {
+    let verdict = entry
+        .experiment
+        .as_ref()
+        .map(|e| format!(" [{:?}, {}+{} tasks]", e.verdict, e.control_samples, e.variant_samples))
+        .unwrap_or_default();
+    let name = entry
+        .strategy
+        .as_ref()
+        .map_or(entry.change.description.as_str(), |s| s.name.as_str());
+    let status = format!("{:?}", entry.status);
+    let kind = format!("{:?}", entry.change.change_type);
+    format!(
+        "{}  {}  {:<11} {:<13} {}{}",
+        entry.change.id,
+        entry.applied_at.format("%Y-%m-%d %H:%M:%S"),
+        status,
+        kind,
+        name,
+        verdict
+    )
+}
+
+/// Send one request to a running daemon and print the result
+async fn run_client_command(
+    config: &agent_config::AgentConfig,
+    workspace: Option<&str>,
+    action: &ClientAction,
+) -> Result<()> {
+    use common::serde_json::{json, Value};
+
+    let client = agent_core::control::ControlClient::new(&config.control).await?;
+    let (method, params) = match action {
+        ClientAction::Submit { description, priority } => (
+            "submit",
+            json!({ "description": description, "priority": priority, "workspace": workspace }),
+        ),
+        ClientAction::Status => ("status", Value::Null),
+        ClientAction::Task { task_id } => ("task", json!({ "task_id": task_id })),
+        ClientAction::Sessions => ("sessions", Value::Null),
+        ClientAction::Cancel { task_id } => ("cancel", json!({ "task_id": task_id })),
+        ClientAction::Approvals => ("approvals", Value::Null),
+        ClientAction::Approve { id } => (
+            "decide",
+            json!({ "id": id, "decision": "approve", "reviewer": reviewer_name() }),
+        ),
+        ClientAction::Deny { id, reason } => (
+            "decide",
+            json!({ "id": id, "decision": "deny", "reason": reason, "reviewer": reviewer_name() }),
+        ),
+        ClientAction::Metrics => ("metrics", Value::Null),
+        // The daemon may run from another directory
+        ClientAction::Swap { binary } => ("swap", json!({ "binary": std::fs::canonicalize(binary)? })),
+    };
+
+    let result = client.call(method, params).await?;
+    println!("{}", common::serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
 /// Print metrics in a formatted way
 fn print_metrics(metrics: &agent_core::AgentMetrics) {
     println!("\n=== Agent Metrics ===");